@@ -11,12 +11,13 @@ _alacritty()
     cur="${COMP_WORDS[COMP_CWORD]}"
     prev="${COMP_WORDS[COMP_CWORD-1]}"
     prevprev="${COMP_WORDS[COMP_CWORD-2]}"
-    opts="-h --help -V --version --live-config-reload --no-live-config-reload --print-events -q -qq -v -vv -vvv --ref-test -e --command --config-file -d --dimensions -t --title --working-directory"
+    opts="-h --help -V --version --live-config-reload --no-live-config-reload --print-events -q -qq -v -vv -vvv --ref-test -e --command --config-file -d --dimensions -t --title --working-directory --message --socket"
 
-    # If `--command` or `-e` is used, stop completing
+    # If `--command`, `-e` or `--` is used, stop completing
     for i in "${!COMP_WORDS[@]}"; do
         if [[ "${COMP_WORDS[i]}" == "--command" ]] \
             || [[ "${COMP_WORDS[i]}" == "-e" ]] \
+            || [[ "${COMP_WORDS[i]}" == "--" ]] \
             && [[ "${#COMP_WORDS[@]}" -gt "$(($i + 2))" ]]
         then
             return 0
@@ -30,17 +31,17 @@ _alacritty()
 
     # Match the previous word
     case "${prev}" in
-        --command | -e)
+        --command | -e | --)
             # Complete all commands in $PATH
             COMPREPLY=( $(compgen -c -- "${cur}") )
             return 0;;
-        --config-file)
+        --config-file | --socket)
             # Path based completion
             local IFS=$'\n'
             compopt -o filenames
             COMPREPLY=( $(compgen -f -- "${cur}") )
             return 0;;
-        --dimensions | -d | --title | -t)
+        --dimensions | -d | --title | -t | --message)
             # Don't complete here
             return 0;;
         --working-directory)
@@ -28,6 +28,16 @@ complete -c alacritty \
   -l "working-directory" \
   -d "Start shell in specified directory"
 
+# IPC
+complete -c alacritty \
+  -x \
+  -l "message" \
+  -d "Send a message to a running instance"
+complete -c alacritty \
+  -r \
+  -l "socket" \
+  -d "Socket of the instance receiving the message"
+
 # Output
 complete \
   -c alacritty \
@@ -30,6 +30,7 @@ use gl_generator::{Api, Fallbacks, GlobalGenerator, Profile, Registry};
 use std::env;
 use std::fs::File;
 use std::path::Path;
+use std::process::Command;
 
 #[cfg(windows)]
 use std::io;
@@ -52,8 +53,18 @@ fn main() {
     ).write_bindings(GlobalGenerator, &mut file)
         .unwrap();
 
+    // Only the terminfo installation depends on anything outside of build.rs
+    println!("cargo:rerun-if-env-changed=ALACRITTY_TERMINFO_DIR");
+    println!("cargo:rerun-if-changed=alacritty.info");
+
+    // Compile and install the terminfo entry when a destination is requested
+    if let Ok(dir) = env::var("ALACRITTY_TERMINFO_DIR") {
+        install_terminfo(&dir);
+    }
+
     #[cfg(windows)]
     {
+        println!("cargo:rerun-if-changed=assets/windows/windows.rc");
         embed_resource::compile("assets/windows/windows.rc");
 
         // Path is relative to target/{profile}/build/alacritty-HASH/out
@@ -64,6 +75,21 @@ fn main() {
     }
 }
 
+/// Compile `alacritty.info` with tic into the terminfo database at `dir`
+///
+/// Failures don't break the build, since the entry is optional.
+fn install_terminfo(dir: &str) {
+    let status = Command::new("tic")
+        .args(&["-x", "-e", "alacritty,alacritty-direct", "-o", dir, "alacritty.info"])
+        .status();
+
+    match status {
+        Ok(ref status) if status.success() => (),
+        Ok(status) => println!("cargo:warning=Unable to install terminfo: tic {}", status),
+        Err(err) => println!("cargo:warning=Unable to install terminfo: {}", err),
+    }
+}
+
 #[cfg(windows)]
 fn aquire_winpty_agent(out_path: &Path) {
     let tmp_dir = TempDir::new("alacritty_build").unwrap();
@@ -6,6 +6,7 @@ aux_files=("alacritty-completions.bash"
            "alacritty-completions.zsh"
            "alacritty.desktop"
            "alacritty.info"
+           "alacritty.terminfo"
            "alacritty.yml"
            "alacritty_macos.yml"
            "alacritty_windows.yml")
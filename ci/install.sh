@@ -4,3 +4,9 @@
 if [ "$TRAVIS_RUST_VERSION" == "nightly" ]; then
     rustup component add clippy-preview
 fi
+
+# The DBUS service is built on Linux, it needs the libdbus headers
+if [ "$TRAVIS_OS_NAME" == "linux" ]; then
+    sudo apt-get update
+    sudo apt-get install -y libdbus-1-dev
+fi
@@ -15,6 +15,11 @@ else
     cargo test || error=true
 fi
 
+# Test the optional DBUS service
+if [ "$TRAVIS_OS_NAME" == "linux" ]; then
+    cargo test --features dbus || error=true
+fi
+
 # Test the font subcrate
 cargo test -p font || error=true
 
@@ -304,6 +304,11 @@ impl super::Load for Clipboard {
         self.0.read_object()
             .map_err(::std::convert::From::from)
     }
+
+    fn load_selection(&self) -> Result<String, Self::Err> {
+        // No such thing on macOS
+        Ok(String::new())
+    }
 }
 
 impl super::Store for Clipboard {
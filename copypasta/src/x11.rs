@@ -5,17 +5,27 @@
 //! probably because xclip is unavailable. There's currently no non-GPL x11
 //! clipboard library for Rust. Until then, we have this hack.
 //!
+//! Wayland compositors are handled the same way, using `wl-copy` and
+//! `wl-paste` from wl-clipboard. Those access the PRIMARY selection through
+//! the primary selection protocol (`zwp_primary_selection_device_manager_v1`).
+//!
 //! FIXME: Implement actual X11 clipboard API using the ICCCM reference
 //!        https://tronche.com/gui/x/icccm/
+use std::env;
 use std::io;
 use std::process::{Output, Command};
 use std::string::FromUtf8Error;
 use std::ffi::OsStr;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::sync::{Once, ONCE_INIT};
 
 use super::{Load, Store};
 
 /// The x11 clipboard
-pub struct Clipboard;
+pub struct Clipboard {
+    /// Use wl-clipboard instead of xclip
+    wayland: bool,
+}
 
 #[derive(Debug)]
 pub enum Error {
@@ -36,7 +46,7 @@ impl ::std::error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::Io(..) => "error calling xclip",
-            Error::Xclip(..) => "error reported by xclip",
+            Error::Xclip(..) => "error reported by clipboard utility",
             Error::Utf8(..) => "clipboard contents not utf8",
         }
     }
@@ -48,12 +58,16 @@ impl ::std::fmt::Display for Error {
             Error::Io(ref err) => {
                 match err.kind() {
                     io::ErrorKind::NotFound => {
-                        write!(f, "Please install `xclip` to enable clipboard support")
+                        write!(
+                            f,
+                            "Please install `xclip` (X11) or `wl-clipboard` (Wayland) to \
+                             enable clipboard support"
+                        )
                     },
                     _ => write!(f, "error calling xclip: {}", err),
                 }
             },
-            Error::Xclip(ref s) => write!(f, "error from xclip: {}", s),
+            Error::Xclip(ref s) => write!(f, "error from clipboard utility: {}", s),
             Error::Utf8(ref err) => write!(f, "error parsing xclip output: {}", err),
         }
     }
@@ -71,27 +85,58 @@ impl From<FromUtf8Error> for Error {
     }
 }
 
+/// Whether wl-clipboard should be used instead of xclip
+///
+/// A clipboard is created for every access, so the check for wl-clipboard only
+/// runs the first time.
+fn use_wl_clipboard() -> bool {
+    static DETECT: Once = ONCE_INIT;
+    static WAYLAND: AtomicBool = ATOMIC_BOOL_INIT;
+
+    DETECT.call_once(|| {
+        // Fall back to xclip when wl-clipboard isn't installed, since XWayland
+        // keeps the X11 selections in sync with the Wayland ones.
+        let wayland = env::var_os("WAYLAND_DISPLAY").is_some()
+            && Command::new("wl-paste").arg("--version").output().is_ok();
+        WAYLAND.store(wayland, Ordering::Relaxed);
+    });
+
+    WAYLAND.load(Ordering::Relaxed)
+}
+
 impl Load for Clipboard {
     type Err = Error;
 
     fn new() -> Result<Self, Error> {
-        Ok(Clipboard)
+        Ok(Clipboard { wayland: use_wl_clipboard() })
     }
 
     fn load_primary(&self) -> Result<String, Self::Err> {
-        let output = Command::new("xclip")
-            .args(&["-o", "-selection", "clipboard"])
-            .output()?;
+        let output = if self.wayland {
+            Command::new("wl-paste")
+                .args(&["--no-newline"])
+                .output()?
+        } else {
+            Command::new("xclip")
+                .args(&["-o", "-selection", "clipboard"])
+                .output()?
+        };
 
-        Clipboard::process_xclip_output(output)
+        Clipboard::process_output(output)
     }
 
     fn load_selection(&self) -> Result<String, Self::Err> {
-        let output = Command::new("xclip")
-            .args(&["-o"])
-            .output()?;
+        let output = if self.wayland {
+            Command::new("wl-paste")
+                .args(&["--no-newline", "--primary"])
+                .output()?
+        } else {
+            Command::new("xclip")
+                .args(&["-o"])
+                .output()?
+        };
 
-        Clipboard::process_xclip_output(output)
+        Clipboard::process_output(output)
     }
 }
 
@@ -101,7 +146,11 @@ impl Store for Clipboard {
     fn store_primary<S>(&mut self, contents: S) -> Result<(), Self::Err>
         where S: Into<String>
     {
-        self.store(contents, &["-i", "-selection", "clipboard"])
+        if self.wayland {
+            self.store("wl-copy", contents, &[] as &[&str])
+        } else {
+            self.store("xclip", contents, &["-i", "-selection", "clipboard"])
+        }
     }
 
     /// Sets the secondary clipboard contents
@@ -109,22 +158,27 @@ impl Store for Clipboard {
     fn store_selection<S>(&mut self, contents: S) -> Result<(), Self::Err>
         where S: Into<String>
     {
-        self.store(contents, &["-i"])
+        if self.wayland {
+            self.store("wl-copy", contents, &["--primary"])
+        } else {
+            self.store("xclip", contents, &["-i"])
+        }
     }
 }
 
 impl Clipboard {
-    fn process_xclip_output(output: Output) -> Result<String, Error> {
+    fn process_output(output: Output) -> Result<String, Error> {
         if output.status.success() {
             String::from_utf8(output.stdout)
                 .map_err(::std::convert::From::from)
         } else {
-            String::from_utf8(output.stderr)
-                .map_err(::std::convert::From::from)
+            // An empty selection is reported as an error, which must not be pasted
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(Error::Xclip(stderr.trim().into()))
         }
     }
 
-    fn store<C, S>(&mut self, contents: C, args: &[S]) -> Result<(), Error>
+    fn store<C, S>(&mut self, program: &str, contents: C, args: &[S]) -> Result<(), Error>
         where C: Into<String>,
               S: AsRef<OsStr>,
     {
@@ -132,7 +186,7 @@ impl Clipboard {
         use std::process::{Command, Stdio};
 
         let contents = contents.into();
-        let mut child = Command::new("xclip")
+        let mut child = Command::new(program)
             .args(args)
             .stdin(Stdio::piped())
             .spawn()?;
@@ -146,16 +200,31 @@ impl Clipboard {
         if exit_status.success() {
             Ok(())
         } else {
-            Err(Error::Xclip("xclip returned non-zero exit code".into()))
+            Err(Error::Xclip(format!("{} returned non-zero exit code", program)))
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+
     use super::Clipboard;
     use ::{Load, Store};
 
+    #[test]
+    fn failed_output_is_an_error() {
+        let output = Output {
+            status: ExitStatus::from_raw(1 << 8),
+            stdout: Vec::new(),
+            stderr: b"No selection\n".to_vec(),
+        };
+
+        let err = Clipboard::process_output(output).unwrap_err();
+        assert_eq!(err.to_string(), "error from clipboard utility: No selection");
+    }
+
     #[test]
     fn clipboard_works() {
         let mut clipboard = Clipboard::new().expect("create clipboard");
@@ -251,7 +251,8 @@ impl Rasterizer {
         font: &Font,
     ) -> Option<Result<RasterizedGlyph, Error>> {
         let scaled_size = self.device_pixel_ratio * glyph.size.as_f32_pts();
-        font.get_glyph(glyph.c, f64::from(scaled_size), self.use_thin_strokes)
+        let dpr = self.device_pixel_ratio;
+        font.get_glyph(glyph.c, f64::from(scaled_size), dpr, self.use_thin_strokes)
             .map(|r| Some(Ok(r)))
             .unwrap_or_else(|e| match e {
                 Error::MissingGlyph(_) => None,
@@ -425,11 +426,20 @@ impl Font {
         let leading = self.ct_font.leading() as f64;
         let line_height = (ascent + descent + leading + 0.5).floor();
 
-        Metrics {
+        let mut metrics = Metrics::with_default_lines(
             average_advance,
             line_height,
-            descent: -(self.ct_font.descent() as f32),
+            -(self.ct_font.descent() as f32),
+        );
+
+        let thickness = (self.ct_font.underline_thickness() as f32).round();
+        if thickness >= 1. {
+            metrics.underline_position = (self.ct_font.underline_position() as f32).round();
+            metrics.underline_thickness = thickness;
+            metrics.strikeout_thickness = thickness;
         }
+
+        metrics
     }
 
     pub fn is_bold(&self) -> bool {
@@ -455,7 +465,13 @@ impl Font {
         }
     }
 
-    pub fn get_glyph(&self, character: char, _size: f64, use_thin_strokes: bool) -> Result<RasterizedGlyph, Error> {
+    pub fn get_glyph(
+        &self,
+        character: char,
+        _size: f64,
+        device_pixel_ratio: f32,
+        use_thin_strokes: bool,
+    ) -> Result<RasterizedGlyph, Error> {
         // Render custom symbols for underline and beam cursor
         match character {
             super::UNDERLINE_CURSOR_CHAR => {
@@ -479,7 +495,8 @@ impl Font {
                 if character == super::BEAM_CURSOR_CHAR {
                     return super::get_beam_cursor_glyph(ascent as i32, height as i32, width);
                 } else {
-                    return super::get_box_cursor_glyph(ascent as i32, height as i32, width);
+                    let border_width = super::box_cursor_border_width(device_pixel_ratio);
+                    return super::get_box_cursor_glyph(ascent as i32, height as i32, width, border_width);
                 }
             }
             _ => ()
@@ -621,7 +638,7 @@ mod tests {
         for font in fonts {
             // Get a glyph
             for c in &['a', 'b', 'c', 'd'] {
-                let glyph = font.get_glyph(*c, 72., false).unwrap();
+                let glyph = font.get_glyph(*c, 72., 1., false).unwrap();
 
                 // Debug the glyph.. sigh
                 for row in 0..glyph.height {
@@ -607,7 +607,9 @@ impl PatternRef {
     }
 
     pattern_get_integer! {
-        index() => b"index\0"
+        index() => b"index\0",
+        slant() => b"slant\0",
+        weight() => b"weight\0"
     }
 
     pub fn config_substitute(&mut self, config: &ConfigRef, kind: MatchKind) {
@@ -30,13 +30,22 @@ struct FixedSize {
     pixelsize: f64,
 }
 
+/// Styles which are faked because the matched face doesn't have them
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+struct Synthesis {
+    embolden: bool,
+    oblique: bool,
+}
+
 struct Face {
     ft_face: freetype::Face,
     key: FontKey,
     load_flags: freetype::face::LoadFlag,
     render_mode: freetype::RenderMode,
     lcd_filter: c_uint,
-    non_scalable: Option<FixedSize>
+    bgr: bool,
+    non_scalable: Option<FixedSize>,
+    synthesis: Synthesis,
 }
 
 impl fmt::Debug for Face {
@@ -54,6 +63,8 @@ impl fmt::Debug for Face {
                 freetype::RenderMode::Max => "Max",
             })
             .field("lcd_filter", &self.lcd_filter)
+            .field("bgr", &self.bgr)
+            .field("synthesis", &self.synthesis)
             .finish()
     }
 }
@@ -62,8 +73,13 @@ impl fmt::Debug for Face {
 pub struct FreeTypeRasterizer {
     faces: HashMap<FontKey, Face>,
     library: Library,
-    keys: HashMap<PathBuf, FontKey>,
+    keys: HashMap<(PathBuf, Synthesis), FontKey>,
+    /// Faces for characters which are missing in the configured fonts, by
+    /// character and the face they're missing in
+    fallbacks: HashMap<(char, FontKey), Option<FontKey>>,
     device_pixel_ratio: f32,
+    /// Antialiasing overriding the fontconfig settings
+    antialias: Option<::Antialias>,
 }
 
 #[inline]
@@ -80,8 +96,10 @@ impl ::Rasterize for FreeTypeRasterizer {
         Ok(FreeTypeRasterizer {
             faces: HashMap::new(),
             keys: HashMap::new(),
+            fallbacks: HashMap::new(),
             library,
             device_pixel_ratio,
+            antialias: None,
         })
     }
 
@@ -91,11 +109,20 @@ impl ::Rasterize for FreeTypeRasterizer {
         let height = (full.size_metrics.height / 64) as f64;
         let descent = (full.size_metrics.descender / 64) as f32;
 
-        Ok(Metrics {
-            average_advance: full.cell_width,
-            line_height: height,
-            descent,
-        })
+        let mut metrics = Metrics::with_default_lines(full.cell_width, height, descent);
+
+        // Convert the underline from font units, bitmap fonts don't have one
+        let face = self.faces.get(&key).ok_or(Error::FontNotLoaded)?;
+        let raw = face.ft_face.raw();
+        let y_scale = full.size_metrics.y_scale as f32 / 65536. / 64.;
+        let thickness = (f32::from(raw.underline_thickness) * y_scale).round();
+        if thickness >= 1. {
+            metrics.underline_position = (f32::from(raw.underline_position) * y_scale).round();
+            metrics.underline_thickness = thickness;
+            metrics.strikeout_thickness = thickness;
+        }
+
+        Ok(metrics)
     }
 
     fn load_font(&mut self, desc: &FontDesc, size: Size) -> Result<FontKey, Error> {
@@ -106,6 +133,9 @@ impl ::Rasterize for FreeTypeRasterizer {
         self.get_rendered_glyph(glyph_key)
     }
 
+    fn set_antialias(&mut self, antialias: ::Antialias) {
+        self.antialias = Some(antialias);
+    }
 }
 
 pub trait IntoFontconfigType {
@@ -193,7 +223,16 @@ impl FreeTypeRasterizer {
         let font = fc::font_match(fc::Config::get_current(), &mut pattern)
             .ok_or_else(|| Error::MissingFont(desc.to_owned()))?;
 
-        self.face_from_pattern(&font)
+        // Families without a real bold or italic face fall back to the
+        // closest one, which is then emboldened or slanted while rendering.
+        let matched_weight = font.weight().next().unwrap_or(fc::Weight::Regular as isize);
+        let matched_slant = font.slant().next().unwrap_or(fc::Slant::Roman as isize);
+        let synthesis = Synthesis {
+            embolden: weight == Weight::Bold && matched_weight < fc::Weight::Semibold as isize,
+            oblique: slant != Slant::Normal && matched_slant == fc::Slant::Roman as isize,
+        };
+
+        self.face_from_pattern(&font, synthesis)
             .and_then(|pattern| {
                 pattern
                     .map(Ok)
@@ -214,7 +253,7 @@ impl FreeTypeRasterizer {
 
         let font = fc::font_match(fc::Config::get_current(), &mut pattern)
             .ok_or_else(|| Error::MissingFont(desc.to_owned()))?;
-        self.face_from_pattern(&font)
+        self.face_from_pattern(&font, Synthesis::default())
             .and_then(|pattern| {
                 pattern
                     .map(Ok)
@@ -222,14 +261,19 @@ impl FreeTypeRasterizer {
             })
     }
 
-    fn face_from_pattern(&mut self, pattern: &fc::Pattern) -> Result<Option<FontKey>, Error> {
+    fn face_from_pattern(
+        &mut self,
+        pattern: &fc::Pattern,
+        synthesis: Synthesis,
+    ) -> Result<Option<FontKey>, Error> {
         if let (Some(path), Some(index)) = (pattern.file(0), pattern.index().nth(0)) {
+            let path = (path, synthesis);
             if let Some(key) = self.keys.get(&path) {
                 return Ok(Some(*key));
             }
 
-            trace!("got font path={:?}", path);
-            let ft_face = self.library.new_face(&path, index)?;
+            trace!("got font path={:?}", path.0);
+            let ft_face = self.library.new_face(&path.0, index)?;
 
             // Get available pixel sizes if font isn't scalable.
             let non_scalable = if pattern.scalable().next().unwrap_or(true) {
@@ -246,10 +290,12 @@ impl FreeTypeRasterizer {
             let face = Face {
                 ft_face,
                 key: FontKey::next(),
-                load_flags: Self::ft_load_flags(pattern),
-                render_mode: Self::ft_render_mode(pattern),
+                load_flags: Self::ft_load_flags(pattern, self.antialias),
+                render_mode: Self::ft_render_mode(pattern, self.antialias),
                 lcd_filter: Self::ft_lcd_filter(pattern),
+                bgr: Self::ft_bgr(pattern, self.antialias),
                 non_scalable,
+                synthesis,
             };
 
             debug!("Loaded Face {:?}", face);
@@ -264,25 +310,33 @@ impl FreeTypeRasterizer {
         }
     }
 
-    fn face_for_glyph(&mut self, glyph_key: GlyphKey, have_recursed: bool) -> Result<FontKey, Error> {
+    /// Find a face which has a glyph for the character
+    ///
+    /// Characters missing in the requested face are looked up in all fonts
+    /// of the system once per face, the result is reused for all later
+    /// lookups.
+    fn face_for_glyph(&mut self, glyph_key: GlyphKey) -> Result<FontKey, Error> {
         let c = glyph_key.c;
+        let has_glyph = |face: &Face| face.ft_face.get_char_index(c as usize) != 0;
 
-        let use_initial_face = if self.faces.contains_key(&glyph_key.font_key) {
-            // Get face and unwrap since we just checked for presence.
-            let face = &self.faces[&glyph_key.font_key];
-            let index = face.ft_face.get_char_index(c as usize);
+        if self.faces.get(&glyph_key.font_key).map_or(false, &has_glyph) {
+            return Ok(glyph_key.font_key);
+        }
 
-            index != 0 || have_recursed
-        } else {
-            false
+        let cache_key = (c, glyph_key.font_key);
+        if let Some(&fallback) = self.fallbacks.get(&cache_key) {
+            return fallback.ok_or(Error::MissingGlyph(c));
+        }
+
+        // Fontconfig returns its best match, even if it doesn't cover `c`
+        let fallback = match self.load_face_with_glyph(c) {
+            Ok(key) if self.faces.get(&key).map_or(false, &has_glyph) => Some(key),
+            _ => None,
         };
+        debug!("Fallback face for {:?}: {:?}", c, fallback);
 
-        if use_initial_face {
-            Ok(glyph_key.font_key)
-        } else {
-            let key = self.load_face_with_glyph(c).unwrap_or(glyph_key.font_key);
-            Ok(key)
-        }
+        self.fallbacks.insert(cache_key, fallback);
+        fallback.ok_or(Error::MissingGlyph(c))
     }
 
     fn get_rendered_glyph(&mut self, glyph_key: GlyphKey)
@@ -322,14 +376,15 @@ impl FreeTypeRasterizer {
                 return if glyph_key.c == super::BEAM_CURSOR_CHAR {
                     super::get_beam_cursor_glyph(ascent, height, width)
                 } else {
-                    super::get_box_cursor_glyph(ascent, height, width)
+                    let border_width = super::box_cursor_border_width(self.device_pixel_ratio);
+                    super::get_box_cursor_glyph(ascent, height, width, border_width)
                 };
             },
             _ => (),
         }
 
         // Render a normal character if it's not a cursor
-        let font_key = self.face_for_glyph(glyph_key, false)?;
+        let font_key = self.face_for_glyph(glyph_key)?;
         let face = &self.faces[&font_key];
         let index = face.ft_face.get_char_index(glyph_key.c as usize);
 
@@ -345,26 +400,76 @@ impl FreeTypeRasterizer {
         }
 
         face.ft_face.load_glyph(index as u32, face.load_flags)?;
+
+        unsafe {
+            let slot = face.ft_face.raw().glyph;
+            if face.synthesis.embolden {
+                freetype::ffi::FT_GlyphSlot_Embolden(slot);
+            }
+            if face.synthesis.oblique {
+                freetype::ffi::FT_GlyphSlot_Oblique(slot);
+            }
+        }
+
         let glyph = face.ft_face.glyph();
         glyph.render_glyph(face.render_mode)?;
 
-        let (pixel_height, pixel_width, buf) = Self::normalize_buffer(&glyph.bitmap())?;
+        let (mut pixel_height, mut pixel_width, mut buf) = Self::normalize_buffer(&glyph.bitmap())?;
+        if face.bgr {
+            for pixel in buf.chunks_mut(3) {
+                pixel.swap(0, 2);
+            }
+        }
+        let (mut top, mut left) = (glyph.bitmap_top(), glyph.bitmap_left());
+
+        // Fallback faces which can't be scaled, like most emoji fonts, are
+        // shrunk to fit into the cell of the requested face
+        if font_key != glyph_key.font_key && face.non_scalable.is_some() {
+            let line_height = (self.full_metrics(glyph_key.font_key)?.size_metrics.height / 64) as i32;
+            if line_height > 0 && pixel_height > line_height {
+                let factor = (pixel_height + line_height - 1) / line_height;
+                buf = downsample(&buf, pixel_width, pixel_height, factor);
+                pixel_width /= factor;
+                pixel_height /= factor;
+                top /= factor;
+                left /= factor;
+            }
+        }
 
         Ok(RasterizedGlyph {
             c: glyph_key.c,
-            top: glyph.bitmap_top(),
-            left: glyph.bitmap_left(),
+            top,
+            left,
             width: pixel_width,
             height: pixel_height,
             buf,
         })
     }
 
-    fn ft_load_flags(pat: &fc::Pattern) -> freetype::face::LoadFlag {
-        let antialias = pat.antialias().next().unwrap_or(true);
+    /// Antialiasing, hinting and subpixel geometry, with the configured antialiasing
+    /// taking precedence over fontconfig.
+    fn ft_rendering(
+        pat: &fc::Pattern,
+        antialias: Option<::Antialias>,
+    ) -> (bool, fc::HintStyle, fc::Rgba) {
         let hinting = pat.hintstyle().next().unwrap_or(fc::HintStyle::Slight);
         let rgba = pat.rgba().next().unwrap_or(fc::Rgba::Unknown);
 
+        match antialias {
+            None => (pat.antialias().next().unwrap_or(true), hinting, rgba),
+            Some(::Antialias::Grayscale) => (true, hinting, fc::Rgba::None),
+            Some(::Antialias::Subpixel) => match rgba {
+                // Assume the most common geometry if fontconfig doesn't know it
+                fc::Rgba::Unknown | fc::Rgba::None => (true, hinting, fc::Rgba::Rgb),
+                rgba => (true, hinting, rgba),
+            },
+            Some(::Antialias::None) => (false, fc::HintStyle::None, rgba),
+        }
+    }
+
+    fn ft_load_flags(pat: &fc::Pattern, antialias: Option<::Antialias>) -> freetype::face::LoadFlag {
+        let (antialias, hinting, rgba) = Self::ft_rendering(pat, antialias);
+
         use freetype::face::LoadFlag;
         match (antialias, hinting, rgba) {
             (false, fc::HintStyle::None, _) => LoadFlag::NO_HINTING | LoadFlag::MONOCHROME,
@@ -398,9 +503,8 @@ impl FreeTypeRasterizer {
         }
     }
 
-    fn ft_render_mode(pat: &fc::Pattern) -> freetype::RenderMode {
-        let antialias = pat.antialias().next().unwrap_or(true);
-        let rgba = pat.rgba().next().unwrap_or(fc::Rgba::Unknown);
+    fn ft_render_mode(pat: &fc::Pattern, antialias: Option<::Antialias>) -> freetype::RenderMode {
+        let (antialias, _, rgba) = Self::ft_rendering(pat, antialias);
 
         match (antialias, rgba) {
             (false, _) => freetype::RenderMode::Mono,
@@ -410,6 +514,14 @@ impl FreeTypeRasterizer {
         }
     }
 
+    /// FreeType always renders subpixels in RGB order, so BGR screens need their channels swapped
+    fn ft_bgr(pat: &fc::Pattern, antialias: Option<::Antialias>) -> bool {
+        match Self::ft_rendering(pat, antialias) {
+            (true, _, fc::Rgba::Bgr) | (true, _, fc::Rgba::Vbgr) => true,
+            _ => false,
+        }
+    }
+
     fn ft_lcd_filter(pat: &fc::Pattern) -> c_uint {
         match pat.lcdfilter().next().unwrap_or(fc::LcdFilter::Default) {
             fc::LcdFilter::None => freetype::ffi::FT_LCD_FILTER_NONE,
@@ -492,6 +604,19 @@ impl FreeTypeRasterizer {
                 }
                 Ok((bitmap.rows(), bitmap.width(), packed))
             },
+            // Color glyphs can't be drawn, so only their shape is kept
+            PixelMode::Bgra => {
+                for i in 0..bitmap.rows() {
+                    let start = (i as usize) * pitch;
+                    let stop = start + 4 * bitmap.width() as usize;
+                    for pixel in buf[start..stop].chunks(4) {
+                        packed.push(pixel[3]);
+                        packed.push(pixel[3]);
+                        packed.push(pixel[3]);
+                    }
+                }
+                Ok((bitmap.rows(), bitmap.width(), packed))
+            },
             mode => panic!("unhandled pixel mode: {:?}", mode)
         }
     }
@@ -509,7 +634,7 @@ impl FreeTypeRasterizer {
         match fc::font_match(config, &mut pattern) {
             Some(pattern) => {
                 if let (Some(path), Some(_)) = (pattern.file(0), pattern.index().nth(0)) {
-                    match self.keys.get(&path) {
+                    match self.keys.get(&(path.clone(), Synthesis::default())) {
                         // We've previously loaded this font, so don't
                         // load it again.
                         Some(&key) => {
@@ -521,7 +646,7 @@ impl FreeTypeRasterizer {
                             debug!("Miss for font {:?}; loading now.", path);
                             // Safe to unwrap the option since we've already checked for the path
                             // and index above.
-                            let key = self.face_from_pattern(&pattern)?.unwrap();
+                            let key = self.face_from_pattern(&pattern, Synthesis::default())?.unwrap();
                             Ok(key)
                         }
                     }
@@ -540,12 +665,39 @@ impl FreeTypeRasterizer {
     }
 }
 
+/// Shrink a packed RGB buffer by an integer `factor`, averaging the pixels
+fn downsample(buf: &[u8], width: i32, height: i32, factor: i32) -> Vec<u8> {
+    let (width, height, factor) = (width as usize, height as usize, factor as usize);
+    let (new_width, new_height) = (width / factor, height / factor);
+
+    let mut scaled = Vec::with_capacity(new_width * new_height * 3);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            for channel in 0..3 {
+                let mut sum = 0usize;
+                for dy in 0..factor {
+                    for dx in 0..factor {
+                        let offset = ((y * factor + dy) * width + x * factor + dx) * 3 + channel;
+                        sum += usize::from(buf[offset]);
+                    }
+                }
+                scaled.push((sum / (factor * factor)) as u8);
+            }
+        }
+    }
+
+    scaled
+}
+
 /// Errors occurring when using the freetype rasterizer
 #[derive(Debug)]
 pub enum Error {
     /// Error occurred within the FreeType library
     FreeType(freetype::Error),
 
+    /// Couldn't find a glyph for the char in any font
+    MissingGlyph(char),
+
     /// Couldn't find font matching description
     MissingFont(FontDesc),
 
@@ -567,6 +719,7 @@ impl ::std::error::Error for Error {
     fn description(&self) -> &str {
         match *self {
             Error::FreeType(ref err) => err.description(),
+            Error::MissingGlyph(ref _c) => "couldn't find the requested glyph",
             Error::MissingFont(ref _desc) => "couldn't find the requested font",
             Error::FontNotLoaded => "tried to operate on font that hasn't been loaded",
             Error::MissingSizeMetrics => "tried to get size metrics from a face without a size",
@@ -580,6 +733,9 @@ impl ::std::fmt::Display for Error {
             Error::FreeType(ref err) => {
                 err.fmt(f)
             },
+            Error::MissingGlyph(ref c) => {
+                write!(f, "Glyph not found for char {:?}", c)
+            },
             Error::MissingFont(ref desc) => {
                 write!(f, "Couldn't find a font with {}\
                        \n\tPlease check the font config in your alacritty.yml.", desc)
@@ -100,6 +100,17 @@ pub enum Weight {
     Bold,
 }
 
+/// Smoothing of glyph edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Antialias {
+    /// Coverage of the whole pixel
+    Grayscale,
+    /// Coverage of each of the LCD's color channels
+    Subpixel,
+    /// Unhinted monochrome glyphs
+    None,
+}
+
 /// Style of font
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Style {
@@ -277,14 +288,19 @@ pub fn get_beam_cursor_glyph(
     })
 }
 
+/// Width of the box cursor's outline, which is one pixel scaled by the DPI
+pub fn box_cursor_border_width(device_pixel_ratio: f32) -> i32 {
+    cmp::max(device_pixel_ratio.round() as i32, 1)
+}
+
 // Returns a custom box cursor character
 pub fn get_box_cursor_glyph(
     ascent: i32,
     height: i32,
     width: i32,
+    border_width: i32,
 ) -> Result<RasterizedGlyph, Error> {
     // Create a new box outline rectangle
-    let border_width = cmp::max(width * CURSOR_WIDTH_PERCENTAGE / 100, 1);
     let mut buf = Vec::with_capacity((width * height * 3) as usize);
     for y in 0..height {
         for x in 0..width {
@@ -336,6 +352,33 @@ pub struct Metrics {
     pub average_advance: f64,
     pub line_height: f64,
     pub descent: f32,
+
+    /// Center of the underline relative to the baseline, negative below it
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+
+    /// Center of the strikeout relative to the baseline
+    pub strikeout_position: f32,
+    pub strikeout_thickness: f32,
+}
+
+impl Metrics {
+    /// Metrics for fonts which don't specify their line decorations
+    ///
+    /// Underlines are 1px thick and sit on the descent, strikeouts cross a
+    /// third of the ascent.
+    pub fn with_default_lines(average_advance: f64, line_height: f64, descent: f32) -> Metrics {
+        let ascent = line_height as f32 + descent;
+        Metrics {
+            average_advance,
+            line_height,
+            descent,
+            underline_position: descent + 0.5,
+            underline_thickness: 1.,
+            strikeout_position: (ascent / 3.).round(),
+            strikeout_thickness: 1.,
+        }
+    }
 }
 
 pub trait Rasterize {
@@ -355,4 +398,9 @@ pub trait Rasterize {
 
     /// Rasterize the glyph described by `GlyphKey`.
     fn get_glyph(&mut self, GlyphKey) -> Result<RasterizedGlyph, Self::Err>;
+
+    /// Override the system's antialiasing, this must happen before loading any fonts.
+    ///
+    /// Rasterizers which can't change the antialiasing ignore it.
+    fn set_antialias(&mut self, _: Antialias) {}
 }
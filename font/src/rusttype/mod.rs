@@ -33,11 +33,11 @@ impl ::Rasterize for RustTypeRasterizer {
             .ok_or(Error::MissingGlyph)?
             .scaled(scale)
             .h_metrics();
-        Ok(Metrics {
-            descent: vmetrics.descent,
-            average_advance: f64::from(hmetrics.advance_width),
-            line_height: f64::from(vmetrics.ascent - vmetrics.descent + vmetrics.line_gap),
-        })
+        Ok(Metrics::with_default_lines(
+            f64::from(hmetrics.advance_width),
+            f64::from(vmetrics.ascent - vmetrics.descent + vmetrics.line_gap),
+            vmetrics.descent,
+        ))
     }
 
     fn load_font(&mut self, desc: &FontDesc, _size: Size) -> Result<FontKey, Error> {
@@ -92,7 +92,8 @@ impl ::Rasterize for RustTypeRasterizer {
                 return super::get_box_cursor_glyph(
                     (metrics.line_height + f64::from(metrics.descent)).round() as i32,
                     metrics.line_height.round() as i32,
-                    metrics.average_advance.round() as i32
+                    metrics.average_advance.round() as i32,
+                    super::box_cursor_border_width(self.dpi_ratio),
                 );
             }
             _ => ()
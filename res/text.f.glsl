@@ -23,6 +23,7 @@ layout(location = 0, index = 1) out vec4 alphaMask;
 
 uniform float bgOpacity;
 uniform sampler2D mask;
+uniform vec3 visualBellColor;
 
 void main()
 {
@@ -31,7 +32,7 @@ void main()
             discard;
 
         alphaMask = vec4(1.0);
-        color = vec4(bg.rgb + vb, 1.0);
+        color = vec4(mix(bg.rgb, visualBellColor, vb), 1.0);
     } else {
         vec3 textColor = texture(mask, TexCoords).rgb;
         alphaMask = vec4(textColor, textColor.r);
@@ -14,6 +14,8 @@
 //
 //! ANSI Terminal Stream Parsing
 use std::io;
+use std::cmp::min;
+use std::mem;
 use std::ops::Range;
 use std::str;
 
@@ -23,6 +25,14 @@ use index::{Column, Line, Contains};
 
 use ::{MouseCursor, Rgb};
 
+/// Size of the buffer for OSC strings in the parser
+///
+/// Longer strings are cut off by vte without any notice.
+const MAX_OSC_RAW: usize = 1024;
+
+/// Maximum length of the parameters of an SGR sequence with subparameters
+const MAX_SUBPARAM_BYTES: usize = 64;
+
 // Parse color arguments
 //
 // Expect that color argument looks like "rgb:xx/xx/xx" or "#xxxxxx"
@@ -101,6 +111,7 @@ fn parse_number(input: &[u8]) -> Option<u8> {
 pub struct Processor {
     state: ProcessorState,
     parser: vte::Parser,
+    subparams: SubparamState,
 }
 
 /// Internal state for VTE processor
@@ -108,6 +119,67 @@ struct ProcessorState {
     preceding_char: Option<char>
 }
 
+/// Collects SGR sequences which use `:` to separate subparameters
+///
+/// The parser ignores CSI sequences containing `:`, so SGR sequences like
+/// `CSI 38:2::r:g:b m` are picked up here instead, next to the parser.
+#[derive(Default)]
+struct SubparamState {
+    /// Parameter bytes of the current CSI sequence, `None` outside of one
+    params: Option<Vec<u8>>,
+
+    /// Whether the previous byte was an ESC
+    escape: bool,
+}
+
+impl SubparamState {
+    /// Parameters of an SGR sequence with subparameters which ends with `byte`
+    fn advance(&mut self, byte: u8) -> Option<Vec<Vec<i64>>> {
+        let escape = mem::replace(&mut self.escape, byte == 0x1b);
+        let mut params = match self.params.take() {
+            Some(params) => params,
+            None => {
+                if escape && byte == b'[' {
+                    self.params = Some(Vec::new());
+                }
+                return None;
+            },
+        };
+
+        match byte {
+            b'0'...b'9' | b':' | b';' if params.len() < MAX_SUBPARAM_BYTES => {
+                params.push(byte);
+                self.params = Some(params);
+            },
+            // Control characters are executed without ending the sequence
+            0x00...0x17 | 0x19 | 0x1c...0x1f => self.params = Some(params),
+            b'm' if params.contains(&b':') => return Some(parse_subparams(&params)),
+            _ => (),
+        }
+
+        None
+    }
+}
+
+/// Split SGR parameters at `;` and their subparameters at `:`
+///
+/// Empty parameters are zero, like they are for the parser.
+fn parse_subparams(params: &[u8]) -> Vec<Vec<i64>> {
+    params
+        .split(|&byte| byte == b';')
+        .map(|param| {
+            param
+                .split(|&byte| byte == b':')
+                .map(|sub| {
+                    sub.iter().fold(0i64, |value, &digit| {
+                        value.saturating_mul(10).saturating_add(i64::from(digit - b'0'))
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
 /// Helper type that implements `vte::Perform`.
 ///
 /// Processor creates a Performer when running advance and passes the Performer
@@ -115,21 +187,67 @@ struct ProcessorState {
 struct Performer<'a, H: Handler + TermInfo + 'a, W: io::Write + 'a> {
     _state: &'a mut ProcessorState,
     handler: &'a mut H,
-    writer: &'a mut W
+    writer: &'a mut W,
+
+    /// Byte which is currently being parsed
+    ///
+    /// The parser doesn't pass the final byte of a DCS to `hook`, but it is
+    /// always the byte which triggered the call.
+    byte: u8,
 }
 
 impl<'a, H: Handler + TermInfo + 'a, W: io::Write> Performer<'a, H, W> {
+    /// Apply an SGR sequence with `:` separated subparameters
+    ///
+    /// Every parameter with subparameters is applied on its own, so a
+    /// malformed one can't take arguments from the parameters behind it.
+    fn sgr_subparams(&mut self, params: &[Vec<i64>]) {
+        let mut args = Vec::new();
+        for param in params {
+            if param.len() == 1 {
+                args.push(param[0]);
+                continue;
+            }
+
+            if !args.is_empty() {
+                vte::Perform::csi_dispatch(self, &args, &[], false, 'm');
+                args.clear();
+            }
+
+            match param[0] {
+                4 => match underline_style(param[1]) {
+                    Some(attr) => self.handler.terminal_attribute(attr),
+                    None => warn!("[Unhandled SGR] underline style={}", param[1]),
+                },
+                // Skip the color space id of `38:2:<id>:r:g:b`
+                38 | 48 | 58 if param.len() > 5 && param[1] == 2 => {
+                    let color = [param[0], 2, param[3], param[4], param[5]];
+                    vte::Perform::csi_dispatch(self, &color, &[], false, 'm');
+                },
+                38 | 48 | 58 => vte::Perform::csi_dispatch(self, param, &[], false, 'm'),
+                // Subparameters of other attributes aren't supported
+                _ => vte::Perform::csi_dispatch(self, &param[..1], &[], false, 'm'),
+            }
+        }
+
+        if !args.is_empty() {
+            vte::Perform::csi_dispatch(self, &args, &[], false, 'm');
+        }
+    }
+
     /// Create a performer
     #[inline]
     pub fn new<'b>(
         state: &'b mut ProcessorState,
         handler: &'b mut H,
         writer: &'b mut W,
+        byte: u8,
     ) -> Performer<'b, H, W> {
         Performer {
             _state: state,
             handler,
             writer,
+            byte,
         }
     }
 }
@@ -139,6 +257,7 @@ impl Default for Processor {
         Processor {
             state: ProcessorState { preceding_char: None },
             parser: vte::Parser::new(),
+            subparams: SubparamState::default(),
         }
     }
 }
@@ -158,8 +277,14 @@ impl Processor {
         where H: Handler + TermInfo,
               W: io::Write
     {
-        let mut performer = Performer::new(&mut self.state, handler, writer);
+        let subparams = self.subparams.advance(byte);
+
+        let mut performer = Performer::new(&mut self.state, handler, writer, byte);
         self.parser.advance(&mut performer, byte);
+
+        if let Some(params) = subparams {
+            performer.sgr_subparams(&params);
+        }
     }
 }
 
@@ -178,15 +303,31 @@ pub trait Handler {
     /// OSC to set window title
     fn set_title(&mut self, &str) {}
 
+    /// Push the current window title onto the title stack
+    fn push_title(&mut self) {}
+
+    /// Restore the window title from the title stack
+    fn pop_title(&mut self) {}
+
     /// Set the window's mouse cursor
     fn set_mouse_cursor(&mut self, MouseCursor) {}
 
     /// Set the cursor style
     fn set_cursor_style(&mut self, _: Option<CursorStyle>) {}
 
+    /// Set whether the cursor blinks
+    fn set_cursor_blinking(&mut self, _: bool) {}
+
     /// A character to be displayed
     fn input(&mut self, _c: char) {}
 
+    /// Repeat the preceding graphic character `count` times (REP)
+    fn repeat(&mut self, c: char, count: usize) {
+        for _ in 0..count {
+            self.input(c);
+        }
+    }
+
     /// Set cursor to position
     fn goto(&mut self, Line, Column) {}
 
@@ -207,12 +348,32 @@ pub trait Handler {
 
     /// Identify the terminal (should write back to the pty stream)
     ///
+    /// The intermediate is `None` for the primary device attributes (DA1) and
+    /// `Some('>')` for the secondary ones (DA2).
+    ///
     /// TODO this should probably return an io::Result
-    fn identify_terminal<W: io::Write>(&mut self, &mut W) {}
+    fn identify_terminal<W: io::Write>(&mut self, &mut W, _intermediate: Option<char>) {}
 
     // Report device status
     fn device_status<W: io::Write>(&mut self, &mut W, usize) {}
 
+    /// Report DEC private device status (`CSI ? Ps n`)
+    fn private_device_status<W: io::Write>(&mut self, &mut W, usize) {}
+
+    /// Report whether a mode is set (DECRQM)
+    ///
+    /// The reply echoes the requested number, which may be an alias of the mode.
+    fn report_mode<W: io::Write>(&mut self, &mut W, Mode, i64) {}
+
+    /// Report the size of the text area in pixels
+    fn text_area_size_pixels<W: io::Write>(&mut self, &mut W) {}
+
+    /// Report the size of a cell in pixels
+    fn cell_size_pixels<W: io::Write>(&mut self, &mut W) {}
+
+    /// Report the size of the screen in characters
+    fn screen_size_chars<W: io::Write>(&mut self, &mut W) {}
+
     /// Move cursor forward `cols`
     fn move_forward(&mut self, Column) {}
 
@@ -299,6 +460,9 @@ pub trait Handler {
     /// Reset terminal state
     fn reset_state(&mut self) {}
 
+    /// DECSTR - Soft terminal reset, preserving the screen content
+    fn soft_reset(&mut self) {}
+
     /// Reverse Index
     ///
     /// Move the active position to the same horizontal position on the
@@ -342,11 +506,99 @@ pub trait Handler {
     /// Reset an indexed color to original value
     fn reset_color(&mut self, usize) {}
 
-    /// Set the clipboard
-    fn set_clipboard(&mut self, &str) {}
+    /// Store data into the clipboard identified by the OSC 52 selection parameter
+    fn set_clipboard(&mut self, _clipboard: u8, _data: &str) {}
+
+    /// Write the clipboard contents back to the pty stream
+    fn write_clipboard<W: io::Write>(&mut self, _clipboard: u8, &mut W) {}
+
+    /// OSC to start or end a hyperlink
+    fn set_hyperlink(&mut self, Option<Hyperlink>) {}
 
     /// Run the dectest routine
     fn dectest(&mut self) {}
+
+    /// Push keyboard enhancement flags onto the kitty keyboard stack
+    fn push_keyboard_mode(&mut self, _mode: KeyboardModes) {}
+
+    /// Pop `count` entries from the kitty keyboard stack
+    fn pop_keyboard_modes(&mut self, _count: u16) {}
+
+    /// Modify the keyboard enhancement flags at the top of the stack
+    fn set_keyboard_mode(&mut self, _mode: KeyboardModes, _behavior: KeyboardModesApplyBehavior) {}
+
+    /// Report the active keyboard enhancement flags (should write back to the pty stream)
+    fn report_keyboard_mode<W: io::Write>(&mut self, &mut W) {}
+
+    /// XTMODKEYS - Set how keys with modifiers are reported
+    fn set_modify_other_keys(&mut self, _modify: ModifyOtherKeys) {}
+
+    /// Report the modifyOtherKeys level (should write back to the pty stream)
+    fn report_modify_other_keys<W: io::Write>(&mut self, &mut W) {}
+
+    /// DECCARA - Change the text attributes of the cells in a rectangular area
+    fn set_rectangle_attributes(&mut self, Rectangle, &[Attr]) {}
+
+    /// DECCRA - Copy a rectangular area, placing its top left corner at the position
+    fn copy_rectangle(&mut self, Rectangle, Line, Column) {}
+}
+
+/// Rectangular area of the screen used by the DEC rectangle operations
+///
+/// All bounds are inclusive and zero-based. They are passed on as requested
+/// by the application, so the handler needs to clamp them to the screen.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Rectangle {
+    pub top: Line,
+    pub left: Column,
+    pub bottom: Line,
+    pub right: Column,
+}
+
+/// XTerm's modifyOtherKeys resource levels
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ModifyOtherKeys {
+    /// Send modified keys as usual
+    Reset,
+
+    /// Report modified keys which have no well-known legacy encoding
+    EnableExceptWellDefined,
+
+    /// Report all modified keys
+    EnableAll,
+}
+
+bitflags! {
+    /// Progressive enhancement flags of the kitty keyboard protocol
+    pub struct KeyboardModes: u8 {
+        const NO_MODE                 = 0b0000_0000;
+        const DISAMBIGUATE_ESC_CODES  = 0b0000_0001;
+        const REPORT_EVENT_TYPES      = 0b0000_0010;
+        const REPORT_ALTERNATE_KEYS   = 0b0000_0100;
+        const REPORT_ALL_KEYS_AS_ESC  = 0b0000_1000;
+        const REPORT_ASSOCIATED_TEXT  = 0b0001_0000;
+    }
+}
+
+/// Describes how `CSI = flags ; mode u` combines new and active keyboard flags
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum KeyboardModesApplyBehavior {
+    /// Replace the active flags with the new ones
+    Replace,
+
+    /// Set the new flags in addition to the active ones
+    Union,
+
+    /// Clear the new flags from the active ones
+    Difference,
+}
+
+/// Hyperlink started with OSC 8
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+pub struct Hyperlink {
+    /// Optional `id=` parameter, which joins cells written at different times
+    pub id: Option<String>,
+    pub uri: String,
 }
 
 /// Describes shape of cursor
@@ -372,7 +624,7 @@ impl Default for CursorStyle {
 }
 
 /// Terminal modes
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Mode {
     /// ?1
     CursorKeys = 1,
@@ -395,12 +647,16 @@ pub enum Mode {
     /// * `CSI 4 h` change to insert mode
     /// * `CSI 4 l` reset to replacement mode
     Insert = 4,
+    /// ?5
+    ReverseVideo = 5,
     /// ?6
     Origin = 6,
     /// ?7
     LineWrap = 7,
     /// ?12
     BlinkingCursor = 12,
+    /// ?45
+    ReverseWrap = 45,
     /// 20
     ///
     /// NB This is actually a private mode. We should consider adding a second
@@ -416,12 +672,20 @@ pub enum Mode {
     ReportAllMouseMotion = 1003,
     /// ?1004
     ReportFocusInOut = 1004,
+    /// ?1005
+    Utf8Mouse = 1005,
     /// ?1006
     SgrMouse = 1006,
+    /// ?1015
+    UrxvtMouse = 1015,
+    /// ?47, ?1047
+    SwapScreen = 1047,
     /// ?1049
     SwapScreenAndSetRestoreCursor = 1049,
     /// ?2004
     BracketedPaste = 2004,
+    /// ?2026
+    SyncUpdate = 2026,
 }
 
 impl Mode {
@@ -433,17 +697,23 @@ impl Mode {
             Some(match num {
                 1 => Mode::CursorKeys,
                 3 => Mode::DECCOLM,
+                5 => Mode::ReverseVideo,
                 6 => Mode::Origin,
                 7 => Mode::LineWrap,
                 12 => Mode::BlinkingCursor,
+                45 => Mode::ReverseWrap,
                 25 => Mode::ShowCursor,
                 1000 => Mode::ReportMouseClicks,
                 1002 => Mode::ReportCellMouseMotion,
                 1003 => Mode::ReportAllMouseMotion,
                 1004 => Mode::ReportFocusInOut,
+                1005 => Mode::Utf8Mouse,
                 1006 => Mode::SgrMouse,
+                1015 => Mode::UrxvtMouse,
+                47 | 1047 => Mode::SwapScreen,
                 1049 => Mode::SwapScreenAndSetRestoreCursor,
                 2004 => Mode::BracketedPaste,
+                2026 => Mode::SyncUpdate,
                 _ => {
                     trace!("[unhandled] mode={:?}", num);
                     return None
@@ -622,7 +892,7 @@ pub enum Color {
 }
 
 /// Terminal character attributes
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Attr {
     /// Clear all special abilities
     Reset,
@@ -634,6 +904,14 @@ pub enum Attr {
     Italic,
     /// Underscore text
     Underscore,
+    /// Underscore text twice
+    DoubleUnderline,
+    /// Underscore text with a wavy line
+    CurlyUnderline,
+    /// Underscore text with a dotted line
+    DottedUnderline,
+    /// Underscore text with a dashed line
+    DashedUnderline,
     /// Blink cursor slowly
     BlinkSlow,
     /// Blink cursor fast
@@ -660,10 +938,16 @@ pub enum Attr {
     CancelHidden,
     /// Cancel strike through
     CancelStrike,
+    /// Draw a line above the text
+    Overline,
+    /// Cancel overline
+    CancelOverline,
     /// Set indexed foreground color
     Foreground(Color),
     /// Set indexed background color
     Background(Color),
+    /// Set underline color, `None` follows the foreground color
+    UnderlineColor(Option<Color>),
 }
 
 /// Identifiers which can be assigned to a graphic character set
@@ -718,15 +1002,24 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             C0::SO => self.handler.set_active_charset(CharsetIndex::G1),
             C1::NEL => self.handler.newline(),
             C1::HTS => self.handler.set_horizontal_tabstop(),
-            C1::DECID => self.handler.identify_terminal(self.writer),
+            C1::DECID => self.handler.identify_terminal(self.writer, None),
             _ => debug!("[unhandled] execute byte={:02x}", byte)
         }
     }
 
     #[inline]
     fn hook(&mut self, params: &[i64], intermediates: &[u8], ignore: bool) {
-        debug!("[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}",
-                     params, intermediates, ignore);
+        // Synchronized updates using `DCS = 1 s` and `DCS = 2 s`
+        if self.byte == b's' && intermediates == &b"="[..] && params.len() == 1 {
+            match params[0] {
+                1 => return self.handler.set_mode(Mode::SyncUpdate),
+                2 => return self.handler.unset_mode(Mode::SyncUpdate),
+                _ => (),
+            }
+        }
+
+        debug!("[unhandled hook] params={:?}, ints: {:?}, ignore: {:?}, action: {:?}",
+                     params, intermediates, ignore, self.byte as char);
     }
 
     #[inline]
@@ -822,6 +1115,36 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 unhandled(params);
             }
 
+            // Start or end a hyperlink
+            //
+            // The URI may contain semicolons, which the parser splits on.
+            b"8" if params.len() > 2 => {
+                let uri = params[2..].join(&b';');
+                let uri = match str::from_utf8(&uri) {
+                    Ok(uri) => uri,
+                    Err(_) => return unhandled(params),
+                };
+
+                if uri.is_empty() {
+                    self.handler.set_hyperlink(None);
+                    return;
+                }
+
+                let id = params[1]
+                    .split(|&b| b == b':')
+                    .filter_map(|param| {
+                        if param.len() > 3 && param.starts_with(b"id=") {
+                            str::from_utf8(&param[3..]).ok()
+                        } else {
+                            None
+                        }
+                    })
+                    .next()
+                    .map(String::from);
+
+                self.handler.set_hyperlink(Some(Hyperlink { id, uri: uri.to_owned() }));
+            }
+
             // Set cursor style
             b"50" => {
                 if params.len() >= 2 && params[1].len() >= 13 && params[1][0..12] == *b"CursorShape=" {
@@ -843,12 +1166,22 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     return unhandled(params);
                 }
 
+                // Copying a cut off payload would silently store the wrong text
+                let raw_len = params.iter().map(|param| param.len()).sum::<usize>() + params.len() - 1;
+                if raw_len >= MAX_OSC_RAW {
+                    warn!("Ignoring clipboard request longer than {} bytes", MAX_OSC_RAW);
+                    return;
+                }
+
+                // An empty selection parameter defaults to `s 0`, which xterm
+                // treats as the clipboard
+                let clipboard = params[1].get(0).cloned().unwrap_or(b'c');
                 match params[2] {
-                    b"?" => unhandled(params),
-                    selection => {
-                        if let Ok(string) = base64::decode(selection) {
+                    b"?" => self.handler.write_clipboard(clipboard, self.writer),
+                    data => {
+                        if let Ok(string) = base64::decode(data) {
                             if let Ok(utf8_string) = str::from_utf8(&string) {
-                                self.handler.set_clipboard(utf8_string);
+                                self.handler.set_clipboard(clipboard, utf8_string);
                             }
                         }
                     }
@@ -926,16 +1259,16 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             },
             'b' => {
                 if let Some(c) = self._state.preceding_char {
-                    for _ in 0..arg_or_default!(idx: 0, default: 1) {
-                        handler.input(c);
-                    }
+                    handler.repeat(c, arg_or_default!(idx: 0, default: 1) as usize);
                 }
                 else {
                     warn!("tried to repeat with no preceding char");
                 }
             },
             'B' | 'e' => handler.move_down(Line(arg_or_default!(idx: 0, default: 1) as usize)),
-            'c' => handler.identify_terminal(writer),
+            'c' if intermediates.is_empty() || intermediates == &b">"[..] => {
+                handler.identify_terminal(writer, intermediates.get(0).map(|&b| b as char))
+            },
             'C' | 'a' => handler.move_forward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'D' => handler.move_backward(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'E' => handler.move_down_and_cr(Line(arg_or_default!(idx: 0, default: 1) as usize)),
@@ -979,6 +1312,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             },
             'S' => handler.scroll_up(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             'T' => handler.scroll_down(Line(arg_or_default!(idx: 0, default: 1) as usize)),
+            't' => match arg_or_default!(idx: 0, default: 1) {
+                14 => handler.text_area_size_pixels(writer),
+                16 => handler.cell_size_pixels(writer),
+                18 => {
+                    let _ = write!(writer, "\x1b[8;{};{}t", handler.lines(), handler.cols());
+                },
+                19 => handler.screen_size_chars(writer),
+                // Only the window title is supported, icon titles are ignored
+                22 if args.get(1).cloned().unwrap_or(0) != 1 => handler.push_title(),
+                23 if args.get(1).cloned().unwrap_or(0) != 1 => handler.pop_title(),
+                _ => unhandled!(),
+            },
             'L' => handler.insert_blank_lines(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             'l' => {
                 for arg in args {
@@ -991,6 +1336,17 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             },
             'M' => handler.delete_lines(Line(arg_or_default!(idx: 0, default: 1) as usize)),
             'X' => handler.erase_chars(Column(arg_or_default!(idx: 0, default: 1) as usize)),
+            'p' if intermediates.get(0) == Some(&b'!') => handler.soft_reset(),
+            'p' if intermediates.last() == Some(&b'$') => {
+                let mode_number = arg_or_default!(idx: 0, default: 0);
+                match Mode::from_primitive(private, mode_number) {
+                    Some(mode) => handler.report_mode(writer, mode, mode_number),
+                    None => {
+                        let prefix = if private { "?" } else { "" };
+                        let _ = write!(writer, "\x1b[{}{};0$y", prefix, mode_number);
+                    },
+                }
+            },
             'P' => handler.delete_chars(Column(arg_or_default!(idx: 0, default: 1) as usize)),
             'Z' => handler.move_backward_tabs(arg_or_default!(idx: 0, default: 1)),
             'd' => handler.goto_line(Line(arg_or_default!(idx: 0, default: 1) as usize - 1)),
@@ -1003,6 +1359,27 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     }
                 }
             },
+            'm' if intermediates.get(0) == Some(&b'>') => {
+                // Only the modifyOtherKeys resource is supported
+                if args.get(0) != Some(&4) {
+                    unhandled!();
+                }
+
+                let modify = match args.get(1).cloned().unwrap_or(0) {
+                    0 => ModifyOtherKeys::Reset,
+                    1 => ModifyOtherKeys::EnableExceptWellDefined,
+                    2 => ModifyOtherKeys::EnableAll,
+                    _ => unhandled!(),
+                };
+                handler.set_modify_other_keys(modify);
+            },
+            'm' if private => {
+                if args.get(0) != Some(&4) {
+                    unhandled!();
+                }
+
+                handler.report_modify_other_keys(writer);
+            },
             'm' => {
                 // Sometimes a C-style for loop is just what you need
                 let mut i = 0; // C-for initializer
@@ -1070,6 +1447,18 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                             }
                         },
                         49 => Attr::Background(Color::Named(NamedColor::Background)),
+                        53 => Attr::Overline,
+                        55 => Attr::CancelOverline,
+                        58 => {
+                            let mut start = 0;
+                            if let Some(color) = parse_color(&args[i..], &mut start) {
+                                i += start;
+                                Attr::UnderlineColor(Some(color))
+                            } else {
+                                break;
+                            }
+                        },
+                        59 => Attr::UnderlineColor(None),
                         90 => Attr::Foreground(Color::Named(NamedColor::BrightBlack)),
                         91 => Attr::Foreground(Color::Named(NamedColor::BrightRed)),
                         92 => Attr::Foreground(Color::Named(NamedColor::BrightGreen)),
@@ -1094,7 +1483,45 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                     i += 1; // C-for expr
                 }
             }
+            'n' if private => {
+                handler.private_device_status(writer, arg_or_default!(idx: 0, default: 0) as usize)
+            },
             'n' => handler.device_status(writer, arg_or_default!(idx: 0, default: 0) as usize),
+            'r' if intermediates.last() == Some(&b'$') => {
+                let area = parse_rectangle(args, handler.lines(), handler.cols());
+
+                // Only the attributes supported by the VT400 series are applied
+                let mut attrs = Vec::new();
+                for arg in args.iter().skip(4) {
+                    let attr = match *arg {
+                        0 => Attr::Reset,
+                        1 => Attr::Bold,
+                        4 => Attr::Underscore,
+                        5 => Attr::BlinkSlow,
+                        7 => Attr::Reverse,
+                        22 => Attr::CancelBoldDim,
+                        24 => Attr::CancelUnderline,
+                        25 => Attr::CancelBlink,
+                        27 => Attr::CancelReverse,
+                        _ => continue,
+                    };
+                    attrs.push(attr);
+                }
+
+                // Without any attributes, all of them are turned off
+                if args.len() <= 4 {
+                    attrs.push(Attr::Reset);
+                }
+                handler.set_rectangle_attributes(area, &attrs);
+            },
+            'v' if intermediates.last() == Some(&b'$') => {
+                let area = parse_rectangle(args, handler.lines(), handler.cols());
+
+                // Pages are not supported, so the source and destination page are ignored
+                let line = arg_or_default!(idx: 5, default: 1) as usize;
+                let col = arg_or_default!(idx: 6, default: 1) as usize;
+                handler.copy_rectangle(area, Line(line - 1), Column(col - 1));
+            },
             'r' => {
                 if private {
                     unhandled!();
@@ -1111,9 +1538,35 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 handler.set_scrolling_region(top..bottom);
             },
             's' => handler.save_cursor_position(),
-            'u' => handler.restore_cursor_position(),
-            'q' => {
-                let style = match arg_or_default!(idx: 0, default: 0) {
+            'u' => match intermediates.get(0) {
+                None => handler.restore_cursor_position(),
+                Some(b'?') => handler.report_keyboard_mode(writer),
+                Some(b'>') => {
+                    let mode = arg_or_default!(idx: 0, default: 0) as u8;
+                    handler.push_keyboard_mode(KeyboardModes::from_bits_truncate(mode));
+                },
+                Some(b'<') => {
+                    let count = arg_or_default!(idx: 0, default: 1) as u16;
+                    handler.pop_keyboard_modes(count);
+                },
+                Some(b'=') => {
+                    let mode = KeyboardModes::from_bits_truncate(
+                        arg_or_default!(idx: 0, default: 0) as u8
+                    );
+                    let behavior = match arg_or_default!(idx: 1, default: 1) {
+                        1 => KeyboardModesApplyBehavior::Replace,
+                        2 => KeyboardModesApplyBehavior::Union,
+                        3 => KeyboardModesApplyBehavior::Difference,
+                        _ => unhandled!(),
+                    };
+                    handler.set_keyboard_mode(mode, behavior);
+                },
+                _ => unhandled!(),
+            },
+            'q' if intermediates.get(0) == Some(&b' ') => {
+                // Odd styles blink, `0` restores the steady configured style
+                let arg = arg_or_default!(idx: 0, default: 0);
+                let style = match arg {
                     0 => None,
                     1 | 2 => Some(CursorStyle::Block),
                     3 | 4 => Some(CursorStyle::Underline),
@@ -1122,6 +1575,7 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
                 };
 
                 handler.set_cursor_style(style);
+                handler.set_cursor_blinking(arg % 2 == 1);
             }
             _ => unhandled!(),
         }
@@ -1156,8 +1610,23 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             }}
         }
 
+        // Designate a G0-G3 character set
+        match intermediates.first() {
+            Some(&b'(') | Some(&b')') | Some(&b'*') | Some(&b'+') => {
+                match byte {
+                    b'B' => configure_charset!(StandardCharset::Ascii),
+                    b'0' => configure_charset!(StandardCharset::SpecialCharacterAndLineDrawing),
+                    _ => {
+                        debug!("Unsupported character set {:?}, using ASCII", byte as char);
+                        configure_charset!(StandardCharset::Ascii)
+                    },
+                }
+                return;
+            },
+            _ => (),
+        }
+
         match byte {
-            b'B' => configure_charset!(StandardCharset::Ascii),
             b'D' => self.handler.linefeed(),
             b'E' => {
                 self.handler.linefeed();
@@ -1165,9 +1634,8 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
             }
             b'H' => self.handler.set_horizontal_tabstop(),
             b'M' => self.handler.reverse_index(),
-            b'Z' => self.handler.identify_terminal(self.writer),
+            b'Z' => self.handler.identify_terminal(self.writer, None),
             b'c' => self.handler.reset_state(),
-            b'0' => configure_charset!(StandardCharset::SpecialCharacterAndLineDrawing),
             b'7' => self.handler.save_cursor_position(),
             b'8' => {
                 if !intermediates.is_empty() && intermediates[0] == b'#' {
@@ -1184,6 +1652,35 @@ impl<'a, H, W> vte::Perform for Performer<'a, H, W>
     }
 }
 
+/// Parse the `Pt ; Pl ; Pb ; Pr` bounds of a rectangular area
+///
+/// Omitted bounds default to the edges of the screen.
+fn parse_rectangle(args: &[i64], lines: Line, cols: Column) -> Rectangle {
+    let arg = |idx: usize, default: usize| match args.get(idx) {
+        Some(&value) if value > 0 => value as usize,
+        _ => default,
+    };
+
+    Rectangle {
+        top: Line(arg(0, 1) - 1),
+        left: Column(arg(1, 1) - 1),
+        bottom: Line(arg(2, lines.0) - 1),
+        right: Column(arg(3, cols.0) - 1),
+    }
+}
+
+/// Underline style selected with `CSI 4:n m`
+fn underline_style(style: i64) -> Option<Attr> {
+    match style {
+        0 => Some(Attr::CancelUnderline),
+        1 => Some(Attr::Underscore),
+        2 => Some(Attr::DoubleUnderline),
+        3 => Some(Attr::CurlyUnderline),
+        4 => Some(Attr::DottedUnderline),
+        5 => Some(Attr::DashedUnderline),
+        _ => None,
+    }
+}
 
 /// Parse a color specifier from list of attributes
 fn parse_color(attrs: &[i64], i: &mut usize) -> Option<Color> {
@@ -1224,15 +1721,10 @@ fn parse_color(attrs: &[i64], i: &mut usize) -> Option<Color> {
             } else {
                 *i += 2;
                 let idx = attrs[*i];
-                match idx {
-                    0 ... 255 => {
-                        Some(Color::Indexed(idx as u8))
-                    },
-                    _ => {
-                        warn!("Invalid color index: {}", idx);
-                        None
-                    }
+                if idx > 255 {
+                    warn!("Invalid color index: {}; Using 255 instead", idx);
                 }
+                Some(Color::Indexed(min(idx, 255) as u8))
             }
         },
         _ => {
@@ -1393,8 +1885,12 @@ pub mod C1 {
 #[cfg(test)]
 mod tests {
     use std::io;
+    use base64;
     use index::{Line, Column};
-    use super::{Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, parse_rgb_color, parse_number};
+    use super::{
+        Processor, Handler, Attr, TermInfo, Color, StandardCharset, CharsetIndex, ModifyOtherKeys,
+        CursorStyle, Mode, Rectangle, Hyperlink, parse_rgb_color, parse_number,
+    };
     use ::Rgb;
 
     /// The /dev/null of `io::Write`
@@ -1413,12 +1909,58 @@ mod tests {
     #[derive(Default)]
     struct AttrHandler {
         attr: Option<Attr>,
+        modify_other_keys: Option<ModifyOtherKeys>,
+        cursor_style: Option<Option<CursorStyle>>,
+        cursor_blinking: Option<bool>,
+        rectangle_attrs: Option<(Rectangle, Vec<Attr>)>,
+        rectangle_copy: Option<(Rectangle, Line, Column)>,
+        hyperlink: Option<Option<Hyperlink>>,
+        clipboard: Option<String>,
+        clipboard_selection: Option<u8>,
+        mode: Option<(Mode, bool)>,
     }
 
     impl Handler for AttrHandler {
         fn terminal_attribute(&mut self, attr: Attr) {
             self.attr = Some(attr);
         }
+
+        fn set_modify_other_keys(&mut self, modify: ModifyOtherKeys) {
+            self.modify_other_keys = Some(modify);
+        }
+
+        fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
+            self.cursor_style = Some(style);
+        }
+
+        fn set_cursor_blinking(&mut self, blinking: bool) {
+            self.cursor_blinking = Some(blinking);
+        }
+
+        fn set_rectangle_attributes(&mut self, area: Rectangle, attrs: &[Attr]) {
+            self.rectangle_attrs = Some((area, attrs.to_vec()));
+        }
+
+        fn copy_rectangle(&mut self, area: Rectangle, line: Line, col: Column) {
+            self.rectangle_copy = Some((area, line, col));
+        }
+
+        fn set_hyperlink(&mut self, link: Option<Hyperlink>) {
+            self.hyperlink = Some(link);
+        }
+
+        fn set_clipboard(&mut self, clipboard: u8, data: &str) {
+            self.clipboard = Some(data.into());
+            self.clipboard_selection = Some(clipboard);
+        }
+
+        fn set_mode(&mut self, mode: Mode) {
+            self.mode = Some((mode, true));
+        }
+
+        fn unset_mode(&mut self, mode: Mode) {
+            self.mode = Some((mode, false));
+        }
     }
 
     impl TermInfo for AttrHandler {
@@ -1447,6 +1989,53 @@ mod tests {
         assert_eq!(handler.attr, Some(Attr::Bold));
     }
 
+    #[test]
+    fn parse_text_attributes() {
+        let attrs = [
+            (&b"\x1b[2m"[..], Attr::Dim),
+            (b"\x1b[3m", Attr::Italic),
+            (b"\x1b[8m", Attr::Hidden),
+            (b"\x1b[9m", Attr::Strike),
+            (b"\x1b[22m", Attr::CancelBoldDim),
+            (b"\x1b[23m", Attr::CancelItalic),
+            (b"\x1b[28m", Attr::CancelHidden),
+            (b"\x1b[29m", Attr::CancelStrike),
+            (b"\x1b[0m", Attr::Reset),
+        ];
+
+        for &(bytes, ref attr) in &attrs {
+            let mut parser = Processor::new();
+            let mut handler = AttrHandler::default();
+
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+
+            assert_eq!(handler.attr.as_ref(), Some(attr));
+        }
+    }
+
+    #[test]
+    fn parse_sync_update_dcs() {
+        let sequences = [
+            (&b"\x1bP=1s\x1b\\"[..], Some((Mode::SyncUpdate, true))),
+            (b"\x1bP=2s\x1b\\", Some((Mode::SyncUpdate, false))),
+            // Same parameters with a different final byte
+            (b"\x1bP=1q\x1b\\", None),
+        ];
+
+        for &(bytes, ref mode) in &sequences {
+            let mut parser = Processor::new();
+            let mut handler = AttrHandler::default();
+
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+
+            assert_eq!(&handler.mode, mode);
+        }
+    }
+
     #[test]
     fn parse_truecolor_attr() {
         static BYTES: &'static [u8] = &[
@@ -1470,6 +2059,327 @@ mod tests {
         assert_eq!(handler.attr, Some(Attr::Foreground(Color::Spec(spec))));
     }
 
+    #[test]
+    fn parse_truecolor_background_after_other_attrs() {
+        static BYTES: &'static [u8] = b"\x1b[1;48;2;1;2;3m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        let spec = Rgb { r: 1, g: 2, b: 3 };
+        assert_eq!(handler.attr, Some(Attr::Background(Color::Spec(spec))));
+    }
+
+    #[test]
+    fn parse_truecolor_attr_missing_components() {
+        // Too few parameters must not read past the end of the arguments
+        static BYTES: &'static [u8] = b"\x1b[1;38;2;1;2m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.attr, Some(Attr::Bold));
+    }
+
+    #[test]
+    fn parse_truecolor_attr_with_colons() {
+        let spec = Color::Spec(Rgb { r: 1, g: 2, b: 3 });
+        let sequences = [
+            (&b"\x1b[38:2::1:2:3m"[..], Attr::Foreground(spec)),
+            (b"\x1b[38:2:1:2:3m", Attr::Foreground(spec)),
+            (b"\x1b[1;48:2:0:1:2:3m", Attr::Background(spec)),
+            (b"\x1b[58:2::1:2:3m", Attr::UnderlineColor(Some(spec))),
+            (b"\x1b[38:5:196m", Attr::Foreground(Color::Indexed(196))),
+            // Parameters after the color are applied too
+            (b"\x1b[38:2::1:2:3;1m", Attr::Bold),
+        ];
+
+        for &(bytes, ref attr) in &sequences {
+            let mut parser = Processor::new();
+            let mut handler = AttrHandler::default();
+
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+
+            assert_eq!(handler.attr.as_ref(), Some(attr));
+        }
+    }
+
+    #[test]
+    fn parse_truecolor_attr_with_colons_missing_components() {
+        // A malformed color must not take the parameters behind it
+        static BYTES: &'static [u8] = b"\x1b[1;38:2:1:2;4m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.attr, Some(Attr::Underscore));
+    }
+
+    #[test]
+    fn parse_indexed_color_attr() {
+        static BYTES: &'static [u8] = b"\x1b[48;5;196m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.attr, Some(Attr::Background(Color::Indexed(196))));
+    }
+
+    #[test]
+    fn parse_indexed_color_attr_out_of_range() {
+        static BYTES: &'static [u8] = b"\x1b[38;5;1000m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.attr, Some(Attr::Foreground(Color::Indexed(255))));
+    }
+
+    #[test]
+    fn parse_underline_style_attrs() {
+        let attrs = [
+            (&b"\x1b[4:0m"[..], Attr::CancelUnderline),
+            (b"\x1b[4:1m", Attr::Underscore),
+            (b"\x1b[4:2m", Attr::DoubleUnderline),
+            (b"\x1b[4:3m", Attr::CurlyUnderline),
+            (b"\x1b[4:4m", Attr::DottedUnderline),
+            (b"\x1b[4:5m", Attr::DashedUnderline),
+            (b"\x1b[1;4:3m", Attr::CurlyUnderline),
+            // Unknown styles are ignored
+            (b"\x1b[1;4:9m", Attr::Bold),
+            // SGR 21 still cancels bold
+            (b"\x1b[21m", Attr::CancelBold),
+        ];
+
+        for &(bytes, ref attr) in &attrs {
+            let mut parser = Processor::new();
+            let mut handler = AttrHandler::default();
+
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+
+            assert_eq!(handler.attr.as_ref(), Some(attr));
+        }
+    }
+
+    #[test]
+    fn parse_overline_attr() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &b"\x1b[53m"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(handler.attr, Some(Attr::Overline));
+
+        for byte in &b"\x1b[55m"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(handler.attr, Some(Attr::CancelOverline));
+    }
+
+    #[test]
+    fn parse_text_area_size_queries() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+        let mut reply = Vec::new();
+
+        for byte in &b"\x1b[18t"[..] {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+
+        assert_eq!(reply, b"\x1b[8;24;80t".to_vec());
+    }
+
+    #[test]
+    fn parse_clipboard_store_default_selection() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &b"\x1b]52;;YWJj\x07"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(handler.clipboard, Some(String::from("abc")));
+        assert_eq!(handler.clipboard_selection, Some(b'c'));
+    }
+
+    #[test]
+    fn parse_clipboard_store() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+        let mut reply = Vec::new();
+
+        for byte in &b"\x1b]52;c;YWJj\x07"[..] {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+        assert_eq!(handler.clipboard, Some(String::from("abc")));
+
+        // Payloads which don't fit into the parser's buffer are cut off
+        handler.clipboard = None;
+        let mut bytes = b"\x1b]52;c;".to_vec();
+        bytes.extend(base64::encode(&[b'a'; 1000][..]).bytes());
+        bytes.push(0x07);
+        for byte in bytes {
+            parser.advance(&mut handler, byte, &mut reply);
+        }
+        assert_eq!(handler.clipboard, None);
+    }
+
+    #[test]
+    fn parse_unknown_mode_request() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+        let mut reply = Vec::new();
+
+        for byte in &b"\x1b[?9999$p\x1b[2$p"[..] {
+            parser.advance(&mut handler, *byte, &mut reply);
+        }
+
+        assert_eq!(reply, b"\x1b[?9999;0$y\x1b[2;0$y".to_vec());
+    }
+
+    #[test]
+    fn parse_hyperlink() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        let link = |id: Option<&str>, uri: &str| Some(Hyperlink {
+            id: id.map(String::from),
+            uri: uri.into(),
+        });
+        let links = [
+            (&b"\x1b]8;;https://a.de\x07"[..], link(None, "https://a.de")),
+            (&b"\x1b]8;id=x;https://a.de\x07"[..], link(Some("x"), "https://a.de")),
+            (&b"\x1b]8;foo=bar:id=x;https://a.de/?a;b\x1b\\"[..], link(Some("x"), "https://a.de/?a;b")),
+            (&b"\x1b]8;id=;https://a.de\x07"[..], link(None, "https://a.de")),
+            (&b"\x1b]8;;\x07"[..], None),
+        ];
+
+        for &(bytes, ref expected) in &links {
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+            assert_eq!(handler.hyperlink.take().as_ref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn parse_cursor_style() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        let styles = [
+            (&b"\x1b[ q"[..], None, false),
+            (&b"\x1b[0 q"[..], None, false),
+            (&b"\x1b[1 q"[..], Some(CursorStyle::Block), true),
+            (&b"\x1b[2 q"[..], Some(CursorStyle::Block), false),
+            (&b"\x1b[3 q"[..], Some(CursorStyle::Underline), true),
+            (&b"\x1b[4 q"[..], Some(CursorStyle::Underline), false),
+            (&b"\x1b[5 q"[..], Some(CursorStyle::Beam), true),
+            (&b"\x1b[6 q"[..], Some(CursorStyle::Beam), false),
+        ];
+
+        for &(bytes, style, blinking) in &styles {
+            for byte in bytes {
+                parser.advance(&mut handler, *byte, &mut Void);
+            }
+            assert_eq!(handler.cursor_style.take(), Some(style));
+            assert_eq!(handler.cursor_blinking.take(), Some(blinking));
+        }
+    }
+
+    #[test]
+    fn parse_underline_color_attr() {
+        static BYTES: &'static [u8] = b"\x1b[58;2;128;66;255m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        let spec = Rgb {
+            r: 128,
+            g: 66,
+            b: 255
+        };
+
+        assert_eq!(handler.attr, Some(Attr::UnderlineColor(Some(Color::Spec(spec)))));
+
+        for byte in &b"\x1b[4;58;5;9;59m"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.attr, Some(Attr::UnderlineColor(None)));
+    }
+
+    #[test]
+    fn parse_modify_other_keys() {
+        static BYTES: &'static [u8] = b"\x1b[>4;2m";
+
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &BYTES[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.modify_other_keys, Some(ModifyOtherKeys::EnableAll));
+        assert_eq!(handler.attr, None);
+    }
+
+    #[test]
+    fn parse_rectangle_operations() {
+        let mut parser = Processor::new();
+        let mut handler = AttrHandler::default();
+
+        for byte in &b"\x1b[2;3;4;5;1;7;8$r"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        let area = Rectangle { top: Line(1), left: Column(2), bottom: Line(3), right: Column(4) };
+        assert_eq!(handler.rectangle_attrs, Some((area, vec![Attr::Bold, Attr::Reverse])));
+        assert_eq!(handler.attr, None);
+
+        // Omitted bounds cover the whole screen and no attributes turn all of them off
+        for byte in &b"\x1b[$r"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        let screen = Rectangle { top: Line(0), left: Column(0), bottom: Line(23), right: Column(79) };
+        assert_eq!(handler.rectangle_attrs, Some((screen, vec![Attr::Reset])));
+
+        for byte in &b"\x1b[2;3;4;5;1;10;20;1$v"[..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+
+        assert_eq!(handler.rectangle_copy, Some((area, Line(9), Column(19))));
+    }
+
     /// No exactly a test; useful for debugging
     #[test]
     fn parse_zsh_startup() {
@@ -1563,6 +2473,24 @@ mod tests {
         assert_eq!(handler.index, CharsetIndex::G1);
     }
 
+    #[test]
+    fn parse_designate_unsupported_charset_as_ascii() {
+        static BYTES: &'static [u8] = b"\x1b(0\x1b(A";
+        let mut parser = Processor::new();
+        let mut handler = CharsetHandler::default();
+
+        for byte in &BYTES[..3] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(handler.charset, StandardCharset::SpecialCharacterAndLineDrawing);
+
+        for byte in &BYTES[3..] {
+            parser.advance(&mut handler, *byte, &mut Void);
+        }
+        assert_eq!(handler.index, CharsetIndex::G0);
+        assert_eq!(handler.charset, StandardCharset::Ascii);
+    }
+
     #[test]
     fn parse_valid_rgb_color() {
         assert_eq!(parse_rgb_color(b"rgb:11/aa/ff"), Some(Rgb { r: 0x11, g: 0xaa, b: 0xff }));
@@ -0,0 +1,86 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Timer for blinking text
+//!
+//! While there is blinking text on screen, a ticker advances the blink epoch at
+//! the configured rate and wakes up the render loop, so an idle terminal
+//! doesn't wake up at all. The epoch is shared with the terminal through an
+//! atomic, so it can be sampled each frame without taking any locks.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use display::Notifier;
+use ticker::Ticker;
+
+pub struct Blink {
+    /// Time between blink phases in milliseconds, `0` disables blinking
+    rate_ms: u64,
+
+    /// Whether the timer is running
+    active: bool,
+
+    timer: Ticker,
+}
+
+impl Blink {
+    /// Create the blink timer
+    ///
+    /// A `rate_ms` of `0` disables blinking.
+    pub fn new(rate_ms: u64, notifier: Notifier) -> Blink {
+        let timer = Ticker::new("blink timer", Duration::from_millis(rate_ms.max(1)), notifier);
+
+        Blink {
+            rate_ms,
+            active: false,
+            timer,
+        }
+    }
+
+    /// Change the blink rate, `0` disables blinking
+    pub fn set_rate(&mut self, rate_ms: u64) {
+        self.rate_ms = rate_ms;
+        if rate_ms == 0 {
+            self.set_active(false);
+        } else {
+            self.timer.set_interval(Duration::from_millis(rate_ms));
+        }
+    }
+
+    /// Start or stop blinking, depending on whether blinking text is visible
+    ///
+    /// Blinking always starts and ends in the visible phase.
+    pub fn set_active(&mut self, active: bool) {
+        let active = active && self.rate_ms != 0;
+        if active == self.active {
+            return;
+        }
+
+        self.active = active;
+        if active {
+            self.timer.ticks().store(0, Ordering::Relaxed);
+            self.timer.start();
+        } else {
+            self.timer.stop();
+            self.timer.ticks().store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Number of times the blink state changed
+    #[inline]
+    pub fn epoch(&self) -> &Arc<AtomicUsize> {
+        self.timer.ticks()
+    }
+}
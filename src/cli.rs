@@ -18,6 +18,8 @@ use config::{Dimensions, Shell};
 use window::{DEFAULT_TITLE, DEFAULT_CLASS};
 use std::path::{Path, PathBuf};
 use std::borrow::Cow;
+use std::env;
+use std::ffi::OsString;
 
 /// Options specified on the command line
 pub struct Options {
@@ -31,6 +33,8 @@ pub struct Options {
     pub command: Option<Shell<'static>>,
     pub working_dir: Option<PathBuf>,
     pub config: Option<PathBuf>,
+    pub message: Option<String>,
+    pub socket: Option<PathBuf>,
 }
 
 impl Default for Options {
@@ -46,6 +50,8 @@ impl Default for Options {
             command: None,
             working_dir: None,
             config: None,
+            message: None,
+            socket: None,
         }
     }
 }
@@ -53,6 +59,13 @@ impl Default for Options {
 impl Options {
     /// Build `Options` from command line arguments
     pub fn load() -> Options {
+        Options::from_args(env::args_os())
+    }
+
+    fn from_args<I, T>(args: I) -> Options
+        where I: IntoIterator<Item = T>,
+              T: Into<OsString> + Clone
+    {
         let mut options = Options::default();
 
         let matches = App::new(crate_name!())
@@ -105,6 +118,17 @@ impl Options {
                  .takes_value(true)
                  .help("Specify alternative configuration file \
                        [default: $XDG_CONFIG_HOME/alacritty/alacritty.yml]"))
+            .arg(Arg::with_name("message")
+                 .long("message")
+                 .takes_value(true)
+                 .value_name("json")
+                 .help("Send a message to a running instance and exit, \
+                       e.g. '{\"action\": \"reload-config\"}'"))
+            .arg(Arg::with_name("socket")
+                 .long("socket")
+                 .takes_value(true)
+                 .requires("message")
+                 .help("Socket of the instance receiving --message [default: $ALACRITTY_SOCKET]"))
             .arg(Arg::with_name("command")
                 .long("command")
                 .short("e")
@@ -113,7 +137,12 @@ impl Options {
                 .min_values(1)
                 .allow_hyphen_values(true)
                 .help("Command and args to execute (must be last argument)"))
-            .get_matches();
+            .arg(Arg::with_name("program")
+                .multiple(true)
+                .last(true)
+                .conflicts_with("command")
+                .help("Program and args to execute instead of the shell"))
+            .get_matches_from(args);
 
         if matches.is_present("ref-test") {
             options.ref_test = true;
@@ -161,10 +190,15 @@ impl Options {
             options.config = Some(PathBuf::from(path.to_string()));
         }
 
-        if let Some(mut args) = matches.values_of("command") {
+        options.message = matches.value_of("message").map(|m| m.to_owned());
+        options.socket = matches.value_of("socket").map(PathBuf::from);
+
+        // Everything after `--` is handled just like the arguments of `-e`
+        let command = matches.values_of("command").or_else(|| matches.values_of("program"));
+        if let Some(mut args) = command {
             // The following unwrap is guaranteed to succeed.
             // If 'command' exists it must also have a first item since
-            // Arg::min_values(1) is set.
+            // Arg::min_values(1) is set, positional values are never empty.
             let command = String::from(args.next().unwrap());
             let args = args.map(String::from).collect();
             options.command = Some(Shell::new_with_args(command, args));
@@ -185,3 +219,19 @@ impl Options {
         self.config.as_ref().map(|p| Cow::Borrowed(p.as_path()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    #[test]
+    fn program_after_double_dash() {
+        let options = Options::from_args(vec!["alacritty", "-v", "--", "vim", "-u", "NONE", "-e"]);
+        let command = options.command.expect("command");
+        assert_eq!(command.program(), "vim");
+        assert_eq!(command.args(), &["-u", "NONE", "-e"]);
+
+        // Flags in front of `--` are still parsed
+        assert_eq!(options.log_level, ::log::LevelFilter::Info);
+    }
+}
@@ -14,7 +14,7 @@ use std::time::Duration;
 use std::collections::HashMap;
 
 use ::Rgb;
-use font::Size;
+use font::{self, Size};
 use serde_yaml;
 use serde::{self, de, Deserialize};
 use serde::de::Error as SerdeError;
@@ -22,8 +22,8 @@ use serde::de::{Visitor, MapAccess, Unexpected};
 use notify::{Watcher, watcher, DebouncedEvent, RecursiveMode};
 
 use glutin::ModifiersState;
+use regex::Regex;
 
-use cli::Options;
 use input::{Action, Binding, MouseBinding, KeyBinding};
 use index::{Line, Column};
 use ansi::{CursorStyle, NamedColor, Color};
@@ -38,6 +38,15 @@ fn true_bool() -> bool {
 #[derive(Clone, Debug, Deserialize)]
 pub struct Selection {
     pub semantic_escape_chars: String,
+
+    /// Words for semantic selection, replacing the `semantic_escape_chars`
+    #[serde(default, deserialize_with = "deserialize_word_pattern")]
+    pub word_pattern: Option<Regex>,
+
+    /// Start line selections at the first non-whitespace character
+    #[serde(default, deserialize_with = "failure_default")]
+    pub skip_leading_whitespace: bool,
+
     #[serde(default, deserialize_with = "failure_default")]
     pub save_to_clipboard: bool,
 }
@@ -46,20 +55,50 @@ impl Default for Selection {
     fn default() -> Selection {
         Selection {
             semantic_escape_chars: String::new(),
+            word_pattern: None,
+            skip_leading_whitespace: false,
             save_to_clipboard: false
         }
     }
 }
 
+fn deserialize_word_pattern<'a, D>(deserializer: D) -> ::std::result::Result<Option<Regex>, D::Error>
+    where D: de::Deserializer<'a>
+{
+    let pattern = match Option::<String>::deserialize(deserializer) {
+        Ok(Some(pattern)) => pattern,
+        Ok(None) => return Ok(None),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using semantic_escape_chars", err);
+            return Ok(None);
+        },
+    };
+
+    match Regex::new(&pattern) {
+        Ok(regex) => Ok(Some(regex)),
+        Err(err) => {
+            eprintln!("problem with config: invalid word_pattern: {}; Using semantic_escape_chars", err);
+            Ok(None)
+        },
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ClickHandler {
     #[serde(deserialize_with="deserialize_duration_ms")]
     pub threshold: Duration,
+
+    /// Maximum distance in pixels the mouse may move between the clicks
+    #[serde(default = "default_click_distance", deserialize_with = "deserialize_click_distance")]
+    pub distance: usize,
 }
 
 impl Default for ClickHandler {
     fn default() -> Self {
-        ClickHandler { threshold: default_threshold_ms() }
+        ClickHandler {
+            threshold: default_threshold_ms(),
+            distance: default_click_distance(),
+        }
     }
 }
 
@@ -67,6 +106,22 @@ fn default_threshold_ms() -> Duration {
     Duration::from_millis(300)
 }
 
+fn default_click_distance() -> usize {
+    5
+}
+
+fn deserialize_click_distance<'a, D>(deserializer: D) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match usize::deserialize(deserializer) {
+        Ok(distance) => Ok(distance),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using default value", err);
+            Ok(default_click_distance())
+        },
+    }
+}
+
 fn deserialize_duration_ms<'a, D>(deserializer: D) -> ::std::result::Result<Duration, D::Error>
     where D: de::Deserializer<'a>
 {
@@ -90,22 +145,56 @@ pub struct Mouse {
     #[serde(default, deserialize_with = "failure_default")]
     pub url: Url,
 
+    // Modifier used to start a block selection
+    #[serde(
+        default = "default_block_selection_modifiers",
+        deserialize_with = "deserialize_modifiers"
+    )]
+    pub block_selection_modifiers: ModifiersState,
+
+    // Action of the right mouse button
+    #[serde(default, deserialize_with = "failure_default")]
+    pub right_click_action: RightClickAction,
+
     // TODO: DEPRECATED
     #[serde(default)]
     pub faux_scrollback_lines: Option<usize>,
 }
 
-#[derive(Default, Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Url {
     // Program for opening links
     #[serde(default, deserialize_with = "failure_default")]
     pub launcher: Option<CommandWrapper>,
 
     // Modifier used to open links
-    #[serde(default, deserialize_with = "deserialize_modifiers")]
+    #[serde(default = "default_url_modifiers", deserialize_with = "deserialize_modifiers")]
     pub modifiers: ModifiersState,
 }
 
+impl Default for Url {
+    fn default() -> Url {
+        Url {
+            launcher: None,
+            modifiers: default_url_modifiers(),
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn default_url_modifiers() -> ModifiersState {
+    ModifiersState { ctrl: true, ..ModifiersState::default() }
+}
+
+#[cfg(target_os = "macos")]
+fn default_url_modifiers() -> ModifiersState {
+    ModifiersState { logo: true, ..ModifiersState::default() }
+}
+
+fn default_block_selection_modifiers() -> ModifiersState {
+    ModifiersState { alt: true, ..ModifiersState::default() }
+}
+
 fn deserialize_modifiers<'a, D>(deserializer: D) -> ::std::result::Result<ModifiersState, D::Error>
     where D: de::Deserializer<'a>
 {
@@ -117,17 +206,40 @@ impl Default for Mouse {
         Mouse {
             double_click: ClickHandler {
                 threshold: Duration::from_millis(300),
+                distance: default_click_distance(),
             },
             triple_click: ClickHandler {
                 threshold: Duration::from_millis(300),
+                distance: default_click_distance(),
             },
             hide_when_typing: false,
             url: Url::default(),
+            block_selection_modifiers: default_block_selection_modifiers(),
+            right_click_action: RightClickAction::default(),
             faux_scrollback_lines: None,
         }
     }
 }
 
+/// What happens when clicking the right mouse button
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum RightClickAction {
+    /// Open a menu with Copy, Paste and Open URL
+    ContextMenu,
+
+    /// Paste the clipboard
+    Paste,
+
+    /// Don't do anything special
+    None,
+}
+
+impl Default for RightClickAction {
+    fn default() -> Self {
+        RightClickAction::ContextMenu
+    }
+}
+
 /// `VisualBellAnimations` are modeled after a subset of CSS transitions and Robert
 /// Penner's Easing Functions.
 #[derive(Clone, Copy, Debug, Deserialize)]
@@ -201,6 +313,47 @@ impl Default for VisualBellConfig {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BellConfig {
+    /// Request attention when the bell rings in an unfocused window
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub urgent: bool,
+
+    /// Program run when the bell rings in an unfocused window
+    #[serde(default, deserialize_with = "failure_default")]
+    pub command: Option<Shell<'static>>,
+
+    /// Color blended over the window by the visual bell
+    #[serde(default="default_bell_color", deserialize_with = "deserialize_bell_color")]
+    pub color: Rgb,
+}
+
+impl Default for BellConfig {
+    fn default() -> BellConfig {
+        BellConfig {
+            urgent: true,
+            command: None,
+            color: default_bell_color(),
+        }
+    }
+}
+
+fn default_bell_color() -> Rgb {
+    Rgb { r: 0xff, g: 0xff, b: 0xff }
+}
+
+fn deserialize_bell_color<'a, D>(deserializer: D) -> ::std::result::Result<Rgb, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match rgb_from_hex(deserializer) {
+        Ok(color) => Ok(color),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using default value", err);
+            Ok(default_bell_color())
+        },
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Shell<'a> {
     program: Cow<'a, str>,
@@ -363,7 +516,7 @@ impl<'de> Deserialize<'de> for Decorations {
     }
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct WindowConfig {
     /// Initial dimensions
     #[serde(default, deserialize_with = "failure_default")]
@@ -376,6 +529,14 @@ pub struct WindowConfig {
     /// Draw the window with title bar / borders
     #[serde(default)]
     decorations: Decorations,
+
+    /// Initial window title
+    #[serde(default, deserialize_with = "failure_default")]
+    title: Option<String>,
+
+    /// Insert the paths of files dropped onto the window
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    accept_drops: bool,
 }
 
 fn default_padding() -> Delta<u8> {
@@ -398,6 +559,15 @@ impl WindowConfig {
     pub fn decorations(&self) -> Decorations {
         self.decorations
     }
+
+    /// Title used when it's not specified with `--title`
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_ref().map(String::as_str)
+    }
+
+    pub fn accept_drops(&self) -> bool {
+        self.accept_drops
+    }
 }
 
 impl Default for WindowConfig {
@@ -406,6 +576,8 @@ impl Default for WindowConfig {
             dimensions: Default::default(),
             padding: default_padding(),
             decorations: Default::default(),
+            title: None,
+            accept_drops: true,
         }
     }
 }
@@ -421,10 +593,14 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     padding: Option<Delta<u8>>,
 
-    /// TERM env variable
+    /// Extra environment variables
     #[serde(default, deserialize_with = "failure_default")]
     env: HashMap<String, String>,
 
+    /// TERM env variable, used when its terminfo entry is installed
+    #[serde(default = "default_term_env", deserialize_with = "deserialize_term_env")]
+    term_env: String,
+
     /// Font configuration
     #[serde(default, deserialize_with = "failure_default")]
     font: Font,
@@ -433,6 +609,14 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     render_timer: bool,
 
+    /// Redraw every line with each frame instead of only the changed ones
+    #[serde(default, deserialize_with = "failure_default")]
+    full_redraw: bool,
+
+    /// Frame pacing options
+    #[serde(default, deserialize_with = "failure_default")]
+    debug: DebugConfig,
+
     /// Should draw bold text with brighter colors instead of bold font
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     draw_bold_text_with_bright_colors: bool,
@@ -449,7 +633,7 @@ pub struct Config {
     window: WindowConfig,
 
     /// Keybindings
-    #[serde(default, deserialize_with = "failure_default_vec")]
+    #[serde(default = "default_key_bindings", deserialize_with = "failure_default_vec")]
     key_bindings: Vec<KeyBinding>,
 
     /// Bindings for the mouse
@@ -474,6 +658,10 @@ pub struct Config {
     #[serde(default, deserialize_with = "failure_default")]
     visual_bell: VisualBellConfig,
 
+    /// Bell configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    bell: BellConfig,
+
     /// Use dynamic title
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     dynamic_title: bool,
@@ -494,6 +682,14 @@ pub struct Config {
     #[serde(default, deserialize_with="failure_default")]
     cursor: Cursor,
 
+    /// Allow applications to enable the kitty keyboard protocol
+    #[serde(default, deserialize_with = "failure_default")]
+    enable_kitty_keyboard: bool,
+
+    /// Terminal behavior configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    terminal: Terminal,
+
     // TODO: DEPRECATED
     #[serde(default, deserialize_with = "failure_default")]
     custom_cursor_colors: Option<bool>,
@@ -538,6 +734,26 @@ fn failure_default_vec<'a, D, T>(deserializer: D) -> ::std::result::Result<Vec<T
     Ok(bindings)
 }
 
+fn default_term_env() -> String {
+    String::from("alacritty")
+}
+
+fn deserialize_term_env<'a, D>(deserializer: D) -> ::std::result::Result<String, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match String::deserialize(deserializer) {
+        Ok(ref value) if value.is_empty() => {
+            eprintln!("problem with config: empty term_env; Using `alacritty`");
+            Ok(default_term_env())
+        },
+        Ok(value) => Ok(value),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using `alacritty`", err);
+            Ok(default_term_env())
+        },
+    }
+}
+
 fn default_tabspaces() -> usize {
     8
 }
@@ -594,6 +810,18 @@ impl Default for Config {
     }
 }
 
+/// Bindings of the default config, used when `key_bindings` is missing
+///
+/// This makes sure the scrollback and clipboard shortcuts are available with
+/// configuration files which don't list any bindings.
+fn default_key_bindings() -> Vec<KeyBinding> {
+    lazy_static! {
+        static ref DEFAULT_KEY_BINDINGS: Vec<KeyBinding> = Config::default().key_bindings;
+    }
+
+    DEFAULT_KEY_BINDINGS.clone()
+}
+
 /// Struct for scrolling related settings
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct Scrolling {
@@ -606,8 +834,10 @@ pub struct Scrolling {
     #[serde(deserialize_with="deserialize_scrolling_multiplier")]
     #[serde(default="default_scrolling_multiplier")]
     pub faux_multiplier: u8,
-    #[serde(default, deserialize_with="failure_default")]
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     pub auto_scroll: bool,
+    #[serde(default, deserialize_with="failure_default")]
+    pub natural: bool,
 }
 
 fn default_scrolling_history() -> u32 {
@@ -625,7 +855,34 @@ impl Default for Scrolling {
             history: default_scrolling_history(),
             multiplier: default_scrolling_multiplier(),
             faux_multiplier: default_scrolling_multiplier(),
-            auto_scroll: false,
+            auto_scroll: true,
+            natural: false,
+        }
+    }
+}
+
+/// Struct for frame pacing related settings
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct DebugConfig {
+    /// Synchronize buffer swaps with the refresh rate of the display
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    pub vsync: bool,
+
+    /// Upper limit for the frames per second drawn for terminal output
+    #[serde(default, deserialize_with="failure_default")]
+    pub max_fps: Option<u32>,
+
+    /// Print the render timer to stderr with every frame
+    #[serde(default, deserialize_with="failure_default")]
+    pub print_frame_times: bool,
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            max_fps: None,
+            print_frame_times: false,
         }
     }
 }
@@ -733,7 +990,8 @@ impl<'a> de::Deserialize<'a> for ActionWrapper {
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 f.write_str("Paste, Copy, PasteSelection, IncreaseFontSize, DecreaseFontSize, \
                             ResetFontSize, ScrollPageUp, ScrollPageDown, ScrollToTop, \
-                            ScrollToBottom, ClearHistory, Hide, or Quit")
+                            ScrollToBottom, ClearHistory, SearchBackward, ToggleViMode, Hide, \
+                            or Quit")
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<ActionWrapper, E>
@@ -751,6 +1009,8 @@ impl<'a> de::Deserialize<'a> for ActionWrapper {
                     "ScrollToTop" => Action::ScrollToTop,
                     "ScrollToBottom" => Action::ScrollToBottom,
                     "ClearHistory" => Action::ClearHistory,
+                    "SearchBackward" => Action::SearchBackward,
+                    "ToggleViMode" => Action::ToggleViMode,
                     "Hide" => Action::Hide,
                     "Quit" => Action::Quit,
                     _ => return Err(E::invalid_value(Unexpected::Str(value), &self)),
@@ -805,7 +1065,7 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
             type Value = ModeWrapper;
 
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                f.write_str("Combination of AppCursor | AppKeypad, possibly with negation (~)")
+                f.write_str("Combination of AppCursor | AppKeypad | Alt, possibly with negation (~)")
             }
 
             fn visit_str<E>(self, value: &str) -> ::std::result::Result<ModeWrapper, E>
@@ -822,6 +1082,8 @@ impl<'a> de::Deserialize<'a> for ModeWrapper {
                         "~AppCursor" => res.not_mode |= mode::TermMode::APP_CURSOR,
                         "AppKeypad" => res.mode |= mode::TermMode::APP_KEYPAD,
                         "~AppKeypad" => res.not_mode |= mode::TermMode::APP_KEYPAD,
+                        "Alt" => res.mode |= mode::TermMode::ALT_SCREEN,
+                        "~Alt" => res.not_mode |= mode::TermMode::ALT_SCREEN,
                         _ => eprintln!("unknown mode {:?}", modifier),
                     }
                 }
@@ -1162,6 +1424,10 @@ pub struct Colors {
     pub primary: PrimaryColors,
     #[serde(default, deserialize_with = "failure_default")]
     pub cursor: CursorColors,
+    #[serde(default, deserialize_with = "failure_default")]
+    pub search: SearchColors,
+    #[serde(default, deserialize_with = "failure_default")]
+    pub vi_mode_cursor: ViModeCursorColors,
     pub normal: AnsiColors,
     pub bright: AnsiColors,
     #[serde(default, deserialize_with = "failure_default")]
@@ -1206,12 +1472,67 @@ fn deserialize_color_index<'a, D>(deserializer: D) -> ::std::result::Result<u8,
     }
 }
 
+/// Clipboard access granted to applications through OSC 52
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum Osc52 {
+    /// Ignore all clipboard requests
+    Disabled,
+
+    /// Allow applications to write to the clipboard
+    OnlyCopy,
+
+    /// Allow applications to write to and read from the clipboard
+    CopyPaste,
+}
+
+impl Default for Osc52 {
+    fn default() -> Osc52 {
+        Osc52::OnlyCopy
+    }
+}
+
+/// Struct for terminal behavior related settings
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Terminal {
+    #[serde(default, deserialize_with = "failure_default")]
+    pub osc52: Osc52,
+    #[serde(default="default_osc52_max_bytes", deserialize_with = "deserialize_osc52_max_bytes")]
+    pub osc52_max_bytes: usize,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self {
+            osc52: Default::default(),
+            osc52_max_bytes: default_osc52_max_bytes(),
+        }
+    }
+}
+
+fn default_osc52_max_bytes() -> usize {
+    100_000
+}
+
+fn deserialize_osc52_max_bytes<'a, D>(deserializer: D) -> ::std::result::Result<usize, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match usize::deserialize(deserializer) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using `{}`", err, default_osc52_max_bytes());
+            Ok(default_osc52_max_bytes())
+        },
+    }
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct Cursor {
     #[serde(default, deserialize_with = "failure_default")]
     pub style: CursorStyle,
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     pub unfocused_hollow: bool,
+    #[serde(default="default_text_blink_rate_ms", deserialize_with = "deserialize_text_blink_rate_ms")]
+    pub text_blink_rate_ms: u64,
 }
 
 impl Default for Cursor {
@@ -1219,10 +1540,27 @@ impl Default for Cursor {
         Self {
             style: Default::default(),
             unfocused_hollow: true,
+            text_blink_rate_ms: default_text_blink_rate_ms(),
         }
     }
 }
 
+fn default_text_blink_rate_ms() -> u64 {
+    500
+}
+
+fn deserialize_text_blink_rate_ms<'a, D>(deserializer: D) -> ::std::result::Result<u64, D::Error>
+    where D: de::Deserializer<'a>
+{
+    match u64::deserialize(deserializer) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            eprintln!("problem with config: {}; Using `500`", err);
+            Ok(default_text_blink_rate_ms())
+        },
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default, Deserialize)]
 pub struct CursorColors {
     #[serde(default, deserialize_with = "deserialize_optional_color")]
@@ -1231,6 +1569,72 @@ pub struct CursorColors {
     pub cursor: Option<Rgb>,
 }
 
+/// Colors used for highlighting search matches
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct SearchColors {
+    #[serde(default = "default_search_matches")]
+    pub matches: MatchColors,
+    #[serde(default = "default_search_focused_match")]
+    pub focused_match: MatchColors,
+}
+
+impl Default for SearchColors {
+    fn default() -> SearchColors {
+        SearchColors {
+            matches: default_search_matches(),
+            focused_match: default_search_focused_match(),
+        }
+    }
+}
+
+fn default_search_matches() -> MatchColors {
+    MatchColors {
+        foreground: Rgb { r: 0x00, g: 0x00, b: 0x00 },
+        background: Rgb { r: 0xe6, g: 0xc5, b: 0x47 },
+    }
+}
+
+fn default_search_focused_match() -> MatchColors {
+    MatchColors {
+        foreground: Rgb { r: 0x00, g: 0x00, b: 0x00 },
+        background: Rgb { r: 0xe7, g: 0x8c, b: 0x45 },
+    }
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct MatchColors {
+    #[serde(deserialize_with = "rgb_from_hex")]
+    pub foreground: Rgb,
+    #[serde(deserialize_with = "rgb_from_hex")]
+    pub background: Rgb,
+}
+
+/// Colors of the vi mode cursor
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct ViModeCursorColors {
+    #[serde(default = "default_vi_mode_cursor_text", deserialize_with = "rgb_from_hex")]
+    pub text: Rgb,
+    #[serde(default = "default_vi_mode_cursor_cursor", deserialize_with = "rgb_from_hex")]
+    pub cursor: Rgb,
+}
+
+impl Default for ViModeCursorColors {
+    fn default() -> ViModeCursorColors {
+        ViModeCursorColors {
+            text: default_vi_mode_cursor_text(),
+            cursor: default_vi_mode_cursor_cursor(),
+        }
+    }
+}
+
+fn default_vi_mode_cursor_text() -> Rgb {
+    Rgb { r: 0x00, g: 0x00, b: 0x00 }
+}
+
+fn default_vi_mode_cursor_cursor() -> Rgb {
+    Rgb { r: 0x70, g: 0xc0, b: 0xba }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PrimaryColors {
     #[serde(deserialize_with = "rgb_from_hex")]
@@ -1275,6 +1679,8 @@ impl Default for Colors {
         Colors {
             primary: PrimaryColors::default(),
             cursor: CursorColors::default(),
+            search: SearchColors::default(),
+            vi_mode_cursor: ViModeCursorColors::default(),
             normal: AnsiColors {
                 black: Rgb {r: 0x00, g: 0x00, b: 0x00},
                 red: Rgb {r: 0xd5, g: 0x4e, b: 0x53},
@@ -1585,12 +1991,24 @@ impl Config {
         &self.visual_bell
     }
 
+    /// Get bell config
+    #[inline]
+    pub fn bell(&self) -> &BellConfig {
+        &self.bell
+    }
+
     /// Should show render timer
     #[inline]
     pub fn render_timer(&self) -> bool {
         self.render_timer
     }
 
+    /// Redraw every line with each frame
+    #[inline]
+    pub fn full_redraw(&self) -> bool {
+        self.full_redraw
+    }
+
     #[inline]
     pub fn use_thin_strokes(&self) -> bool {
         self.font.use_thin_strokes
@@ -1606,6 +2024,12 @@ impl Config {
         self.shell.as_ref()
     }
 
+    /// Preferred value of the TERM env variable
+    #[inline]
+    pub fn term_env(&self) -> &str {
+        &self.term_env
+    }
+
     pub fn env(&self) -> &HashMap<String, String> {
         &self.env
     }
@@ -1628,6 +2052,12 @@ impl Config {
         self.unfocused_hollow_cursor.unwrap_or(self.cursor.unfocused_hollow)
     }
 
+    /// Time between showing and hiding blinking text, `0` disables blinking
+    #[inline]
+    pub fn text_blink_rate_ms(&self) -> u64 {
+        self.cursor.text_blink_rate_ms
+    }
+
     /// Live config reload
     #[inline]
     pub fn live_config_reload(&self) -> bool {
@@ -1639,12 +2069,30 @@ impl Config {
         self.dynamic_title
     }
 
+    /// Allow applications to enable the kitty keyboard protocol
+    #[inline]
+    pub fn enable_kitty_keyboard(&self) -> bool {
+        self.enable_kitty_keyboard
+    }
+
+    /// Terminal behavior settings
+    #[inline]
+    pub fn terminal(&self) -> Terminal {
+        self.terminal
+    }
+
     /// Scrolling settings
     #[inline]
     pub fn scrolling(&self) -> Scrolling {
         self.scrolling
     }
 
+    /// Frame pacing settings
+    #[inline]
+    pub fn debug(&self) -> DebugConfig {
+        self.debug
+    }
+
     /// Cursor foreground color
     #[inline]
     pub fn cursor_text_color(&self) -> Option<Color> {
@@ -1672,14 +2120,6 @@ impl Config {
         Ok(config)
     }
 
-    /// Overrides the `dynamic_title` configuration based on `--title`.
-    pub fn update_dynamic_title(mut self, options: &Options) -> Self {
-        if options.title.is_some() {
-            self.dynamic_title = false;
-        }
-        self
-    }
-
     fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
         let mut f = fs::File::open(path)?;
         let mut contents = String::new();
@@ -1848,11 +2288,15 @@ pub struct Font {
     /// Font family
     pub normal: FontDescription,
 
-    #[serde(default="default_italic_desc")]
-    pub italic: FontDescription,
+    /// Faces for styled text, using the normal family when missing
+    #[serde(default)]
+    italic: Option<FontDescription>,
+
+    #[serde(default)]
+    bold: Option<FontDescription>,
 
-    #[serde(default="default_bold_desc")]
-    pub bold: FontDescription,
+    #[serde(default)]
+    bold_italic: Option<FontDescription>,
 
     // Font size in points
     #[serde(deserialize_with="DeserializeSize::deserialize")]
@@ -1871,14 +2315,37 @@ pub struct Font {
 
     #[serde(default="true_bool", deserialize_with = "default_true_bool")]
     scale_with_dpi: bool,
+
+    /// Draw box drawing characters instead of using the font's glyphs
+    #[serde(default="true_bool", deserialize_with = "default_true_bool")]
+    builtin_box_drawing: bool,
+
+    /// Antialiasing overriding the system's font configuration
+    #[serde(default, deserialize_with = "failure_default")]
+    antialias: Option<Antialias>,
 }
 
-fn default_bold_desc() -> FontDescription {
-    Font::default().bold
+/// Smoothing of glyph edges
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum Antialias {
+    /// Shade pixels by how much of them is covered
+    Grayscale,
+
+    /// Shade each of the LCD's color channels separately
+    Subpixel,
+
+    /// Unhinted monochrome glyphs, best suited for bitmap fonts
+    None,
 }
 
-fn default_italic_desc() -> FontDescription {
-    Font::default().italic
+impl From<Antialias> for font::Antialias {
+    fn from(antialias: Antialias) -> font::Antialias {
+        match antialias {
+            Antialias::Grayscale => font::Antialias::Grayscale,
+            Antialias::Subpixel => font::Antialias::Subpixel,
+            Antialias::None => font::Antialias::None,
+        }
+    }
 }
 
 /// Description of a single font
@@ -1916,6 +2383,25 @@ impl Font {
         &self.glyph_offset
     }
 
+    /// Get the bold font face
+    pub fn bold(&self) -> FontDescription {
+        self.styled_desc(&self.bold)
+    }
+
+    /// Get the italic font face
+    pub fn italic(&self) -> FontDescription {
+        self.styled_desc(&self.italic)
+    }
+
+    /// Get the bold italic font face
+    pub fn bold_italic(&self) -> FontDescription {
+        self.styled_desc(&self.bold_italic)
+    }
+
+    fn styled_desc(&self, desc: &Option<FontDescription>) -> FontDescription {
+        desc.clone().unwrap_or_else(|| FontDescription::new_with_family(&self.normal.family[..]))
+    }
+
     /// Get a font clone with a size modification
     pub fn with_size(self, size: Size) -> Font {
         Font {
@@ -1928,6 +2414,18 @@ impl Font {
     pub fn scale_with_dpi(&self) -> bool {
         self.scale_with_dpi
     }
+
+    /// Check whether box drawing characters are drawn instead of loaded
+    #[inline]
+    pub fn builtin_box_drawing(&self) -> bool {
+        self.builtin_box_drawing
+    }
+
+    /// Get the antialiasing overriding the system's font configuration
+    #[inline]
+    pub fn antialias(&self) -> Option<font::Antialias> {
+        self.antialias.map(Into::into)
+    }
 }
 
 #[cfg(target_os = "macos")]
@@ -1935,13 +2433,16 @@ impl Default for Font {
     fn default() -> Font {
         Font {
             normal: FontDescription::new_with_family("Menlo"),
-            bold: FontDescription::new_with_family("Menlo"),
-            italic: FontDescription::new_with_family("Menlo"),
+            bold: None,
+            italic: None,
+            bold_italic: None,
             size: Size::new(11.0),
             use_thin_strokes: true,
             scale_with_dpi: true,
             glyph_offset: Default::default(),
             offset: Default::default(),
+            builtin_box_drawing: true,
+            antialias: None,
         }
     }
 }
@@ -1951,13 +2452,16 @@ impl Default for Font {
     fn default() -> Font {
         Font {
             normal: FontDescription::new_with_family("monospace"),
-            bold: FontDescription::new_with_family("monospace"),
-            italic: FontDescription::new_with_family("monospace"),
+            bold: None,
+            italic: None,
+            bold_italic: None,
             size: Size::new(11.0),
             use_thin_strokes: false,
             scale_with_dpi: true,
             glyph_offset: Default::default(),
             offset: Default::default(),
+            builtin_box_drawing: true,
+            antialias: None,
         }
     }
 }
@@ -1967,13 +2471,16 @@ impl Default for Font {
     fn default() -> Font {
         Font {
             normal: FontDescription::new_with_family("Consolas"),
-            bold: FontDescription::new_with_family("Consolas"),
-            italic: FontDescription::new_with_family("Consolas"),
+            bold: None,
+            italic: None,
+            bold_italic: None,
             size: Size::new(11.0),
             use_thin_strokes: false,
             offset: Default::default(),
             glyph_offset: Default::default(),
             scale_with_dpi: false,
+            builtin_box_drawing: true,
+            antialias: None,
         }
     }
 }
@@ -2055,8 +2562,9 @@ impl Monitor {
 
 #[cfg(test)]
 mod tests {
-    use cli::Options;
-    use super::Config;
+    use input::Action;
+    use Rgb;
+    use super::{Config, Key};
 
     #[cfg(target_os="macos")]
     static ALACRITTY_YML: &'static str =
@@ -2081,23 +2589,124 @@ mod tests {
     }
 
     #[test]
-    fn dynamic_title_ignoring_options_by_default() {
+    fn default_key_bindings() {
+        let config: Config = ::serde_yaml::from_str("dynamic_title: true")
+            .expect("deserialize config");
+
+        let has_binding = |key: Key, scroll: fn(&Action) -> bool| {
+            config.key_bindings().iter().any(|binding| {
+                binding.trigger == key && binding.mods.shift && scroll(&binding.action)
+            })
+        };
+        assert!(has_binding(Key::PageUp, |action| match *action {
+            Action::ScrollPageUp => true,
+            _ => false,
+        }));
+        assert!(has_binding(Key::PageDown, |action| match *action {
+            Action::ScrollPageDown => true,
+            _ => false,
+        }));
+        assert!(has_binding(Key::Home, |action| match *action {
+            Action::ScrollToTop => true,
+            _ => false,
+        }));
+        assert!(has_binding(Key::End, |action| match *action {
+            Action::ScrollToBottom => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn debug_frame_pacing() {
+        let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
+            .expect("deserialize config");
+        assert!(config.debug().vsync);
+        assert_eq!(config.debug().max_fps, None);
+
+        let config: Config = ::serde_yaml::from_str("debug:\n  vsync: false\n  max_fps: 30")
+            .expect("deserialize config");
+        assert!(!config.debug().vsync);
+        assert_eq!(config.debug().max_fps, Some(30));
+    }
+
+    #[test]
+    fn bell_color() {
         let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
             .expect("deserialize config");
-        let old_dynamic_title = config.dynamic_title;
-        let options = Options::default();
-        let config = config.update_dynamic_title(&options);
-        assert_eq!(old_dynamic_title, config.dynamic_title);
+        assert_eq!(config.bell().color, Rgb { r: 0xff, g: 0xff, b: 0xff });
+
+        let config: Config = ::serde_yaml::from_str("bell:\n  color: '0x20a0ff'")
+            .expect("deserialize config");
+        assert_eq!(config.bell().color, Rgb { r: 0x20, g: 0xa0, b: 0xff });
     }
 
     #[test]
-    fn dynamic_title_overridden_by_options() {
+    fn bell_command() {
         let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
             .expect("deserialize config");
-        let mut options = Options::default();
-        options.title = Some("foo".to_owned());
-        let config = config.update_dynamic_title(&options);
-        assert!(!config.dynamic_title);
+        assert!(config.bell().command.is_none());
+
+        let yaml = "bell:\n  command:\n    program: notify-send\n    args: [\"Bell\"]";
+        let config: Config = ::serde_yaml::from_str(yaml).expect("deserialize config");
+        let command = config.bell().command.as_ref().unwrap();
+        assert_eq!(command.program(), "notify-send");
+        assert_eq!(command.args(), ["Bell"]);
+    }
+
+    #[test]
+    fn window_title() {
+        let config: Config = ::serde_yaml::from_str(ALACRITTY_YML)
+            .expect("deserialize config");
+        assert_eq!(config.window().title(), None);
+        assert!(config.dynamic_title());
+
+        let config: Config = ::serde_yaml::from_str("window:\n  title: foo")
+            .expect("deserialize config");
+        assert_eq!(config.window().title(), Some("foo"));
+    }
+
+    #[test]
+    fn styled_font_faces() {
+        let config: Config = ::serde_yaml::from_str(
+            "font:\n  normal:\n    family: Fira\n  bold:\n    family: Hack\n    style: Heavy\n  size: 11.0"
+        ).expect("deserialize config");
+        let font = config.font();
+
+        assert_eq!(font.bold().family, "Hack");
+        assert_eq!(font.bold().style, Some(String::from("Heavy")));
+
+        // Missing faces use the normal family
+        assert_eq!(font.italic().family, "Fira");
+        assert_eq!(font.bold_italic().family, "Fira");
+        assert_eq!(font.bold_italic().style, None);
+    }
+
+    #[test]
+    fn font_antialias() {
+        let config: Config = ::serde_yaml::from_str("font:\n  size: 11.0\n  antialias: Subpixel")
+            .expect("deserialize config");
+        assert_eq!(config.font().antialias(), Some(::font::Antialias::Subpixel));
+
+        // Invalid values keep the system's configuration
+        let config: Config = ::serde_yaml::from_str("font:\n  size: 11.0\n  antialias: Lcd")
+            .expect("deserialize config");
+        assert_eq!(config.font().antialias(), None);
+    }
+
+    #[test]
+    fn word_pattern() {
+        let config: Config = ::serde_yaml::from_str(
+            "selection:\n  semantic_escape_chars: ' '\n  word_pattern: '\\w+'"
+        ).expect("deserialize config");
+        let pattern = config.selection().word_pattern.as_ref().map(|regex| regex.as_str());
+        assert_eq!(pattern, Some("\\w+"));
+
+        // Invalid patterns fall back to the escape chars
+        let config: Config = ::serde_yaml::from_str(
+            "selection:\n  semantic_escape_chars: ' '\n  word_pattern: '(\\w+'"
+        ).expect("deserialize config");
+        assert!(config.selection().word_pattern.is_none());
+        assert_eq!(config.selection().semantic_escape_chars, " ");
     }
 }
 
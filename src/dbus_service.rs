@@ -0,0 +1,221 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! DBUS session bus service
+//!
+//! The `org.alacritty.Terminal` interface is exported at
+//! `/org/alacritty/Terminal`, owning the bus name `org.alacritty.Terminal`.
+//! Only the first instance gets the name, others run without the service.
+//! Method calls are turned into the same messages which are accepted on the
+//! IPC socket.
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+use dbus::{
+    BusType, Connection, Interface, Member, Message as DBusMessage, NameFlag, Path, RequestNameReply,
+};
+use dbus::tree::{Factory, MethodErr};
+
+use ipc::{Message, OnMessage};
+
+const BUS_NAME: &str = "org.alacritty.Terminal";
+const INTERFACE: &str = "org.alacritty.Terminal";
+const OBJECT_PATH: &str = "/org/alacritty/Terminal";
+
+/// Time spent waiting for method calls before pending signals are sent
+const POLL_TIMEOUT_MS: u32 = 100;
+
+/// Signals emitted on the bus
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Signal {
+    /// The configuration was reloaded
+    ConfigChanged,
+
+    /// The terminal bell rang
+    BellReceived,
+}
+
+impl Signal {
+    fn member(self) -> &'static str {
+        match self {
+            Signal::ConfigChanged => "ConfigChanged",
+            Signal::BellReceived => "BellReceived",
+        }
+    }
+}
+
+/// Handle for the service running on its own thread
+///
+/// The thread exits and releases the bus name when this is dropped.
+pub struct Service {
+    _thread: ::std::thread::JoinHandle<()>,
+    rx: mpsc::Receiver<Message>,
+    signals: mpsc::Sender<Signal>,
+}
+
+impl Service {
+    /// Connect to the session bus and export the interface
+    pub fn new<H>(handler: H) -> Result<Service, String>
+        where H: OnMessage + Send + 'static
+    {
+        let (tx, rx) = mpsc::channel();
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+
+        // A connection can't be moved between threads, so it's set up on the
+        // service thread and only the result is reported back.
+        let thread = ::util::thread::spawn_named("dbus service", move || {
+            let conn = match connect(tx, handler) {
+                Ok(conn) => {
+                    let _ = result_tx.send(Ok(()));
+                    conn
+                },
+                Err(err) => {
+                    let _ = result_tx.send(Err(err));
+                    return;
+                },
+            };
+
+            loop {
+                for _ in conn.incoming(POLL_TIMEOUT_MS) {}
+
+                loop {
+                    match signal_rx.try_recv() {
+                        Ok(signal) => emit(&conn, signal),
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => return,
+                    }
+                }
+            }
+        });
+
+        match result_rx.recv() {
+            Ok(Ok(())) => Ok(Service { _thread: thread, rx, signals: signal_tx }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(String::from("DBUS service thread exited")),
+        }
+    }
+
+    /// Get all messages received since the last call
+    pub fn pending_messages(&self) -> Vec<Message> {
+        self.rx.try_iter().collect()
+    }
+
+    /// Queue a signal for emission
+    pub fn emit(&self, signal: Signal) {
+        let _ = self.signals.send(signal);
+    }
+}
+
+/// Claim the bus name and register the object
+fn connect<H>(tx: mpsc::Sender<Message>, handler: H) -> Result<Connection, String>
+    where H: OnMessage + 'static
+{
+    let conn = Connection::get_private(BusType::Session).map_err(|err| err.to_string())?;
+
+    match conn.register_name(BUS_NAME, NameFlag::DoNotQueue as u32) {
+        Ok(RequestNameReply::PrimaryOwner) | Ok(RequestNameReply::AlreadyOwner) => (),
+        Ok(_) => return Err(format!("{} is owned by another instance", BUS_NAME)),
+        Err(err) => return Err(err.to_string()),
+    }
+
+    let handler = RefCell::new(handler);
+    let send = Rc::new(move |message: Message| {
+        debug!("Received DBUS message: {:?}", message);
+        let _ = tx.send(message);
+        handler.borrow_mut().on_message();
+    });
+
+    let f = Factory::new_fn::<()>();
+    let (reload, font_size, spawn) = (Rc::clone(&send), Rc::clone(&send), send);
+    let interface = f.interface(INTERFACE, ())
+        .add_m(f.method("ReloadConfig", (), move |m| {
+            reload(method_message(m.msg)?);
+            Ok(vec![m.msg.method_return()])
+        }))
+        .add_m(f.method("SetFontSize", (), move |m| {
+            font_size(method_message(m.msg)?);
+            Ok(vec![m.msg.method_return()])
+        }).inarg::<f64, _>("size"))
+        .add_m(f.method("SpawnWindow", (), move |m| {
+            spawn(method_message(m.msg)?);
+            Ok(vec![m.msg.method_return()])
+        }))
+        .add_s(f.signal(Signal::ConfigChanged.member(), ()))
+        .add_s(f.signal(Signal::BellReceived.member(), ()));
+
+    let tree = f.tree(()).add(f.object_path(OBJECT_PATH, ()).introspectable().add(interface));
+    tree.set_registered(&conn, true).map_err(|err| err.to_string())?;
+    conn.add_handler(tree);
+
+    info!("Registered DBUS service {}", BUS_NAME);
+
+    Ok(conn)
+}
+
+/// Turn a method call on the interface into the matching IPC message
+fn method_message(msg: &DBusMessage) -> Result<Message, MethodErr> {
+    let member = msg.member();
+    match member.as_ref().map(|member| &**member) {
+        Some("ReloadConfig") => Ok(Message::ReloadConfig),
+        Some("SetFontSize") => {
+            let size: f64 = msg.read1()?;
+            Ok(Message::SetFontSize { size: size as f32 })
+        },
+        Some("SpawnWindow") => Ok(Message::SpawnWindow),
+        _ => Err(MethodErr::failed(&format!("Unknown method {:?}", member))),
+    }
+}
+
+fn emit(conn: &Connection, signal: Signal) {
+    let message = DBusMessage::signal(
+        &Path::from(OBJECT_PATH),
+        &Interface::from(INTERFACE),
+        &Member::from(signal.member()),
+    );
+
+    if conn.send(message).is_err() {
+        warn!("Unable to emit DBUS signal {}", signal.member());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dbus::Message as DBusMessage;
+
+    use ipc::Message;
+
+    use super::{method_message, BUS_NAME, INTERFACE, OBJECT_PATH};
+
+    fn method_call(method: &str) -> DBusMessage {
+        DBusMessage::new_method_call(BUS_NAME, OBJECT_PATH, INTERFACE, method).unwrap()
+    }
+
+    #[test]
+    fn dispatch_method_calls() {
+        assert_eq!(method_message(&method_call("ReloadConfig")).ok(), Some(Message::ReloadConfig));
+        assert_eq!(method_message(&method_call("SpawnWindow")).ok(), Some(Message::SpawnWindow));
+
+        let set_font_size = method_call("SetFontSize").append1(14.0f64);
+        assert_eq!(method_message(&set_font_size).ok(), Some(Message::SetFontSize { size: 14.0 }));
+    }
+
+    #[test]
+    fn reject_invalid_method_calls() {
+        assert!(method_message(&method_call("SetFontSize")).is_err());
+        assert!(method_message(&method_call("SetFontSize").append1("14")).is_err());
+        assert!(method_message(&method_call("Quit")).is_err());
+    }
+}
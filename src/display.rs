@@ -14,17 +14,24 @@
 
 //! The display subsystem including window management, font rasterization, and
 //! GPU drawing.
-use std::sync::mpsc;
+use std::iter;
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 use parking_lot::MutexGuard;
 
 use Rgb;
+use blink::Blink;
 use cli;
 use config::Config;
 use font::{self, Rasterize};
+use index::{Column, Line};
 use meter::Meter;
+use pacer::FramePacer;
 use renderer::{self, GlyphCache, QuadRenderer};
 use term::{Term, SizeInfo, RenderableCell};
+use term::damage::Damage;
 use sync::FairMutex;
 
 use window::{self, Pixels, SetInnerSize, Size, Window};
@@ -93,11 +100,24 @@ pub struct Display {
     renderer: QuadRenderer,
     glyph_cache: GlyphCache,
     render_timer: bool,
+    /// Print the render time of every frame to stderr
+    print_frame_times: bool,
+    full_redraw: bool,
     rx: mpsc::Receiver<(u32, u32)>,
     tx: mpsc::Sender<(u32, u32)>,
     meter: Meter,
+    /// Time between frames, shown next to the render time
+    frame_meter: Meter,
+    pacer: FramePacer,
     font_size: font::Size,
+    /// Device pixel ratio the glyphs are rasterized for
+    dpr: f32,
+    /// Antialiasing the glyphs are rasterized with
+    antialias: Option<font::Antialias>,
     size_info: SizeInfo,
+    blink: Blink,
+    blinking: bool,
+    drawn_blink_epoch: usize,
 }
 
 /// Can wakeup the render loop from other threads
@@ -121,6 +141,28 @@ impl Display {
 
     pub fn update_config(&mut self, config: &Config) {
         self.render_timer = config.render_timer();
+        self.print_frame_times = config.debug().print_frame_times;
+        self.full_redraw = config.full_redraw();
+        self.pacer.update_config(config.debug());
+        self.blink.set_rate(config.text_blink_rate_ms());
+    }
+
+    /// Whether the next frame may be drawn now
+    ///
+    /// Redraws caused by output are limited to the configured frame rate, the
+    /// render loop is woken up again once the next frame is due.
+    pub fn frame_due(&mut self, last_key_input: Instant) -> bool {
+        self.pacer.frame_due(last_key_input)
+    }
+
+    /// Whether blinking text is on screen and due to be redrawn
+    pub fn blink_changed(&self) -> bool {
+        self.blinking && self.blink.epoch().load(Ordering::Relaxed) != self.drawn_blink_epoch
+    }
+
+    /// Blink epoch advanced by the blink timer, shared with the terminal
+    pub fn blink_epoch(&self) -> Arc<AtomicUsize> {
+        Arc::clone(self.blink.epoch())
     }
 
     /// Get size info about the display
@@ -133,7 +175,7 @@ impl Display {
         let render_timer = config.render_timer();
 
         // Create the window where Alacritty will be displayed
-        let mut window = Window::new(&options, config.window())?;
+        let mut window = Window::new(&options, config.window(), config.debug().vsync)?;
 
         // get window properties for initializing the other subsystems
         let mut viewport_size = window.inner_size_pixels()
@@ -202,16 +244,32 @@ impl Display {
             },
         );
 
+        let blink = Blink::new(
+            config.text_blink_rate_ms(),
+            Notifier(window.create_window_proxy()),
+        );
+
+        let pacer = FramePacer::new(config.debug(), Notifier(window.create_window_proxy()));
+
         Ok(Display {
             window,
             renderer,
             glyph_cache,
             render_timer,
+            print_frame_times: config.debug().print_frame_times,
+            full_redraw: config.full_redraw(),
             tx,
             rx,
             meter: Meter::new(),
+            frame_meter: Meter::new(),
+            pacer,
             font_size: font::Size::new(0.),
+            dpr,
+            antialias: config.font().antialias(),
             size_info,
+            blink,
+            blinking: false,
+            drawn_blink_epoch: 0,
         })
     }
 
@@ -219,7 +277,7 @@ impl Display {
         -> Result<(GlyphCache, f32, f32), Error>
     {
         let font = config.font().clone();
-        let rasterizer = font::Rasterizer::new(dpr, config.use_thin_strokes())?;
+        let rasterizer = Self::new_rasterizer(dpr, config)?;
 
         // Initialize glyph cache
         let glyph_cache = {
@@ -251,14 +309,55 @@ impl Display {
         Ok((glyph_cache, cell_width.floor(), cell_height.floor()))
     }
 
+    fn new_rasterizer(dpr: f32, config: &Config) -> Result<font::Rasterizer, Error> {
+        let mut rasterizer = font::Rasterizer::new(dpr, config.use_thin_strokes())?;
+        if let Some(antialias) = config.font().antialias() {
+            rasterizer.set_antialias(antialias);
+        }
+
+        Ok(rasterizer)
+    }
+
     pub fn update_glyph_cache(&mut self, config: &Config) {
-        let cache = &mut self.glyph_cache;
-        let size = self.font_size;
-        self.renderer.with_loader(|mut api| {
-            let _ = cache.update_font_size(config.font(), size, &mut api);
-        });
+        {
+            let cache = &mut self.glyph_cache;
+            let size = self.font_size;
+            self.renderer.with_loader(|mut api| {
+                if let Err(err) = cache.update_font_size(config.font(), size, &mut api) {
+                    error!("Unable to change font size: {}", err);
+                }
+            });
+        }
+
+        self.update_cell_size(config);
+    }
 
-        let metrics = cache.font_metrics();
+    /// Rasterize all glyphs again for the current device pixel ratio and antialiasing
+    fn update_rasterizer(&mut self, config: &Config) {
+        info!("device_pixel_ratio: {}, antialias: {:?}", self.dpr, self.antialias);
+
+        let rasterizer = match Self::new_rasterizer(self.dpr, config) {
+            Ok(rasterizer) => rasterizer,
+            Err(err) => {
+                error!("Unable to create rasterizer: {}", err);
+                return;
+            },
+        };
+
+        {
+            let cache = &mut self.glyph_cache;
+            self.renderer.with_loader(|mut api| {
+                if let Err(err) = cache.update_rasterizer(rasterizer, config.font(), &mut api) {
+                    error!("Unable to update rasterizer: {}", err);
+                }
+            });
+        }
+
+        self.update_cell_size(config);
+    }
+
+    fn update_cell_size(&mut self, config: &Config) {
+        let metrics = self.glyph_cache.font_metrics();
         self.size_info.cell_width = ((metrics.average_advance + f64::from(config.font().offset().x)) as f32).floor();
         self.size_info.cell_height = ((metrics.line_height + f64::from(config.font().offset().y)) as f32).floor();
     }
@@ -290,14 +389,33 @@ impl Display {
         }
 
         // Font size modification detected
+        let mut font_changed = false;
         if terminal.font_size != self.font_size {
             self.font_size = terminal.font_size;
             self.update_glyph_cache(config);
+            font_changed = true;
+        }
 
-            if new_size == None {
-                // Force a resize to refresh things
-                new_size = Some((self.size_info.width as u32, self.size_info.height as u32));
-            }
+        // Window moved to a display with a different DPI
+        let dpr = self.window.hidpi_factor();
+        if config.font().scale_with_dpi() && dpr != self.dpr {
+            self.dpr = dpr;
+            self.update_rasterizer(config);
+            font_changed = true;
+        }
+
+        // Antialiasing changed with a config reload
+        let antialias = config.font().antialias();
+        if antialias != self.antialias {
+            self.antialias = antialias;
+            self.update_rasterizer(config);
+            font_changed = true;
+        }
+
+        // Force a resize to refresh things, combined with any pending resize
+        // so the pty is only notified once
+        if font_changed && new_size == None {
+            new_size = Some((self.size_info.width as u32, self.size_info.height as u32));
         }
 
         // Receive any resize events; only call gl::Viewport on last
@@ -309,6 +427,10 @@ impl Display {
             let size = &self.size_info;
             terminal.resize(size);
 
+            // The window may have moved to another screen
+            let (width, height) = self.window.screen_size();
+            terminal.set_screen_size(width, height);
+
             for item in items {
                 item.on_resize(size)
             }
@@ -329,10 +451,26 @@ impl Display {
         let visual_bell_intensity = terminal.visual_bell.intensity();
         let background_color = terminal.background_color();
 
+        if let Some(frame_time) = self.pacer.frame_drawn() {
+            self.frame_meter.add_sample(frame_time);
+        }
+
         let window_focused = self.window.is_focused;
+        self.drawn_blink_epoch = self.blink.epoch().load(Ordering::Relaxed);
+        let blink_visible = terminal.blink_visible();
+
+        // Only the lines which changed since the last frame are rebuilt
+        let mut damage = terminal.damage(config, window_focused, blink_visible);
+        if self.full_redraw || !self.renderer.has_line_cache(size_info.lines()) {
+            damage = Damage::Full;
+        }
         let grid_cells: Vec<RenderableCell> = terminal
-            .renderable_cells(config, window_focused)
+            .damaged_cells(config, window_focused, blink_visible, &damage)
             .collect();
+        self.blinking = terminal.has_blinking_text() || terminal.has_blinking_cursor();
+        self.blink.set_active(self.blinking);
+        let search_query = terminal.search().map(|search| search.query.clone());
+        let context_menu = terminal.context_menu().cloned();
 
         // Clear dirty flag
         terminal.dirty = !terminal.visual_bell.completed();
@@ -377,21 +515,106 @@ impl Display {
 
                 self.renderer.with_api(config, &size_info, visual_bell_intensity, |mut api| {
                     // Draw the grid
-                    api.render_cells(grid_cells.iter(), glyph_cache);
+                    api.render_damaged_cells(
+                        grid_cells.iter(),
+                        &damage,
+                        size_info.lines(),
+                        glyph_cache,
+                    );
                 });
             }
 
-            // Draw render timer
-            if self.render_timer {
-                let timing = format!("{:.3} usec", self.meter.average());
-                let color = Rgb {
-                    r: 0xd5,
-                    g: 0x4e,
-                    b: 0x53,
+            // Draw the search bar over the last line
+            let last_line = size_info.lines() - 1;
+            let mut timer_line = Some(last_line);
+            if let Some(query) = search_query {
+                let cols = size_info.cols().0;
+                let text: String = format!("Search: {}", query)
+                    .chars()
+                    .chain(iter::repeat(' '))
+                    .take(cols)
+                    .collect();
+                let colors = config.colors().search.matches;
+                self.renderer
+                    .with_api(config, &size_info, visual_bell_intensity, |mut api| {
+                        api.render_string(
+                            &text,
+                            last_line,
+                            glyph_cache,
+                            colors.foreground,
+                            colors.background,
+                        );
+                    });
+
+                // Without room above the search bar the render timer is skipped
+                timer_line = last_line.0.checked_sub(1).map(Line);
+            }
+
+            if self.render_timer || self.print_frame_times {
+                // There's no frame rate until the first frame after being idle
+                let fps = match self.frame_meter.average() {
+                    average if average > 0.0 => format!("{:.1}", 1e6 / average),
+                    _ => String::from("--"),
+                };
+                let timing = format!(" {:.3} usec, {} fps ", self.meter.average(), fps);
+                if self.print_frame_times {
+                    eprintln!("{}", timing.trim());
+                }
+
+                // Draw render timer in the bottom right corner
+                if let (true, Some(timer_line)) = (self.render_timer, timer_line) {
+                    let primary = &config.colors().primary;
+
+                    // Shade the background slightly towards the text color
+                    let shade = |fg: u8, bg: u8| ((u16::from(fg) + u16::from(bg) * 3) / 4) as u8;
+                    let bg = Rgb {
+                        r: shade(primary.foreground.r, primary.background.r),
+                        g: shade(primary.foreground.g, primary.background.g),
+                        b: shade(primary.foreground.b, primary.background.b),
+                    };
+
+                    let col = Column(size_info.cols().0.saturating_sub(timing.len()));
+                    self.renderer
+                        .with_api(config, &size_info, visual_bell_intensity, |mut api| {
+                            api.render_string_at(
+                                &timing,
+                                timer_line,
+                                col,
+                                glyph_cache,
+                                primary.foreground,
+                                bg,
+                            );
+                        });
+                }
+            }
+
+            // Draw the context menu on top of everything else
+            if let Some(menu) = context_menu {
+                let primary = &config.colors().primary;
+
+                // Disabled items are grayed out by mixing both colors
+                let mix = |a: u8, b: u8| ((u16::from(a) + u16::from(b)) / 2) as u8;
+                let disabled = Rgb {
+                    r: mix(primary.foreground.r, primary.background.r),
+                    g: mix(primary.foreground.g, primary.background.g),
+                    b: mix(primary.foreground.b, primary.background.b),
                 };
                 self.renderer
                     .with_api(config, &size_info, visual_bell_intensity, |mut api| {
-                        api.render_string(&timing[..], glyph_cache, color);
+                        for (index, item) in menu.items.iter().enumerate() {
+                            // Items are drawn inverted, unless they're hovered
+                            let (fg, bg) = if !item.enabled {
+                                (disabled, primary.foreground)
+                            } else if menu.hovered == Some(index) {
+                                (primary.foreground, primary.background)
+                            } else {
+                                (primary.background, primary.foreground)
+                            };
+
+                            let line = menu.position.line + index;
+                            let text = menu.text(index);
+                            api.render_string_at(&text, line, menu.position.col, glyph_cache, fg, bg);
+                        }
                     });
             }
         }
@@ -3,23 +3,24 @@ use std::borrow::Cow;
 use std::fs::File;
 use std::io::Write;
 use std::sync::mpsc;
-use std::time::{Instant};
+use std::time::{Duration, Instant};
 
 use serde_json as json;
 use parking_lot::MutexGuard;
-use glutin::{self, ModifiersState, Event, ElementState};
+use glutin::{self, ModifiersState, Event, ElementState, MouseButton};
 use copypasta::{Clipboard, Load, Store, Buffer as ClipboardBuffer};
 
 use ansi::{Handler, ClearMode};
 use grid::Scroll;
 use config::{self, Config};
 use cli::Options;
-use display::OnResize;
+use display::{self, OnResize};
 use index::{Line, Column, Side, Point};
 use input::{self, MouseBinding, KeyBinding};
 use selection::Selection;
 use sync::FairMutex;
 use term::{Term, SizeInfo, TermMode, Search};
+use ticker::Ticker;
 use util::limit;
 use util::fmt::Red;
 use window::Window;
@@ -52,6 +53,14 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         *self.terminal.mode()
     }
 
+    fn terminal(&self) -> &Term {
+        self.terminal
+    }
+
+    fn terminal_mut(&mut self) -> &mut Term {
+        self.terminal
+    }
+
     fn size_info(&self) -> SizeInfo {
         *self.size_info
     }
@@ -113,6 +122,12 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
         self.terminal.dirty = true;
     }
 
+    fn block_selection(&mut self, point: Point, side: Side) {
+        let point = self.terminal.visible_to_buffer(point);
+        *self.terminal.selection_mut() = Some(Selection::block(point, side));
+        self.terminal.dirty = true;
+    }
+
     fn semantic_selection(&mut self, point: Point) {
         let point = self.terminal.visible_to_buffer(point);
         *self.terminal.selection_mut() = Some(Selection::semantic(point));
@@ -178,11 +193,15 @@ impl<'a, N: Notify + 'a> input::ActionContext for ActionContext<'a, N> {
 /// the actual changes.
 pub struct WindowChanges {
     pub hide: bool,
+
+    /// The window may have moved onto a monitor with a different DPI
+    pub monitor: bool,
 }
 
 impl WindowChanges {
     fn clear(&mut self) {
         self.hide = false;
+        self.monitor = false;
     }
 }
 
@@ -190,6 +209,7 @@ impl Default for WindowChanges {
     fn default() -> WindowChanges {
         WindowChanges {
             hide: false,
+            monitor: false,
         }
     }
 }
@@ -209,6 +229,8 @@ pub struct Mouse {
     pub middle_button_state: ElementState,
     pub right_button_state: ElementState,
     pub last_click_timestamp: Instant,
+    pub last_click_button: MouseButton,
+    pub last_click_position: (usize, usize),
     pub click_state: ClickState,
     pub scroll_px: i32,
     pub line: Line,
@@ -216,6 +238,9 @@ pub struct Mouse {
     pub cell_side: Side,
     pub lines_scrolled: f32,
     pub block_url_launcher: bool,
+    /// Lines scrolled in each step while a selection is dragged past the window's edge
+    pub selection_scroll_lines: isize,
+    pub last_selection_scroll: Instant,
 }
 
 impl Default for Mouse {
@@ -224,6 +249,8 @@ impl Default for Mouse {
             x: 0,
             y: 0,
             last_click_timestamp: Instant::now(),
+            last_click_button: MouseButton::Left,
+            last_click_position: (0, 0),
             left_button_state: ElementState::Released,
             middle_button_state: ElementState::Released,
             right_button_state: ElementState::Released,
@@ -234,6 +261,8 @@ impl Default for Mouse {
             cell_side: Side::Left,
             lines_scrolled: 0.0,
             block_url_launcher: false,
+            selection_scroll_lines: 0,
+            last_selection_scroll: Instant::now(),
         }
     }
 }
@@ -262,6 +291,9 @@ pub struct Processor<N> {
     pending_events: Vec<Event>,
     window_changes: WindowChanges,
     save_to_clipboard: bool,
+    accept_drops: bool,
+    selection_scroll_timer: Ticker,
+    last_key_input: Instant,
 }
 
 /// Notify that the terminal was resized
@@ -277,9 +309,10 @@ impl<N: Notify> Processor<N> {
     /// Create a new event processor
     ///
     /// Takes a writer which is expected to be hooked up to the write end of a
-    /// pty.
+    /// pty, and a notifier to wake up the render loop.
     pub fn new(
         notifier: N,
+        display_notifier: display::Notifier,
         resize_tx: mpsc::Sender<(u32, u32)>,
         options: &Options,
         config: &Config,
@@ -306,6 +339,13 @@ impl<N: Notify> Processor<N> {
             pending_events: Vec::with_capacity(4),
             window_changes: Default::default(),
             save_to_clipboard: config.selection().save_to_clipboard,
+            accept_drops: config.window().accept_drops(),
+            selection_scroll_timer: Ticker::new(
+                "selection scrolling",
+                Duration::from_millis(input::SELECTION_SCROLL_INTERVAL_MS),
+                display_notifier,
+            ),
+            last_key_input: Instant::now(),
         }
     }
 
@@ -353,6 +393,10 @@ impl<N: Notify> Processor<N> {
                     Resized(w, h) => {
                         resize_tx.send((w, h)).expect("send new size");
                         processor.ctx.terminal.dirty = true;
+                        processor.ctx.window_changes.monitor = true;
+                    },
+                    Moved(..) => {
+                        processor.ctx.window_changes.monitor = true;
                     },
                     KeyboardInput { input, .. } => {
                         processor.process_key(input);
@@ -371,12 +415,13 @@ impl<N: Notify> Processor<N> {
                             processor.ctx.terminal.dirty = true;
                         }
                     },
-                    CursorMoved { position: (x, y), modifiers, .. } => {
-                        let x = limit(x as i32, 0, processor.ctx.size_info.width as i32);
-                        let y = limit(y as i32, 0, processor.ctx.size_info.height as i32);
+                    CursorMoved { position, modifiers, .. } => {
+                        let x = limit(position.0 as i32, 0, processor.ctx.size_info.width as i32);
+                        let y = limit(position.1 as i32, 0, processor.ctx.size_info.height as i32);
 
                         *hide_mouse = false;
                         processor.mouse_moved(x as usize, y as usize, modifiers);
+                        processor.update_selection_scrolling(position.1, modifiers);
                     },
                     MouseWheel { delta, phase, modifiers, .. } => {
                         *hide_mouse = false;
@@ -385,7 +430,17 @@ impl<N: Notify> Processor<N> {
                     Refresh => {
                         processor.ctx.terminal.dirty = true;
                     },
+                    HiDPIFactorChanged(_) => {
+                        // The glyphs are rasterized again before the next frame
+                        processor.ctx.terminal.dirty = true;
+                        processor.ctx.window_changes.monitor = true;
+                    },
                     Focused(is_focused) => {
+                        // Some platforms report the same focus state repeatedly
+                        if *window_is_focused == is_focused {
+                            return;
+                        }
+
                         *window_is_focused = is_focused;
 
                         if is_focused {
@@ -399,14 +454,13 @@ impl<N: Notify> Processor<N> {
                         processor.on_focus_change(is_focused);
                     },
                     DroppedFile(path) => {
-                        use input::ActionContext;
-                        let path: String = path.to_string_lossy().into();
-                        processor.ctx.write_to_pty(path.into_bytes());
-                    }
+                        processor.dropped_file(&path);
+                    },
                     _ => (),
                 }
             },
             Event::Awakened => {
+                processor.scroll_selection();
                 processor.ctx.terminal.dirty = true;
             }
         }
@@ -467,6 +521,7 @@ impl<N: Notify> Processor<N> {
                 key_bindings: &self.key_bindings[..],
                 mouse_bindings: &self.mouse_bindings[..],
                 save_to_clipboard: self.save_to_clipboard,
+                accept_drops: self.accept_drops,
             };
 
             let mut window_is_focused = window.is_focused;
@@ -475,10 +530,22 @@ impl<N: Notify> Processor<N> {
             // ends.
             {
                 let hide_mouse = &mut self.hide_mouse;
+                let last_key_input = &mut self.last_key_input;
                 let mut process = |event| {
                     if print_events {
                         println!("glutin event: {:?}", event);
                     }
+
+                    // Typing is drawn without waiting for the frame limit
+                    if let Event::WindowEvent { ref event, .. } = event {
+                        match *event {
+                            glutin::WindowEvent::KeyboardInput { .. }
+                            | glutin::WindowEvent::ReceivedCharacter(_) => {
+                                *last_key_input = Instant::now();
+                            },
+                            _ => (),
+                        }
+                    }
                     Processor::handle_event(
                         &mut processor,
                         event,
@@ -496,6 +563,9 @@ impl<N: Notify> Processor<N> {
                 window.poll_events(process);
             }
 
+            #[cfg(target_os = "macos")]
+            processor.show_native_context_menu();
+
             if self.hide_mouse_when_typing {
                 window.set_mouse_visible(!self.hide_mouse);
             }
@@ -503,6 +573,13 @@ impl<N: Notify> Processor<N> {
             window.is_focused = window_is_focused;
         }
 
+        // Keep scrolling while a selection is dragged past the window's edge
+        if self.mouse.selection_scroll_lines != 0 {
+            self.selection_scroll_timer.start();
+        } else {
+            self.selection_scroll_timer.stop();
+        }
+
         if self.window_changes.hide {
             window.hide();
         }
@@ -511,16 +588,34 @@ impl<N: Notify> Processor<N> {
             window.hide();
         }
 
+        if self.window_changes.monitor && window.update_hidpi_factor() {
+            terminal.dirty = true;
+        }
+
         self.window_changes.clear();
         self.wait_for_event = !terminal.dirty;
 
         terminal
     }
 
+    /// Time of the last key press
+    pub fn last_key_input(&self) -> Instant {
+        self.last_key_input
+    }
+
+    /// Sleep until the next event, even though the terminal is dirty
+    ///
+    /// Used while a frame is deferred by the frame limit, the frame timer
+    /// wakes up the render loop once it's due.
+    pub fn wait_for_next_event(&mut self) {
+        self.wait_for_event = true;
+    }
+
     pub fn update_config(&mut self, config: &Config) {
         self.key_bindings = config.key_bindings().to_vec();
         self.mouse_bindings = config.mouse_bindings().to_vec();
         self.mouse_config = config.mouse().to_owned();
         self.save_to_clipboard = config.selection().save_to_clipboard;
+        self.accept_drops = config.window().accept_drops();
     }
 }
@@ -5,6 +5,7 @@ use std::io::{self, ErrorKind, Read, Write};
 use std::fs::File;
 use std::sync::Arc;
 use std::marker::Send;
+use std::time::{Duration, Instant};
 
 use mio::{self, Events, PollOpt, Ready};
 use mio_more::channel::{self, Receiver, Sender};
@@ -78,6 +79,7 @@ pub struct State {
     write_list: VecDeque<Cow<'static, [u8]>>,
     writing: Option<Writing>,
     parser: ansi::Processor,
+    sync_update_deadline: Option<Instant>,
 }
 
 pub struct Notifier(pub Sender<Msg>);
@@ -91,8 +93,10 @@ impl event::Notify for Notifier {
         if bytes.len() == 0 {
             return
         }
+        // The pty may not be running (yet or anymore), e.g. for focus events
+        // during startup or shutdown, so the input is dropped in that case
         if self.0.send(Msg::Input(bytes)).is_err() {
-            panic!("expected send event loop msg");
+            warn!("Dropping pty input, the event loop is not running");
         }
     }
 }
@@ -103,6 +107,7 @@ impl Default for State {
             write_list: VecDeque::new(),
             parser: ansi::Processor::new(),
             writing: None,
+            sync_update_deadline: None,
         }
     }
 }
@@ -266,7 +271,9 @@ impl<T> EventLoop<T>
                     let terminal = if terminal.is_none() {
                         terminal = Some(self.terminal.lock());
                         let terminal = terminal.as_mut().unwrap();
-                        send_wakeup = !terminal.dirty;
+                        // Reads during a synchronized update leave the terminal
+                        // dirty, the wakeup is still due when the update ends
+                        send_wakeup = !terminal.dirty || terminal.sync_update_deadline().is_some();
                         terminal
                     } else {
                         terminal.as_mut().unwrap()
@@ -278,6 +285,7 @@ impl<T> EventLoop<T>
                             .parser
                             .advance(&mut **terminal, *byte, &mut self.pty.writer());
                     }
+                    terminal.scroll_to_output();
 
                     // Exit if we've processed enough bytes
                     if processed > MAX_READ {
@@ -295,9 +303,15 @@ impl<T> EventLoop<T>
 
         // Only request a draw if one hasn't already been requested.
         if let Some(mut terminal) = terminal {
+            state.sync_update_deadline = terminal.sync_update_deadline();
+
+            // Drawing is held back during a synchronized update, so the display
+            // is only woken up once the update ended
             if send_wakeup {
-                self.display.notify();
                 terminal.dirty = true;
+                if state.sync_update_deadline.is_none() {
+                    self.display.notify();
+                }
             }
         }
 
@@ -362,13 +376,29 @@ impl<T> EventLoop<T>
             };
 
             'event_loop: loop {
-                if let Err(err) = self.poll.poll(&mut events, None) {
+                // Wake up to draw synchronized updates which never receive their end marker
+                let now = Instant::now();
+                let timeout = state.sync_update_deadline.map(|deadline| {
+                    if deadline > now {
+                        deadline - now
+                    } else {
+                        Duration::from_secs(0)
+                    }
+                });
+
+                if let Err(err) = self.poll.poll(&mut events, timeout) {
                     match err.kind() {
                         ErrorKind::Interrupted => continue,
                         _ => panic!("EventLoop polling error: {:?}", err),
                     }
                 }
 
+                if events.is_empty() && state.sync_update_deadline.take().is_some() {
+                    self.terminal.lock().dirty = true;
+                    self.display.notify();
+                    continue;
+                }
+
                 for event in events.iter() {
                     match event.token() {
                         CHANNEL => if !self.channel_event(&mut state) {
@@ -15,6 +15,7 @@
 //! A specialized 2d grid implementation optimized for use in a terminal.
 
 use std::cmp::{min, max, Ordering};
+use std::mem;
 use std::ops::{Deref, Range, Index, IndexMut, RangeTo, RangeFrom, RangeFull};
 
 use index::{self, Point, Line, Column, IndexRange};
@@ -31,6 +32,20 @@ use self::storage::Storage;
 
 const MIN_INIT_SIZE: usize = 1_000;
 
+/// Cell properties which are needed to reflow wrapped lines
+pub trait GridCell {
+    /// Check if the cell has no visible content
+    fn is_empty(&self) -> bool;
+
+    /// Check if the line continues on the next row after this cell
+    fn is_wrap(&self) -> bool;
+
+    fn set_wrap(&mut self, wrap: bool);
+
+    /// Check if the cell holds the first half of a double-width character
+    fn is_wide(&self) -> bool;
+}
+
 /// Bidirection iterator
 pub trait BidirectionalIterator: Iterator {
     fn prev(&mut self) -> Option<Self::Item>;
@@ -123,7 +138,7 @@ pub enum ViewportPosition {
     Below,
 }
 
-impl<T: Copy + Clone> Grid<T> {
+impl<T: Copy + Clone + PartialEq> Grid<T> {
     pub fn new(lines: index::Line, cols: index::Column, scrollback: usize, template: T) -> Grid<T> {
         let raw = Storage::with_capacity(lines, Row::new(cols, &template));
         Grid {
@@ -213,6 +228,9 @@ impl<T: Copy + Clone> Grid<T> {
             Ordering::Greater => self.shrink_cols(cols),
             Ordering::Equal => (),
         }
+
+        // Growing moves history into the visible area
+        self.display_offset = min(self.display_offset, self.scroll_limit);
     }
 
     fn increase_scroll_limit(&mut self, count: usize, template: &T)
@@ -315,7 +333,10 @@ impl<T: Copy + Clone> Grid<T> {
         // changing the start index.
         //
         // To accomodate scroll regions, rows are reordered at the end.
-        if region.start == Line(0) {
+        //
+        // The rotation recycles the newest lines of the scrollback history,
+        // so it can only be used when there is no history to preserve.
+        if region.start == Line(0) && self.max_scroll_limit == 0 {
             // Rotate the entire line buffer. If there's a scrolling region
             // active, the bottom lines are restored in the next step.
             self.raw.rotate_up(*positions);
@@ -391,16 +412,185 @@ impl<T: Copy + Clone> Grid<T> {
                 self.raw[i + fixed_lines].reset(&template);
             }
         } else {
-            // Subregion rotation
-            for line in IndexRange(region.start..(region.end - positions)) {
-                self.raw.swap_lines(line, line + positions);
+            self.scroll_region_up(region, positions, template);
+        }
+    }
+
+    /// Move lines at the bottom of the region towards the top
+    ///
+    /// Unlike `scroll_up`, the lines scrolled out of the region are discarded
+    /// instead of being added to the scrollback history.
+    pub fn scroll_region_up(
+        &mut self,
+        region: &Range<index::Line>,
+        positions: index::Line,
+        template: &T
+    ) {
+        // Subregion rotation
+        for line in IndexRange(region.start..(region.end - positions)) {
+            self.raw.swap_lines(line, line + positions);
+        }
+
+        // Clear reused lines
+        for line in IndexRange((region.end - positions) .. region.end) {
+            self.raw[line].reset(&template);
+        }
+    }
+}
+
+impl<T: GridCell + Copy + Clone + PartialEq> Grid<T> {
+    /// Re-wrap all lines to a new number of columns
+    ///
+    /// Rows joined by the wrap flag are treated as a single line, which is
+    /// split up again at the new width. The history takes part too, so
+    /// growing unwraps lines and pulls content back onto the screen, while
+    /// shrinking pushes the additional rows into the history. Empty rows below
+    /// the cursor are dropped before anything is pushed out.
+    ///
+    /// The cursor stays on the cell it was on.
+    pub fn reflow(&mut self, cols: index::Column, cursor: &mut Point, template: &T) {
+        if cols == self.cols {
+            return;
+        }
+
+        // Rows are moved out of the buffer and their allocations reused for
+        // the new rows, so only the cells themselves are copied
+        let cursor_index = self.line_to_offset(cursor.line);
+        let last_col = self.cols - 1;
+        let mut spare: Vec<Row<T>> = Vec::new();
+        let mut rows: Vec<Row<T>> = Vec::with_capacity(self.raw.len());
+        let mut line: Vec<T> = Vec::with_capacity(self.cols.0);
+        let mut new_cursor = (0, Column(0));
+
+        // Join wrapped rows into logical lines, starting with the oldest one,
+        // and split them up again at the new width
+        let mut index = self.lines.0 + self.scroll_limit;
+        while index > 0 {
+            line.clear();
+            let mut cursor_offset = None;
+            loop {
+                index -= 1;
+                if index == cursor_index {
+                    cursor_offset = Some(line.len() + cursor.col.0);
+                }
+
+                let row = mem::replace(&mut self.raw[index], Row::new(Column(0), template));
+                line.extend_from_slice(&row[..]);
+                let wrapped = row[last_col].is_wrap();
+
+                // Drop the blank cell left behind by a wide char which was
+                // moved to the next row
+                if wrapped
+                    && index > 0
+                    && row[last_col].is_empty()
+                    && self.raw[index - 1][Column(0)].is_wide()
+                {
+                    line.pop();
+                }
+                spare.push(row);
+
+                if !wrapped || index == 0 {
+                    break;
+                }
             }
 
-            // Clear reused lines
-            for line in IndexRange((region.end - positions) .. region.end) {
-                self.raw[line].reset(&template);
+            while line.last().map_or(false, |cell| cell.is_empty()) {
+                line.pop();
+            }
+
+            let mut row = recycle_row(&mut spare, cols, template);
+            let mut col = 0;
+            for (offset, cell) in line.iter().enumerate() {
+                // Double-width characters are never split across rows
+                if col == cols.0 || (cell.is_wide() && col + 1 == cols.0) {
+                    row[cols - 1].set_wrap(true);
+                    let next = recycle_row(&mut spare, cols, template);
+                    rows.push(mem::replace(&mut row, next));
+                    col = 0;
+                }
+
+                if cursor_offset == Some(offset) {
+                    new_cursor = (rows.len(), Column(col));
+                }
+
+                let mut cell = *cell;
+                cell.set_wrap(false);
+                row[Column(col)] = cell;
+                col += 1;
+            }
+
+            // The cursor can be behind the end of the line's content
+            match cursor_offset {
+                Some(offset) if offset >= line.len() => {
+                    let col = col + offset - line.len();
+                    new_cursor = (rows.len() + col / cols.0, Column(col % cols.0));
+                },
+                _ => (),
+            }
+
+            rows.push(row);
+
+            // Rows the cursor moved onto stay joined with its line
+            while rows.len() <= new_cursor.0 {
+                let last = rows.len() - 1;
+                rows[last][cols - 1].set_wrap(true);
+                rows.push(recycle_row(&mut spare, cols, template));
+            }
+        }
+
+        // Drop empty rows below the cursor which don't fit on the screen
+        while rows.len() > self.lines.0
+            && rows.len() - 1 > new_cursor.0
+            && rows.last().map_or(false, |row| row.iter().all(|cell| cell.is_empty()))
+        {
+            rows.pop();
+        }
+
+        // Keep the content at the top when it doesn't fill the screen
+        while rows.len() < self.lines.0 {
+            rows.push(recycle_row(&mut spare, cols, template));
+        }
+
+        // Rows above the screen go into the history, as far as it reaches
+        let history = min(rows.len() - self.lines.0, self.max_scroll_limit);
+        let dropped = rows.len() - self.lines.0 - history;
+        rows.drain(..dropped);
+
+        let available = self.raw.len() - self.lines.0;
+        if history > available {
+            self.raw.initialize(history - available, Row::new(cols, template));
+        }
+
+        let distance = rows.len() - 1 - new_cursor.0.saturating_sub(dropped);
+        for (index, row) in rows.into_iter().rev().enumerate() {
+            self.raw[index] = row;
+        }
+
+        // Unused rows of the buffer still have the old width
+        for row in self.raw.iter_mut_raw() {
+            if row.len() != cols.0 {
+                row.recycle(cols, template);
             }
         }
+
+        self.cols = cols;
+        self.scroll_limit = history;
+        self.display_offset = 0;
+        self.selection = None;
+
+        cursor.line = self.lines - 1 - min(distance, self.lines.0 - 1);
+        cursor.col = new_cursor.1;
+    }
+}
+
+/// Take a row left over from reflowing and reset it to the new width
+fn recycle_row<T: Copy>(spare: &mut Vec<Row<T>>, cols: index::Column, template: &T) -> Row<T> {
+    match spare.pop() {
+        Some(mut row) => {
+            row.recycle(cols, template);
+            row
+        },
+        None => Row::new(cols, template),
     }
 }
 
@@ -422,6 +612,7 @@ impl<T> Grid<T> {
 
     pub fn clear_history(&mut self) {
         self.scroll_limit = 0;
+        self.display_offset = 0;
     }
 
     #[inline]
@@ -429,6 +620,12 @@ impl<T> Grid<T> {
         self.scroll_limit
     }
 
+    /// Number of lines the viewport is scrolled up into the history
+    #[inline]
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
+    }
+
     /// Total number of lines in the buffer, this includes scrollback + visible lines
     #[inline]
     pub fn len(&self) -> usize {
@@ -756,6 +953,21 @@ impl<'a, T: 'a> DisplayIter<'a, T> {
     pub fn line(&self) -> Line {
         self.line
     }
+
+    /// Move to the start of the next line
+    ///
+    /// Returns `false` if there is no line left.
+    pub fn skip_line(&mut self) -> bool {
+        if self.offset == self.limit {
+            self.col = self.grid.num_cols();
+            return false;
+        }
+
+        self.offset -= 1;
+        self.col = Column(0);
+        self.line = Line(*self.grid.lines - 1 - (self.offset - self.limit));
+        true
+    }
 }
 
 impl<'a, T: Copy + 'a> Iterator for DisplayIter<'a, T> {
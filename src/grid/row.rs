@@ -51,6 +51,13 @@ impl<T: Copy + Clone> Row<T> {
         }
     }
 
+    /// Reuse the row's allocation for `columns` copies of `template`
+    pub fn recycle(&mut self, columns: Column, template: &T) {
+        self.inner.clear();
+        self.inner.resize(*columns, *template);
+        self.occ = 0;
+    }
+
     pub fn grow(&mut self, cols: Column, template: &T) {
         assert!(self.len() < * cols);
 
@@ -58,10 +65,19 @@ impl<T: Copy + Clone> Row<T> {
             self.inner.push(*template);
         }
     }
+}
 
+impl<T: Copy + PartialEq> Row<T> {
     /// Resets contents to the contents of `other`
     #[inline(never)]
     pub fn reset(&mut self, other: &T) {
+        // Unoccupied cells still hold the previous template, so they need to
+        // be reset too when the template has changed
+        let len = self.inner.len();
+        if len > 0 && self.inner[len - 1] != *other {
+            self.occ = len;
+        }
+
         let occ = self.occ;
         for item in &mut self.inner[..occ] {
             *item = *other;
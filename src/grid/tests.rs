@@ -14,7 +14,7 @@
 
 //! Tests for the Gird
 
-use super::{Grid, BidirectionalIterator};
+use super::{Grid, BidirectionalIterator, Scroll};
 use index::{Point, Line, Column};
 
 // Scroll up moves lines upwards
@@ -139,3 +139,50 @@ fn test_iter() {
     assert_eq!(None, final_iter.next());
     assert_eq!(Some(&23), final_iter.prev());
 }
+
+// Growing pulls lines out of the history and pads with the template
+#[test]
+fn resize_grow() {
+    let mut grid = Grid::new(Line(3), Column(2), 10, 0);
+    for i in 0..3 {
+        grid[Line(i)][Column(0)] = i + 1;
+    }
+
+    grid.scroll_up(&(Line(0)..Line(3)), Line(2), &0);
+    grid.scroll_display(Scroll::Top);
+    assert_eq!(grid.display_offset(), 2);
+
+    grid.resize(Line(5), Column(3), &9);
+
+    assert_eq!(grid.scroll_limit(), 0);
+    assert_eq!(grid.display_offset(), 0);
+    assert_eq!(grid[Line(0)][Column(0)], 1);
+    assert_eq!(grid[Line(1)][Column(0)], 2);
+    assert_eq!(grid[Line(2)][Column(0)], 3);
+    for i in 0..5 {
+        assert_eq!(grid[Line(i)][Column(2)], 9);
+    }
+}
+
+// Shrinking drops lines from the bottom and truncates rows
+#[test]
+fn resize_shrink() {
+    let mut grid = Grid::new(Line(4), Column(3), 10, 0);
+    for i in 0..4 {
+        grid[Line(i)][Column(0)] = i + 1;
+        grid[Line(i)][Column(2)] = i + 1;
+    }
+
+    grid.resize(Line(2), Column(1), &0);
+
+    assert_eq!(grid.num_lines(), Line(2));
+    assert_eq!(grid.num_cols(), Column(1));
+    assert_eq!(grid.scroll_limit(), 0);
+    assert_eq!(grid[Line(0)][Column(0)], 1);
+    assert_eq!(grid[Line(1)][Column(0)], 2);
+    assert_eq!(grid[Line(1)][..].len(), 1);
+
+    // Truncated cells don't come back
+    grid.resize(Line(2), Column(3), &0);
+    assert_eq!(grid[Line(1)][Column(2)], 0);
+}
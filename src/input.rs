@@ -19,25 +19,43 @@
 //! needs to be tracked. Additionally, we need a bit of a state machine to
 //! determine what to do when a non-modifier key is pressed.
 use std::borrow::Cow;
+use std::cmp::{max, min};
 use std::mem;
+use std::path::Path;
 use std::process::Command;
-use std::time::Instant;
-#[cfg(not(windows))]
-use std::os::unix::process::CommandExt;
+use std::time::{Duration, Instant};
 
 use copypasta::{Clipboard, Load, Buffer as ClipboardBuffer};
-use glutin::{ElementState, MouseButton, TouchPhase, MouseScrollDelta, ModifiersState, KeyboardInput};
+use glutin::{
+    ElementState, MouseButton, TouchPhase, MouseScrollDelta, ModifiersState, KeyboardInput,
+    VirtualKeyCode,
+};
 
-use config::{self, Key};
+use config::{self, Key, RightClickAction};
 use grid::Scroll;
 use event::{ClickState, Mouse};
 use index::{Line, Column, Side, Point};
-use term::SizeInfo;
+use term::{SizeInfo, Term};
+use term::context_menu::{ContextMenu, MenuAction};
 use term::mode::TermMode;
+use term::vi_mode::ViMotion;
 use util::fmt::Red;
+use util::start_daemon;
 
 pub const FONT_SIZE_STEP: f32 = 0.5;
 
+/// Interval between scrolling steps while a selection is dragged past the window's edge
+pub const SELECTION_SCROLL_INTERVAL_MS: u64 = 15;
+
+/// Distance from the top and bottom of the window in which selections start scrolling
+const SELECTION_SCROLL_MARGIN: f64 = 5.;
+
+/// Pixels past the margin which add another line to each scrolling step
+const SELECTION_SCROLL_STEP: f64 = 20.;
+
+/// Maximum number of lines scrolled in a single step
+const MAX_SELECTION_SCROLL_LINES: isize = 10;
+
 /// Processes input from glutin.
 ///
 /// An escape sequence may be emitted in case specific keys or key combinations
@@ -51,16 +69,20 @@ pub struct Processor<'a, A: 'a> {
     pub scrolling_config: &'a config::Scrolling,
     pub ctx: A,
     pub save_to_clipboard: bool,
+    pub accept_drops: bool,
 }
 
 pub trait ActionContext {
     fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, B);
     fn terminal_mode(&self) -> TermMode;
+    fn terminal(&self) -> &Term;
+    fn terminal_mut(&mut self) -> &mut Term;
     fn size_info(&self) -> SizeInfo;
     fn copy_selection(&self, ClipboardBuffer);
     fn clear_selection(&mut self);
     fn update_selection(&mut self, point: Point, side: Side);
     fn simple_selection(&mut self, point: Point, side: Side);
+    fn block_selection(&mut self, point: Point, side: Side);
     fn semantic_selection(&mut self, point: Point);
     fn line_selection(&mut self, point: Point);
     fn selection_is_empty(&self) -> bool;
@@ -192,6 +214,12 @@ pub enum Action {
     /// Clear the display buffer(s) to remove history
     ClearHistory,
 
+    /// Search backward through the scrollback history
+    SearchBackward,
+
+    /// Enter or leave the keyboard selection mode
+    ToggleViMode,
+
     /// Run given command
     Command(String, Vec<String>),
 
@@ -235,30 +263,8 @@ impl Action {
             Action::Command(ref program, ref args) => {
                 trace!("running command: {} {:?}", program, args);
 
-                #[cfg(not(windows))]
-                let spawned = Command::new(program)
-                    .args(args)
-                    .before_exec(|| {
-                        // Detach forked process from Alacritty. This will cause
-                        // init or whatever to clean up child processes for us.
-                        unsafe { ::libc::daemon(1, 0); }
-                        Ok(())
-                    })
-                    .spawn();
-
-                #[cfg(windows)]
-                let spawned = Command::new(program)
-                    .args(args)
-                    .spawn();
-
-                match spawned
-                {
-                    Ok(child) => {
-                        debug!("spawned new proc with pid: {}", child.id());
-                    },
-                    Err(err) => {
-                        warn!("couldn't run command: {}", err);
-                    },
+                if let Err(err) = start_daemon(program, args) {
+                    warn!("couldn't run command: {}", err);
                 }
             },
             Action::Hide => {
@@ -292,13 +298,26 @@ impl Action {
             Action::ClearHistory => {
                 ctx.clear_history();
             },
+            Action::SearchBackward => {
+                ctx.terminal_mut().start_search();
+            },
+            Action::ToggleViMode => {
+                ctx.terminal_mut().toggle_vi_mode();
+            },
         }
     }
 
     fn paste<A: ActionContext>(&self, ctx: &mut A, contents: &str) {
+        // Without a PRIMARY selection on macOS, middle-click pastes nothing
+        if contents.is_empty() {
+            return;
+        }
+
+        let contents = sanitize_paste(contents);
+
         if ctx.terminal_mode().contains(TermMode::BRACKETED_PASTE) {
             ctx.write_to_pty(&b"\x1b[200~"[..]);
-            ctx.write_to_pty(contents.replace("\x1b","").into_bytes());
+            ctx.write_to_pty(contents.into_bytes());
             ctx.write_to_pty(&b"\x1b[201~"[..]);
         } else {
             // In non-bracketed (ie: normal) mode, terminal applications cannot distinguish
@@ -312,6 +331,65 @@ impl Action {
     }
 }
 
+/// Strip control characters from pasted text
+///
+/// Only tabs and line breaks are kept. Everything else in the C0 and C1 ranges
+/// and DEL could be used to inject escape sequences, like terminating a
+/// bracketed paste on terminals which accept 8-bit controls.
+fn sanitize_paste(contents: &str) -> String {
+    contents
+        .chars()
+        .filter(|&c| !c.is_control() || c == '\t' || c == '\n' || c == '\r')
+        .collect()
+}
+
+/// Quote a path so the shell reads it as a single word
+///
+/// Paths which only contain safe characters are left alone. Control characters
+/// are escaped with ANSI-C quoting, since a line break would submit the command
+/// line; Windows shells can't quote them, so those paths are rejected there.
+fn shell_escape(path: &str) -> Option<String> {
+    let is_safe = |c: char| c.is_alphanumeric() || "/._-+,:@%=".contains(c);
+    if !path.is_empty() && path.chars().all(is_safe) {
+        return Some(path.into());
+    }
+
+    if cfg!(windows) {
+        // Windows paths can not contain double quotes
+        return if path.chars().any(char::is_control) {
+            None
+        } else {
+            Some(format!("\"{}\"", path))
+        };
+    }
+
+    if !path.chars().any(char::is_control) {
+        return Some(format!("'{}'", path.replace('\'', "'\\''")));
+    }
+
+    let mut escaped = String::from("$'");
+    for c in path.chars() {
+        match c {
+            '\\' | '\'' => {
+                escaped.push('\\');
+                escaped.push(c);
+            },
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    escaped.push_str(&format!("\\x{:02x}", byte));
+                }
+            },
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('\'');
+    Some(escaped)
+}
+
 trait RelaxedEq<T: ?Sized = Self> {
     fn relaxed_eq(&self, other: T) -> bool;
 }
@@ -347,6 +425,12 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         let prev_line = mem::replace(&mut self.ctx.mouse_mut().line, point.line);
         let prev_col = mem::replace(&mut self.ctx.mouse_mut().column, point.col);
 
+        // The context menu takes the mouse until it's closed
+        if self.ctx.terminal().context_menu().is_some() {
+            self.ctx.terminal_mut().hover_context_menu(point);
+            return;
+        }
+
         let motion_mode = TermMode::MOUSE_MOTION | TermMode::MOUSE_DRAG;
         let report_mode = TermMode::MOUSE_REPORT_CLICK | motion_mode;
 
@@ -358,6 +442,23 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             self.ctx.mouse_mut().block_url_launcher = true;
         }
 
+        // Clicks are only combined while the mouse stays close to the last click
+        let (click_x, click_y) = self.ctx.mouse().last_click_position;
+        let diff = |a: usize, b: usize| max(a, b) - min(a, b);
+        let distance = max(diff(x, click_x), diff(y, click_y));
+        let max_distance = match self.ctx.mouse().click_state {
+            ClickState::DoubleClick => self.mouse_config.triple_click.distance,
+            _ => self.mouse_config.double_click.distance,
+        };
+        if prev_line != self.ctx.mouse().line
+            || prev_col != self.ctx.mouse().column
+            || distance > max_distance
+        {
+            self.ctx.mouse_mut().click_state = ClickState::None;
+        }
+
+        self.update_url_highlight(modifiers);
+
         if self.ctx.mouse().left_button_state == ElementState::Pressed
             && (modifiers.shift || !self.ctx.terminal_mode().intersects(report_mode))
         {
@@ -405,23 +506,41 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Report using the X10 encoding, or its UTF-8 extension
+    ///
+    /// Coordinates are sent as single bytes, so positions which don't fit are
+    /// not reported. The UTF-8 extension encodes them as characters instead,
+    /// which raises the limit to 2015.
     pub fn normal_mouse_report(&mut self, button: u8) {
         let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+        let utf8 = self.ctx.terminal_mode().contains(TermMode::UTF8_MOUSE);
+        let max_point = if utf8 { 2015 } else { 223 };
 
-        if line < Line(223) && column < Column(223) {
-            let msg = vec![
-                b'\x1b',
-                b'[',
-                b'M',
-                32 + button,
-                32 + 1 + column.0 as u8,
-                32 + 1 + line.0 as u8,
-            ];
+        if line < Line(max_point) && column < Column(max_point) {
+            let mut msg = vec![b'\x1b', b'[', b'M', 32 + button];
+
+            for &value in &[32 + 1 + column.0, 32 + 1 + line.0] {
+                if utf8 {
+                    let c = ::std::char::from_u32(value as u32).unwrap_or(' ');
+                    let mut buf = [0; 4];
+                    msg.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                } else {
+                    msg.push(value as u8);
+                }
+            }
 
             self.ctx.write_to_pty(msg);
         }
     }
 
+    /// Report using the URXVT encoding, with decimal coordinates
+    pub fn urxvt_mouse_report(&mut self, button: u8) {
+        let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
+
+        let msg = format!("\x1b[{};{};{}M", 32 + button, column + 1, line + 1);
+        self.ctx.write_to_pty(msg.into_bytes());
+    }
+
     pub fn sgr_mouse_report(&mut self, button: u8, state: ElementState) {
         let (line, column) = (self.ctx.mouse().line, self.ctx.mouse().column);
         let c = match state {
@@ -446,13 +565,21 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
             mods += 16;
         }
 
-        // Report mouse events
+        // Only the SGR encoding can tell which button was released
         if self.ctx.terminal_mode().contains(TermMode::SGR_MOUSE) {
             self.sgr_mouse_report(button + mods, state);
-        } else if let ElementState::Released = state {
-            self.normal_mouse_report(3 + mods);
+            return;
+        }
+
+        let button = match state {
+            ElementState::Released => 3 + mods,
+            ElementState::Pressed => button + mods,
+        };
+
+        if self.ctx.terminal_mode().contains(TermMode::URXVT_MOUSE) {
+            self.urxvt_mouse_report(button);
         } else {
-            self.normal_mouse_report(button + mods);
+            self.normal_mouse_report(button);
         }
     }
 
@@ -469,10 +596,42 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     }
 
     pub fn on_mouse_press(&mut self, button: MouseButton, modifiers: ModifiersState) {
+        // Clicks only close the context menu while it's open
+        if self.ctx.terminal().context_menu().is_some() {
+            self.context_menu_click(button);
+            return;
+        }
+
+        // Configured mouse bindings take precedence over the right click action
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        if button == MouseButton::Right
+            && (modifiers.shift || !self.ctx.terminal_mode().intersects(mouse_modes))
+            && !self.has_mouse_binding(modifiers, button)
+        {
+            match self.mouse_config.right_click_action {
+                RightClickAction::ContextMenu => {
+                    self.open_context_menu();
+                    return;
+                },
+                RightClickAction::Paste => {
+                    Action::Paste.execute(&mut self.ctx, false);
+                    return;
+                },
+                RightClickAction::None => (),
+            }
+        }
+
         let now = Instant::now();
         let elapsed = self.ctx.mouse().last_click_timestamp.elapsed();
         self.ctx.mouse_mut().last_click_timestamp = now;
 
+        // Clicks with different buttons are never combined
+        if mem::replace(&mut self.ctx.mouse_mut().last_click_button, button) != button {
+            self.ctx.mouse_mut().click_state = ClickState::None;
+        }
+        let position = (self.ctx.mouse().x, self.ctx.mouse().y);
+        self.ctx.mouse_mut().last_click_position = position;
+
         self.ctx.mouse_mut().click_state = match self.ctx.mouse().click_state {
             ClickState::Click if elapsed < self.mouse_config.double_click.threshold => {
                 self.ctx.mouse_mut().block_url_launcher = true;
@@ -490,10 +649,17 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
                 self.ctx.clear_selection();
 
-                // Start new empty selection
+                // Start new empty selection, the kind is kept until the button is released
                 if let Some(point) = self.ctx.mouse_coords() {
                     let side = self.ctx.mouse().cell_side;
-                    self.ctx.simple_selection(point, side);
+                    let block_modifiers = self.mouse_config.block_selection_modifiers;
+                    if block_modifiers != ModifiersState::default()
+                        && block_modifiers.relaxed_eq(modifiers)
+                    {
+                        self.ctx.block_selection(point, side);
+                    } else {
+                        self.ctx.simple_selection(point, side);
+                    }
                 }
 
                 let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
@@ -513,7 +679,144 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         };
     }
 
+    /// Scroll while a selection is dragged close to or past the top or bottom of the window
+    ///
+    /// The position isn't limited to the window, since the mouse is still
+    /// reported while dragging outside of it. The further it is past the edge,
+    /// the more lines are scrolled in each step.
+    pub fn update_selection_scrolling(&mut self, y: f64, modifiers: ModifiersState) {
+        let report_mode = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        let selecting = self.ctx.mouse().left_button_state == ElementState::Pressed
+            && (modifiers.shift || !self.ctx.terminal_mode().intersects(report_mode));
+
+        let size_info = self.ctx.size_info();
+        let top = f64::from(size_info.padding_y).max(SELECTION_SCROLL_MARGIN);
+        let bottom = f64::from(size_info.height) - top;
+
+        let lines = if !selecting {
+            0
+        } else if y <= top {
+            ((top - y + SELECTION_SCROLL_STEP) / SELECTION_SCROLL_STEP) as isize
+        } else if y >= bottom {
+            -(((y - bottom + SELECTION_SCROLL_STEP) / SELECTION_SCROLL_STEP) as isize)
+        } else {
+            0
+        };
+        let lines = max(-MAX_SELECTION_SCROLL_LINES, min(lines, MAX_SELECTION_SCROLL_LINES));
+
+        let was_scrolling = self.ctx.mouse().selection_scroll_lines != 0;
+        self.ctx.mouse_mut().selection_scroll_lines = lines;
+
+        // Don't wait for the next step when reaching the edge
+        if !was_scrolling && lines != 0 {
+            self.selection_scroll_step();
+        }
+    }
+
+    /// Scroll the selection when the interval since the last step has passed
+    pub fn scroll_selection(&mut self) {
+        let interval = Duration::from_millis(SELECTION_SCROLL_INTERVAL_MS);
+        if self.ctx.mouse().selection_scroll_lines != 0
+            && self.ctx.mouse().last_selection_scroll.elapsed() >= interval
+        {
+            self.selection_scroll_step();
+        }
+    }
+
+    /// Scroll and extend the selection to the lines scrolled into view
+    fn selection_scroll_step(&mut self) {
+        self.ctx.mouse_mut().last_selection_scroll = Instant::now();
+
+        let lines = self.ctx.mouse().selection_scroll_lines;
+        self.ctx.scroll(Scroll::Lines(lines));
+
+        let (x, y) = (self.ctx.mouse().x, self.ctx.mouse().y);
+        let point = self.ctx.size_info().pixels_to_coords(x, y);
+        let side = self.ctx.mouse().cell_side;
+        self.ctx.update_selection(point, side);
+    }
+
+    /// Open the context menu at the mouse cursor
+    fn open_context_menu(&mut self) {
+        let point = match self.ctx.mouse_coords() {
+            Some(point) => point,
+            None => return,
+        };
+
+        let url = if self.mouse_config.url.launcher.is_some() {
+            self.ctx.url(point.into())
+        } else {
+            None
+        };
+
+        let has_selection = !self.ctx.selection_is_empty();
+        let size_info = self.ctx.size_info();
+        let menu = ContextMenu::new(point, has_selection, url, size_info.lines(), size_info.cols());
+        self.ctx.terminal_mut().open_context_menu(menu);
+        self.consume_click(MouseButton::Right);
+    }
+
+    /// Close the context menu, running the clicked item
+    fn context_menu_click(&mut self, button: MouseButton) {
+        self.consume_click(button);
+
+        let menu = match self.ctx.terminal_mut().close_context_menu() {
+            Some(menu) => menu,
+            None => return,
+        };
+        if button != MouseButton::Left {
+            return;
+        }
+
+        let item = self.ctx.mouse_coords()
+            .and_then(|point| menu.item_at(point))
+            .map(|index| &menu.items[index])
+            .filter(|item| item.enabled);
+        if let Some(item) = item {
+            self.run_menu_action(&item.action);
+        }
+    }
+
+    /// Show the open context menu with NSMenu instead of drawing it
+    ///
+    /// This blocks until the menu is closed, the clicked item is run afterwards.
+    #[cfg(target_os = "macos")]
+    pub fn show_native_context_menu(&mut self) {
+        let menu = match self.ctx.terminal_mut().close_context_menu() {
+            Some(menu) => menu,
+            None => return,
+        };
+
+        let items: Vec<_> = menu.items.iter().map(|item| (item.label(), item.enabled)).collect();
+        if let Some(index) = ::native_menu::popup(&items) {
+            self.run_menu_action(&menu.items[index].action);
+        }
+    }
+
+    fn run_menu_action(&mut self, action: &MenuAction) {
+        match *action {
+            MenuAction::Copy => self.ctx.copy_selection(ClipboardBuffer::Primary),
+            MenuAction::Paste => Action::Paste.execute(&mut self.ctx, false),
+            MenuAction::OpenUrl(ref url) => self.open_url(url.clone()),
+        }
+    }
+
+    /// Ignore the release and drags of a click which was handled by the context menu
+    fn consume_click(&mut self, button: MouseButton) {
+        let mouse = self.ctx.mouse_mut();
+        match button {
+            MouseButton::Left => mouse.left_button_state = ElementState::Released,
+            MouseButton::Middle => mouse.middle_button_state = ElementState::Released,
+            MouseButton::Right => mouse.right_button_state = ElementState::Released,
+            MouseButton::Other(_) => (),
+        }
+    }
+
     pub fn on_mouse_release(&mut self, button: MouseButton, modifiers: ModifiersState) {
+        if button == MouseButton::Left {
+            self.ctx.mouse_mut().selection_scroll_lines = 0;
+        }
+
         let report_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
         if !modifiers.shift && self.ctx.terminal_mode().intersects(report_modes)
         {
@@ -535,6 +838,25 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         self.ctx.copy_selection(ClipboardBuffer::Selection);
     }
 
+    /// Underline the URL under the mouse cursor while the URL modifiers are held
+    ///
+    /// Applications which grabbed the mouse keep it, unless Shift is held to
+    /// override the mouse mode.
+    fn update_url_highlight(&mut self, modifiers: ModifiersState) {
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        let enabled = self.mouse_config.url.launcher.is_some()
+            && self.mouse_config.url.modifiers.relaxed_eq(modifiers)
+            && (modifiers.shift || !self.ctx.terminal_mode().intersects(mouse_modes));
+
+        let url = if enabled {
+            self.ctx.mouse_coords().and_then(|point| self.ctx.terminal().url_at(point.into()))
+        } else {
+            None
+        };
+
+        self.ctx.terminal_mut().set_hovered_url(url);
+    }
+
     // Spawn URL launcher when clicking on URLs
     fn launch_url(&self, modifiers: ModifiersState) -> Option<()> {
         if !self.mouse_config.url.modifiers.relaxed_eq(modifiers)
@@ -545,17 +867,25 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
 
         let point = self.ctx.mouse_coords()?;
         let text = self.ctx.url(point.into())?;
+        self.open_url(text);
+
+        Some(())
+    }
+
+    /// Open a URL with the configured launcher
+    fn open_url(&self, url: String) {
+        let launcher = match self.mouse_config.url.launcher {
+            Some(ref launcher) => launcher,
+            None => return,
+        };
 
-        let launcher = self.mouse_config.url.launcher.as_ref()?;
         let mut args = launcher.args().to_vec();
-        args.push(text);
+        args.push(url);
 
         match Command::new(launcher.program()).args(&args).spawn() {
             Ok(_) => debug!("Launched: {} {:?}", launcher.program(), args),
             Err(_) => warn!("Unable to launch: {} {:?}", launcher.program(), args),
         }
-
-        Some(())
     }
 
     pub fn on_mouse_wheel(&mut self, delta: MouseScrollDelta, phase: TouchPhase, modifiers: ModifiersState) {
@@ -606,6 +936,9 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     fn scroll_terminal(&mut self, code: u8, modifiers: ModifiersState, scroll_multiplier: u8) {
         debug_assert!(code == 64 || code == 65);
 
+        // Natural scrolling swaps wheel up (64) and wheel down (65)
+        let code = if self.scrolling_config.natural { 129 - code } else { code };
+
         let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
 
         // Make sure the new and deprecated setting are both allowed
@@ -618,12 +951,17 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         } else if self.ctx.terminal_mode().contains(TermMode::ALT_SCREEN)
             && faux_scrolling_lines > 0 && !modifiers.shift
         {
-            // Faux scrolling
+            // Faux scrolling, using the sequences of the current cursor key mode
             let cmd = code + 1; // 64 + 1 = A, 65 + 1 = B
+            let prefix = if self.ctx.terminal_mode().contains(TermMode::APP_CURSOR) {
+                b'O'
+            } else {
+                b'['
+            };
             let mut content = Vec::with_capacity(faux_scrolling_lines as usize * 3);
             for _ in 0..faux_scrolling_lines {
                 content.push(0x1b);
-                content.push(b'O');
+                content.push(prefix);
                 content.push(cmd);
             }
             self.ctx.write_to_pty(content);
@@ -635,6 +973,7 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Report focus changes to the application if it requested them (DECSET 1004)
     pub fn on_focus_change(&mut self, is_focused: bool) {
         if self.ctx.terminal_mode().contains(TermMode::FOCUS_IN_OUT) {
             let chr = if is_focused {
@@ -648,6 +987,21 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Insert the path of a file dropped onto the window
+    ///
+    /// Every file is dropped separately, so a space follows each path to keep
+    /// them apart when multiple files are dropped at once.
+    pub fn dropped_file(&mut self, path: &Path) {
+        if !self.accept_drops {
+            return;
+        }
+
+        match shell_escape(&path.to_string_lossy()) {
+            Some(path) => self.ctx.write_to_pty(format!("{} ", path).into_bytes()),
+            None => warn!("Ignoring dropped path with control characters: {:?}", path),
+        }
+    }
+
     pub fn mouse_input(&mut self, state: ElementState, button: MouseButton, modifiers: ModifiersState) {
         let prev_state = match button {
             MouseButton::Left     => Some(mem::replace(&mut self.ctx.mouse_mut().left_button_state, state)),
@@ -677,22 +1031,139 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     ///
     /// If a keybinding was run, returns true. Otherwise returns false.
     pub fn process_key(&mut self, input: KeyboardInput) {
+        // Pressing or releasing the URL modifiers changes the highlight
+        self.update_url_highlight(input.modifiers);
+
+        // Key presses close the context menu, Escape doesn't do anything else
+        if self.ctx.terminal().context_menu().is_some() && input.state == ElementState::Pressed {
+            self.ctx.terminal_mut().close_context_menu();
+            if input.virtual_keycode == Some(VirtualKeyCode::Escape) {
+                *self.ctx.suppress_chars() = true;
+                return;
+            }
+        }
+
+        if self.ctx.terminal().search().is_some() {
+            self.process_search_key(input);
+            return;
+        }
+
+        if self.ctx.terminal().vi_mode().is_some() {
+            self.process_vi_key(input);
+            return;
+        }
+
+        let key_sequence = self.key_sequence(input);
+
         match input.state {
             ElementState::Pressed => {
                 *self.ctx.last_modifiers() = input.modifiers;
                 *self.ctx.received_count() = 0;
                 *self.ctx.suppress_chars() = false;
 
-                if self.process_key_bindings(input) {
+                // Cursor and keypad keys still work when they're missing from the bindings
+                let mode = self.ctx.terminal_mode();
+                let fallback = || {
+                    let key = Key::from_glutin_input(input.virtual_keycode?);
+                    cursor_key_sequence(mode, key, input.modifiers)
+                        .or_else(|| keypad_key_sequence(mode, key, input.modifiers))
+                };
+
+                if self.process_key_bindings(input, key_sequence.is_some()) {
                     *self.ctx.suppress_chars() = true;
+                } else if let Some(sequence) = key_sequence.or_else(fallback) {
+                    self.ctx.scroll(Scroll::Bottom);
+                    self.ctx.write_to_pty(sequence);
+                    *self.ctx.suppress_chars() = true;
+                }
+            },
+            ElementState::Released => {
+                *self.ctx.suppress_chars() = false;
+
+                if let Some(sequence) = key_sequence {
+                    self.ctx.write_to_pty(sequence);
                 }
             },
-            ElementState::Released => *self.ctx.suppress_chars() = false,
+        }
+    }
+
+    /// Process a key press while the search is active
+    ///
+    /// Keys never reach the application during a search. Characters are
+    /// handled by `received_char`, only editing and navigation keys are
+    /// processed here.
+    fn process_search_key(&mut self, input: KeyboardInput) {
+        *self.ctx.suppress_chars() = false;
+        if input.state == ElementState::Released {
+            return;
+        }
+
+        let editing = self.ctx.terminal().search().map_or(false, |search| search.editing);
+        let terminal = self.ctx.terminal_mut();
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::Escape) => terminal.cancel_search(),
+            Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) => if editing {
+                terminal.search_confirm();
+            } else {
+                terminal.search_previous();
+            },
+            Some(VirtualKeyCode::Back) if editing => terminal.search_pop(),
+            Some(VirtualKeyCode::F3) if input.modifiers.shift => terminal.search_next(),
+            Some(VirtualKeyCode::F3) => terminal.search_previous(),
+            _ => (),
+        }
+    }
+
+    /// Process a key press while the vi mode is active
+    ///
+    /// Key bindings which don't write to the pty still work, everything else
+    /// is swallowed. Commands are read from the received characters.
+    fn process_vi_key(&mut self, input: KeyboardInput) {
+        *self.ctx.suppress_chars() = false;
+        if input.state == ElementState::Released {
+            return;
+        }
+
+        *self.ctx.last_modifiers() = input.modifiers;
+        *self.ctx.received_count() = 0;
+
+        if self.process_key_bindings(input, true) {
+            *self.ctx.suppress_chars() = true;
+        } else if input.virtual_keycode == Some(VirtualKeyCode::Escape) {
+            self.ctx.terminal_mut().toggle_vi_mode();
+        }
+    }
+
+    /// Encode a key event using the keyboard protocol requested by the application
+    ///
+    /// The kitty keyboard protocol takes precedence over `modifyOtherKeys`.
+    /// Returns `None` if no protocol is active or the key should be sent
+    /// using the legacy encoding.
+    fn key_sequence(&mut self, input: KeyboardInput) -> Option<Vec<u8>> {
+        let mode = self.ctx.terminal_mode();
+        let key = Key::from_glutin_input(input.virtual_keycode?);
+
+        if mode.intersects(TermMode::KITTY_KEYBOARD_PROTOCOL) {
+            kitty_key_sequence(mode, key, input.modifiers, input.state)
+        } else if input.state == ElementState::Pressed {
+            modify_other_keys_sequence(mode, key, input.modifiers)
+        } else {
+            None
         }
     }
 
     /// Process a received character
     pub fn received_char(&mut self, c: char) {
+        if self.ctx.terminal().search().is_some() {
+            self.received_search_char(c);
+            return;
+        }
+
+        if self.ctx.terminal().vi_mode().is_some() {
+            self.received_vi_char(c);
+            return;
+        }
+
         if !*self.ctx.suppress_chars() {
             self.ctx.scroll(Scroll::Bottom);
             self.ctx.clear_selection();
@@ -714,15 +1185,85 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         }
     }
 
+    /// Process a received character while the search is active
+    ///
+    /// Characters are appended to the query while it's being edited, after
+    /// that `n` and `N` move to the previous and next match.
+    fn received_search_char(&mut self, c: char) {
+        if *self.ctx.suppress_chars() || c.is_control() {
+            return;
+        }
+
+        let terminal = self.ctx.terminal_mut();
+        match c {
+            _ if terminal.search().map_or(false, |search| search.editing) => {
+                terminal.search_input(c)
+            },
+            'n' => terminal.search_previous(),
+            'N' => terminal.search_next(),
+            _ => (),
+        }
+    }
+
+    /// Process a received character while the vi mode is active
+    fn received_vi_char(&mut self, c: char) {
+        if *self.ctx.suppress_chars() {
+            return;
+        }
+
+        // Commands like `gg` are made up of two keys
+        let pending = self.ctx.terminal_mut()
+            .vi_mode_mut()
+            .and_then(|vi_mode| vi_mode.pending.take());
+        let motion = match (pending, c) {
+            (_, 'h') => ViMotion::Left,
+            (_, 'j') => ViMotion::Down,
+            (_, 'k') => ViMotion::Up,
+            (_, 'l') => ViMotion::Right,
+            (_, '0') => ViMotion::First,
+            (_, '$') => ViMotion::Last,
+            (_, 'b') => ViMotion::WordLeft,
+            (_, 'w') => ViMotion::WordRight,
+            (Some('g'), 'g') => ViMotion::Top,
+            (_, 'G') => ViMotion::Bottom,
+            (_, 'g') => {
+                if let Some(vi_mode) = self.ctx.terminal_mut().vi_mode_mut() {
+                    vi_mode.pending = Some('g');
+                }
+                return;
+            },
+            (_, 'v') => return self.ctx.terminal_mut().vi_select(false),
+            (_, 'V') => return self.ctx.terminal_mut().vi_select(true),
+            (_, '/') => return self.ctx.terminal_mut().start_search(),
+            (_, 'i') => return self.ctx.terminal_mut().toggle_vi_mode(),
+            (_, 'y') => {
+                self.ctx.copy_selection(ClipboardBuffer::Primary);
+                self.ctx.terminal_mut().vi_clear_selection();
+                return;
+            },
+            _ => return,
+        };
+
+        self.ctx.terminal_mut().vi_motion(motion);
+    }
+
     /// Attempts to find a binding and execute its action
     ///
     /// The provided mode, mods, and key must match what is allowed by a binding
     /// for its action to be executed.
     ///
     /// Returns true if an action is executed.
-    fn process_key_bindings(&mut self, input: KeyboardInput) -> bool {
+    ///
+    /// When `skip_escapes` is set, bindings which would write an escape
+    /// sequence are ignored in favor of the encoding requested by the application.
+    fn process_key_bindings(&mut self, input: KeyboardInput, skip_escapes: bool) -> bool {
         let mut has_binding = false;
         for binding in self.key_bindings {
+            match binding.action {
+                Action::Esc(_) if skip_escapes => continue,
+                _ => (),
+            }
+
             let is_triggered = match binding.trigger {
                 Key::Scancode(_) => binding.is_triggered_by(
                     self.ctx.terminal_mode(),
@@ -748,6 +1289,12 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
         has_binding
     }
 
+    /// Check if a mouse binding would be triggered by `button`
+    fn has_mouse_binding(&self, mods: ModifiersState, button: MouseButton) -> bool {
+        let mode = self.ctx.terminal_mode();
+        self.mouse_bindings.iter().any(|binding| binding.is_triggered_by(mode, mods, &button, true))
+    }
+
     /// Attempts to find a binding and execute its action
     ///
     /// The provided mode, mods, and key must match what is allowed by a binding
@@ -773,21 +1320,294 @@ impl<'a, A: ActionContext + 'a> Processor<'a, A> {
     }
 }
 
+/// Build the kitty keyboard protocol escape sequence for a key event
+///
+/// Keys are reported as `CSI code ; modifiers : event u`, with the legacy
+/// `CSI 1 ; modifiers X` and `CSI number ; modifiers ~` forms being kept for
+/// cursor, editing and function keys.
+fn kitty_key_sequence(
+    mode: TermMode,
+    key: Key,
+    mods: ModifiersState,
+    state: ElementState,
+) -> Option<Vec<u8>> {
+    let all_keys = mode.contains(TermMode::REPORT_ALL_KEYS_AS_ESC);
+    let released = state == ElementState::Released;
+    if released && !mode.contains(TermMode::REPORT_EVENT_TYPES) {
+        return None;
+    }
+
+    let modifiers = modifier_param(mods);
+    let has_mods = modifiers > 1;
+
+    let (code, terminator) = match key {
+        Key::Escape => (27, 'u'),
+        Key::Return | Key::NumpadEnter | Key::Tab | Key::Back => {
+            // Keep these keys usable if an application crashes with the protocol enabled
+            if !all_keys && !has_mods {
+                return None;
+            }
+
+            let code = match key {
+                Key::Tab => 9,
+                Key::Back => 127,
+                _ => 13,
+            };
+            (code, 'u')
+        },
+        Key::Up => (1, 'A'),
+        Key::Down => (1, 'B'),
+        Key::Right => (1, 'C'),
+        Key::Left => (1, 'D'),
+        Key::End => (1, 'F'),
+        Key::Home => (1, 'H'),
+        Key::F1 => (1, 'P'),
+        Key::F2 => (1, 'Q'),
+        Key::F4 => (1, 'S'),
+        Key::Insert => (2, '~'),
+        Key::Delete => (3, '~'),
+        Key::PageUp => (5, '~'),
+        Key::PageDown => (6, '~'),
+        Key::F3 => (13, '~'),
+        Key::F5 => (15, '~'),
+        Key::F6 => (17, '~'),
+        Key::F7 => (18, '~'),
+        Key::F8 => (19, '~'),
+        Key::F9 => (20, '~'),
+        Key::F10 => (21, '~'),
+        Key::F11 => (23, '~'),
+        Key::F12 => (24, '~'),
+        Key::F13 => (57376, 'u'),
+        Key::F14 => (57377, 'u'),
+        Key::F15 => (57378, 'u'),
+        Key::LShift if all_keys => (57441, 'u'),
+        Key::LControl if all_keys => (57442, 'u'),
+        Key::LAlt if all_keys => (57443, 'u'),
+        Key::LWin if all_keys => (57444, 'u'),
+        Key::RShift if all_keys => (57447, 'u'),
+        Key::RControl if all_keys => (57448, 'u'),
+        Key::RAlt if all_keys => (57449, 'u'),
+        Key::RWin if all_keys => (57450, 'u'),
+        _ => {
+            let c = text_key_codepoint(key)?;
+
+            // Text is only encoded when it can't be sent unambiguously as is
+            if !all_keys && !released && !mods.ctrl && !mods.alt && !mods.logo {
+                return None;
+            }
+
+            (c as u32, 'u')
+        },
+    };
+
+    // Cursor keys without modifiers keep their legacy encoding
+    if terminator != 'u' && terminator != '~' && !has_mods && !released && !all_keys {
+        return None;
+    }
+
+    let mut sequence = String::from("\x1b[");
+    if terminator == 'u' || terminator == '~' || has_mods || released {
+        sequence.push_str(&code.to_string());
+    }
+
+    if released {
+        sequence.push_str(&format!(";{}:3", modifiers));
+    } else if has_mods {
+        sequence.push_str(&format!(";{}", modifiers));
+    }
+
+    sequence.push(terminator);
+    Some(sequence.into_bytes())
+}
+
+/// Legacy encoding of unmodified cursor keys without a matching binding
+///
+/// Keys are sent as `SS3 X` in application cursor mode (DECCKM) and as
+/// `CSI X` otherwise.
+fn cursor_key_sequence(mode: TermMode, key: Key, mods: ModifiersState) -> Option<Vec<u8>> {
+    if mods.shift || mods.ctrl || mods.alt || mods.logo {
+        return None;
+    }
+
+    let c = match key {
+        Key::Up => 'A',
+        Key::Down => 'B',
+        Key::Right => 'C',
+        Key::Left => 'D',
+        Key::End => 'F',
+        Key::Home => 'H',
+        _ => return None,
+    };
+
+    let prefix = if mode.contains(TermMode::APP_CURSOR) { "\x1bO" } else { "\x1b[" };
+    Some(format!("{}{}", prefix, c).into_bytes())
+}
+
+/// Encoding of unmodified numpad keys in application keypad mode (DECKPAM)
+///
+/// Keys are sent as `SS3 X`. In numeric keypad mode the keys produce their
+/// text instead, with the platform reporting navigation keys while NumLock
+/// is off.
+fn keypad_key_sequence(mode: TermMode, key: Key, mods: ModifiersState) -> Option<Vec<u8>> {
+    if !mode.contains(TermMode::APP_KEYPAD) || mods.shift || mods.ctrl || mods.alt || mods.logo {
+        return None;
+    }
+
+    // `Add` and `Multiply` are not included since they are also reported for
+    // the `+` and `*` keys of the main keyboard on some platforms.
+    let c = match key {
+        Key::Numpad0 => 'p',
+        Key::Numpad1 => 'q',
+        Key::Numpad2 => 'r',
+        Key::Numpad3 => 's',
+        Key::Numpad4 => 't',
+        Key::Numpad5 => 'u',
+        Key::Numpad6 => 'v',
+        Key::Numpad7 => 'w',
+        Key::Numpad8 => 'x',
+        Key::Numpad9 => 'y',
+        Key::NumpadComma => 'l',
+        Key::Subtract => 'm',
+        Key::Decimal => 'n',
+        Key::Divide => 'o',
+        Key::NumpadEnter => 'M',
+        Key::NumpadEquals => 'X',
+        _ => return None,
+    };
+
+    Some(format!("\x1bO{}", c).into_bytes())
+}
+
+/// Build the XTerm `modifyOtherKeys` escape sequence for a key press
+///
+/// Modified keys are reported as `CSI 27 ; modifiers ; codepoint ~`. Returns
+/// `None` if the key should be sent using the legacy encoding.
+fn modify_other_keys_sequence(mode: TermMode, key: Key, mods: ModifiersState) -> Option<Vec<u8>> {
+    let (codepoint, is_text) = match key {
+        Key::Tab => ('\t', false),
+        Key::Return | Key::NumpadEnter => ('\r', false),
+        Key::Escape => ('\x1b', false),
+        Key::Back => ('\x7f', false),
+        _ => (text_key_codepoint(key)?, true),
+    };
+
+    let encode = if mode.contains(TermMode::MODIFY_OTHER_KEYS_ALL) {
+        // Shifted text is sent as is
+        mods.ctrl || mods.alt || mods.logo || (mods.shift && !is_text)
+    } else if mode.contains(TermMode::MODIFY_OTHER_KEYS) {
+        // Control characters which can be sent unambiguously keep their legacy encoding
+        let well_defined = match key {
+            Key::Space | Key::LBracket | Key::Backslash | Key::RBracket => true,
+            _ => codepoint.is_ascii_alphabetic(),
+        };
+        mods.ctrl && !(well_defined && !mods.shift)
+    } else {
+        false
+    };
+
+    if !encode {
+        return None;
+    }
+
+    // Report the shifted letter, other keys can't be shifted without the keyboard layout
+    let codepoint = if mods.shift && is_text {
+        codepoint.to_ascii_uppercase()
+    } else {
+        codepoint
+    };
+
+    let sequence = format!("\x1b[27;{};{}~", modifier_param(mods), codepoint as u32);
+    Some(sequence.into_bytes())
+}
+
+/// XTerm style modifier parameter of escape sequences
+#[inline]
+fn modifier_param(mods: ModifiersState) -> u8 {
+    1 + mods.shift as u8
+        + ((mods.alt as u8) << 1)
+        + ((mods.ctrl as u8) << 2)
+        + ((mods.logo as u8) << 3)
+}
+
+/// Unshifted codepoint of keys which produce text
+fn text_key_codepoint(key: Key) -> Option<char> {
+    let c = match key {
+        Key::A => 'a',
+        Key::B => 'b',
+        Key::C => 'c',
+        Key::D => 'd',
+        Key::E => 'e',
+        Key::F => 'f',
+        Key::G => 'g',
+        Key::H => 'h',
+        Key::I => 'i',
+        Key::J => 'j',
+        Key::K => 'k',
+        Key::L => 'l',
+        Key::M => 'm',
+        Key::N => 'n',
+        Key::O => 'o',
+        Key::P => 'p',
+        Key::Q => 'q',
+        Key::R => 'r',
+        Key::S => 's',
+        Key::T => 't',
+        Key::U => 'u',
+        Key::V => 'v',
+        Key::W => 'w',
+        Key::X => 'x',
+        Key::Y => 'y',
+        Key::Z => 'z',
+        Key::Key0 => '0',
+        Key::Key1 => '1',
+        Key::Key2 => '2',
+        Key::Key3 => '3',
+        Key::Key4 => '4',
+        Key::Key5 => '5',
+        Key::Key6 => '6',
+        Key::Key7 => '7',
+        Key::Key8 => '8',
+        Key::Key9 => '9',
+        Key::Space => ' ',
+        Key::Apostrophe => '\'',
+        Key::Backslash => '\\',
+        Key::Comma => ',',
+        Key::Equals => '=',
+        Key::Grave => '`',
+        Key::LBracket => '[',
+        Key::Minus => '-',
+        Key::Period => '.',
+        Key::RBracket => ']',
+        Key::Semicolon => ';',
+        Key::Slash => '/',
+        _ => return None,
+    };
+
+    Some(c)
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
     use std::time::Duration;
 
-    use glutin::{VirtualKeyCode, Event, WindowEvent, ElementState, MouseButton, ModifiersState};
+    use glutin::{
+        VirtualKeyCode, Event, WindowEvent, ElementState, MouseButton, ModifiersState,
+        KeyboardInput, MouseScrollDelta, TouchPhase,
+    };
 
     use term::{SizeInfo, Term, TermMode};
     use event::{Mouse, ClickState, WindowChanges};
-    use config::{self, Config, ClickHandler};
-    use index::{Point, Side};
+    use config::{self, Config, ClickHandler, Key, RightClickAction};
+    use index::{Column, Line, Point, Side};
     use selection::Selection;
     use grid::Scroll;
+    use ansi::{self, Handler};
 
-    use super::{Action, Binding, Processor};
+    use super::{
+        Action, Binding, Processor, kitty_key_sequence, modify_other_keys_sequence, sanitize_paste,
+        shell_escape, MAX_SELECTION_SCROLL_LINES, SELECTION_SCROLL_INTERVAL_MS,
+    };
     use copypasta::Buffer as ClipboardBuffer;
 
     const KEY: VirtualKeyCode = VirtualKeyCode::Key0;
@@ -809,17 +1629,26 @@ mod tests {
         pub suppress_chars: bool,
         pub last_modifiers: ModifiersState,
         pub window_changes: &'a mut WindowChanges,
+        pub written: Vec<u8>,
     }
 
     impl <'a>super::ActionContext for ActionContext<'a> {
-        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, _val: B) {
-            // STUBBED
+        fn write_to_pty<B: Into<Cow<'static, [u8]>>>(&mut self, val: B) {
+            self.written.extend_from_slice(&val.into());
         }
 
         fn terminal_mode(&self) -> TermMode {
             *self.terminal.mode()
         }
 
+        fn terminal(&self) -> &Term {
+            self.terminal
+        }
+
+        fn terminal_mut(&mut self) -> &mut Term {
+            self.terminal
+        }
+
         fn size_info(&self) -> SizeInfo {
             *self.size_info
         }
@@ -831,6 +1660,7 @@ mod tests {
         fn clear_selection(&mut self) {}
         fn update_selection(&mut self, _point: Point, _side: Side) {}
         fn simple_selection(&mut self, _point: Point, _side: Side) {}
+        fn block_selection(&mut self, _point: Point, _side: Side) {}
 
         fn semantic_selection(&mut self, _point: Point) {
             // set something that we can check for here
@@ -886,6 +1716,73 @@ mod tests {
         }
     }
 
+    /// Size of a terminal with 17 lines and 7 columns of 3x3 pixel cells
+    fn test_size() -> SizeInfo {
+        SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        }
+    }
+
+    /// State borrowed by the input processor of a test
+    struct Fixture {
+        config: Config,
+        terminal: Term,
+        size: SizeInfo,
+        mouse: Mouse,
+        selection: Option<Selection>,
+        window_changes: WindowChanges,
+        mouse_config: config::Mouse,
+        scrolling_config: config::Scrolling,
+    }
+
+    impl Fixture {
+        fn new(size: SizeInfo) -> Fixture {
+            let config = Config::default();
+            let terminal = Term::new(&config, size);
+
+            Fixture {
+                terminal,
+                size,
+                mouse: Mouse::default(),
+                selection: None,
+                window_changes: WindowChanges::default(),
+                mouse_config: config::Mouse::default(),
+                scrolling_config: config::Scrolling::default(),
+                config,
+            }
+        }
+
+        fn processor(&mut self) -> Processor<ActionContext> {
+            let context = ActionContext {
+                terminal: &mut self.terminal,
+                selection: &mut self.selection,
+                mouse: &mut self.mouse,
+                size_info: &self.size,
+                last_action: MultiClick::None,
+                received_count: 0,
+                suppress_chars: false,
+                last_modifiers: ModifiersState::default(),
+                window_changes: &mut self.window_changes,
+                written: Vec::new(),
+            };
+
+            Processor {
+                ctx: context,
+                mouse_config: &self.mouse_config,
+                scrolling_config: &self.scrolling_config,
+                key_bindings: &self.config.key_bindings()[..],
+                mouse_bindings: &self.config.mouse_bindings()[..],
+                save_to_clipboard: self.config.selection().save_to_clipboard,
+                accept_drops: self.config.window().accept_drops(),
+            }
+        }
+    }
+
     macro_rules! test_clickstate {
         {
             name: $name:ident,
@@ -923,6 +1820,7 @@ mod tests {
                     suppress_chars: false,
                     last_modifiers: ModifiersState::default(),
                     window_changes: &mut WindowChanges::default(),
+                    written: Vec::new(),
                 };
 
                 let mut processor = Processor {
@@ -930,18 +1828,22 @@ mod tests {
                     mouse_config: &config::Mouse {
                         double_click: ClickHandler {
                             threshold: Duration::from_millis(1000),
+                            distance: 5,
                         },
                         triple_click: ClickHandler {
                             threshold: Duration::from_millis(1000),
+                            distance: 5,
                         },
                         hide_when_typing: false,
                         faux_scrollback_lines: None,
                         url: Default::default(),
+                        block_selection_modifiers: ModifiersState::default(),
                     },
                     scrolling_config: &config::Scrolling::default(),
                     key_bindings: &config.key_bindings()[..],
                     mouse_bindings: &config.mouse_bindings()[..],
                     save_to_clipboard: config.selection().save_to_clipboard,
+                    accept_drops: config.window().accept_drops(),
                 };
 
                 if let Event::WindowEvent { event: WindowEvent::MouseInput { state, button, modifiers, .. }, .. } = $input {
@@ -1086,4 +1988,415 @@ mod tests {
         mode: TermMode::NONE,
         mods: ModifiersState { shift: false, ctrl: false, alt: true, logo: true }
     }
+
+    test_process_binding! {
+        name: process_binding_scroll_inert_in_alt_screen,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::ScrollPageUp, mode: TermMode::NONE, notmode: TermMode::ALT_SCREEN },
+        triggers: false,
+        mode: TermMode::ALT_SCREEN,
+        mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }
+    }
+
+    test_process_binding! {
+        name: process_binding_scroll_outside_alt_screen,
+        binding: Binding { trigger: KEY, mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }, action: Action::ScrollPageUp, mode: TermMode::NONE, notmode: TermMode::ALT_SCREEN },
+        triggers: true,
+        mode: TermMode::NONE,
+        mods: ModifiersState { shift: true, ctrl: false, alt: false, logo: false }
+    }
+
+    #[test]
+    fn kitty_keyboard_disambiguate() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES;
+        let none = ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+        let ctrl = ModifiersState { shift: false, ctrl: true, alt: false, logo: false };
+        let pressed = ElementState::Pressed;
+
+        assert_eq!(kitty_key_sequence(mode, Key::Escape, none, pressed), Some(b"\x1b[27u".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::A, ctrl, pressed), Some(b"\x1b[97;5u".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::Left, ctrl, pressed), Some(b"\x1b[1;5D".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::Delete, none, pressed), Some(b"\x1b[3~".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::A, none, pressed), None);
+        assert_eq!(kitty_key_sequence(mode, Key::Return, none, pressed), None);
+        assert_eq!(kitty_key_sequence(mode, Key::Left, none, pressed), None);
+        assert_eq!(kitty_key_sequence(mode, Key::A, ctrl, ElementState::Released), None);
+    }
+
+    #[test]
+    fn kitty_keyboard_event_types() {
+        let mode = TermMode::DISAMBIGUATE_ESC_CODES | TermMode::REPORT_EVENT_TYPES;
+        let none = ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+        let released = ElementState::Released;
+
+        assert_eq!(kitty_key_sequence(mode, Key::A, none, released), Some(b"\x1b[97;1:3u".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::Up, none, released), Some(b"\x1b[1;1:3A".to_vec()));
+        assert_eq!(kitty_key_sequence(mode, Key::LShift, none, released), None);
+    }
+
+    #[test]
+    fn modify_other_keys() {
+        let none = ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+        let ctrl = ModifiersState { shift: false, ctrl: true, alt: false, logo: false };
+        let ctrl_shift = ModifiersState { shift: true, ctrl: true, alt: false, logo: false };
+
+        let mode = TermMode::MODIFY_OTHER_KEYS;
+        assert_eq!(modify_other_keys_sequence(mode, Key::Tab, ctrl), Some(b"\x1b[27;5;9~".to_vec()));
+        assert_eq!(modify_other_keys_sequence(mode, Key::I, ctrl), None);
+        assert_eq!(modify_other_keys_sequence(mode, Key::I, ctrl_shift), Some(b"\x1b[27;6;73~".to_vec()));
+        assert_eq!(modify_other_keys_sequence(mode, Key::Tab, none), None);
+
+        let mode = TermMode::MODIFY_OTHER_KEYS_ALL;
+        assert_eq!(modify_other_keys_sequence(mode, Key::I, ctrl), Some(b"\x1b[27;5;105~".to_vec()));
+        assert_eq!(modify_other_keys_sequence(mode, Key::M, ctrl), Some(b"\x1b[27;5;109~".to_vec()));
+        assert_eq!(modify_other_keys_sequence(TermMode::NONE, Key::I, ctrl), None);
+    }
+
+    #[test]
+    fn cursor_keys() {
+        let none = ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+        let ctrl = ModifiersState { shift: false, ctrl: true, alt: false, logo: false };
+
+        let mode = TermMode::NONE;
+        assert_eq!(cursor_key_sequence(mode, Key::Up, none), Some(b"\x1b[A".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Down, none), Some(b"\x1b[B".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Right, none), Some(b"\x1b[C".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Left, none), Some(b"\x1b[D".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::End, none), Some(b"\x1b[F".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Home, none), Some(b"\x1b[H".to_vec()));
+
+        let mode = TermMode::APP_CURSOR;
+        assert_eq!(cursor_key_sequence(mode, Key::Up, none), Some(b"\x1bOA".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Down, none), Some(b"\x1bOB".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Right, none), Some(b"\x1bOC".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Left, none), Some(b"\x1bOD".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::End, none), Some(b"\x1bOF".to_vec()));
+        assert_eq!(cursor_key_sequence(mode, Key::Home, none), Some(b"\x1bOH".to_vec()));
+
+        // Modified and other keys are left to the bindings
+        assert_eq!(cursor_key_sequence(mode, Key::Up, ctrl), None);
+        assert_eq!(cursor_key_sequence(mode, Key::PageUp, none), None);
+    }
+
+    #[test]
+    fn keypad_keys() {
+        let none = ModifiersState { shift: false, ctrl: false, alt: false, logo: false };
+        let alt = ModifiersState { shift: false, ctrl: false, alt: true, logo: false };
+
+        // Numeric keypad mode sends the key's text
+        assert_eq!(keypad_key_sequence(TermMode::NONE, Key::Numpad5, none), None);
+        assert_eq!(keypad_key_sequence(TermMode::NONE, Key::NumpadEnter, none), None);
+
+        let mode = TermMode::APP_KEYPAD;
+        assert_eq!(keypad_key_sequence(mode, Key::Numpad0, none), Some(b"\x1bOp".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::Numpad9, none), Some(b"\x1bOy".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::Decimal, none), Some(b"\x1bOn".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::Subtract, none), Some(b"\x1bOm".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::Divide, none), Some(b"\x1bOo".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::NumpadEnter, none), Some(b"\x1bOM".to_vec()));
+        assert_eq!(keypad_key_sequence(mode, Key::Numpad1, alt), None);
+        assert_eq!(keypad_key_sequence(mode, Key::Key1, none), None);
+    }
+
+    #[test]
+    fn right_click_opens_context_menu() {
+        let no_menu = config::Mouse {
+            right_click_action: RightClickAction::None,
+            ..config::Mouse::default()
+        };
+        let menu = config::Mouse::default();
+        let right_bindings = [Binding {
+            trigger: MouseButton::Right,
+            mods: ModifiersState::default(),
+            action: Action::from("\x1b[right"),
+            mode: TermMode::NONE,
+            notmode: TermMode::NONE,
+        }];
+
+        let mut fixture = Fixture::new(SizeInfo {
+            width: 31.0,
+            height: 61.0,
+            padding_x: 5.0,
+            padding_y: 5.0,
+            ..test_size()
+        });
+        let mut processor = fixture.processor();
+        processor.mouse_bindings = &[];
+
+        let none = ModifiersState::default();
+        processor.mouse_moved(12, 9, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Right, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Right, none);
+        {
+            let menu = processor.ctx.terminal.context_menu().expect("context menu");
+            assert_eq!(menu.position, Point::new(Line(1), Column(2)));
+            assert_eq!(menu.items.len(), 2);
+            assert!(!menu.items[0].enabled);
+        }
+
+        // Moving the mouse highlights the item below it
+        processor.mouse_moved(12, 12, none);
+        assert_eq!(processor.ctx.terminal.context_menu().unwrap().hovered, Some(1));
+        processor.mouse_moved(12, 9, none);
+        assert_eq!(processor.ctx.terminal.context_menu().unwrap().hovered, Some(0));
+
+        // Clicking the disabled Copy item only closes the menu
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        assert!(processor.ctx.terminal.context_menu().is_none());
+        assert_eq!(processor.ctx.mouse.left_button_state, ElementState::Released);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+
+        // The menu can be disabled
+        processor.mouse_config = &no_menu;
+        processor.mouse_input(ElementState::Pressed, MouseButton::Right, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Right, none);
+        assert!(processor.ctx.terminal.context_menu().is_none());
+
+        // Configured right button bindings take precedence over the menu
+        processor.mouse_config = &menu;
+        processor.mouse_bindings = &right_bindings;
+        processor.mouse_input(ElementState::Pressed, MouseButton::Right, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Right, none);
+        assert!(processor.ctx.terminal.context_menu().is_none());
+    }
+
+    #[test]
+    fn paste_strips_control_characters() {
+        assert_eq!(sanitize_paste("a\tb\r\nc\n"), "a\tb\r\nc\n");
+        assert_eq!(sanitize_paste("evil\x1b[201~\x07\x00rm -rf"), "evil[201~rm -rf");
+        assert_eq!(sanitize_paste("evil\u{9b}201~\x7frm -rf"), "evil201~rm -rf");
+        assert_eq!(sanitize_paste("caf\u{e9} \u{a0}ok"), "caf\u{e9} \u{a0}ok");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn dropped_paths_are_escaped() {
+        let escape = |path: &str| shell_escape(path).unwrap();
+        assert_eq!(escape("/home/user/notes.txt"), "/home/user/notes.txt");
+        assert_eq!(escape("/tmp/my file"), "'/tmp/my file'");
+        assert_eq!(escape("/tmp/it's $HOME"), "'/tmp/it'\\''s $HOME'");
+
+        // Control characters are kept, but can't submit the command line
+        assert_eq!(escape("/tmp/a\nb"), "$'/tmp/a\\nb'");
+        assert_eq!(escape("/tmp/it's\t\\\x1b"), "$'/tmp/it\\'s\\t\\\\\\x1b'");
+        assert_eq!(escape("/tmp/\u{9b}1"), "$'/tmp/\\xc2\\x9b1'");
+    }
+
+    #[test]
+    fn mouse_reports() {
+        let mut fixture = Fixture::new(SizeInfo {
+            width: 31.0,
+            height: 61.0,
+            padding_x: 5.0,
+            padding_y: 5.0,
+            ..test_size()
+        });
+        fixture.terminal.set_mode(ansi::Mode::ReportMouseClicks);
+        let mut processor = fixture.processor();
+
+        let none = ModifiersState::default();
+        let shift = ModifiersState { shift: true, ctrl: false, alt: false, logo: false };
+
+        // Cell coordinates exclude the padding
+        processor.mouse_moved(12, 9, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        assert_eq!(processor.ctx.written, b"\x1b[M #\"\x1b[M##\"".to_vec());
+
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::SgrMouse);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Right, none);
+        processor.mouse_input(ElementState::Released, MouseButton::Right, none);
+        assert_eq!(processor.ctx.written, b"\x1b[<2;3;2M\x1b[<2;3;2m".to_vec());
+
+        // The UTF-8 encoding replaces SGR and encodes large coordinates as characters
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::Utf8Mouse);
+        assert!(!processor.ctx.terminal.mode().contains(TermMode::SGR_MOUSE));
+        processor.ctx.mouse.column = Column(100);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        assert_eq!(processor.ctx.written, b"\x1b[M \xc2\x85\"".to_vec());
+
+        // URXVT uses decimal coordinates, but can't tell which button was released
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::UrxvtMouse);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        processor.mouse_input(ElementState::Pressed, MouseButton::Middle, none);
+        assert_eq!(processor.ctx.written, b"\x1b[35;101;2M\x1b[33;101;2M".to_vec());
+        processor.mouse_input(ElementState::Released, MouseButton::Middle, none);
+
+        // Button-event tracking replaces click tracking and reports drags
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::ReportCellMouseMotion);
+        assert!(!processor.ctx.terminal.mode().contains(TermMode::MOUSE_REPORT_CLICK));
+        processor.mouse_moved(12, 9, none);
+        assert!(processor.ctx.written.is_empty());
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, none);
+        processor.mouse_moved(15, 9, none);
+        assert_eq!(processor.ctx.written, b"\x1b[32;3;2M\x1b[64;4;2M".to_vec());
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        processor.ctx.terminal.unset_mode(ansi::Mode::UrxvtMouse);
+
+        // Shift bypasses mouse reporting for local selection
+        processor.ctx.written.clear();
+        processor.mouse_input(ElementState::Pressed, MouseButton::Left, shift);
+        processor.mouse_input(ElementState::Released, MouseButton::Left, shift);
+        assert!(processor.ctx.written.is_empty());
+    }
+
+    #[test]
+    fn focus_reports() {
+        let mut fixture = Fixture::new(test_size());
+        let mut processor = fixture.processor();
+
+        // Nothing is reported unless requested
+        processor.on_focus_change(true);
+        assert!(processor.ctx.written.is_empty());
+
+        processor.ctx.terminal.set_mode(ansi::Mode::ReportFocusInOut);
+        processor.on_focus_change(false);
+        processor.on_focus_change(true);
+        assert_eq!(processor.ctx.written, b"\x1b[O\x1b[I".to_vec());
+
+        processor.ctx.written.clear();
+        processor.ctx.terminal.unset_mode(ansi::Mode::ReportFocusInOut);
+        processor.on_focus_change(false);
+        assert!(processor.ctx.written.is_empty());
+    }
+
+    #[test]
+    fn mouse_wheel() {
+        let mut fixture = Fixture::new(test_size());
+        for _ in 0..30 {
+            fixture.terminal.linefeed();
+        }
+        fixture.scrolling_config.faux_multiplier = 1;
+        let natural_config = config::Scrolling { natural: true, ..fixture.scrolling_config };
+        let mut processor = fixture.processor();
+
+        let none = ModifiersState::default();
+        let up = MouseScrollDelta::LineDelta(0., 1.);
+        let down = MouseScrollDelta::LineDelta(0., -1.);
+
+        // The primary screen scrolls through the history
+        processor.on_mouse_wheel(up, TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 3);
+        processor.on_mouse_wheel(down, TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 0);
+        assert!(processor.ctx.written.is_empty());
+
+        // The alternate screen receives cursor keys for the current cursor key mode
+        processor.ctx.terminal.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        processor.on_mouse_wheel(up, TouchPhase::Moved, none);
+        processor.ctx.terminal.set_mode(ansi::Mode::CursorKeys);
+        processor.on_mouse_wheel(down, TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.written, b"\x1b[A\x1bOB".to_vec());
+
+        // Mouse reporting takes precedence
+        processor.ctx.written.clear();
+        processor.ctx.terminal.set_mode(ansi::Mode::ReportMouseClicks);
+        processor.ctx.terminal.set_mode(ansi::Mode::SgrMouse);
+        processor.on_mouse_wheel(up, TouchPhase::Moved, none);
+        processor.on_mouse_wheel(down, TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.written, b"\x1b[<64;1;1M\x1b[<65;1;1M".to_vec());
+
+        // Natural scrolling reverses the direction
+        processor.ctx.written.clear();
+        processor.scrolling_config = &natural_config;
+        processor.on_mouse_wheel(up, TouchPhase::Moved, none);
+        assert_eq!(processor.ctx.written, b"\x1b[<65;1;1M".to_vec());
+    }
+
+    #[test]
+    fn selection_scrolls_past_edges() {
+        let mut fixture = Fixture::new(test_size());
+        for _ in 0..30 {
+            fixture.terminal.linefeed();
+        }
+        fixture.mouse.left_button_state = ElementState::Pressed;
+        let mut processor = fixture.processor();
+
+        let none = ModifiersState::default();
+
+        // Scrolling starts right away and gets faster further past the edge
+        processor.update_selection_scrolling(3., none);
+        assert_eq!(processor.ctx.mouse.selection_scroll_lines, 1);
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 1);
+        processor.update_selection_scrolling(-40., none);
+        assert_eq!(processor.ctx.mouse.selection_scroll_lines, 3);
+
+        // Steps are limited by the interval
+        processor.scroll_selection();
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 1);
+        processor.ctx.mouse.last_selection_scroll -= Duration::from_millis(SELECTION_SCROLL_INTERVAL_MS);
+        processor.scroll_selection();
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 4);
+
+        // The speed is capped below the window
+        processor.update_selection_scrolling(25., none);
+        assert_eq!(processor.ctx.mouse.selection_scroll_lines, 0);
+        processor.update_selection_scrolling(1000., none);
+        assert_eq!(processor.ctx.mouse.selection_scroll_lines, -MAX_SELECTION_SCROLL_LINES);
+        assert_eq!(processor.ctx.terminal.grid().display_offset(), 0);
+
+        // Releasing the button stops scrolling
+        processor.mouse_input(ElementState::Released, MouseButton::Left, none);
+        assert_eq!(processor.ctx.mouse.selection_scroll_lines, 0);
+    }
+
+    #[test]
+    fn vi_mode_keys() {
+        let mut fixture = Fixture::new(test_size());
+        for c in "ab cd".chars() {
+            fixture.terminal.input(c);
+        }
+        let mut processor = fixture.processor();
+
+        Action::ToggleViMode.execute(&mut processor.ctx, false);
+
+        for c in "hv0".chars() {
+            processor.received_char(c);
+        }
+        let selected = processor.ctx.terminal.selection_to_string();
+        assert_eq!(selected, Some(String::from("ab cd")));
+
+        // Yanking ends the selection
+        processor.received_char('y');
+        assert!(processor.ctx.terminal.selection().is_none());
+
+        processor.received_char('G');
+        assert_eq!(processor.ctx.terminal.vi_mode().unwrap().cursor, Point::new(0, Column(0)));
+
+        // A single `g` is dropped by the next key
+        processor.received_char('g');
+        processor.received_char('l');
+        assert_eq!(processor.ctx.terminal.vi_mode().unwrap().cursor, Point::new(0, Column(1)));
+        processor.received_char('g');
+        processor.received_char('g');
+        assert_eq!(processor.ctx.terminal.vi_mode().unwrap().cursor, Point::new(16, Column(0)));
+
+        let escape = KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(VirtualKeyCode::Escape),
+            modifiers: ModifiersState::default(),
+        };
+
+        // Escape cancels a search before leaving the vi mode
+        processor.received_char('/');
+        assert!(processor.ctx.terminal.search().is_some());
+        processor.process_key(escape);
+        assert!(processor.ctx.terminal.search().is_none());
+        assert!(processor.ctx.terminal.vi_mode().is_some());
+
+        processor.process_key(escape);
+        assert!(processor.ctx.terminal.vi_mode().is_none());
+
+        // Like in vi, `i` returns to input
+        Action::ToggleViMode.execute(&mut processor.ctx, false);
+        processor.received_char('i');
+        assert!(processor.ctx.terminal.vi_mode().is_none());
+
+        // None of the keys reached the application
+        assert!(processor.ctx.written.is_empty());
+    }
 }
@@ -0,0 +1,293 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Runtime control through a Unix domain socket
+//!
+//! Every instance listens on `$XDG_RUNTIME_DIR/alacritty-$PID.sock`, or in an
+//! `alacritty-$UID` directory in the temporary directory when that isn't set.
+//! Only the owner can connect. Clients send one JSON message per line, like `{"action": "reload-config"}`. Lines
+//! which can't be parsed are answered with an error, valid messages aren't
+//! answered at all.
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+
+use libc;
+use serde_json;
+
+/// Environment variable pointing child processes at their terminal's socket
+pub const SOCKET_ENV: &str = "ALACRITTY_SOCKET";
+
+/// Messages accepted on the socket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Message {
+    /// Reload the configuration file
+    ReloadConfig,
+
+    /// Change the font size in points
+    SetFontSize { size: f32 },
+
+    /// Restore the configured font size
+    ResetFontSize,
+
+    /// Write text to the pty as if it was typed
+    SendInput { text: String },
+
+    /// Start another instance
+    SpawnWindow,
+}
+
+impl FromStr for Message {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Message, serde_json::Error> {
+        serde_json::from_str(s)
+    }
+}
+
+pub trait OnMessage {
+    fn on_message(&mut self);
+}
+
+impl OnMessage for ::display::Notifier {
+    fn on_message(&mut self) {
+        self.notify();
+    }
+}
+
+/// Accepts messages on a Unix domain socket
+///
+/// The socket file is removed when the listener is dropped.
+pub struct Listener {
+    _thread: ::std::thread::JoinHandle<()>,
+    rx: mpsc::Receiver<Message>,
+    path: PathBuf,
+}
+
+impl Listener {
+    /// Bind the socket at `path` and start accepting clients
+    pub fn new<H, P>(path: P, handler: H) -> io::Result<Listener>
+        where H: OnMessage + Send + 'static,
+              P: Into<PathBuf>
+    {
+        let path = path.into();
+
+        // Socket names are unique per process, so any existing file was left
+        // behind by a previous process with the same PID.
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+
+        // Clients can write to the shell, so nobody else may connect
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+        let (tx, rx) = mpsc::channel();
+        let handler = Arc::new(Mutex::new(handler));
+
+        Ok(Listener {
+            _thread: ::util::thread::spawn_named("ipc listener", move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            warn!("Unable to accept IPC client: {}", err);
+                            continue;
+                        },
+                    };
+
+                    // Every client gets its own thread, so one which never
+                    // closes its socket can't keep the others waiting
+                    let tx = tx.clone();
+                    let handler = handler.clone();
+                    ::util::thread::spawn_named("ipc client", move || {
+                        if let Err(err) = handle_client(stream, &tx, &handler) {
+                            warn!("IPC client error: {}", err);
+                        }
+                    });
+                }
+            }),
+            rx,
+            path,
+        })
+    }
+
+    /// Path of the socket
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get all messages received since the last call
+    pub fn pending_messages(&self) -> Vec<Message> {
+        self.rx.try_iter().collect()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Read messages from a client until it closes its end of the socket
+fn handle_client<H: OnMessage>(
+    stream: UnixStream,
+    tx: &mpsc::Sender<Message>,
+    handler: &Mutex<H>,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.parse::<Message>() {
+            Ok(message) => {
+                debug!("Received IPC message: {:?}", message);
+                let _ = tx.send(message);
+                if let Ok(mut handler) = handler.lock() {
+                    handler.on_message();
+                }
+            },
+            Err(err) => writeln!(writer, "invalid message: {}", err)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Default socket location for this process
+pub fn socket_path() -> io::Result<PathBuf> {
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => private_temp_dir()?,
+    };
+
+    Ok(dir.join(format!("alacritty-{}.sock", process::id())))
+}
+
+/// Directory in the temporary directory which only the current user can access
+fn private_temp_dir() -> io::Result<PathBuf> {
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("alacritty-{}", uid));
+
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+        result => result?,
+    }
+
+    // Another user could have created the directory first
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        let err = format!("{} is accessible by other users", dir.display());
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, err));
+    }
+
+    Ok(dir)
+}
+
+/// Send a message to the instance listening on `socket`
+///
+/// Errors reported by the instance are returned as `io::ErrorKind::InvalidData`.
+pub fn send_message(socket: &Path, message: &Message) -> io::Result<()> {
+    let json = serde_json::to_string(message)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", json)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+
+    if reply.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, reply.trim().to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::process;
+
+    use super::{send_message, Listener, Message, OnMessage};
+
+    struct Handler;
+
+    impl OnMessage for Handler {
+        fn on_message(&mut self) {}
+    }
+
+    #[test]
+    fn parse_messages() {
+        assert_eq!("{\"action\": \"reload-config\"}".parse::<Message>().ok(), Some(Message::ReloadConfig));
+        assert_eq!(
+            "{\"action\": \"set-font-size\", \"size\": 14.0}".parse::<Message>().ok(),
+            Some(Message::SetFontSize { size: 14.0 })
+        );
+        assert_eq!(
+            "{\"action\": \"send-input\", \"text\": \"ls\\n\"}".parse::<Message>().ok(),
+            Some(Message::SendInput { text: String::from("ls\n") })
+        );
+        assert!("{\"action\": \"set-font-size\"}".parse::<Message>().is_err());
+        assert!("{\"action\": \"quit\"}".parse::<Message>().is_err());
+    }
+
+    #[test]
+    fn messages_over_socket() {
+        let path = env::temp_dir().join(format!("alacritty-test-{}.sock", process::id()));
+        let listener = Listener::new(path.clone(), Handler).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        send_message(&path, &Message::SetFontSize { size: 14.0 }).unwrap();
+        send_message(&path, &Message::ReloadConfig).unwrap();
+        assert_eq!(
+            listener.pending_messages(),
+            vec![Message::SetFontSize { size: 14.0 }, Message::ReloadConfig]
+        );
+        assert!(listener.pending_messages().is_empty());
+
+        // Invalid lines are answered with an error
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream.write_all(b"{\"action\": \"quit\"}\n").unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("invalid message: "));
+        assert!(listener.pending_messages().is_empty());
+
+        // A client which never sends anything doesn't block the others
+        let _silent = UnixStream::connect(&path).unwrap();
+        send_message(&path, &Message::ResetFontSize).unwrap();
+        assert_eq!(listener.pending_messages(), vec![Message::ResetFontSize]);
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+}
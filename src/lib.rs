@@ -19,6 +19,7 @@
 
 #[macro_use] extern crate bitflags;
 #[macro_use] extern crate clap;
+#[macro_use] extern crate lazy_static;
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate static_assertions;
@@ -26,6 +27,9 @@
 #[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly",
           target_os = "openbsd"))]
 extern crate x11_dl;
+#[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                target_os = "dragonfly", target_os = "openbsd")))]
+extern crate dbus;
 
 #[cfg(windows)]
 extern crate mio_named_pipes;
@@ -57,6 +61,7 @@ extern crate mio;
 extern crate mio_more;
 extern crate notify;
 extern crate parking_lot;
+extern crate regex;
 extern crate serde;
 extern crate serde_json;
 extern crate serde_yaml;
@@ -71,21 +76,30 @@ extern crate url;
 pub mod macros;
 
 pub mod ansi;
+pub mod blink;
 pub mod cli;
 pub mod config;
+#[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                target_os = "dragonfly", target_os = "openbsd")))]
+pub mod dbus_service;
 pub mod display;
 pub mod event;
 pub mod event_loop;
 pub mod grid;
 pub mod index;
 pub mod input;
+#[cfg(not(windows))]
+pub mod ipc;
 pub mod locale;
 pub mod logging;
 pub mod meter;
+pub mod native_menu;
+pub mod pacer;
 pub mod renderer;
 pub mod selection;
 pub mod sync;
 pub mod term;
+pub mod ticker;
 pub mod tty;
 pub mod util;
 pub mod window;
@@ -33,12 +33,17 @@ extern crate dirs;
 
 use std::error::Error;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[cfg(target_os = "macos")]
+#[cfg(not(windows))]
 use std::env;
 
 #[cfg(not(windows))]
 use std::os::unix::io::AsRawFd;
+#[cfg(not(windows))]
+use std::path::PathBuf;
+#[cfg(not(windows))]
+use std::process::Command;
 
 #[cfg(windows)]
 extern crate winapi;
@@ -50,12 +55,18 @@ use alacritty::config::{self, Config};
 use alacritty::display::Display;
 use alacritty::event;
 use alacritty::event_loop::{self, EventLoop, Msg};
+#[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                target_os = "dragonfly", target_os = "openbsd")))]
+use alacritty::dbus_service;
+#[cfg(not(windows))]
+use alacritty::ipc;
 #[cfg(target_os = "macos")]
 use alacritty::locale;
 use alacritty::logging;
 use alacritty::sync::FairMutex;
 use alacritty::term::Term;
 use alacritty::tty::{self, process_should_exit};
+use alacritty::util;
 use alacritty::util::fmt::Red;
 
 fn main() {
@@ -66,8 +77,28 @@ fn main() {
     unsafe { AttachConsole(ATTACH_PARENT_PROCESS); }
 
     // Load command line options and config
-    let options = cli::Options::load();
-    let config = load_config(&options).update_dynamic_title(&options);
+    let mut options = cli::Options::load();
+
+    // Hand messages over to a running instance instead of starting a new one
+    #[cfg(not(windows))]
+    {
+        if let Some(ref message) = options.message {
+            send_message(&options, message);
+            return;
+        }
+    }
+
+    // Check the working directory before any window is created and resolve
+    // it, since the current directory might be changed below
+    if let Some(dir) = options.working_dir.take() {
+        match dir.canonicalize() {
+            Ok(ref path) if path.is_dir() => options.working_dir = Some(path.clone()),
+            Ok(_) => die!("Invalid working directory {}: Not a directory", dir.display()),
+            Err(err) => die!("Invalid working directory {}: {}", dir.display(), err),
+        }
+    }
+
+    let config = load_config(&options);
 
     // Switch to home directory
     #[cfg(target_os = "macos")]
@@ -103,6 +134,35 @@ fn load_config(options: &cli::Options) -> Config {
     })
 }
 
+/// Send an IPC message to the instance selected by `--socket` or `$ALACRITTY_SOCKET`
+#[cfg(not(windows))]
+fn send_message(options: &cli::Options, message: &str) {
+    let message = message.parse::<ipc::Message>()
+        .unwrap_or_else(|err| die!("Invalid message: {}", err));
+
+    let socket = options.socket.clone()
+        .or_else(|| env::var_os(ipc::SOCKET_ENV).map(PathBuf::from))
+        .unwrap_or_else(|| die!("No socket specified; use --socket or set ${}", ipc::SOCKET_ENV));
+
+    if let Err(err) = ipc::send_message(&socket, &message) {
+        die!("Unable to send message to {}: {}", socket.display(), err);
+    }
+}
+
+/// Start another instance with the default options
+#[cfg(not(windows))]
+fn spawn_window() {
+    match env::current_exe().and_then(|exe| Command::new(exe).spawn()) {
+        // The SIGCHLD handler only reaps the shell, so wait for the instance here
+        Ok(mut child) => {
+            alacritty::util::thread::spawn_named("window reaper", move || {
+                let _ = child.wait();
+            });
+        },
+        Err(err) => warn!("Unable to spawn window: {}", err),
+    }
+}
+
 /// Run Alacritty
 ///
 /// Creates a window, the terminal state, pty, I/O event loop, input processor,
@@ -132,12 +192,40 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
     // This object contains all of the state about what's being displayed. It's
     // wrapped in a clonable mutex since both the I/O loop and display need to
     // access it.
-    let terminal = Term::new(&config, display.size().to_owned());
+    let mut terminal = Term::new(&config, display.size().to_owned());
+    terminal.set_blink_epoch(display.blink_epoch());
+    let (screen_width, screen_height) = display.window().screen_size();
+    terminal.set_screen_size(screen_width, screen_height);
     let terminal = Arc::new(FairMutex::new(terminal));
 
     // Find the window ID for setting $WINDOWID
     let window_id = display.get_window_id();
 
+    // Listen for IPC messages
+    //
+    // The socket is exported through $ALACRITTY_SOCKET, so programs running
+    // inside the terminal can control it. It's removed again on shutdown.
+    #[cfg(not(windows))]
+    let ipc_listener = match ipc::socket_path()
+        .and_then(|path| ipc::Listener::new(path, display.notifier()))
+    {
+        Ok(listener) => {
+            env::set_var(ipc::SOCKET_ENV, listener.path());
+            Some(listener)
+        },
+        Err(err) => {
+            warn!("Unable to create IPC socket: {}", err);
+            None
+        },
+    };
+
+    // Export the DBUS service
+    #[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                    target_os = "dragonfly", target_os = "openbsd")))]
+    let dbus_service = dbus_service::Service::new(display.notifier())
+        .map_err(|err| warn!("Unable to start DBUS service: {}", err))
+        .ok();
+
     // Create the pty
     //
     // The pty forks a process to run the shell on the slave side of the
@@ -177,6 +265,7 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
     // Need the Rc<RefCell<_>> here since a ref is shared in the resize callback
     let mut processor = event::Processor::new(
         event_loop::Notifier(event_loop.channel()),
+        display.notifier(),
         display.resize_channel(),
         options,
         &config,
@@ -203,25 +292,98 @@ fn run(mut config: Config, options: &cli::Options) -> Result<(), Box<Error>> {
 
     info!("Initialisation complete");
 
+    let mut bell_command_throttle = util::Throttle::new(Duration::from_secs(1));
+
     // Main display loop
     loop {
         // Process input and window events
         let mut terminal_lock = processor.process_events(&terminal, display.window());
 
         // Handle config reloads
-        if let Some(new_config) = config_monitor
+        #[cfg_attr(windows, allow(unused_mut))]
+        let mut new_config = config_monitor
             .as_ref()
-            .and_then(|monitor| monitor.pending_config())
-        {
-            config = new_config.update_dynamic_title(options);
+            .and_then(|monitor| monitor.pending_config());
+
+        // Handle IPC messages
+        #[cfg(not(windows))]
+        #[cfg_attr(not(feature = "dbus"), allow(unused_mut))]
+        let mut messages: Vec<_> = ipc_listener
+            .iter()
+            .flat_map(|listener| listener.pending_messages())
+            .collect();
+        #[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                        target_os = "dragonfly", target_os = "openbsd")))]
+        messages.extend(dbus_service.iter().flat_map(|service| service.pending_messages()));
+
+        #[cfg(not(windows))]
+        for message in messages {
+            match message {
+                ipc::Message::ReloadConfig => {
+                    new_config = config.path().and_then(|path| {
+                        Config::load_from(path)
+                            .map_err(|err| error!("Unable to reload config: {}", err))
+                            .ok()
+                    });
+                },
+                ipc::Message::SetFontSize { size } => terminal_lock.set_font_size(size),
+                ipc::Message::ResetFontSize => terminal_lock.reset_font_size(),
+                ipc::Message::SendInput { text } => {
+                    let _ = loop_tx.send(Msg::Input(text.into_bytes().into()));
+                },
+                ipc::Message::SpawnWindow => spawn_window(),
+            }
+        }
+
+        if let Some(new_config) = new_config {
+            config = new_config;
             display.update_config(&config);
             processor.update_config(&config);
             terminal_lock.update_config(&config);
             terminal_lock.dirty = true;
+
+            #[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                            target_os = "dragonfly", target_os = "openbsd")))]
+            {
+                if let Some(ref service) = dbus_service {
+                    service.emit(dbus_service::Signal::ConfigChanged);
+                }
+            }
+        }
+
+        // Report the bell on the bus
+        if terminal_lock.bell_rung {
+            terminal_lock.bell_rung = false;
+
+            // Notify about bells which ring in the background, without
+            // starting a process for every bell of a flood
+            if let Some(ref command) = config.bell().command {
+                if !display.window().is_focused && bell_command_throttle.ready(Instant::now()) {
+                    if let Err(err) = util::start_daemon(command.program(), command.args()) {
+                        warn!("Unable to run bell command: {}", err);
+                    }
+                }
+            }
+
+            #[cfg(all(feature = "dbus", any(target_os = "linux", target_os = "freebsd",
+                                            target_os = "dragonfly", target_os = "openbsd")))]
+            {
+                if let Some(ref service) = dbus_service {
+                    service.emit(dbus_service::Signal::BellReceived);
+                }
+            }
+        }
+
+        // Redraw blinking text when its phase changed
+        if display.blink_changed() {
+            terminal_lock.dirty = true;
         }
 
         // Maybe draw the terminal
-        if terminal_lock.needs_draw() {
+        if terminal_lock.needs_draw() && !display.frame_due(processor.last_key_input()) {
+            // Let output pile up until the next frame
+            processor.wait_for_next_event();
+        } else if terminal_lock.needs_draw() {
             // Try to update the position of the input method editor
             display.update_ime_position(&terminal_lock);
 
@@ -100,7 +100,7 @@ impl Meter {
     /// Add a sample
     ///
     /// Used by Sampler::drop.
-    fn add_sample(&mut self, sample: Duration) {
+    pub fn add_sample(&mut self, sample: Duration) {
         let mut usec = 0f64;
 
         usec += f64::from(sample.subsec_nanos()) / 1e3;
@@ -0,0 +1,117 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Context menu using NSMenu
+//!
+//! The items of the menu all share a target object, which remembers the tag
+//! of the clicked item until the menu is closed.
+#![cfg_attr(feature = "cargo-clippy", allow(let_unit_value))]
+#![cfg(target_os = "macos")]
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::{Once, ONCE_INIT};
+
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel, BOOL, NO, YES};
+
+const UTF8_ENCODING: usize = 4;
+
+const TARGET_CLASS: &str = "AlacrittyMenuTarget";
+
+/// Tag of the item clicked in the open menu, negative if there is none
+static CLICKED: AtomicIsize = AtomicIsize::new(-1);
+
+static REGISTER_TARGET: Once = ONCE_INIT;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct NSPoint {
+    x: f64,
+    y: f64,
+}
+
+/// Show a menu at the mouse cursor
+///
+/// Items are given as their label and whether they are enabled. This blocks
+/// until the menu is closed and returns the index of the clicked item.
+pub fn popup(items: &[(&str, bool)]) -> Option<usize> {
+    unsafe {
+        let target: *mut Object = msg_send![target_class(), new];
+        let menu: *mut Object = msg_send![Class::get("NSMenu").unwrap(), new];
+        let _: () = msg_send![menu, setAutoenablesItems: NO];
+
+        for (index, &(label, enabled)) in items.iter().enumerate() {
+            let title = nsstring(label);
+            let key = nsstring("");
+
+            let item: *mut Object = msg_send![Class::get("NSMenuItem").unwrap(), alloc];
+            let item: *mut Object = msg_send![item, initWithTitle:title
+                                                     action:sel!(itemClicked:)
+                                                     keyEquivalent:key];
+            let _: () = msg_send![item, setTarget: target];
+            let _: () = msg_send![item, setTag: index as isize];
+            let _: () = msg_send![item, setEnabled: if enabled { YES } else { NO }];
+            let _: () = msg_send![menu, addItem: item];
+
+            let _: () = msg_send![item, release];
+            let _: () = msg_send![title, release];
+            let _: () = msg_send![key, release];
+        }
+
+        CLICKED.store(-1, Ordering::SeqCst);
+
+        // Without a view, the location is in screen coordinates
+        let nil: *mut Object = ptr::null_mut();
+        let location: NSPoint = msg_send![Class::get("NSEvent").unwrap(), mouseLocation];
+        let _: BOOL = msg_send![menu, popUpMenuPositioningItem:nil atLocation:location inView:nil];
+
+        let _: () = msg_send![menu, release];
+        let _: () = msg_send![target, release];
+
+        let clicked = CLICKED.load(Ordering::SeqCst);
+        if clicked >= 0 {
+            Some(clicked as usize)
+        } else {
+            None
+        }
+    }
+}
+
+/// Class of the object receiving the actions of all menu items
+fn target_class() -> &'static Class {
+    extern fn item_clicked(_this: &Object, _cmd: Sel, item: *mut Object) {
+        let tag: isize = unsafe { msg_send![item, tag] };
+        CLICKED.store(tag, Ordering::SeqCst);
+    }
+
+    REGISTER_TARGET.call_once(|| {
+        let superclass = Class::get("NSObject").unwrap();
+        let mut decl = ClassDecl::new(TARGET_CLASS, superclass).unwrap();
+        unsafe {
+            decl.add_method(
+                sel!(itemClicked:),
+                item_clicked as extern fn(&Object, Sel, *mut Object),
+            );
+        }
+        decl.register();
+    });
+
+    Class::get(TARGET_CLASS).unwrap()
+}
+
+/// Create a retained NSString
+unsafe fn nsstring(text: &str) -> *mut Object {
+    let string: *mut Object = msg_send![Class::get("NSString").unwrap(), alloc];
+    msg_send![string, initWithBytes:text.as_ptr() length:text.len() encoding:UTF8_ENCODING]
+}
@@ -0,0 +1,179 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Frame rate limiting for terminal output
+//!
+//! Output from the pty marks the terminal dirty as soon as it's parsed. While
+//! a frame isn't due yet, the render loop goes back to sleep and lets more
+//! output pile up; a timer wakes it once the next frame may be drawn. Key
+//! presses bypass the limit, so typing is never delayed.
+use std::time::{Duration, Instant};
+
+use config::DebugConfig;
+use display::Notifier;
+use ticker::Ticker;
+
+/// Frame rate used when vsync is disabled and no limit is configured
+const FALLBACK_FPS: u32 = 60;
+
+/// Time after a key press during which frames are drawn without delay
+const KEY_INPUT_PRIORITY_MS: u64 = 100;
+
+/// Number of frame intervals without a frame after which the terminal is idle
+const IDLE_INTERVALS: u32 = 2;
+
+pub struct FramePacer {
+    /// Minimum time between two frames, `None` leaves the pacing to vsync
+    interval: Option<Duration>,
+
+    last_frame: Instant,
+
+    /// Wakes up the render loop when a deferred frame is due
+    timer: Ticker,
+}
+
+impl FramePacer {
+    pub fn new(config: DebugConfig, notifier: Notifier) -> FramePacer {
+        let interval = frame_interval(config);
+        let timer = Ticker::new(
+            "frame timer",
+            interval.unwrap_or_else(|| fps_interval(FALLBACK_FPS)),
+            notifier,
+        );
+
+        FramePacer {
+            interval,
+            last_frame: Instant::now(),
+            timer,
+        }
+    }
+
+    pub fn update_config(&mut self, config: DebugConfig) {
+        self.interval = frame_interval(config);
+        if let Some(interval) = self.interval {
+            self.timer.set_interval(interval);
+        }
+    }
+
+    /// Whether the next frame may be drawn right away
+    ///
+    /// When it may not, the timer is started to wake up the render loop later.
+    pub fn frame_due(&mut self, last_key_input: Instant) -> bool {
+        if delay(self.interval, self.last_frame, last_key_input, Instant::now()).is_some() {
+            self.timer.start();
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Record that a frame was drawn, returning the time since the last one
+    ///
+    /// Nothing is returned when the terminal was idle in between, since that
+    /// doesn't say anything about how fast frames are drawn.
+    pub fn frame_drawn(&mut self) -> Option<Duration> {
+        self.timer.stop();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_frame);
+        self.last_frame = now;
+
+        let interval = self.interval.unwrap_or_else(|| fps_interval(FALLBACK_FPS));
+        if elapsed <= interval * IDLE_INTERVALS {
+            Some(elapsed)
+        } else {
+            None
+        }
+    }
+}
+
+fn frame_interval(config: DebugConfig) -> Option<Duration> {
+    match config.max_fps {
+        Some(0) | None if config.vsync => None,
+        Some(0) | None => Some(fps_interval(FALLBACK_FPS)),
+        Some(fps) => Some(fps_interval(fps)),
+    }
+}
+
+fn fps_interval(fps: u32) -> Duration {
+    Duration::from_micros(1_000_000 / u64::from(fps))
+}
+
+/// Time left until the next frame may be drawn
+fn delay(
+    interval: Option<Duration>,
+    last_frame: Instant,
+    last_key_input: Instant,
+    now: Instant,
+) -> Option<Duration> {
+    let interval = interval?;
+
+    // Echo of typed text is drawn immediately
+    if last_key_input + Duration::from_millis(KEY_INPUT_PRIORITY_MS) > now {
+        return None;
+    }
+
+    let next_frame = last_frame + interval;
+    if next_frame > now {
+        Some(next_frame - now)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use config::DebugConfig;
+
+    use super::{delay, frame_interval, KEY_INPUT_PRIORITY_MS};
+
+    #[test]
+    fn interval_from_config() {
+        let vsync = DebugConfig { vsync: true, max_fps: None, ..DebugConfig::default() };
+        assert_eq!(frame_interval(vsync), None);
+
+        let capped = DebugConfig { vsync: true, max_fps: Some(50), ..DebugConfig::default() };
+        assert_eq!(frame_interval(capped), Some(Duration::from_millis(20)));
+
+        let no_vsync = DebugConfig { vsync: false, max_fps: Some(0), ..DebugConfig::default() };
+        assert_eq!(frame_interval(no_vsync), Some(Duration::from_micros(16_666)));
+    }
+
+    #[test]
+    fn output_waits_for_next_frame() {
+        let interval = Some(Duration::from_millis(20));
+        let last_frame = Instant::now();
+        let last_key_input = last_frame - Duration::from_millis(KEY_INPUT_PRIORITY_MS * 2);
+
+        let now = last_frame + Duration::from_millis(5);
+        assert_eq!(delay(interval, last_frame, last_key_input, now), Some(Duration::from_millis(15)));
+
+        let now = last_frame + Duration::from_millis(20);
+        assert_eq!(delay(interval, last_frame, last_key_input, now), None);
+
+        // Without a limit, only vsync paces the frames
+        assert_eq!(delay(None, last_frame, last_key_input, last_frame), None);
+    }
+
+    #[test]
+    fn key_input_skips_frame_limit() {
+        let interval = Some(Duration::from_millis(20));
+        let last_frame = Instant::now();
+        let now = last_frame + Duration::from_millis(5);
+
+        assert_eq!(delay(interval, last_frame, now, now), None);
+    }
+}
@@ -0,0 +1,631 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Glyphs which are drawn instead of loaded from the font
+//!
+//! Box drawing characters, block elements and braille patterns are generated
+//! to fill the cell exactly. Since every cell uses the same geometry, the
+//! lines of neighboring cells always connect, independent of the font size
+//! and DPI.
+use std::cmp::{max, min};
+use std::iter;
+
+use font::{Metrics, RasterizedGlyph};
+
+use config::Delta;
+
+/// Generate the glyph for `c`, if it is one of the builtin characters
+pub fn builtin_glyph(c: char, metrics: &Metrics, offset: &Delta<i8>) -> Option<RasterizedGlyph> {
+    let (width, height) = cell_size(metrics, offset);
+    let light = line_width(metrics);
+
+    let mut canvas = Canvas::new(width, height);
+    match c as u32 {
+        0x2500...0x257f => canvas.draw_box(c, light),
+        0x2580...0x259f => canvas.draw_block(c),
+        0x2800...0x28ff => canvas.draw_braille(c as u32 - 0x2800),
+        _ => return None,
+    }
+
+    Some(canvas.into_glyph(c))
+}
+
+/// Outline of a box, for characters which aren't in any font
+pub fn missing_glyph(c: char, metrics: &Metrics, offset: &Delta<i8>) -> RasterizedGlyph {
+    let (width, height) = cell_size(metrics, offset);
+    let light = line_width(metrics);
+
+    let mut canvas = Canvas::new(width, height);
+    let (x0, x1) = centered(width, width.saturating_sub(2 * max(1, width / 8)));
+    let (y0, y1) = centered(height, height.saturating_sub(2 * max(1, height / 8)));
+    if x1 > x0 + 2 * light && y1 > y0 + 2 * light {
+        canvas.fill_rect(x0, y0, x1, y0 + light);
+        canvas.fill_rect(x0, y1 - light, x1, y1);
+        canvas.fill_rect(x0, y0, x0 + light, y1);
+        canvas.fill_rect(x1 - light, y0, x1, y1);
+    }
+
+    canvas.into_glyph(c)
+}
+
+fn cell_size(metrics: &Metrics, offset: &Delta<i8>) -> (usize, usize) {
+    let width = (metrics.average_advance as f32 + f32::from(offset.x)).floor().max(1.);
+    let height = (metrics.line_height as f32 + f32::from(offset.y)).floor().max(1.);
+    (width as usize, height as usize)
+}
+
+/// Width of light lines, matching the underline
+fn line_width(metrics: &Metrics) -> usize {
+    metrics.underline_thickness.round().max(1.) as usize
+}
+
+/// Weight of a box drawing line
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Stroke {
+    Empty,
+    Light,
+    Heavy,
+    Double,
+}
+
+impl Stroke {
+    /// Width of the stroke, including the gap between double lines
+    fn size(self, light: usize) -> usize {
+        match self {
+            Stroke::Empty => 0,
+            Stroke::Light => light,
+            Stroke::Heavy => 2 * light,
+            Stroke::Double => 3 * light,
+        }
+    }
+}
+
+const N: Stroke = Stroke::Empty;
+const L: Stroke = Stroke::Light;
+const H: Stroke = Stroke::Heavy;
+const D: Stroke = Stroke::Double;
+
+/// Lines from the center of the cell to its edges, as (up, right, down, left)
+fn arms(c: char) -> Option<(Stroke, Stroke, Stroke, Stroke)> {
+    let arms = match c {
+        '─' => (N, L, N, L),
+        '━' => (N, H, N, H),
+        '│' => (L, N, L, N),
+        '┃' => (H, N, H, N),
+        '┌' => (N, L, L, N),
+        '┍' => (N, H, L, N),
+        '┎' => (N, L, H, N),
+        '┏' => (N, H, H, N),
+        '┐' => (N, N, L, L),
+        '┑' => (N, N, L, H),
+        '┒' => (N, N, H, L),
+        '┓' => (N, N, H, H),
+        '└' => (L, L, N, N),
+        '┕' => (L, H, N, N),
+        '┖' => (H, L, N, N),
+        '┗' => (H, H, N, N),
+        '┘' => (L, N, N, L),
+        '┙' => (L, N, N, H),
+        '┚' => (H, N, N, L),
+        '┛' => (H, N, N, H),
+        '├' => (L, L, L, N),
+        '┝' => (L, H, L, N),
+        '┞' => (H, L, L, N),
+        '┟' => (L, L, H, N),
+        '┠' => (H, L, H, N),
+        '┡' => (H, H, L, N),
+        '┢' => (L, H, H, N),
+        '┣' => (H, H, H, N),
+        '┤' => (L, N, L, L),
+        '┥' => (L, N, L, H),
+        '┦' => (H, N, L, L),
+        '┧' => (L, N, H, L),
+        '┨' => (H, N, H, L),
+        '┩' => (H, N, L, H),
+        '┪' => (L, N, H, H),
+        '┫' => (H, N, H, H),
+        '┬' => (N, L, L, L),
+        '┭' => (N, L, L, H),
+        '┮' => (N, H, L, L),
+        '┯' => (N, H, L, H),
+        '┰' => (N, L, H, L),
+        '┱' => (N, L, H, H),
+        '┲' => (N, H, H, L),
+        '┳' => (N, H, H, H),
+        '┴' => (L, L, N, L),
+        '┵' => (L, L, N, H),
+        '┶' => (L, H, N, L),
+        '┷' => (L, H, N, H),
+        '┸' => (H, L, N, L),
+        '┹' => (H, L, N, H),
+        '┺' => (H, H, N, L),
+        '┻' => (H, H, N, H),
+        '┼' => (L, L, L, L),
+        '┽' => (L, L, L, H),
+        '┾' => (L, H, L, L),
+        '┿' => (L, H, L, H),
+        '╀' => (H, L, L, L),
+        '╁' => (L, L, H, L),
+        '╂' => (H, L, H, L),
+        '╃' => (H, L, L, H),
+        '╄' => (H, H, L, L),
+        '╅' => (L, L, H, H),
+        '╆' => (L, H, H, L),
+        '╇' => (H, H, L, H),
+        '╈' => (L, H, H, H),
+        '╉' => (H, L, H, H),
+        '╊' => (H, H, H, L),
+        '╋' => (H, H, H, H),
+        '═' => (N, D, N, D),
+        '║' => (D, N, D, N),
+        '╒' => (N, D, L, N),
+        '╓' => (N, L, D, N),
+        '╔' => (N, D, D, N),
+        '╕' => (N, N, L, D),
+        '╖' => (N, N, D, L),
+        '╗' => (N, N, D, D),
+        '╘' => (L, D, N, N),
+        '╙' => (D, L, N, N),
+        '╚' => (D, D, N, N),
+        '╛' => (L, N, N, D),
+        '╜' => (D, N, N, L),
+        '╝' => (D, N, N, D),
+        '╞' => (L, D, L, N),
+        '╟' => (D, L, D, N),
+        '╠' => (D, D, D, N),
+        '╡' => (L, N, L, D),
+        '╢' => (D, N, D, L),
+        '╣' => (D, N, D, D),
+        '╤' => (N, D, L, D),
+        '╥' => (N, L, D, L),
+        '╦' => (N, D, D, D),
+        '╧' => (L, D, N, D),
+        '╨' => (D, L, N, L),
+        '╩' => (D, D, N, D),
+        '╪' => (L, D, L, D),
+        '╫' => (D, L, D, L),
+        '╬' => (D, D, D, D),
+        '╴' => (N, N, N, L),
+        '╵' => (L, N, N, N),
+        '╶' => (N, L, N, N),
+        '╷' => (N, N, L, N),
+        '╸' => (N, N, N, H),
+        '╹' => (H, N, N, N),
+        '╺' => (N, H, N, N),
+        '╻' => (N, N, H, N),
+        '╼' => (N, H, N, L),
+        '╽' => (L, N, H, N),
+        '╾' => (N, L, N, H),
+        '╿' => (H, N, L, N),
+        _ => return None,
+    };
+    Some(arms)
+}
+
+/// Dashed lines, as (stroke, horizontal, number of dashes)
+fn dashes(c: char) -> Option<(Stroke, bool, usize)> {
+    let dashes = match c {
+        '┄' => (L, true, 3),
+        '┅' => (H, true, 3),
+        '┆' => (L, false, 3),
+        '┇' => (H, false, 3),
+        '┈' => (L, true, 4),
+        '┉' => (H, true, 4),
+        '┊' => (L, false, 4),
+        '┋' => (H, false, 4),
+        '╌' => (L, true, 2),
+        '╍' => (H, true, 2),
+        '╎' => (L, false, 2),
+        '╏' => (H, false, 2),
+        _ => return None,
+    };
+    Some(dashes)
+}
+
+/// Start and end of a stroke of `size` centered in `len`
+fn centered(len: usize, size: usize) -> (usize, usize) {
+    let start = len.saturating_sub(size) / 2;
+    (start, min(start + size, len))
+}
+
+/// Single channel coverage of a cell
+struct Canvas {
+    width: usize,
+    height: usize,
+    buf: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            buf: vec![0; width * height],
+        }
+    }
+
+    /// Fill the pixels from `(x0, y0)` up to `(x1, y1)`, excluding the end
+    fn fill_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        self.shade_rect(x0, y0, x1, y1, 255);
+    }
+
+    fn shade_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, alpha: u8) {
+        for y in y0..min(y1, self.height) {
+            for x in x0..min(x1, self.width) {
+                let pixel = &mut self.buf[y * self.width + x];
+                *pixel = max(*pixel, alpha);
+            }
+        }
+    }
+
+    /// Fill a rectangle which is given along an axis and across it
+    fn fill_axis(&mut self, horizontal: bool, along: (usize, usize), across: (usize, usize)) {
+        if horizontal {
+            self.fill_rect(along.0, across.0, along.1, across.1);
+        } else {
+            self.fill_rect(across.0, along.0, across.1, along.1);
+        }
+    }
+
+    /// Fill every pixel with the coverage at its center, for anti-aliased shapes
+    fn fill_coverage<F: Fn(f32, f32) -> f32>(&mut self, coverage: F) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let alpha = coverage(x as f32 + 0.5, y as f32 + 0.5).max(0.).min(1.);
+                let pixel = &mut self.buf[y * self.width + x];
+                *pixel = max(*pixel, (alpha * 255.).round() as u8);
+            }
+        }
+    }
+
+    fn draw_box(&mut self, c: char, light: usize) {
+        if let Some((stroke, horizontal, count)) = dashes(c) {
+            self.draw_dashes(stroke.size(light), horizontal, count);
+        } else if let Some((up, right, down, left)) = arms(c) {
+            self.draw_arms(true, (left, right), (up, down), light);
+            self.draw_arms(false, (up, down), (left, right), light);
+        } else {
+            match c {
+                '╭' => self.draw_arc(1., 1., light),
+                '╮' => self.draw_arc(-1., 1., light),
+                '╯' => self.draw_arc(-1., -1., light),
+                '╰' => self.draw_arc(1., -1., light),
+                '╱' => self.draw_diagonal(false, light),
+                '╲' => self.draw_diagonal(true, light),
+                '╳' => {
+                    self.draw_diagonal(false, light);
+                    self.draw_diagonal(true, light);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    /// Draw the arms of a box drawing character along one axis
+    ///
+    /// The `arms` point into negative and positive direction of the axis, the
+    /// `crossing` arms are perpendicular to them.
+    fn draw_arms(
+        &mut self,
+        horizontal: bool,
+        arms: (Stroke, Stroke),
+        crossing: (Stroke, Stroke),
+        light: usize,
+    ) {
+        let (len, cross_len) = if horizontal {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+
+        let crossing_size = max(crossing.0.size(light), crossing.1.size(light));
+        let crossing_double = crossing.0 == Stroke::Double || crossing.1 == Stroke::Double;
+        let crossing_straight = crossing.0 != Stroke::Empty && crossing.1 != Stroke::Empty;
+
+        // Position of double lines crossing this axis
+        let (double_start, double_end) = centered(len, Stroke::Double.size(light));
+
+        let sides = [(arms.0, arms.1, true), (arms.1, arms.0, false)];
+        for &(stroke, opposite, negative) in sides.iter().filter(|side| side.0 != Stroke::Empty) {
+            // Span from the edge of the cell to the inner end of the arm
+            let span = |inner_start: usize, inner_end: usize| {
+                if negative {
+                    (0, inner_end)
+                } else {
+                    (inner_start, len)
+                }
+            };
+
+            // Arms extend across the crossing lines, or at least to the center
+            let center_size = if crossing_size > 0 { crossing_size } else { stroke.size(light) };
+            let (center_start, center_end) = centered(len, center_size);
+            let through = span(center_start, center_end);
+            let corner = span(double_end.saturating_sub(light), double_start + light);
+
+            if stroke == Stroke::Double {
+                let (across_start, across_end) = centered(cross_len, stroke.size(light));
+                let lines = [(crossing.0, across_start), (crossing.1, across_end.saturating_sub(light))];
+                for &(crossing_stroke, line_start) in &lines {
+                    // Form a corner with double lines crossing on the same side
+                    let along = if crossing_stroke == Stroke::Double { corner } else { through };
+                    self.fill_axis(horizontal, along, (line_start, line_start + light));
+                }
+            } else {
+                // Lines ending at a straight double line only touch its near side
+                let along = if opposite == Stroke::Empty && crossing_double && crossing_straight {
+                    corner
+                } else {
+                    through
+                };
+                self.fill_axis(horizontal, along, centered(cross_len, stroke.size(light)));
+            }
+        }
+    }
+
+    fn draw_dashes(&mut self, size: usize, horizontal: bool, count: usize) {
+        let (len, cross_len) = if horizontal {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        };
+
+        // Gaps are split between both ends of a dash, so they're evenly
+        // distributed over multiple cells
+        let gap = max(1, len / (4 * count));
+        for i in 0..count {
+            let start = len * i / count + gap / 2;
+            let end = (len * (i + 1) / count).saturating_sub(gap - gap / 2);
+            self.fill_axis(horizontal, (start, end), centered(cross_len, size));
+        }
+    }
+
+    /// Draw a rounded corner, with arms pointing into the direction of `dx` and `dy`
+    fn draw_arc(&mut self, dx: f32, dy: f32, light: usize) {
+        let (x0, x1) = centered(self.width, light);
+        let (y0, y1) = centered(self.height, light);
+
+        let radius = (min(self.width, self.height) / 2) as f32;
+        let center_x = (x0 + x1) as f32 / 2. + dx * radius;
+        let center_y = (y0 + y1) as f32 / 2. + dy * radius;
+
+        let half_width = light as f32 / 2.;
+        self.fill_coverage(|x, y| {
+            if (x - center_x) * dx > 0. || (y - center_y) * dy > 0. {
+                return 0.;
+            }
+
+            let distance = ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt();
+            half_width + 0.5 - (distance - radius).abs()
+        });
+
+        // Straight lines from the end of the arc to the edges
+        let (arc_x, arc_y) = (center_x.round() as usize, center_y.round() as usize);
+        let (width, height) = (self.width, self.height);
+        if dx > 0. {
+            self.fill_rect(arc_x, y0, width, y1);
+        } else {
+            self.fill_rect(0, y0, arc_x, y1);
+        }
+        if dy > 0. {
+            self.fill_rect(x0, arc_y, x1, height);
+        } else {
+            self.fill_rect(x0, 0, x1, arc_y);
+        }
+    }
+
+    /// Draw a line between opposing corners, starting at the top left if `falling`
+    fn draw_diagonal(&mut self, falling: bool, light: usize) {
+        let (width, height) = (self.width as f32, self.height as f32);
+        let len = (width * width + height * height).sqrt();
+        let half_width = light as f32 / 2.;
+
+        self.fill_coverage(|x, y| {
+            let distance = if falling {
+                (height * x - width * y).abs() / len
+            } else {
+                (height * x + width * y - width * height).abs() / len
+            };
+            half_width + 0.5 - distance
+        });
+    }
+
+    fn draw_block(&mut self, c: char) {
+        let (width, height) = (self.width, self.height);
+
+        // Boundaries in eighths of the cell, so complementary blocks never overlap
+        let x = |eighths: usize| (width * eighths + 4) / 8;
+        let y = |eighths: usize| (height * eighths + 4) / 8;
+
+        match c {
+            '▀' => self.fill_rect(0, 0, width, y(4)),
+            '▁'...'█' => {
+                let eighths = c as usize - 0x2580;
+                self.fill_rect(0, y(8 - eighths), width, height);
+            },
+            '▉'...'▏' => {
+                let eighths = 0x2590 - c as usize;
+                self.fill_rect(0, 0, x(eighths), height);
+            },
+            '▐' => self.fill_rect(x(4), 0, width, height),
+            '░' => self.shade_rect(0, 0, width, height, 64),
+            '▒' => self.shade_rect(0, 0, width, height, 128),
+            '▓' => self.shade_rect(0, 0, width, height, 191),
+            '▔' => self.fill_rect(0, 0, width, y(1)),
+            '▕' => self.fill_rect(x(7), 0, width, height),
+            _ => {
+                // Quadrants as bits of upper left, upper right, lower left and lower right
+                let quadrants = match c {
+                    '▖' => 0b0100,
+                    '▗' => 0b1000,
+                    '▘' => 0b0001,
+                    '▙' => 0b1101,
+                    '▚' => 0b1001,
+                    '▛' => 0b0111,
+                    '▜' => 0b1011,
+                    '▝' => 0b0010,
+                    '▞' => 0b0110,
+                    '▟' => 0b1110,
+                    _ => 0,
+                };
+
+                let (mid_x, mid_y) = (x(4), y(4));
+                if quadrants & 0b0001 != 0 {
+                    self.fill_rect(0, 0, mid_x, mid_y);
+                }
+                if quadrants & 0b0010 != 0 {
+                    self.fill_rect(mid_x, 0, width, mid_y);
+                }
+                if quadrants & 0b0100 != 0 {
+                    self.fill_rect(0, mid_y, mid_x, height);
+                }
+                if quadrants & 0b1000 != 0 {
+                    self.fill_rect(mid_x, mid_y, width, height);
+                }
+            },
+        }
+    }
+
+    /// Draw the dots of a braille pattern, given as offset from U+2800
+    fn draw_braille(&mut self, dots: u32) {
+        // Column and row of the dot for every bit
+        const POSITIONS: [(usize, usize); 8] =
+            [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
+
+        let (width, height) = (self.width, self.height);
+        let size = max(1, min(width / 2, height / 4) / 2);
+        for (bit, &(col, row)) in POSITIONS.iter().enumerate() {
+            if dots & (1 << bit) == 0 {
+                continue;
+            }
+
+            let (x0, _) = centered(width / 2, size);
+            let (y0, _) = centered(height / 4, size);
+            let x = width * col / 2 + x0;
+            let y = height * row / 4 + y0;
+            self.fill_rect(x, y, x + size, y + size);
+        }
+    }
+
+    fn into_glyph(self, c: char) -> RasterizedGlyph {
+        // Glyphs are stored as RGB, for subpixel antialiasing
+        let buf = self.buf.iter().flat_map(|&alpha| iter::repeat(alpha).take(3)).collect();
+
+        RasterizedGlyph {
+            c,
+            width: self.width as i32,
+            height: self.height as i32,
+            top: self.height as i32,
+            left: 0,
+            buf,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use font::{Metrics, RasterizedGlyph};
+
+    use config::Delta;
+
+    use super::{builtin_glyph, missing_glyph};
+
+    fn metrics(width: f64, height: f64) -> Metrics {
+        Metrics::with_default_lines(width, height, -2.)
+    }
+
+    /// Glyph as text, with `#` for covered and `.` for empty pixels
+    fn rows(glyph: &RasterizedGlyph) -> Vec<String> {
+        glyph.buf
+            .chunks(3 * glyph.width as usize)
+            .map(|row| row.chunks(3).map(|px| if px[0] > 127 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn render(c: char, width: usize, height: usize) -> Vec<String> {
+        let metrics = metrics(width as f64, height as f64);
+        let glyph = builtin_glyph(c, &metrics, &Delta::default()).unwrap();
+        assert_eq!((glyph.width, glyph.height), (width as i32, height as i32));
+        assert_eq!((glyph.left, glyph.top), (0, height as i32));
+
+        rows(&glyph)
+    }
+
+    #[test]
+    fn tiny_cells() {
+        let chars = (0x2500..0x25a0).chain(0x2800..0x2900).map(|c| ::std::char::from_u32(c).unwrap());
+        for size in 1..4 {
+            let metrics = metrics(size as f64, size as f64);
+            assert_eq!(missing_glyph('\u{e000}', &metrics, &Delta::default()).width, size);
+
+            for c in chars.clone() {
+                assert!(builtin_glyph(c, &metrics, &Delta::default()).is_some(), "{:?}", c);
+            }
+        }
+    }
+
+    #[test]
+    fn only_builtin_characters() {
+        let metrics = metrics(7., 9.);
+        assert!(builtin_glyph('a', &metrics, &Delta::default()).is_none());
+        assert!(builtin_glyph('\u{24ff}', &metrics, &Delta::default()).is_none());
+        assert!(builtin_glyph('\u{25a0}', &metrics, &Delta::default()).is_none());
+
+        for c in (0x2500..0x25a0).chain(0x2800..0x2900) {
+            let c = ::std::char::from_u32(c).unwrap();
+            assert!(builtin_glyph(c, &metrics, &Delta::default()).is_some(), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn lines_connect() {
+        assert_eq!(render('┼', 5, 5), vec!["..#..", "..#..", "#####", "..#..", "..#.."]);
+        assert_eq!(render('┌', 5, 5), vec![".....", ".....", "..###", "..#..", "..#.."]);
+        assert_eq!(render('┃', 5, 5)[0], ".##..");
+        assert_eq!(render('╴', 5, 5)[2], "###..");
+    }
+
+    #[test]
+    fn double_lines() {
+        assert_eq!(render('╔', 5, 5), vec![".....", ".####", ".#...", ".#.##", ".#.#."]);
+        assert_eq!(render('╦', 5, 5), vec![".....", "#####", ".....", "##.##", ".#.#."]);
+        assert_eq!(render('╤', 5, 5), vec![".....", "#####", ".....", "#####", "..#.."]);
+        assert_eq!(render('╫', 5, 5), vec![".#.#.", ".#.#.", "#####", ".#.#.", ".#.#."]);
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(render('█', 4, 4), vec!["####"; 4]);
+        assert_eq!(render('▀', 4, 5), vec!["####", "####", "####", "....", "...."]);
+        assert_eq!(render('▄', 4, 5), vec!["....", "....", "....", "####", "####"]);
+        assert_eq!(render('▚', 4, 4), vec!["##..", "##..", "..##", "..##"]);
+        assert_eq!(render('▎', 8, 2), vec!["##......"; 2]);
+    }
+
+    #[test]
+    fn missing() {
+        let glyph = missing_glyph('x', &metrics(8., 8.), &Delta::default());
+        assert_eq!(rows(&glyph), vec![
+            "........", ".######.", ".#....#.", ".#....#.",
+            ".#....#.", ".#....#.", ".######.", "........",
+        ]);
+    }
+
+    #[test]
+    fn braille() {
+        assert_eq!(render('⠁', 4, 8), vec![
+            "#...", "....", "....", "....", "....", "....", "....", "....",
+        ]);
+        assert_eq!(render('⣿', 4, 8).iter().filter(|row| row.contains('#')).count(), 4);
+    }
+}
@@ -15,7 +15,7 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::hash::BuildHasherDefault;
 use std::io::{self, Read};
-use std::mem::size_of;
+use std::mem::{self, size_of};
 use std::path::PathBuf;
 use std::ptr;
 use std::sync::mpsc;
@@ -31,10 +31,13 @@ use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
 
 use config::{self, Config, Delta};
 use term::{self, cell, RenderableCell};
+use term::damage::Damage;
 use window::{Pixels, Size};
 
 use Rgb;
 
+mod builtin_font;
+
 // Shader paths for live reload
 static TEXT_SHADER_F_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.f.glsl");
 static TEXT_SHADER_V_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/text.v.glsl");
@@ -115,6 +118,9 @@ pub struct ShaderProgram {
     /// Visual bell
     u_visual_bell: GLint,
 
+    /// Color blended over the backgrounds by the visual bell
+    u_visual_bell_color: GLint,
+
     /// Background pass flag
     ///
     /// Rendering is split into two passes; 1 for backgrounds, and one for text
@@ -139,8 +145,8 @@ pub struct Glyph {
 
 /// Naïve glyph cache
 ///
-/// Currently only keyed by `char`, and thus not possible to hold different
-/// representations of the same code point.
+/// Keyed by font face and `char`, so every style of a code point is cached
+/// separately. The glyphs are spread over as many atlas textures as needed.
 pub struct GlyphCache {
     /// Cache of buffered glyphs
     cache: HashMap<GlyphKey, Glyph, BuildHasherDefault<FnvHasher>>,
@@ -157,13 +163,25 @@ pub struct GlyphCache {
     /// bold font
     bold_key: FontKey,
 
+    /// bold italic font
+    bold_italic_key: FontKey,
+
     /// font size
     font_size: font::Size,
 
     /// glyph offset
     glyph_offset: Delta<i8>,
 
+    /// font offset, which is part of the cell size
+    offset: Delta<i8>,
+
+    /// draw box drawing characters instead of loading them from the font
+    builtin_box_drawing: bool,
+
     metrics: ::font::Metrics,
+
+    /// Opaque glyph which is stretched for drawing lines
+    solid: Glyph,
 }
 
 impl GlyphCache {
@@ -175,11 +193,14 @@ impl GlyphCache {
     where
         L: LoadGlyph,
     {
-        let (regular, bold, italic) = Self::compute_font_keys(font, &mut rasterizer)?;
+        let (regular, bold, italic, bold_italic) = Self::compute_font_keys(font, &mut rasterizer)?;
 
         // Need to load at least one glyph for the face before calling metrics.
         // The glyph requested here ('m' at the time of writing) has no special
         // meaning.
+        //
+        // Only the regular face is used for metrics, so styled text can't
+        // change the size of the cells.
         rasterizer.get_glyph(GlyphKey { font_key: regular, c: 'm', size: font.size() })?;
 
         let metrics = rasterizer.metrics(regular, font.size())?;
@@ -191,13 +212,18 @@ impl GlyphCache {
             font_key: regular,
             bold_key: bold,
             italic_key: italic,
+            bold_italic_key: bold_italic,
             glyph_offset: *font.glyph_offset(),
+            offset: *font.offset(),
+            builtin_box_drawing: font.builtin_box_drawing(),
             metrics,
+            solid: load_solid_glyph(loader),
         };
 
         cache.load_glyphs_for_font(regular, loader);
         cache.load_glyphs_for_font(bold, loader);
         cache.load_glyphs_for_font(italic, loader);
+        cache.load_glyphs_for_font(bold_italic, loader);
 
         Ok(cache)
     }
@@ -213,11 +239,11 @@ impl GlyphCache {
         }
     }
 
-    /// Computes font keys for (Regular, Bold, Italic)
+    /// Computes font keys for (Regular, Bold, Italic, Bold Italic)
     fn compute_font_keys(
         font: &config::Font,
         rasterizer: &mut Rasterizer,
-    ) -> Result<(FontKey, FontKey, FontKey), font::Error> {
+    ) -> Result<(FontKey, FontKey, FontKey, FontKey), font::Error> {
         let size = font.size();
 
         // Load regular font
@@ -237,16 +263,22 @@ impl GlyphCache {
         };
 
         // Load bold font
-        let bold_desc = Self::make_desc(&font.bold, font::Slant::Normal, font::Weight::Bold);
+        let bold_desc = Self::make_desc(&font.bold(), font::Slant::Normal, font::Weight::Bold);
 
         let bold = load_or_regular(bold_desc);
 
         // Load italic font
-        let italic_desc = Self::make_desc(&font.italic, font::Slant::Italic, font::Weight::Normal);
+        let italic_desc = Self::make_desc(&font.italic(), font::Slant::Italic, font::Weight::Normal);
 
         let italic = load_or_regular(italic_desc);
 
-        Ok((regular, bold, italic))
+        // Load bold italic font
+        let bold_italic_desc =
+            Self::make_desc(&font.bold_italic(), font::Slant::Italic, font::Weight::Bold);
+
+        let bold_italic = load_or_regular(bold_italic_desc);
+
+        Ok((regular, bold, italic, bold_italic))
     }
 
     fn make_desc(
@@ -262,6 +294,75 @@ impl GlyphCache {
         FontDesc::new(&desc.family[..], style)
     }
 
+    /// Horizontal line spanning `width` pixels
+    ///
+    /// The `position` of the line's center is relative to the baseline.
+    fn line(&self, position: f32, thickness: f32, width: f32) -> Glyph {
+        let baseline = f32::from(self.glyph_offset.y) - self.metrics.descent;
+        let thickness = thickness.max(1.).round();
+
+        let mut glyph = self.solid.clone();
+        glyph.left = 0.;
+        glyph.top = (baseline + position + thickness / 2.).round();
+        glyph.width = width;
+        glyph.height = thickness;
+        glyph
+    }
+
+    /// Build the segments of a styled underline spanning `width` pixels
+    fn underline(&self, flags: cell::Flags, width: f32) -> Vec<Glyph> {
+        let position = self.metrics.underline_position;
+        let thickness = self.metrics.underline_thickness.max(1.).round();
+
+        if flags.contains(cell::Flags::DOUBLE_UNDERLINE) {
+            // Double underlines repeat the line a bit further up
+            let gap = (2. * thickness).max(2.);
+            vec![
+                self.line(position, thickness, width),
+                self.line(position + gap, thickness, width),
+            ]
+        } else if flags.contains(cell::Flags::CURLY_UNDERLINE) {
+            // One period of a sine wave per cell, drawn in short steps
+            let amplitude = thickness.max(2.);
+            let step = thickness;
+            let mut segments = Vec::new();
+            let mut x = 0.;
+            while x < width {
+                let phase = 2. * ::std::f32::consts::PI * x / width;
+                let mut segment =
+                    self.line(position + amplitude * phase.sin(), thickness, step.min(width - x));
+                segment.left = x;
+                segments.push(segment);
+                x += step;
+            }
+            segments
+        } else if flags.contains(cell::Flags::DOTTED_UNDERLINE) {
+            // Square dots separated by gaps of the same size
+            let mut segments = Vec::new();
+            let mut x = 0.;
+            while x < width {
+                let mut dot = self.line(position, thickness, thickness.min(width - x));
+                dot.left = x;
+                segments.push(dot);
+                x += 2. * thickness;
+            }
+            segments
+        } else if flags.contains(cell::Flags::DASHED_UNDERLINE) {
+            // Two dashes per cell, centered in each half
+            let dash = (width * 3. / 8.).round().max(1.);
+            [width / 16., width * 9. / 16.]
+                .iter()
+                .map(|&left| {
+                    let mut segment = self.line(position, thickness, dash);
+                    segment.left = left.round();
+                    segment
+                })
+                .collect()
+        } else {
+            vec![self.line(position, thickness, width)]
+        }
+    }
+
     pub fn font_metrics(&self) -> font::Metrics {
         self.rasterizer
             .metrics(self.font_key, self.font_size)
@@ -272,13 +373,32 @@ impl GlyphCache {
         where L: LoadGlyph
     {
         let glyph_offset = self.glyph_offset;
+        let offset = self.offset;
+        let builtin_box_drawing = self.builtin_box_drawing;
         let rasterizer = &mut self.rasterizer;
         let metrics = &self.metrics;
         self.cache
             .entry(glyph_key)
             .or_insert_with(|| {
-                let mut rasterized = rasterizer.get_glyph(glyph_key)
-                    .unwrap_or_else(|_| Default::default());
+                // Builtin glyphs fill the cell, so they aren't offset
+                let builtin = if builtin_box_drawing {
+                    builtin_font::builtin_glyph(glyph_key.c, metrics, &offset)
+                } else {
+                    None
+                };
+                if let Some(rasterized) = builtin {
+                    return loader.load_glyph(&rasterized);
+                }
+
+                // Characters which aren't in any font are shown as a box
+                let mut rasterized = match rasterizer.get_glyph(glyph_key) {
+                    Ok(rasterized) => rasterized,
+                    Err(err) => {
+                        debug!("{}", err);
+                        let missing = builtin_font::missing_glyph(glyph_key.c, metrics, &offset);
+                        return loader.load_glyph(&missing);
+                    },
+                };
 
                 rasterized.left += i32::from(glyph_offset.x);
                 rasterized.top += i32::from(glyph_offset.y);
@@ -300,26 +420,46 @@ impl GlyphCache {
         // Recompute font keys
         let font = font.to_owned().with_size(size);
         info!("Font size changed: {:?}", font.size);
-        let (regular, bold, italic) = Self::compute_font_keys(&font, &mut self.rasterizer)?;
+        let (regular, bold, italic, bold_italic) =
+            Self::compute_font_keys(&font, &mut self.rasterizer)?;
       
         self.rasterizer.get_glyph(GlyphKey { font_key: regular, c: 'm', size: font.size() })?;
         let metrics = self.rasterizer.metrics(regular, size)?;
 
         self.font_size = font.size;
+        self.offset = *font.offset();
+        self.builtin_box_drawing = font.builtin_box_drawing();
         self.font_key = regular;
         self.bold_key = bold;
         self.italic_key = italic;
+        self.bold_italic_key = bold_italic;
         self.metrics = metrics;
+        self.solid = load_solid_glyph(loader);
 
         self.load_glyphs_for_font(regular, loader);
         self.load_glyphs_for_font(bold, loader);
         self.load_glyphs_for_font(italic, loader);
+        self.load_glyphs_for_font(bold_italic, loader);
 
         Ok(())
     }
+
+    /// Replace the rasterizer and load all glyphs again
+    ///
+    /// Used when the window moved to a display with a different DPI.
+    pub fn update_rasterizer<L: LoadGlyph>(
+        &mut self,
+        rasterizer: Rasterizer,
+        font: &config::Font,
+        loader: &mut L
+    ) -> Result<(), font::Error> {
+        self.rasterizer = rasterizer;
+        let size = self.font_size;
+        self.update_font_size(font, size, loader)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C)]
 struct InstanceData {
     // coords
@@ -348,6 +488,37 @@ struct InstanceData {
     bg_a: f32,
 }
 
+impl InstanceData {
+    fn new(cell: &RenderableCell, glyph: &Glyph) -> InstanceData {
+        InstanceData {
+            col: cell.column.0 as f32,
+            row: cell.line.0 as f32,
+
+            top: glyph.top,
+            left: glyph.left,
+            width: glyph.width,
+            height: glyph.height,
+
+            uv_bot: glyph.uv_bot,
+            uv_left: glyph.uv_left,
+            uv_width: glyph.uv_width,
+            uv_height: glyph.uv_height,
+
+            r: f32::from(cell.fg.r),
+            g: f32::from(cell.fg.g),
+            b: f32::from(cell.fg.b),
+
+            bg_r: f32::from(cell.bg.r),
+            bg_g: f32::from(cell.bg.g),
+            bg_b: f32::from(cell.bg.b),
+            bg_a: cell.bg_alpha,
+        }
+    }
+}
+
+/// Instances of a grid line, along with their texture
+type LineInstances = Vec<(GLuint, InstanceData)>;
+
 #[derive(Debug)]
 pub struct QuadRenderer {
     program: ShaderProgram,
@@ -360,6 +531,8 @@ pub struct QuadRenderer {
     active_tex: GLuint,
     batch: Batch,
     rx: mpsc::Receiver<Msg>,
+    /// Instances of the last frame for every line of the grid
+    lines: Vec<LineInstances>,
 }
 
 #[derive(Debug)]
@@ -368,6 +541,9 @@ pub struct RenderApi<'a> {
     batch: &'a mut Batch,
     atlas: &'a mut Vec<Atlas>,
     current_atlas: &'a mut usize,
+    lines: &'a mut Vec<LineInstances>,
+    /// Store instances in `lines` instead of drawing them
+    record_lines: bool,
     program: &'a mut ShaderProgram,
     config: &'a Config,
     visual_bell_intensity: f32,
@@ -378,6 +554,7 @@ pub struct LoaderApi<'a> {
     active_tex: &'a mut GLuint,
     atlas: &'a mut Vec<Atlas>,
     current_atlas: &'a mut usize,
+    lines: &'a mut Vec<LineInstances>,
 }
 
 #[derive(Debug)]
@@ -401,34 +578,12 @@ impl Batch {
         }
     }
 
-    pub fn add_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
+    fn add_item(&mut self, tex: GLuint, instance: InstanceData) {
         if self.is_empty() {
-            self.tex = glyph.tex_id;
+            self.tex = tex;
         }
 
-        self.instances.push(InstanceData {
-            col: cell.column.0 as f32,
-            row: cell.line.0 as f32,
-
-            top: glyph.top,
-            left: glyph.left,
-            width: glyph.width,
-            height: glyph.height,
-
-            uv_bot: glyph.uv_bot,
-            uv_left: glyph.uv_left,
-            uv_width: glyph.uv_width,
-            uv_height: glyph.uv_height,
-
-            r: f32::from(cell.fg.r),
-            g: f32::from(cell.fg.g),
-            b: f32::from(cell.fg.b),
-
-            bg_r: f32::from(cell.bg.r),
-            bg_g: f32::from(cell.bg.g),
-            bg_b: f32::from(cell.bg.b),
-            bg_a: cell.bg_alpha,
-        });
+        self.instances.push(instance);
     }
 
     #[inline]
@@ -643,6 +798,7 @@ impl QuadRenderer {
             active_tex: 0,
             batch: Batch::new(),
             rx: msg_rx,
+            lines: Vec::new(),
         };
 
         let atlas = Atlas::new(ATLAS_SIZE);
@@ -678,7 +834,7 @@ impl QuadRenderer {
         unsafe {
             self.program.activate();
             self.program.set_term_uniforms(props);
-            self.program.set_visual_bell(visual_bell_intensity as _);
+            self.program.set_visual_bell(visual_bell_intensity as _, config.bell().color);
 
             gl::BindVertexArray(self.vao);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
@@ -691,6 +847,8 @@ impl QuadRenderer {
             batch: &mut self.batch,
             atlas: &mut self.atlas,
             current_atlas: &mut self.current_atlas,
+            lines: &mut self.lines,
+            record_lines: false,
             program: &mut self.program,
             visual_bell_intensity: visual_bell_intensity as _,
             config,
@@ -719,9 +877,17 @@ impl QuadRenderer {
             active_tex: &mut self.active_tex,
             atlas: &mut self.atlas,
             current_atlas: &mut self.current_atlas,
+            lines: &mut self.lines,
         })
     }
 
+    /// Whether the instances of the last frame can be reused for `num_lines` lines
+    ///
+    /// If this is `false`, the next frame has to be drawn with `Damage::Full`.
+    pub fn has_line_cache(&self, num_lines: Line) -> bool {
+        self.lines.len() == num_lines.0
+    }
+
     pub fn reload_shaders(&mut self, config: &Config, size: Size<Pixels<u32>>) {
         warn!("Reloading shaders ...");
         let program = match ShaderProgram::new(config, size) {
@@ -774,11 +940,19 @@ impl QuadRenderer {
 impl<'a> RenderApi<'a> {
     pub fn clear(&self, color: Rgb) {
         let alpha = self.config.background_opacity().get();
+        let bell_color = self.config.bell().color;
+        let intensity = self.visual_bell_intensity;
+
+        // Blend the visual bell color over the background
+        let mix = |bg: u8, bell: u8| {
+            (f32::from(bg) * (1.0 - intensity) + f32::from(bell) * intensity) / 255.0 * alpha
+        };
+
         unsafe {
             gl::ClearColor(
-                (self.visual_bell_intensity + f32::from(color.r) / 255.0).min(1.0) * alpha,
-                (self.visual_bell_intensity + f32::from(color.g) / 255.0).min(1.0) * alpha,
-                (self.visual_bell_intensity + f32::from(color.b) / 255.0).min(1.0) * alpha,
+                mix(color.r, bell_color.r),
+                mix(color.g, bell_color.g),
+                mix(color.b, bell_color.b),
                 alpha
                 );
             gl::Clear(gl::COLOR_BUFFER_BIT);
@@ -824,23 +998,45 @@ impl<'a> RenderApi<'a> {
 
         self.batch.clear();
     }
-    /// Render a string in a predefined location. Used for printing render time for profiling and
-    /// optimization.
-    pub fn render_string(&mut self, string: &str, glyph_cache: &mut GlyphCache, color: Rgb) {
-        let line = Line(23);
-        let col = Column(0);
+    /// Render a string at the start of a line. Used for the search bar and for printing render
+    /// time for profiling and optimization.
+    pub fn render_string(
+        &mut self,
+        string: &str,
+        line: Line,
+        glyph_cache: &mut GlyphCache,
+        fg: Rgb,
+        bg: Rgb,
+    ) {
+        self.render_string_at(string, line, Column(0), glyph_cache, fg, bg);
+    }
 
+    /// Render a string starting at any column, like the items of the context menu
+    pub fn render_string_at(
+        &mut self,
+        string: &str,
+        line: Line,
+        col: Column,
+        glyph_cache: &mut GlyphCache,
+        fg: Rgb,
+        bg: Rgb,
+    ) {
         let cells = string
             .chars()
             .enumerate()
             .map(|(i, c)| RenderableCell {
                 line,
                 column: col + i,
-                c,
-                bg: color,
-                fg: Rgb { r: 0, g: 0, b: 0 },
+                chars: {
+                    let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+                    chars[0] = c;
+                    chars
+                },
+                bg,
+                fg,
                 flags: cell::Flags::empty(),
                 bg_alpha: 1.0,
+                underline_color: None,
             })
             .collect::<Vec<_>>();
 
@@ -849,12 +1045,22 @@ impl<'a> RenderApi<'a> {
 
     #[inline]
     fn add_render_item(&mut self, cell: &RenderableCell, glyph: &Glyph) {
+        let instance = InstanceData::new(cell, glyph);
+        if self.record_lines {
+            self.lines[cell.line.0].push((glyph.tex_id, instance));
+        } else {
+            self.add_instance(glyph.tex_id, instance);
+        }
+    }
+
+    #[inline]
+    fn add_instance(&mut self, tex: GLuint, instance: InstanceData) {
         // Flush batch if tex changing
-        if !self.batch.is_empty() && self.batch.tex != glyph.tex_id {
+        if !self.batch.is_empty() && self.batch.tex != tex {
             self.render_batch();
         }
 
-        self.batch.add_item(cell, glyph);
+        self.batch.add_item(tex, instance);
 
         // Render batch and clear if it's full
         if self.batch.full() {
@@ -862,6 +1068,41 @@ impl<'a> RenderApi<'a> {
         }
     }
 
+    /// Render the grid, only rebuilding the instances of damaged lines
+    ///
+    /// The `cells` must cover all damaged lines, the remaining lines are drawn
+    /// from the instances of the last frame.
+    pub fn render_damaged_cells<'b, I>(
+        &mut self,
+        cells: I,
+        damage: &Damage,
+        num_lines: Line,
+        glyph_cache: &mut GlyphCache,
+    )
+        where I: Iterator<Item=&'b RenderableCell>
+    {
+        match *damage {
+            Damage::Full => {
+                *self.lines = (0..num_lines.0).map(|_| Vec::new()).collect();
+            },
+            Damage::Lines(ref lines) => {
+                for line in lines {
+                    self.lines[line.0].clear();
+                }
+            },
+        }
+
+        self.record_lines = true;
+        self.render_cells(cells, glyph_cache);
+        self.record_lines = false;
+
+        let lines = mem::replace(self.lines, Vec::new());
+        for &(tex, instance) in lines.iter().flat_map(|line| line.iter()) {
+            self.add_instance(tex, instance);
+        }
+        *self.lines = lines;
+    }
+
     pub fn render_cells<'b, I>(
         &mut self,
         cells: I,
@@ -871,23 +1112,25 @@ impl<'a> RenderApi<'a> {
     {
         for cell in cells {
             // Get font key for cell
-            // FIXME this is super inefficient.
-            let font_key = if cell.flags.contains(cell::Flags::BOLD) {
-                glyph_cache.bold_key
-            } else if cell.flags.contains(cell::Flags::ITALIC) {
-                glyph_cache.italic_key
-            } else {
-                glyph_cache.font_key
+            let font_key = match (
+                cell.flags.contains(cell::Flags::BOLD),
+                cell.flags.contains(cell::Flags::ITALIC),
+            ) {
+                (true, true) => glyph_cache.bold_italic_key,
+                (true, false) => glyph_cache.bold_key,
+                (false, true) => glyph_cache.italic_key,
+                (false, false) => glyph_cache.font_key,
             };
 
             let mut glyph_key = GlyphKey {
                 font_key,
                 size: glyph_cache.font_size,
-                c: cell.c,
+                c: cell.chars[0],
             };
 
             // Don't render text of HIDDEN cells
-            if cell.flags.contains(cell::Flags::HIDDEN) {
+            let hidden = cell.flags.contains(cell::Flags::HIDDEN);
+            if hidden {
                 glyph_key.c = ' ';
             }
 
@@ -897,23 +1140,86 @@ impl<'a> RenderApi<'a> {
                 self.add_render_item(&cell, glyph);
             }
 
-            // FIXME This is a super hacky way to do underlined text. During
-            //       a time crunch to release 0.1, this seemed like a really
-            //       easy, clean hack.
-            if cell.flags.contains(cell::Flags::UNDERLINE) {
-                let glyph_key = GlyphKey {
-                    font_key,
-                    size: glyph_cache.font_size,
-                    c: '_',
-                };
+            // Overstrike zero-width characters on top of the base glyph
+            let zerowidth = cell.chars[1..].iter().take_while(|c| **c != ' ');
+            for c in zerowidth.filter(|_| !hidden) {
+                glyph_key.c = *c;
+                let mut glyph = glyph_cache.get(glyph_key, self).clone();
+
+                // Combining marks are positioned relative to the end of the
+                // preceding glyph, so move them back into this cell.
+                glyph.left += glyph_cache.metrics.average_advance as f32;
 
-                let underscore = glyph_cache.get(glyph_key, self);
-                self.add_render_item(&cell, underscore);
+                self.add_render_item(&cell, &glyph);
+            }
+
+            // Lines are stretched over the entire cell, so they're joined
+            // with the lines of neighboring cells
+            let lines = cell::Flags::ALL_UNDERLINES | cell::Flags::OVERLINE | cell::Flags::STRIKEOUT;
+            if cell.flags.intersects(lines) {
+                let offset = *self.config.font().offset();
+                let metrics = &glyph_cache.metrics;
+                let mut width = (metrics.average_advance as f32 + f32::from(offset.x)).floor();
+                if cell.flags.contains(cell::Flags::WIDE_CHAR) {
+                    width *= 2.;
+                }
+
+                // Lines follow the foreground unless an underline color was set
+                let mut line_cell = *cell;
+                if let Some(color) = cell.underline_color {
+                    line_cell.fg = color;
+                }
+
+                let thickness = metrics.underline_thickness;
+                if cell.flags.intersects(cell::Flags::ALL_UNDERLINES) {
+                    for underline in glyph_cache.underline(cell.flags, width) {
+                        self.add_render_item(&line_cell, &underline);
+                    }
+                }
+
+                // Overlines are at the top of the cell
+                if cell.flags.contains(cell::Flags::OVERLINE) {
+                    let height = (metrics.line_height as f32 + f32::from(offset.y)).floor();
+                    let mut overline = glyph_cache.line(0., thickness, width);
+                    overline.top = height;
+                    self.add_render_item(&line_cell, &overline);
+                }
+
+                if cell.flags.contains(cell::Flags::STRIKEOUT) {
+                    let strikeout = glyph_cache.line(
+                        metrics.strikeout_position,
+                        metrics.strikeout_thickness,
+                        width,
+                    );
+                    self.add_render_item(&line_cell, &strikeout);
+                }
             }
         }
     }
 }
 
+/// Load a block of opaque texels for drawing lines
+fn load_solid_glyph<L: LoadGlyph>(loader: &mut L) -> Glyph {
+    let rasterized = RasterizedGlyph {
+        c: ' ',
+        width: 3,
+        height: 3,
+        top: 0,
+        left: 0,
+        buf: vec![255; 3 * 3 * 3],
+    };
+    let mut glyph = loader.load_glyph(&rasterized);
+
+    // Only the center texel is sampled, so filtering can't blend in any of
+    // the neighboring glyphs when the quad is stretched
+    glyph.uv_left += glyph.uv_width / 3.;
+    glyph.uv_bot += glyph.uv_height / 3.;
+    glyph.uv_width /= 3.;
+    glyph.uv_height /= 3.;
+
+    glyph
+}
+
 /// Load a glyph into a texture atlas
 ///
 /// If the current atlas is full, a new one will be created.
@@ -953,12 +1259,27 @@ fn load_glyph(
     }
 }
 
+/// Release all atlas textures except the first one, which is reset
+///
+/// The instances of the last frame reference the released textures, so they're
+/// dropped as well.
 #[inline]
-fn clear_atlas(atlas: &mut Vec<Atlas>, current_atlas: &mut usize) {
-    for atlas in atlas.iter_mut() {
-        atlas.clear();
+fn clear_atlas(
+    active_tex: &mut GLuint,
+    atlas: &mut Vec<Atlas>,
+    current_atlas: &mut usize,
+    lines: &mut Vec<LineInstances>,
+) {
+    for atlas in atlas.drain(1..) {
+        unsafe {
+            gl::DeleteTextures(1, &atlas.id);
+        }
     }
+    atlas[0].clear();
+
     *current_atlas = 0;
+    *active_tex = 0;
+    lines.clear();
 }
 
 impl<'a> LoadGlyph for LoaderApi<'a> {
@@ -967,7 +1288,7 @@ impl<'a> LoadGlyph for LoaderApi<'a> {
     }
 
     fn clear(&mut self) {
-        clear_atlas(self.atlas, self.current_atlas)
+        clear_atlas(self.active_tex, self.atlas, self.current_atlas, self.lines)
     }
 }
 
@@ -977,7 +1298,7 @@ impl<'a> LoadGlyph for RenderApi<'a> {
     }
 
     fn clear(&mut self) {
-        clear_atlas(self.atlas, self.current_atlas)
+        clear_atlas(self.active_tex, self.atlas, self.current_atlas, self.lines)
     }
 }
 
@@ -1043,12 +1364,13 @@ impl ShaderProgram {
         }
 
         // get uniform locations
-        let (projection, term_dim, cell_dim, visual_bell, background) = unsafe {
+        let (projection, term_dim, cell_dim, visual_bell, visual_bell_color, background) = unsafe {
             (
                 gl::GetUniformLocation(program, cptr!(b"projection\0")),
                 gl::GetUniformLocation(program, cptr!(b"termDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"cellDim\0")),
                 gl::GetUniformLocation(program, cptr!(b"visualBell\0")),
+                gl::GetUniformLocation(program, cptr!(b"visualBellColor\0")),
                 gl::GetUniformLocation(program, cptr!(b"backgroundPass\0")),
             )
         };
@@ -1061,6 +1383,7 @@ impl ShaderProgram {
             u_term_dim: term_dim,
             u_cell_dim: cell_dim,
             u_visual_bell: visual_bell,
+            u_visual_bell_color: visual_bell_color,
             u_background: background,
             padding_x: config.padding().x,
             padding_y: config.padding().y,
@@ -1115,9 +1438,15 @@ impl ShaderProgram {
         }
     }
 
-    fn set_visual_bell(&self, visual_bell: f32) {
+    fn set_visual_bell(&self, visual_bell: f32, color: Rgb) {
         unsafe {
             gl::Uniform1f(self.u_visual_bell, visual_bell);
+            gl::Uniform3f(
+                self.u_visual_bell_color,
+                f32::from(color.r) / 255.,
+                f32::from(color.g) / 255.,
+                f32::from(color.b) / 255.,
+            );
         }
     }
 
@@ -1411,32 +1740,50 @@ impl Atlas {
         glyph: &RasterizedGlyph,
         active_tex: &mut u32
     ) -> Result<Glyph, AtlasInsertError> {
-        if glyph.width > self.width || glyph.height > self.height {
+        let (offset_x, offset_y) = self.allocate(glyph.width, glyph.height)?;
+
+        // There is room; load the glyph.
+        Ok(self.insert_inner(glyph, offset_x, offset_y, active_tex))
+    }
+
+    /// Reserve space for a glyph, returning the offset of its bottom left corner
+    fn allocate(&mut self, width: i32, height: i32) -> Result<(i32, i32), AtlasInsertError> {
+        if width > self.width || height > self.height {
             return Err(AtlasInsertError::GlyphTooLarge);
         }
 
         // If there's not enough room in current row, go onto next one
-        if !self.room_in_row(glyph) {
+        if !self.room_in_row(width, height) {
             self.advance_row()?;
         }
 
         // If there's still not room, there's nothing that can be done here.
-        if !self.room_in_row(glyph) {
+        if !self.room_in_row(width, height) {
             return Err(AtlasInsertError::Full);
         }
 
-        // There appears to be room; load the glyph.
-        Ok(self.insert_inner(glyph, active_tex))
+        let offset = (self.row_extent, self.row_baseline);
+
+        // Update Atlas state
+        self.row_extent += width;
+        if height > self.row_tallest {
+            self.row_tallest = height;
+        }
+
+        Ok(offset)
     }
 
-    /// Insert the glyph without checking for room
+    /// Upload the glyph to its space in the atlas
     ///
-    /// Internal function for use once atlas has been checked for space. GL
-    /// errors could still occur at this point if we were checking for them;
-    /// hence, the Result.
-    fn insert_inner(&mut self, glyph: &RasterizedGlyph, active_tex: &mut u32) -> Glyph {
-        let offset_y = self.row_baseline;
-        let offset_x = self.row_extent;
+    /// Internal function for use once space has been allocated for the glyph. GL
+    /// errors could still occur at this point if we were checking for them.
+    fn insert_inner(
+        &mut self,
+        glyph: &RasterizedGlyph,
+        offset_x: i32,
+        offset_y: i32,
+        active_tex: &mut u32,
+    ) -> Glyph {
         let height = glyph.height as i32;
         let width = glyph.width as i32;
 
@@ -1460,12 +1807,6 @@ impl Atlas {
             *active_tex = 0;
         }
 
-        // Update Atlas state
-        self.row_extent = offset_x + width;
-        if height > self.row_tallest {
-            self.row_tallest = height;
-        }
-
         // Generate UV coordinates
         let uv_bot = offset_y as f32 / self.height as f32;
         let uv_left = offset_x as f32 / self.width as f32;
@@ -1486,10 +1827,10 @@ impl Atlas {
     }
 
     /// Check if there's room in the current row for given glyph
-    fn room_in_row(&self, raw: &RasterizedGlyph) -> bool {
-        let next_extent = self.row_extent + raw.width as i32;
+    fn room_in_row(&self, width: i32, height: i32) -> bool {
+        let next_extent = self.row_extent + width;
         let enough_width = next_extent <= self.width;
-        let enough_height = (raw.height as i32) < (self.height - self.row_baseline);
+        let enough_height = height < (self.height - self.row_baseline);
 
         enough_width && enough_height
     }
@@ -1508,3 +1849,78 @@ impl Atlas {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Atlas, AtlasInsertError};
+
+    fn atlas(size: i32) -> Atlas {
+        Atlas {
+            id: 0,
+            width: size,
+            height: size,
+            row_extent: 0,
+            row_baseline: 0,
+            row_tallest: 0,
+        }
+    }
+
+    #[test]
+    fn glyphs_spill_into_new_pages() {
+        // Glyph sizes similar to a large font with CJK fallback glyphs
+        let mut seed = 7u32;
+        let sizes: Vec<(i32, i32)> = (0..5000).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (8 + (seed >> 16) as i32 % 40, 16 + (seed >> 8) as i32 % 40)
+        }).collect();
+
+        let mut pages = vec![atlas(256)];
+        let mut placed: Vec<Vec<(i32, i32, i32, i32)>> = vec![Vec::new()];
+        for &(width, height) in &sizes {
+            let allocated = pages.last_mut().unwrap().allocate(width, height);
+            let offset = match allocated {
+                Ok(offset) => offset,
+                Err(AtlasInsertError::Full) => {
+                    pages.push(atlas(256));
+                    placed.push(Vec::new());
+                    match pages.last_mut().unwrap().allocate(width, height) {
+                        Ok(offset) => offset,
+                        Err(_) => panic!("glyph doesn't fit into an empty page"),
+                    }
+                },
+                Err(AtlasInsertError::GlyphTooLarge) => panic!("glyph too large"),
+            };
+            placed.last_mut().unwrap().push((offset.0, offset.1, width, height));
+        }
+
+        assert!(pages.len() > 1);
+        assert_eq!(placed.iter().map(|page| page.len()).sum::<usize>(), sizes.len());
+
+        // Every glyph is within its page and doesn't overlap any other glyph
+        for page in &placed {
+            for (i, &(x, y, width, height)) in page.iter().enumerate() {
+                assert!(x >= 0 && y >= 0 && x + width <= 256 && y + height <= 256);
+                for &(other_x, other_y, other_width, other_height) in &page[i + 1..] {
+                    let disjoint = x + width <= other_x
+                        || other_x + other_width <= x
+                        || y + height <= other_y
+                        || other_y + other_height <= y;
+                    assert!(disjoint);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cleared_atlas_starts_over() {
+        let mut atlas = atlas(64);
+        while atlas.allocate(10, 10).is_ok() {}
+
+        atlas.clear();
+        assert_eq!(atlas.allocate(10, 10).ok(), Some((0, 0)));
+        assert!(match atlas.allocate(65, 10) {
+            Err(AtlasInsertError::GlyphTooLarge) => true,
+            _ => false,
+        });
+    }
+}
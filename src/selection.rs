@@ -26,17 +26,19 @@ use term::Search;
 
 /// Describes a region of a 2-dimensional area
 ///
-/// Used to track a text selection. There are three supported modes, each with its own constructor:
-/// [`simple`], [`semantic`], and [`lines`]. The [`simple`] mode precisely tracks which cells are
-/// selected without any expansion. [`semantic`] mode expands the initial selection to the nearest
-/// semantic escape char in either direction. [`lines`] will always select entire lines.
+/// Used to track a text selection. There are four supported modes, each with its own constructor:
+/// [`simple`], [`block`], [`semantic`], and [`lines`]. The [`simple`] mode precisely tracks which
+/// cells are selected without any expansion. [`block`] mode selects the rectangle between both
+/// points instead of the text in between them. [`semantic`] mode expands the initial selection to
+/// the nearest semantic escape char in either direction. [`lines`] will always select entire lines.
 ///
-/// Calls to [`update`] operate different based on the selection kind. The [`simple`] mode does
-/// nothing special, simply tracks points and sides. [`semantic`] will continue to expand out to
-/// semantic boundaries as the selection point changes. Similarly, [`lines`] will always expand the
-/// new point to encompass entire lines.
+/// Calls to [`update`] operate different based on the selection kind. The [`simple`] and
+/// [`block`] modes do nothing special, simply track points and sides. [`semantic`] will continue
+/// to expand out to semantic boundaries as the selection point changes. Similarly, [`lines`] will
+/// always expand the new point to encompass entire lines.
 ///
 /// [`simple`]: enum.Selection.html#method.simple
+/// [`block`]: enum.Selection.html#method.block
 /// [`semantic`]: enum.Selection.html#method.semantic
 /// [`lines`]: enum.Selection.html#method.lines
 #[derive(Debug, Clone, PartialEq)]
@@ -45,6 +47,10 @@ pub enum Selection {
         /// The region representing start and end of cursor movement
         region: Range<Anchor>,
     },
+    Block {
+        /// The region representing start and end of cursor movement
+        region: Range<Anchor>,
+    },
     Semantic {
         /// The region representing start and end of cursor movement
         region: Range<Point<isize>>,
@@ -88,9 +94,18 @@ impl Selection {
         }
     }
 
+    pub fn block(location: Point<usize>, side: Side) -> Selection {
+        Selection::Block {
+            region: Range {
+                start: Anchor::new(location.into(), side),
+                end: Anchor::new(location.into(), side)
+            }
+        }
+    }
+
     pub fn rotate(&mut self, offset: isize) {
         match *self {
-            Selection::Simple { ref mut region } => {
+            Selection::Simple { ref mut region } | Selection::Block { ref mut region } => {
                 region.start.point.line += offset;
                 region.end.point.line += offset;
             },
@@ -128,7 +143,7 @@ impl Selection {
     pub fn update(&mut self, location: Point<usize>, side: Side) {
         // Always update the `end`; can normalize later during span generation.
         match *self {
-            Selection::Simple { ref mut region } => {
+            Selection::Simple { ref mut region } | Selection::Block { ref mut region } => {
                 region.end = Anchor::new(location.into(), side);
             },
             Selection::Semantic { ref mut region } |
@@ -147,6 +162,9 @@ impl Selection {
             Selection::Simple { ref region } => {
                 Selection::span_simple(grid, region, alt_screen)
             },
+            Selection::Block { ref region } => {
+                Selection::span_block(grid, region, alt_screen)
+            },
             Selection::Semantic { ref region } => {
                 Selection::span_semantic(grid, region, alt_screen)
             },
@@ -159,7 +177,7 @@ impl Selection {
     pub fn is_empty(&self) -> bool
     {
         match *self {
-            Selection::Simple { ref region } => {
+            Selection::Simple { ref region } | Selection::Block { ref region } => {
                 region.start == region.end && region.start.side == region.end.side
             },
             Selection::Semantic { .. } | Selection::Lines { .. } => {
@@ -214,7 +232,7 @@ impl Selection {
         alt_screen: bool,
     ) -> Option<Span>
     where
-        G: Dimensions
+        G: Search + Dimensions
     {
         let cols = grid.dimensions().col;
         let lines = grid.dimensions().line.0 as isize;
@@ -241,6 +259,11 @@ impl Selection {
             end.line = max(end.line, region.start.line);
         }
 
+        // Wrapped lines are selected as a whole
+        let mut start: Point<isize> = grid.line_search_right(start.into()).into();
+        let end = grid.line_search_left(end.into());
+        let mut end: Point<isize> = grid.line_text_start(end).into();
+
         if alt_screen {
             Selection::alt_screen_clamp(&mut start, &mut end, lines, cols)?;
         }
@@ -311,6 +334,72 @@ impl Selection {
         })
     }
 
+    fn span_block<G>(grid: &G, region: &Range<Anchor>, alt_screen: bool) -> Option<Span>
+    where
+        G: Dimensions
+    {
+        let cols = grid.dimensions().col;
+        let lines = grid.dimensions().line.0 as isize;
+
+        // Sort the anchors by column, the lines are independent of that
+        let (left, right) = if region.start.point.col <= region.end.point.col {
+            (&region.start, &region.end)
+        } else {
+            (&region.end, &region.start)
+        };
+
+        let mut left_col = left.point.col;
+        let mut right_col = right.point.col;
+        if left_col == right_col {
+            // No cell is covered when both anchors are on the same side of the column
+            if left.side == right.side {
+                return None;
+            }
+        } else {
+            // Remove cells which are only partially covered
+            if left.side == Side::Right {
+                left_col += 1;
+            }
+            if right.side == Side::Left {
+                right_col -= 1;
+            }
+
+            // Two adjacent cells with right+left sides
+            if left_col > right_col {
+                return None;
+            }
+        }
+
+        let mut bottom = min(region.start.point.line, region.end.point.line);
+        let mut top = max(region.start.point.line, region.end.point.line);
+
+        if alt_screen {
+            if top >= lines {
+                // Don't show selection above visible region
+                if bottom >= lines {
+                    return None;
+                }
+                top = lines - 1;
+            }
+
+            if bottom < 0 {
+                // Don't show selection below visible region
+                if top < 0 {
+                    return None;
+                }
+                bottom = 0;
+            }
+        }
+
+        // Front is the bottom right and tail the top left corner
+        Some(Span {
+            cols,
+            front: Point::new(bottom as usize, right_col),
+            tail: Point::new(top as usize, left_col),
+            ty: SpanType::Block,
+        })
+    }
+
     // Clamp selection in the alternate screen to the visible region
     fn alt_screen_clamp(
         front: &mut Point<isize>,
@@ -358,6 +447,9 @@ pub enum SpanType {
 
     /// Excludes first cell of selection
     ExcludeFront,
+
+    /// Includes the rectangle between the beginning and end locations
+    Block,
 }
 
 /// Represents a span of selected cells
@@ -377,12 +469,14 @@ pub struct Locations {
     pub start: Point<usize>,
     /// End point towards top of buffer
     pub end: Point<usize>,
+    /// Whether only the columns between start and end are selected on each line
+    pub block: bool,
 }
 
 impl Span {
     pub fn to_locations(&self) -> Locations {
         let (start, end) = match self.ty {
-            SpanType::Inclusive => (self.front, self.tail),
+            SpanType::Inclusive | SpanType::Block => (self.front, self.tail),
             SpanType::Exclusive => {
                 (Span::wrap_start(self.front, self.cols), Span::wrap_end(self.tail, self.cols))
             },
@@ -390,7 +484,7 @@ impl Span {
             SpanType::ExcludeTail => (self.front, Span::wrap_end(self.tail, self.cols))
         };
 
-        Locations { start, end }
+        Locations { start, end, block: self.ty == SpanType::Block }
     }
 
     fn wrap_start(mut start: Point<usize>, cols: Column) -> Point<usize> {
@@ -453,6 +547,9 @@ mod test {
     impl super::Search for Dimensions {
         fn semantic_search_left(&self, point: Point<usize>) -> Point<usize> { point }
         fn semantic_search_right(&self, point: Point<usize>) -> Point<usize> { point }
+        fn line_search_left(&self, point: Point<usize>) -> Point<usize> { point }
+        fn line_search_right(&self, point: Point<usize>) -> Point<usize> { point }
+        fn line_text_start(&self, point: Point<usize>) -> Point<usize> { point }
         fn url_search(&self, _: Point<usize>) -> Option<String> { None }
     }
 
@@ -608,4 +705,50 @@ mod test {
             ty: SpanType::Inclusive,
         });
     }
+
+    /// Block selection dragged from the bottom right to the top left
+    ///
+    ///     [ E][XX][XX][  ][  ]
+    ///     [  ][XX][XX][  ][  ]
+    ///     [  ][XX][XB][  ][  ]
+    #[test]
+    fn block_bottom_right_to_top_left() {
+        let mut selection = Selection::block(Point::new(0, Column(2)), Side::Right);
+        selection.update(Point::new(2, Column(0)), Side::Right);
+
+        assert_eq!(selection.to_span(&Dimensions::new(3, 5), false).unwrap(), Span {
+            cols: Column(5),
+            front: Point::new(0, Column(2)),
+            tail: Point::new(2, Column(1)),
+            ty: SpanType::Block,
+        });
+    }
+
+    /// Block selections without any fully covered column are empty
+    ///
+    ///     [ B][  ][  ]      [ B][E ][  ]
+    ///     [ E][  ][  ]      [  ][  ][  ]
+    #[test]
+    fn block_without_columns() {
+        let mut selection = Selection::block(Point::new(1, Column(0)), Side::Right);
+        selection.update(Point::new(0, Column(0)), Side::Right);
+        assert_eq!(selection.to_span(&Dimensions::new(2, 3), false), None);
+
+        selection.update(Point::new(1, Column(1)), Side::Left);
+        assert_eq!(selection.to_span(&Dimensions::new(2, 3), false), None);
+    }
+
+    #[test]
+    fn alt_screen_block() {
+        let mut selection = Selection::block(Point::new(0, Column(3)), Side::Right);
+        selection.update(Point::new(5, Column(1)), Side::Left);
+        selection.rotate(-3);
+
+        assert_eq!(selection.to_span(&Dimensions::new(10, 5), true).unwrap(), Span {
+            cols: Column(5),
+            front: Point::new(0, Column(3)),
+            tail: Point::new(2, Column(1)),
+            ty: SpanType::Block,
+        });
+    }
 }
@@ -12,22 +12,39 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use ansi::{NamedColor, Color};
-use grid;
+use grid::{self, GridCell};
 use index::Column;
+use term::hyperlink::HyperlinkId;
+use term::zerowidth::ZeroWidthId;
+
+/// Maximum number of zero-width characters which can be stored in a cell
+pub const MAX_ZEROWIDTH_CHARS: usize = 5;
 
 bitflags! {
     #[derive(Serialize, Deserialize)]
     pub struct Flags: u32 {
-        const INVERSE           = 0b0_0000_0001;
-        const BOLD              = 0b0_0000_0010;
-        const ITALIC            = 0b0_0000_0100;
-        const UNDERLINE         = 0b0_0000_1000;
-        const WRAPLINE          = 0b0_0001_0000;
-        const WIDE_CHAR         = 0b0_0010_0000;
-        const WIDE_CHAR_SPACER  = 0b0_0100_0000;
-        const DIM               = 0b0_1000_0000;
-        const DIM_BOLD          = 0b0_1000_0010;
-        const HIDDEN            = 0b1_0000_0000;
+        const INVERSE           = 0b0000_0000_0000_0001;
+        const BOLD              = 0b0000_0000_0000_0010;
+        const ITALIC            = 0b0000_0000_0000_0100;
+        const UNDERLINE         = 0b0000_0000_0000_1000;
+        const WRAPLINE          = 0b0000_0000_0001_0000;
+        const WIDE_CHAR         = 0b0000_0000_0010_0000;
+        const WIDE_CHAR_SPACER  = 0b0000_0000_0100_0000;
+        const DIM               = 0b0000_0000_1000_0000;
+        const DIM_BOLD          = 0b0000_0000_1000_0010;
+        const HIDDEN            = 0b0000_0001_0000_0000;
+        const DOUBLE_UNDERLINE  = 0b0000_0010_0000_0000;
+        const OVERLINE          = 0b0000_0100_0000_0000;
+        const STRIKEOUT         = 0b0000_1000_0000_0000;
+        const BLINK             = 0b0001_0000_0000_0000;
+        const CURLY_UNDERLINE   = 0b0010_0000_0000_0000;
+        const DOTTED_UNDERLINE  = 0b0100_0000_0000_0000;
+        const DASHED_UNDERLINE  = 0b1000_0000_0000_0000;
+        const ALL_UNDERLINES    = Self::UNDERLINE.bits
+                                | Self::DOUBLE_UNDERLINE.bits
+                                | Self::CURLY_UNDERLINE.bits
+                                | Self::DOTTED_UNDERLINE.bits
+                                | Self::DASHED_UNDERLINE.bits;
     }
 }
 
@@ -37,6 +54,12 @@ pub struct Cell {
     pub fg: Color,
     pub bg: Color,
     pub flags: Flags,
+    #[serde(default)]
+    pub underline_color: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zerowidth: Option<ZeroWidthId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hyperlink: Option<HyperlinkId>,
 }
 
 impl Default for Cell {
@@ -50,6 +73,28 @@ impl Default for Cell {
 
 }
 
+impl GridCell for Cell {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        Cell::is_empty(self)
+    }
+
+    #[inline]
+    fn is_wrap(&self) -> bool {
+        self.flags.contains(Flags::WRAPLINE)
+    }
+
+    #[inline]
+    fn set_wrap(&mut self, wrap: bool) {
+        self.flags.set(Flags::WRAPLINE, wrap);
+    }
+
+    #[inline]
+    fn is_wide(&self) -> bool {
+        self.flags.contains(Flags::WIDE_CHAR)
+    }
+}
+
 /// Get the length of occupied cells in a line
 pub trait LineLength {
     /// Calculate the occupied line length
@@ -93,6 +138,9 @@ impl Cell {
 
     pub fn new(c: char, fg: Color, bg: Color) -> Cell {
         Cell {
+            zerowidth: None,
+            underline_color: None,
+            hyperlink: None,
             c,
             bg,
             fg,
@@ -103,8 +151,9 @@ impl Cell {
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.c == ' ' &&
+            self.zerowidth.is_none() &&
             self.bg == Color::Named(NamedColor::Background) &&
-            !self.flags.intersects(Flags::INVERSE | Flags::UNDERLINE)
+            !self.flags.intersects(Flags::INVERSE | Flags::ALL_UNDERLINES | Flags::OVERLINE | Flags::STRIKEOUT)
     }
 
     #[inline]
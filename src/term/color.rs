@@ -6,10 +6,13 @@ use config::Colors;
 
 pub const COUNT: usize = 270;
 
+/// Factor for deriving dim colors from their regular counterparts
+pub const DIM_FACTOR: f32 = 0.66;
+
 /// List of indexed colors
 ///
 /// The first 16 entries are the standard ansi named colors. Items 16..232 are
-/// the color cube.  Items 233..256 are the grayscale ramp. Item 256 is
+/// the color cube.  Items 232..256 are the grayscale ramp. Item 256 is
 /// the configured foreground color, item 257 is the configured background
 /// color, item 258 is the cursor foreground color, item 259 is the cursor
 /// background color. Following that are 8 positions for dim colors.
@@ -19,8 +22,7 @@ pub struct List([Rgb; COUNT]);
 
 impl<'a> From<&'a Colors> for List {
     fn from(colors: &Colors) -> List {
-        // Type inference fails without this annotation
-        let mut list: List = unsafe { ::std::mem::uninitialized() };
+        let mut list = List([Rgb::default(); COUNT]);
 
         list.fill_named(colors);
         list.fill_cube(colors);
@@ -68,7 +70,7 @@ impl List {
         self[ansi::NamedColor::DimForeground] = colors
             .primary
             .dim_foreground
-            .unwrap_or(colors.primary.foreground * 0.66);
+            .unwrap_or(colors.primary.foreground * DIM_FACTOR);
         match colors.dim {
             Some(ref dim) => {
                 trace!("Using config-provided dim colors");
@@ -83,14 +85,14 @@ impl List {
             }
             None => {
                 trace!("Deriving dim colors from normal colors");
-                self[ansi::NamedColor::DimBlack]   = colors.normal.black   * 0.66;
-                self[ansi::NamedColor::DimRed]     = colors.normal.red     * 0.66;
-                self[ansi::NamedColor::DimGreen]   = colors.normal.green   * 0.66;
-                self[ansi::NamedColor::DimYellow]  = colors.normal.yellow  * 0.66;
-                self[ansi::NamedColor::DimBlue]    = colors.normal.blue    * 0.66;
-                self[ansi::NamedColor::DimMagenta] = colors.normal.magenta * 0.66;
-                self[ansi::NamedColor::DimCyan]    = colors.normal.cyan    * 0.66;
-                self[ansi::NamedColor::DimWhite]   = colors.normal.white   * 0.66;
+                self[ansi::NamedColor::DimBlack]   = colors.normal.black   * DIM_FACTOR;
+                self[ansi::NamedColor::DimRed]     = colors.normal.red     * DIM_FACTOR;
+                self[ansi::NamedColor::DimGreen]   = colors.normal.green   * DIM_FACTOR;
+                self[ansi::NamedColor::DimYellow]  = colors.normal.yellow  * DIM_FACTOR;
+                self[ansi::NamedColor::DimBlue]    = colors.normal.blue    * DIM_FACTOR;
+                self[ansi::NamedColor::DimMagenta] = colors.normal.magenta * DIM_FACTOR;
+                self[ansi::NamedColor::DimCyan]    = colors.normal.cyan    * DIM_FACTOR;
+                self[ansi::NamedColor::DimWhite]   = colors.normal.white   * DIM_FACTOR;
             }
         }
     }
@@ -110,8 +112,8 @@ impl List {
                         self[index] = indexed_color.color;
                     } else {
                         self[index] = Rgb { r: if r == 0 { 0 } else { r * 40 + 55 },
-                            b: if b == 0 { 0 } else { b * 40 + 55 },
                             g: if g == 0 { 0 } else { g * 40 + 55 },
+                            b: if b == 0 { 0 } else { b * 40 + 55 },
                         };
                     }
                     index += 1;
@@ -206,3 +208,114 @@ impl IndexMut<u8> for List {
         &mut self.0[idx as usize]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{List, COUNT};
+    use config::{Colors, Config};
+    use serde_yaml;
+    use {Rgb, ansi};
+
+    #[test]
+    fn indexed_colors_follow_xterm_palette() {
+        let config = Config::default();
+        let list = List::from(config.colors());
+
+        // Named colors come from the config
+        assert_eq!(list[1u8], config.colors().normal.red);
+        assert_eq!(list[ansi::NamedColor::BrightWhite], config.colors().bright.white);
+
+        // 6x6x6 color cube
+        assert_eq!(list[16u8], Rgb { r: 0, g: 0, b: 0 });
+        assert_eq!(list[17u8], Rgb { r: 0, g: 0, b: 95 });
+        assert_eq!(list[196u8], Rgb { r: 255, g: 0, b: 0 });
+        assert_eq!(list[231u8], Rgb { r: 255, g: 255, b: 255 });
+
+        // Grayscale ramp
+        assert_eq!(list[232u8], Rgb { r: 8, g: 8, b: 8 });
+        assert_eq!(list[255u8], Rgb { r: 238, g: 238, b: 238 });
+    }
+
+    /// Every entry of the list built from the default colors
+    ///
+    /// The terminal and the renderer both resolve colors through this list, so
+    /// any change to how an index maps to a color shows up here.
+    #[test]
+    fn default_color_list() {
+        let expected: [u32; COUNT] = [
+            0x000000, 0xd54e53, 0xb9ca4a, 0xe6c547, 0x7aa6da, 0xc397d8, 0x70c0ba, 0xeaeaea,
+            0x666666, 0xff3334, 0x9ec400, 0xe7c547, 0x7aa6da, 0xb77ee0, 0x54ced6, 0xffffff,
+            0x000000, 0x00005f, 0x000087, 0x0000af, 0x0000d7, 0x0000ff, 0x005f00, 0x005f5f,
+            0x005f87, 0x005faf, 0x005fd7, 0x005fff, 0x008700, 0x00875f, 0x008787, 0x0087af,
+            0x0087d7, 0x0087ff, 0x00af00, 0x00af5f, 0x00af87, 0x00afaf, 0x00afd7, 0x00afff,
+            0x00d700, 0x00d75f, 0x00d787, 0x00d7af, 0x00d7d7, 0x00d7ff, 0x00ff00, 0x00ff5f,
+            0x00ff87, 0x00ffaf, 0x00ffd7, 0x00ffff, 0x5f0000, 0x5f005f, 0x5f0087, 0x5f00af,
+            0x5f00d7, 0x5f00ff, 0x5f5f00, 0x5f5f5f, 0x5f5f87, 0x5f5faf, 0x5f5fd7, 0x5f5fff,
+            0x5f8700, 0x5f875f, 0x5f8787, 0x5f87af, 0x5f87d7, 0x5f87ff, 0x5faf00, 0x5faf5f,
+            0x5faf87, 0x5fafaf, 0x5fafd7, 0x5fafff, 0x5fd700, 0x5fd75f, 0x5fd787, 0x5fd7af,
+            0x5fd7d7, 0x5fd7ff, 0x5fff00, 0x5fff5f, 0x5fff87, 0x5fffaf, 0x5fffd7, 0x5fffff,
+            0x870000, 0x87005f, 0x870087, 0x8700af, 0x8700d7, 0x8700ff, 0x875f00, 0x875f5f,
+            0x875f87, 0x875faf, 0x875fd7, 0x875fff, 0x878700, 0x87875f, 0x878787, 0x8787af,
+            0x8787d7, 0x8787ff, 0x87af00, 0x87af5f, 0x87af87, 0x87afaf, 0x87afd7, 0x87afff,
+            0x87d700, 0x87d75f, 0x87d787, 0x87d7af, 0x87d7d7, 0x87d7ff, 0x87ff00, 0x87ff5f,
+            0x87ff87, 0x87ffaf, 0x87ffd7, 0x87ffff, 0xaf0000, 0xaf005f, 0xaf0087, 0xaf00af,
+            0xaf00d7, 0xaf00ff, 0xaf5f00, 0xaf5f5f, 0xaf5f87, 0xaf5faf, 0xaf5fd7, 0xaf5fff,
+            0xaf8700, 0xaf875f, 0xaf8787, 0xaf87af, 0xaf87d7, 0xaf87ff, 0xafaf00, 0xafaf5f,
+            0xafaf87, 0xafafaf, 0xafafd7, 0xafafff, 0xafd700, 0xafd75f, 0xafd787, 0xafd7af,
+            0xafd7d7, 0xafd7ff, 0xafff00, 0xafff5f, 0xafff87, 0xafffaf, 0xafffd7, 0xafffff,
+            0xd70000, 0xd7005f, 0xd70087, 0xd700af, 0xd700d7, 0xd700ff, 0xd75f00, 0xd75f5f,
+            0xd75f87, 0xd75faf, 0xd75fd7, 0xd75fff, 0xd78700, 0xd7875f, 0xd78787, 0xd787af,
+            0xd787d7, 0xd787ff, 0xd7af00, 0xd7af5f, 0xd7af87, 0xd7afaf, 0xd7afd7, 0xd7afff,
+            0xd7d700, 0xd7d75f, 0xd7d787, 0xd7d7af, 0xd7d7d7, 0xd7d7ff, 0xd7ff00, 0xd7ff5f,
+            0xd7ff87, 0xd7ffaf, 0xd7ffd7, 0xd7ffff, 0xff0000, 0xff005f, 0xff0087, 0xff00af,
+            0xff00d7, 0xff00ff, 0xff5f00, 0xff5f5f, 0xff5f87, 0xff5faf, 0xff5fd7, 0xff5fff,
+            0xff8700, 0xff875f, 0xff8787, 0xff87af, 0xff87d7, 0xff87ff, 0xffaf00, 0xffaf5f,
+            0xffaf87, 0xffafaf, 0xffafd7, 0xffafff, 0xffd700, 0xffd75f, 0xffd787, 0xffd7af,
+            0xffd7d7, 0xffd7ff, 0xffff00, 0xffff5f, 0xffff87, 0xffffaf, 0xffffd7, 0xffffff,
+            0x080808, 0x121212, 0x1c1c1c, 0x262626, 0x303030, 0x3a3a3a, 0x444444, 0x4e4e4e,
+            0x585858, 0x626262, 0x6c6c6c, 0x767676, 0x808080, 0x8a8a8a, 0x949494, 0x9e9e9e,
+            0xa8a8a8, 0xb2b2b2, 0xbcbcbc, 0xc6c6c6, 0xd0d0d0, 0xdadada, 0xe4e4e4, 0xeeeeee,
+            0xeaeaea, 0x000000, 0x000000, 0x000000, 0x000000, 0x8c3336, 0x7a8530, 0x97822e,
+            0x506d8f, 0x80638e, 0x497e7a, 0x9a9a9a, 0xeaeaea, 0x9a9a9a,
+        ];
+
+        let list = List::from(&Colors::default());
+        for (index, &hex) in expected.iter().enumerate() {
+            let rgb = Rgb { r: (hex >> 16) as u8, g: (hex >> 8) as u8, b: hex as u8 };
+            assert_eq!(list[index], rgb, "color {}", index);
+        }
+    }
+
+    #[test]
+    fn indexed_colors_override_palette() {
+        let colors: Colors = serde_yaml::from_str(r#"
+            normal:
+              black:   '0x000000'
+              red:     '0xcd0000'
+              green:   '0x00cd00'
+              yellow:  '0xcdcd00'
+              blue:    '0x0000ee'
+              magenta: '0xcd00cd'
+              cyan:    '0x00cdcd'
+              white:   '0xe5e5e5'
+            bright:
+              black:   '0x7f7f7f'
+              red:     '0xff0000'
+              green:   '0x00ff00'
+              yellow:  '0xffff00'
+              blue:    '0x5c5cff'
+              magenta: '0xff00ff'
+              cyan:    '0x00ffff'
+              white:   '0xffffff'
+            indexed_colors:
+              - { index: 16,  color: '0x123456' }
+              - { index: 255, color: '0xabcdef' }
+        "#).unwrap();
+        let list = List::from(&colors);
+
+        assert_eq!(list[16u8], Rgb { r: 0x12, g: 0x34, b: 0x56 });
+        assert_eq!(list[17u8], Rgb { r: 0, g: 0, b: 95 });
+        assert_eq!(list[255u8], Rgb { r: 0xab, g: 0xcd, b: 0xef });
+        assert_eq!(list[254u8], Rgb { r: 228, g: 228, b: 228 });
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Menu opened by clicking the right mouse button
+//!
+//! The menu is drawn on top of the grid, with one item per line. Its position
+//! is in visible coordinates and it is closed by the next click or key press.
+use index::{Column, Line, Point};
+
+/// Action of a context menu item
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MenuAction {
+    /// Copy the selection to the clipboard
+    Copy,
+
+    /// Paste the clipboard
+    Paste,
+
+    /// Open the URL under the mouse
+    OpenUrl(String),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MenuItem {
+    pub action: MenuAction,
+
+    /// Disabled items are grayed out and can't be clicked
+    pub enabled: bool,
+}
+
+impl MenuItem {
+    pub fn label(&self) -> &'static str {
+        match self.action {
+            MenuAction::Copy => "Copy",
+            MenuAction::Paste => "Paste",
+            MenuAction::OpenUrl(_) => "Open URL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ContextMenu {
+    /// Top left corner of the menu
+    pub position: Point,
+
+    pub items: Vec<MenuItem>,
+
+    /// Item below the mouse cursor
+    pub hovered: Option<usize>,
+}
+
+impl ContextMenu {
+    /// Create a menu at the clicked cell
+    ///
+    /// Copy is only enabled with a selection and the URL item is only added
+    /// when there's a URL below the mouse. The menu is moved up and left
+    /// when it wouldn't fit into the grid otherwise.
+    pub fn new(
+        point: Point,
+        has_selection: bool,
+        url: Option<String>,
+        lines: Line,
+        cols: Column,
+    ) -> ContextMenu {
+        let mut items = vec![
+            MenuItem { action: MenuAction::Copy, enabled: has_selection },
+            MenuItem { action: MenuAction::Paste, enabled: true },
+        ];
+        if let Some(url) = url {
+            items.push(MenuItem { action: MenuAction::OpenUrl(url), enabled: true });
+        }
+
+        let mut menu = ContextMenu {
+            position: point,
+            items,
+            hovered: None,
+        };
+
+        let height = menu.items.len();
+        let width = menu.width();
+        if menu.position.line.0 + height > lines.0 {
+            menu.position.line = Line(lines.0.saturating_sub(height));
+        }
+        if menu.position.col.0 + width > cols.0 {
+            menu.position.col = Column(cols.0.saturating_sub(width));
+        }
+
+        menu
+    }
+
+    /// Number of columns covered by the menu
+    pub fn width(&self) -> usize {
+        self.items.iter().map(|item| item.label().len()).max().unwrap_or(0) + 2
+    }
+
+    /// Text of an item, padded to the width of the menu
+    pub fn text(&self, index: usize) -> String {
+        format!(" {:1$} ", self.items[index].label(), self.width() - 2)
+    }
+
+    /// Index of the item at a point of the grid
+    pub fn item_at(&self, point: Point) -> Option<usize> {
+        if point.line < self.position.line
+            || point.col < self.position.col
+            || point.col.0 >= self.position.col.0 + self.width()
+        {
+            return None;
+        }
+
+        let index = (point.line - self.position.line).0;
+        if index < self.items.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use index::{Column, Line, Point};
+
+    use super::{ContextMenu, MenuAction};
+
+    #[test]
+    fn menu_items() {
+        let menu = ContextMenu::new(Point::new(Line(0), Column(0)), false, None, Line(10), Column(20));
+        let actions: Vec<_> = menu.items.iter().map(|item| (item.action.clone(), item.enabled)).collect();
+        assert_eq!(actions, vec![(MenuAction::Copy, false), (MenuAction::Paste, true)]);
+        assert_eq!(menu.text(1), " Paste ");
+
+        let url = Some(String::from("https://example.org"));
+        let menu = ContextMenu::new(Point::new(Line(0), Column(0)), true, url, Line(10), Column(20));
+        assert!(menu.items[0].enabled);
+        assert_eq!(menu.items[2].action, MenuAction::OpenUrl(String::from("https://example.org")));
+        assert_eq!(menu.text(0), " Copy     ");
+    }
+
+    #[test]
+    fn menu_stays_inside_grid() {
+        let menu = ContextMenu::new(Point::new(Line(9), Column(18)), false, None, Line(10), Column(20));
+        assert_eq!(menu.position, Point::new(Line(8), Column(13)));
+
+        assert_eq!(menu.item_at(Point::new(Line(8), Column(13))), Some(0));
+        assert_eq!(menu.item_at(Point::new(Line(9), Column(19))), Some(1));
+        assert_eq!(menu.item_at(Point::new(Line(9), Column(12))), None);
+        assert_eq!(menu.item_at(Point::new(Line(7), Column(15))), None);
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Tracking of the lines which changed since the last frame
+//!
+//! The visible rows of the last frame are kept around, so the next frame only
+//! has to rebuild the lines which differ from them. Changes which affect the
+//! entire viewport, like scrolling or resizing, mark everything as damaged.
+use std::mem;
+
+use ansi::CursorStyle;
+use grid::{Grid, Row};
+use index::{Line, Point};
+use selection::Selection;
+use term::cell::{Cell, Flags};
+use term::search::Match;
+use term::{TermMode, UrlMatch};
+
+/// Lines which have to be redrawn
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Damage {
+    /// The entire viewport has to be redrawn
+    Full,
+
+    /// Only these lines changed since the last frame
+    Lines(Vec<Line>),
+}
+
+/// Terminal state which changes how the entire viewport is drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct View {
+    pub display_offset: usize,
+    pub mode: TermMode,
+    pub selection: Option<Selection>,
+    pub search: Option<(String, Option<Match>)>,
+    pub vi_cursor: Option<Point<usize>>,
+    pub url: Option<UrlMatch>,
+}
+
+#[derive(Debug, Default)]
+pub struct DamageTracker {
+    /// Visible rows of the last frame, from top to bottom
+    rows: Vec<Row<Cell>>,
+
+    /// Whether the rows of the last frame contain blinking text
+    blink: Vec<bool>,
+
+    view: Option<View>,
+
+    /// Visible position and style of the cursor in the last frame
+    cursor: Option<(Point, CursorStyle)>,
+
+    blink_visible: bool,
+
+    /// Redraw everything with the next frame
+    full: bool,
+}
+
+impl DamageTracker {
+    /// Redraw the entire viewport with the next frame
+    pub fn damage_all(&mut self) {
+        self.full = true;
+    }
+
+    /// Whether the last frame contained blinking text
+    pub fn blinking(&self) -> bool {
+        self.blink.iter().any(|&blink| blink)
+    }
+
+    /// Compare the viewport against the last frame and store it for the next one
+    pub fn update(
+        &mut self,
+        grid: &Grid<Cell>,
+        view: View,
+        cursor: Option<(Point, CursorStyle)>,
+        blink_visible: bool,
+    ) -> Damage {
+        let lines = grid.num_lines().0;
+        let top = grid.display_offset() + lines - 1;
+
+        let full = mem::replace(&mut self.full, false)
+            || self.rows.len() != lines
+            || self.view.as_ref() != Some(&view);
+
+        let damage = if full {
+            self.rows = (0..lines).map(|line| grid[top - line].clone()).collect();
+            self.blink = self.rows.iter().map(contains_blink).collect();
+
+            Damage::Full
+        } else {
+            let mut damaged = vec![false; lines];
+            for (line, damaged) in damaged.iter_mut().enumerate() {
+                let row = &grid[top - line];
+                if self.rows[line] != *row {
+                    self.rows[line] = row.clone();
+                    self.blink[line] = contains_blink(row);
+                    *damaged = true;
+                }
+            }
+
+            // Blinking text is hidden without any change to the grid
+            if blink_visible != self.blink_visible {
+                for (damaged, &blink) in damaged.iter_mut().zip(&self.blink) {
+                    *damaged |= blink;
+                }
+            }
+
+            // The cursor has to be removed from its old line
+            if cursor != self.cursor {
+                for &(point, _) in self.cursor.iter().chain(cursor.iter()) {
+                    damaged[point.line.0] = true;
+                }
+            }
+
+            Damage::Lines((0..lines).filter(|&line| damaged[line]).map(Line).collect())
+        };
+
+        self.view = Some(view);
+        self.cursor = cursor;
+        self.blink_visible = blink_visible;
+
+        damage
+    }
+}
+
+fn contains_blink(row: &Row<Cell>) -> bool {
+    row[..].iter().any(|cell| cell.flags.contains(Flags::BLINK))
+}
@@ -0,0 +1,119 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Storage for OSC 8 hyperlinks
+//!
+//! Cells only carry a `HyperlinkId`, the links themselves are interned here.
+use std::collections::HashSet;
+
+use ansi::Hyperlink;
+use term::interned::{Interned, InternedId};
+
+/// Handle for a hyperlink stored in `Hyperlinks`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct HyperlinkId(u32);
+
+impl InternedId for HyperlinkId {
+    fn from_index(index: u32) -> HyperlinkId {
+        HyperlinkId(index)
+    }
+}
+
+#[derive(Default)]
+pub struct Hyperlinks {
+    links: Interned<HyperlinkId, Hyperlink>,
+}
+
+impl Hyperlinks {
+    /// Get the id for a newly opened hyperlink
+    ///
+    /// Links with an explicit `id=` parameter are shared by all OSC 8
+    /// sequences with the same id and URI, so separately written cells are
+    /// treated as a single link.
+    pub fn intern(&mut self, link: Hyperlink) -> HyperlinkId {
+        let shared = link.id.is_some();
+        self.links.insert(link, shared)
+    }
+
+    #[inline]
+    pub fn get(&self, id: HyperlinkId) -> Option<&Hyperlink> {
+        self.links.get(id)
+    }
+
+    /// Check if enough links were added since the last collection
+    #[inline]
+    pub fn needs_collect(&self) -> bool {
+        self.links.needs_collect()
+    }
+
+    /// Drop all links which aren't in `used`
+    pub fn retain(&mut self, used: &HashSet<HyperlinkId>) {
+        self.links.retain(used);
+    }
+
+    /// Drop all links
+    pub fn clear(&mut self) {
+        self.links.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use ansi::Hyperlink;
+    use term::interned::MIN_COLLECT_LEN;
+    use super::Hyperlinks;
+
+    fn link(id: Option<&str>, uri: &str) -> Hyperlink {
+        Hyperlink { id: id.map(String::from), uri: uri.into() }
+    }
+
+    #[test]
+    fn links_with_id_are_shared() {
+        let mut links = Hyperlinks::default();
+
+        let a = links.intern(link(Some("a"), "https://a.de"));
+        assert_eq!(links.intern(link(Some("a"), "https://a.de")), a);
+        assert_ne!(links.intern(link(Some("a"), "https://b.de")), a);
+        assert_ne!(links.intern(link(Some("b"), "https://a.de")), a);
+
+        // Without id every link is distinct
+        let anonymous = links.intern(link(None, "https://a.de"));
+        assert_ne!(links.intern(link(None, "https://a.de")), anonymous);
+
+        assert_eq!(links.get(a), Some(&link(Some("a"), "https://a.de")));
+    }
+
+    #[test]
+    fn retain_drops_unused_links() {
+        let mut links = Hyperlinks::default();
+
+        let ids: Vec<_> = (0..MIN_COLLECT_LEN)
+            .map(|i| links.intern(link(Some("x"), &format!("https://{}.de", i))))
+            .collect();
+        assert!(links.needs_collect());
+
+        let used: HashSet<_> = ids[..2].iter().cloned().collect();
+        links.retain(&used);
+        assert!(!links.needs_collect());
+
+        assert!(links.get(ids[1]).is_some());
+        assert!(links.get(ids[2]).is_none());
+
+        // Dropped links aren't shared anymore
+        assert_ne!(links.intern(link(Some("x"), "https://2.de")), ids[2]);
+        assert_eq!(links.intern(link(Some("x"), "https://1.de")), ids[1]);
+    }
+}
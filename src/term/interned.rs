@@ -0,0 +1,148 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Interned storage for data referenced by cells
+//!
+//! Cells have to stay `Copy`, so they only carry a small id for data like
+//! hyperlinks and combining characters. The values are stored here and
+//! reference counted lazily: once the store has doubled in size, the terminal
+//! collects the ids still used by its cells and everything else is dropped.
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Number of values which are stored before unused ones are collected
+pub const MIN_COLLECT_LEN: usize = 256;
+
+/// Handle for a value in an `Interned` store
+pub trait InternedId: Copy + Eq + Hash {
+    fn from_index(index: u32) -> Self;
+}
+
+pub struct Interned<I, V> {
+    values: HashMap<I, V>,
+
+    /// Ids of the values which are shared by everyone storing them
+    shared: HashMap<V, I>,
+
+    next_id: u32,
+
+    /// Number of values which were in use after the last collection
+    live: usize,
+}
+
+impl<I: InternedId, V: Clone + Eq + Hash> Default for Interned<I, V> {
+    fn default() -> Interned<I, V> {
+        Interned {
+            values: HashMap::new(),
+            shared: HashMap::new(),
+            next_id: 0,
+            live: 0,
+        }
+    }
+}
+
+impl<I: InternedId, V: Clone + Eq + Hash> Interned<I, V> {
+    /// Store a value and get its id
+    ///
+    /// Shared values get the same id every time they're stored, all others
+    /// get a new id.
+    pub fn insert(&mut self, value: V, shared: bool) -> I {
+        if let Some(&id) = self.shared.get(&value) {
+            return id;
+        }
+
+        let id = I::from_index(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        if shared {
+            self.shared.insert(value.clone(), id);
+        }
+        self.values.insert(id, value);
+
+        id
+    }
+
+    #[inline]
+    pub fn get(&self, id: I) -> Option<&V> {
+        self.values.get(&id)
+    }
+
+    /// Check if enough values were added since the last collection
+    #[inline]
+    pub fn needs_collect(&self) -> bool {
+        self.values.len() >= MIN_COLLECT_LEN && self.values.len() >= 2 * self.live
+    }
+
+    /// Drop all values which aren't in `used`
+    pub fn retain(&mut self, used: &HashSet<I>) {
+        self.values.retain(|id, _| used.contains(id));
+        self.shared.retain(|_, id| used.contains(id));
+        self.live = self.values.len();
+    }
+
+    /// Drop all values
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.shared.clear();
+        self.live = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{Interned, InternedId, MIN_COLLECT_LEN};
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    struct Id(u32);
+
+    impl InternedId for Id {
+        fn from_index(index: u32) -> Id {
+            Id(index)
+        }
+    }
+
+    #[test]
+    fn only_shared_values_reuse_ids() {
+        let mut store: Interned<Id, &str> = Interned::default();
+
+        let shared = store.insert("a", true);
+        assert_eq!(store.insert("a", true), shared);
+        assert_eq!(store.insert("a", false), shared);
+
+        let unshared = store.insert("b", false);
+        assert_ne!(store.insert("b", false), unshared);
+        assert_eq!(store.get(unshared), Some(&"b"));
+    }
+
+    #[test]
+    fn retain_drops_unused_values() {
+        let mut store: Interned<Id, usize> = Interned::default();
+
+        let ids: Vec<_> = (0..MIN_COLLECT_LEN).map(|i| store.insert(i, true)).collect();
+        assert!(store.needs_collect());
+
+        let used: HashSet<_> = ids[..2].iter().cloned().collect();
+        store.retain(&used);
+        assert!(!store.needs_collect());
+
+        assert_eq!(store.get(ids[1]), Some(&1));
+        assert_eq!(store.get(ids[2]), None);
+
+        // Dropped values get a new id
+        assert_ne!(store.insert(2, true), ids[2]);
+        assert_eq!(store.insert(1, true), ids[1]);
+    }
+}
@@ -13,31 +13,89 @@
 // limitations under the License.
 //
 //! Exports the `Term` type which is a high-level API for the Grid
+use std::collections::HashSet;
+use std::hash::Hash;
 use std::ops::{Range, Index, IndexMut};
 use std::{ptr, io, mem};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::cmp::{min, max};
 use std::time::{Duration, Instant};
 
 use arraydeque::ArrayDeque;
 use unicode_width::UnicodeWidthChar;
 use url::Url;
+use base64;
 
 use font::{self, Size};
-use ansi::{self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle};
+use ansi::{
+    self, Color, NamedColor, Attr, Handler, CharsetIndex, StandardCharset, CursorStyle,
+    KeyboardModes, KeyboardModesApplyBehavior, ModifyOtherKeys,
+};
 use grid::{BidirectionalIterator, Grid, Indexed, IndexRegion, DisplayIter, Scroll, ViewportPosition};
-use index::{self, Point, Column, Line, IndexRange, Contains, RangeInclusive, Linear};
+use index::{self, Point, Column, Line, IndexRange, Contains, RangeInclusive, Linear, Side};
 use selection::{self, Selection, Locations};
-use config::{Config, VisualBellAnimation};
+use config::{Config, Osc52, VisualBellAnimation};
 use {MouseCursor, Rgb};
 use copypasta::{Clipboard, Load, Store};
 use input::FONT_SIZE_STEP;
+use regex::Regex;
 
 pub mod cell;
 pub mod color;
+pub mod context_menu;
+pub mod damage;
+pub mod hyperlink;
+pub mod interned;
+pub mod search;
+pub mod vi_mode;
+pub mod zerowidth;
 pub use self::cell::Cell;
 use self::cell::LineLength;
+use self::context_menu::ContextMenu;
+use self::damage::{Damage, DamageTracker, View};
+use self::hyperlink::{HyperlinkId, Hyperlinks};
+use self::zerowidth::{ZeroWidthChars, ZeroWidthId};
+use self::search::{Match, SearchState};
+use self::vi_mode::{ViMode, ViMotion};
 
-const URL_SEPARATOR_CHARS: [char; 3] = [' ', '"', '\''];
+const URL_SEPARATOR_CHARS: [char; 6] = [' ', '"', '\'', '`', '<', '>'];
+
+/// Schemes of URLs which are recognized in the terminal output
+const URL_SCHEMES: [&str; 5] = ["http", "https", "file", "mailto", "ftp"];
+
+/// Characters which aren't considered part of a URL when they are at its end
+const URL_TRAILING_PUNCTUATION: [char; 6] = ['.', ',', ':', ';', '!', '?'];
+
+/// Pairs of brackets which are stripped from a URL when they're unmatched
+const URL_BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// URL found in the grid
+///
+/// Both points are in buffer coordinates and inclusive.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UrlMatch {
+    pub text: String,
+    pub start: Point<usize>,
+    pub end: Point<usize>,
+
+    /// Set for OSC 8 hyperlinks, whose cells don't have to be contiguous
+    pub hyperlink: Option<HyperlinkId>,
+}
+
+/// Maximum number of entries in the kitty keyboard stack
+const KEYBOARD_MODE_STACK_MAX_DEPTH: usize = 16;
+
+/// Largest font size in points
+///
+/// Keeps glyphs smaller than a texture of the glyph cache's atlas.
+const MAX_FONT_SIZE: f32 = 300.0;
+
+/// Maximum number of saved window titles
+const TITLE_STACK_MAX_DEPTH: usize = 32;
+
+/// Time after which a synchronized update is drawn even without its end marker
+pub const SYNC_UPDATE_TIMEOUT: Duration = Duration::from_millis(200);
 
 /// A type that can expand a given point to a region
 ///
@@ -48,12 +106,22 @@ pub trait Search {
     fn semantic_search_left(&self, _: Point<usize>) -> Point<usize>;
     /// Find the nearest semantic boundary _to the point_ of provided point.
     fn semantic_search_right(&self, _: Point<usize>) -> Point<usize>;
+    /// Find the beginning of the line, following wrapped lines upwards.
+    fn line_search_left(&self, _: Point<usize>) -> Point<usize>;
+    /// Find the end of the line, following wrapped lines downwards.
+    fn line_search_right(&self, _: Point<usize>) -> Point<usize>;
+    /// Find the start of the line's text, skipping leading whitespace if configured.
+    fn line_text_start(&self, _: Point<usize>) -> Point<usize>;
     /// Find the nearest URL boundary in both directions.
     fn url_search(&self, _: Point<usize>) -> Option<String>;
 }
 
 impl Search for Term {
     fn semantic_search_left(&self, mut point: Point<usize>) -> Point<usize> {
+        if self.word_pattern.is_some() {
+            return self.word_at(point).map_or(point, |(start, _)| start);
+        }
+
         // Limit the starting point to the last line in the history
         point.line = min(point.line, self.grid.len() - 1);
 
@@ -76,6 +144,10 @@ impl Search for Term {
     }
 
     fn semantic_search_right(&self, mut point: Point<usize>) -> Point<usize> {
+        if self.word_pattern.is_some() {
+            return self.word_at(point).map_or(point, |(_, end)| end);
+        }
+
         // Limit the starting point to the last line in the history
         point.line = min(point.line, self.grid.len() - 1);
 
@@ -97,50 +169,92 @@ impl Search for Term {
         point
     }
 
-    fn url_search(&self, mut point: Point<usize>) -> Option<String> {
-        point.line = self.grid.num_lines().0 - point.line - 1;
+    fn line_search_left(&self, mut point: Point<usize>) -> Point<usize> {
+        // Limit the starting point to the last line in the history
+        point.line = min(point.line, self.grid.len() - 1);
+
+        let last_col = self.grid.num_cols() - Column(1);
+        while point.line + 1 < self.grid.len()
+            && self.grid[point.line + 1][last_col].flags.contains(cell::Flags::WRAPLINE)
+        {
+            point.line += 1;
+        }
+
+        point.col = Column(0);
+        point
+    }
 
+    fn line_search_right(&self, mut point: Point<usize>) -> Point<usize> {
         // Limit the starting point to the last line in the history
         point.line = min(point.line, self.grid.len() - 1);
 
-        // Create forwards and backwards iterators
-        let iterf = self.grid.iter_from(point);
-        point.col += 1;
-        let mut iterb = self.grid.iter_from(point);
+        let last_col = self.grid.num_cols() - Column(1);
+        while point.line > 0 && self.grid[point.line][last_col].flags.contains(cell::Flags::WRAPLINE) {
+            point.line -= 1;
+        }
 
-        // Put all characters until separators into a string
-        let mut buf = String::new();
-        while let Some(cell) = iterb.prev() {
-            if URL_SEPARATOR_CHARS.contains(&cell.c) {
-                break;
-            }
-            buf.insert(0, cell.c);
+        point.col = last_col;
+        point
+    }
+
+    fn line_text_start(&self, point: Point<usize>) -> Point<usize> {
+        if !self.skip_leading_whitespace {
+            return point;
         }
-        for cell in iterf {
-            if URL_SEPARATOR_CHARS.contains(&cell.c) {
-                break;
+
+        let point = Point::new(min(point.line, self.grid.len() - 1), point.col);
+        let bottom = self.line_search_right(point).line;
+
+        for line in (bottom..=point.line).rev() {
+            let start = if line == point.line { point.col.0 } else { 0 };
+            for col in start..self.grid.num_cols().0 {
+                let cell = &self.grid[line][Column(col)];
+                if cell.c != ' ' && cell.c != '\t' {
+                    return Point::new(line, Column(col));
+                }
             }
-            buf.push(cell.c);
         }
 
-        // Heuristic to remove all leading '('
-        while buf.starts_with('(') {
-            buf.remove(0);
-        }
+        // Lines without any text are selected as a whole
+        point
+    }
 
-        // Heuristic to remove all ')' from end of URLs without matching '('
-        let str_count = |text: &str, c: char| {
-            text.chars().filter(|tc| *tc == c).count()
-        };
-        while buf.ends_with(')') && str_count(&buf, '(') < str_count(&buf, ')') {
-            buf.pop();
-        }
+    fn url_search(&self, point: Point<usize>) -> Option<String> {
+        self.url_at(point).map(|url| url.text)
+    }
+}
 
-        // Check if string is valid url
-        match Url::parse(&buf) {
-            Ok(_) => Some(buf),
-            Err(_) => None,
-        }
+/// Crate version as reported by DA2, `0.2.1` is `201`
+fn version_number() -> usize {
+    let part = |version: &str| version.parse::<usize>().unwrap_or(0);
+    part(env!("CARGO_PKG_VERSION_MAJOR")) * 10_000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"))
+}
+
+/// Count the occurrences of a character in a line of text
+fn count_chars(text: &[(char, Point<usize>)], c: char) -> usize {
+    text.iter().filter(|&&(tc, _)| tc == c).count()
+}
+
+/// Whether a URL is safe to pass to the launcher
+fn is_launchable_url(url: &str) -> bool {
+    match Url::parse(url) {
+        Ok(parsed) => URL_SCHEMES.contains(&parsed.scheme()),
+        Err(_) => false,
+    }
+}
+
+/// Reflow a grid to a new width, along with the cursor which writes to it
+///
+/// A pending wrap only makes sense in the last column. Anywhere else the
+/// cursor moves behind the character it's on instead.
+fn reflow_grid(grid: &mut Grid<Cell>, cols: Column, cursor: &mut Point, input_needs_wrap: &mut bool) {
+    grid.reflow(cols, cursor, &Cell::default());
+
+    if *input_needs_wrap && cursor.col + 1 < cols {
+        cursor.col += 1;
+        *input_needs_wrap = false;
     }
 }
 
@@ -170,7 +284,18 @@ pub struct RenderableCellsIter<'a> {
     config: &'a Config,
     colors: &'a color::List,
     selection: Option<RangeInclusive<index::Linear>>,
+    block_selection: Option<(Range<Line>, Range<Column>)>,
+    search_matches: Vec<(RangeInclusive<index::Linear>, bool)>,
+    vi_mode_cursor: Option<index::Linear>,
+    url: Option<RangeInclusive<index::Linear>>,
+    hyperlink: Option<HyperlinkId>,
+    zerowidth: &'a ZeroWidthChars,
     cursor_cells: ArrayDeque<[Indexed<Cell>; 3]>,
+    /// Number of cursor cells drawn on top of the original cell
+    cursor_overlays: usize,
+    blink_visible: bool,
+    /// Lines which are drawn, all of them if this is `None`
+    damaged: Option<Vec<bool>>,
 }
 
 impl<'a> RenderableCellsIter<'a> {
@@ -186,12 +311,21 @@ impl<'a> RenderableCellsIter<'a> {
         config: &'b Config,
         selection: Option<Locations>,
         cursor_style: CursorStyle,
+        blink_visible: bool,
+        search: Option<&'b SearchState>,
+        vi_mode: Option<&'b ViMode>,
+        url: Option<&'b UrlMatch>,
+        zerowidth: &'b ZeroWidthChars,
     ) -> RenderableCellsIter<'b> {
         let cursor_offset = grid.line_to_offset(cursor.line);
         let inner = grid.display_iter();
 
+        let block_selection = selection.as_ref()
+            .filter(|loc| loc.block)
+            .and_then(|loc| Self::visible_block(grid, loc));
+
         let mut selection_range = None;
-        if let Some(loc) = selection {
+        if let Some(loc) = selection.filter(|loc| !loc.block) {
             // Get on-screen lines of the selection's locations
             let start_line = grid.buffer_line_to_visible(loc.start.line);
             let end_line = grid.buffer_line_to_visible(loc.end.line);
@@ -238,6 +372,25 @@ impl<'a> RenderableCellsIter<'a> {
             }
         }
 
+        let search_matches = search
+            .map(|search| Self::visible_matches(grid, search))
+            .unwrap_or_else(Vec::new);
+
+        let vi_mode_cursor = vi_mode.and_then(|vi_mode| {
+            match grid.buffer_line_to_visible(vi_mode.cursor.line) {
+                ViewportPosition::Visible(line) => {
+                    Some(Linear(line.0 * grid.num_cols().0 + vi_mode.cursor.col.0))
+                },
+                _ => None,
+            }
+        });
+
+        // Hyperlinks are highlighted by id instead of their range
+        let hyperlink = url.and_then(|url| url.hyperlink);
+        let url = url
+            .filter(|url| url.hyperlink.is_none())
+            .and_then(|url| Self::visible_range(grid, url.start, url.end));
+
         RenderableCellsIter {
             cursor,
             cursor_offset,
@@ -245,12 +398,99 @@ impl<'a> RenderableCellsIter<'a> {
             inner,
             mode,
             selection: selection_range,
+            block_selection,
+            search_matches,
+            vi_mode_cursor,
+            url,
+            hyperlink,
+            zerowidth,
             config,
             colors,
             cursor_cells: ArrayDeque::new(),
+            cursor_overlays: 0,
+            blink_visible,
+            damaged: None,
         }.initialize(cursor_style)
     }
 
+    /// Skip all lines which didn't change since the last frame
+    fn only_damaged(mut self, damage: &Damage) -> Self {
+        if let Damage::Lines(ref lines) = *damage {
+            let mut damaged = vec![false; self.grid.num_lines().0];
+            for line in lines {
+                damaged[line.0] = true;
+            }
+            self.damaged = Some(damaged);
+        }
+        self
+    }
+
+    /// Lines and columns of a block selection which are inside the viewport
+    ///
+    /// A block isn't a contiguous run of cells, so it can't be highlighted
+    /// using linear indices like the other selections.
+    fn visible_block(grid: &Grid<Cell>, loc: &Locations) -> Option<(Range<Line>, Range<Column>)> {
+        // The end of the locations is the top left corner of the block
+        let top = match grid.buffer_line_to_visible(loc.end.line) {
+            ViewportPosition::Visible(line) => line,
+            ViewportPosition::Above => Line(0),
+            ViewportPosition::Below => return None,
+        };
+        let bottom = match grid.buffer_line_to_visible(loc.start.line) {
+            ViewportPosition::Visible(line) => line,
+            ViewportPosition::Below => grid.num_lines() - 1,
+            ViewportPosition::Above => return None,
+        };
+
+        Some((top..bottom + 1, loc.end.col..loc.start.col + 1))
+    }
+
+    /// Search matches which are at least partially inside the viewport
+    ///
+    /// Matches are converted to the same linear indices used for the selection
+    /// and marked with whether they're the focused match.
+    fn visible_matches(
+        grid: &Grid<Cell>,
+        search: &SearchState,
+    ) -> Vec<(RangeInclusive<index::Linear>, bool)> {
+        let regex = match search.regex() {
+            Some(regex) => regex,
+            None => return Vec::new(),
+        };
+
+        let bottom = grid.display_offset();
+        let top = bottom + grid.num_lines().0 - 1;
+
+        search::find_matches(grid, regex, top, bottom)
+            .into_iter()
+            .filter_map(|m| {
+                let focused = search.focused == Some(m);
+                Self::visible_range(grid, m.start, m.end).map(|range| (range, focused))
+            })
+            .collect()
+    }
+
+    /// Part of the buffer range from `start` to `end` which is inside the viewport
+    fn visible_range(
+        grid: &Grid<Cell>,
+        start: Point<usize>,
+        end: Point<usize>,
+    ) -> Option<RangeInclusive<index::Linear>> {
+        let cols = grid.num_cols().0;
+        let start = match grid.buffer_line_to_visible(start.line) {
+            ViewportPosition::Visible(line) => line.0 * cols + start.col.0,
+            ViewportPosition::Above => 0,
+            ViewportPosition::Below => return None,
+        };
+        let end = match grid.buffer_line_to_visible(end.line) {
+            ViewportPosition::Visible(line) => line.0 * cols + end.col.0,
+            ViewportPosition::Below => grid.num_lines().0 * cols - 1,
+            ViewportPosition::Above => return None,
+        };
+
+        Some(RangeInclusive::new(Linear(start), Linear(end)))
+    }
+
     fn push_cursor_cells(&mut self, original: Cell, cursor: Cell, wide: Cell) {
         // Prints the char under the cell if cursor is situated on a non-empty cell
         self.cursor_cells.push_back(Indexed {
@@ -298,14 +538,22 @@ impl<'a> RenderableCellsIter<'a> {
         let original_cell = self.grid[self.cursor];
 
         let mut cursor_cell = self.grid[self.cursor];
-        let cursor_color = self.config.cursor_cursor_color().unwrap_or(cursor_cell.fg);
+
+        // The glyph uses the color of the text, which is the background for inverse cells
+        let text_color = if cursor_cell.inverse() { cursor_cell.bg } else { cursor_cell.fg };
+        let cursor_color = self.config.cursor_cursor_color().unwrap_or(text_color);
+        cursor_cell.flags.remove(cell::Flags::INVERSE);
         cursor_cell.c = cursor_cell_char;
+        cursor_cell.zerowidth = None;
         cursor_cell.fg = cursor_color;
 
         let mut wide_cell = cursor_cell;
         wide_cell.c = wide_cell_char;
 
         self.push_cursor_cells(original_cell, cursor_cell, wide_cell);
+
+        // Everything except the original cell is only the cursor's glyph
+        self.cursor_overlays = self.cursor_cells.len() - 1;
     }
 
     fn populate_underline_cursor(&mut self) {
@@ -365,6 +613,8 @@ impl<'a> RenderableCellsIter<'a> {
     fn compute_fg_rgb(&self, fg: Color, cell: &Cell) -> Rgb {
         use self::cell::Flags;
         match fg {
+            // Colors without a dim palette entry are darkened directly
+            Color::Spec(rgb) if cell.flags & Flags::DIM_BOLD == Flags::DIM => rgb * color::DIM_FACTOR,
             Color::Spec(rgb) => rgb,
             Color::Named(ansi) => {
                 match (self.config.draw_bold_text_with_bright_colors(), cell.flags & Flags::DIM_BOLD) {
@@ -390,9 +640,12 @@ impl<'a> RenderableCellsIter<'a> {
                     cell.flags & Flags::DIM_BOLD,
                     idx
                 ) {
-                    (true,  self::cell::Flags::BOLD, 0...7)  => idx as usize + 8,
-                    (false, self::cell::Flags::DIM,  8...15) => idx as usize - 8,
-                    (false, self::cell::Flags::DIM,  0...7)  => idx as usize + 260,
+                    (true, self::cell::Flags::BOLD, 0...7)  => idx as usize + 8,
+                    (_,    self::cell::Flags::DIM,  8...15) => idx as usize - 8,
+                    (_,    self::cell::Flags::DIM,  0...7)  => idx as usize + 260,
+                    (_, self::cell::Flags::DIM, _) if idx > 15 => {
+                        return self.colors[idx as usize] * color::DIM_FACTOR;
+                    },
                     _ => idx as usize,
                 };
 
@@ -401,11 +654,19 @@ impl<'a> RenderableCellsIter<'a> {
         }
     }
 
+    /// Background cells matching the clear color don't need to be drawn
     #[inline]
     fn compute_bg_alpha(&self, bg: Color) -> f32 {
-        match bg {
-            Color::Named(NamedColor::Background) => 0.0,
-            _ => 1.0
+        let clear_color = if self.mode.contains(mode::TermMode::REVERSE_VIDEO) {
+            NamedColor::Foreground
+        } else {
+            NamedColor::Background
+        };
+
+        if bg == Color::Named(clear_color) {
+            0.0
+        } else {
+            1.0
         }
     }
 
@@ -418,15 +679,16 @@ impl<'a> RenderableCellsIter<'a> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RenderableCell {
     /// A _Display_ line (not necessarily an _Active_ line)
     pub line: Line,
     pub column: Column,
-    pub c: char,
+    pub chars: [char; cell::MAX_ZEROWIDTH_CHARS + 1],
     pub fg: Rgb,
     pub bg: Rgb,
     pub bg_alpha: f32,
+    pub underline_color: Option<Rgb>,
     pub flags: cell::Flags,
 }
 
@@ -440,13 +702,23 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         loop {
+            let line = self.inner.line();
+            if self.damaged.as_ref().map_or(false, |damaged| !damaged[line.0]) {
+                if !self.inner.skip_line() {
+                    return None;
+                }
+                continue;
+            }
+
             // Handle cursor
-            let (cell, selected) = if self.cursor_offset == self.inner.offset() &&
-                self.inner.column() == self.cursor.col
-            {
+            let is_cursor = self.cursor_offset == self.inner.offset() &&
+                self.inner.column() == self.cursor.col;
+            let mut cursor_overlay = false;
+            let (cell, selected, matched, vi_mode_cursor) = if is_cursor {
                 // Cursor cell
                 let mut cell = self.cursor_cells.pop_front().unwrap();
                 cell.line = self.inner.line();
+                cursor_overlay = self.cursor_cells.len() < self.cursor_overlays;
 
                 // Since there may be multiple cursor cells (for a wide
                 // char), only update iteration position after all cursor
@@ -454,43 +726,111 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
                 if self.cursor_cells.is_empty() {
                     self.inner.next();
                 }
-                (cell, false)
+
+                // The terminal cursor is hidden when the vi cursor is on top of it
+                let index = Linear(cell.line.0 * self.grid.num_cols().0 + cell.column.0);
+                (cell, false, None, self.vi_mode_cursor == Some(index))
             } else {
                 let cell = self.inner.next()?;
 
                 let index = Linear(cell.line.0 * self.grid.num_cols().0 + cell.column.0);
 
-                let selected = self.selection.as_ref()
+                // Wide chars in a block are selected when their leading cell is inside it
+                let block_selected = self.block_selection.as_ref()
+                    .map_or(false, |&(ref lines, ref cols)| {
+                        let col = if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                            cell.column - 1
+                        } else {
+                            cell.column
+                        };
+                        lines.contains_(cell.line) && cols.contains_(col)
+                    });
+
+                let selected = block_selected || self.selection.as_ref()
                     .map(|range| range.contains_(index))
                     .unwrap_or(false);
 
+                // Whether the cell is part of the focused or any other search match
+                let matched = self.search_matches.iter()
+                    .find(|&&(ref range, _)| range.contains_(index))
+                    .map(|&(_, focused)| focused);
+
                 // Skip empty cells
-                if cell.is_empty() && !selected {
+                let vi_mode_cursor = self.vi_mode_cursor == Some(index);
+                if cell.is_empty() && !selected && matched.is_none() && !vi_mode_cursor {
                     continue;
                 }
 
-                (cell, selected)
+                (cell, selected, matched, vi_mode_cursor)
             };
 
             // Apply inversion and lookup RGB values
             let mut fg_rgb = self.compute_fg_rgb(cell.fg, &cell);
             let mut bg_rgb = self.compute_bg_rgb(cell.bg);
 
-            let bg_alpha = if selected ^ cell.inverse() {
+            let reverse_video = self.mode.contains(mode::TermMode::REVERSE_VIDEO);
+            let mut bg_alpha = if selected ^ cell.inverse() ^ reverse_video {
                 mem::swap(&mut fg_rgb, &mut bg_rgb);
                 self.compute_bg_alpha(cell.fg)
             } else {
                 self.compute_bg_alpha(cell.bg)
             };
 
+            // Search matches are highlighted unless they're selected
+            if let (Some(focused), false) = (matched, selected) {
+                let search_colors = &self.config.colors().search;
+                let colors = if focused {
+                    &search_colors.focused_match
+                } else {
+                    &search_colors.matches
+                };
+                fg_rgb = colors.foreground;
+                bg_rgb = colors.background;
+                bg_alpha = 1.0;
+            }
+
+            if vi_mode_cursor {
+                let colors = self.config.colors().vi_mode_cursor;
+                fg_rgb = colors.text;
+                bg_rgb = colors.cursor;
+                bg_alpha = 1.0;
+            }
+
+            // The background of cursor glyphs would hide the text below them
+            if cursor_overlay {
+                bg_alpha = 0.0;
+            }
+
+            // Blinking text is hidden during the off phase
+            let mut flags = cell.flags;
+            if !self.blink_visible && flags.contains(cell::Flags::BLINK) {
+                flags.insert(cell::Flags::HIDDEN);
+            }
+
+            // The URL under the mouse cursor is underlined
+            let index = Linear(cell.line.0 * self.grid.num_cols().0 + cell.column.0);
+            let hovered_link = cell.hyperlink.is_some() && cell.hyperlink == self.hyperlink;
+            if hovered_link || self.url.as_ref().map_or(false, |url| url.contains_(index)) {
+                flags.insert(cell::Flags::UNDERLINE);
+            }
+
+            let mut chars = [' '; cell::MAX_ZEROWIDTH_CHARS + 1];
+            chars[0] = cell.c;
+            if let Some(id) = cell.zerowidth {
+                for (slot, c) in chars[1..].iter_mut().zip(self.zerowidth.get(id)) {
+                    *slot = *c;
+                }
+            }
+
             return Some(RenderableCell {
                 line: cell.line,
                 column: cell.column,
-                flags: cell.flags,
-                c: cell.c,
+                flags,
+                chars,
                 fg: fg_rgb,
                 bg: bg_rgb,
                 bg_alpha,
+                underline_color: cell.underline_color.map(|color| self.compute_fg_rgb(color, &cell)),
             })
         }
     }
@@ -498,24 +838,42 @@ impl<'a> Iterator for RenderableCellsIter<'a> {
 }
 
 pub mod mode {
+    use ansi::KeyboardModes;
+
     bitflags! {
-        pub struct TermMode: u16 {
-            const SHOW_CURSOR         = 0b00_0000_0000_0001;
-            const APP_CURSOR          = 0b00_0000_0000_0010;
-            const APP_KEYPAD          = 0b00_0000_0000_0100;
-            const MOUSE_REPORT_CLICK  = 0b00_0000_0000_1000;
-            const BRACKETED_PASTE     = 0b00_0000_0001_0000;
-            const SGR_MOUSE           = 0b00_0000_0010_0000;
-            const MOUSE_MOTION        = 0b00_0000_0100_0000;
-            const LINE_WRAP           = 0b00_0000_1000_0000;
-            const LINE_FEED_NEW_LINE  = 0b00_0001_0000_0000;
-            const ORIGIN              = 0b00_0010_0000_0000;
-            const INSERT              = 0b00_0100_0000_0000;
-            const FOCUS_IN_OUT        = 0b00_1000_0000_0000;
-            const ALT_SCREEN          = 0b01_0000_0000_0000;
-            const MOUSE_DRAG          = 0b10_0000_0000_0000;
-            const ANY                 = 0b11_1111_1111_1111;
-            const NONE                = 0;
+        pub struct TermMode: u32 {
+            const SHOW_CURSOR               = 0b0000_0000_0000_0000_0000_0000_0001;
+            const APP_CURSOR                = 0b0000_0000_0000_0000_0000_0000_0010;
+            const APP_KEYPAD                = 0b0000_0000_0000_0000_0000_0000_0100;
+            const MOUSE_REPORT_CLICK        = 0b0000_0000_0000_0000_0000_0000_1000;
+            const BRACKETED_PASTE           = 0b0000_0000_0000_0000_0000_0001_0000;
+            const SGR_MOUSE                 = 0b0000_0000_0000_0000_0000_0010_0000;
+            const MOUSE_MOTION              = 0b0000_0000_0000_0000_0000_0100_0000;
+            const LINE_WRAP                 = 0b0000_0000_0000_0000_0000_1000_0000;
+            const LINE_FEED_NEW_LINE        = 0b0000_0000_0000_0000_0001_0000_0000;
+            const ORIGIN                    = 0b0000_0000_0000_0000_0010_0000_0000;
+            const INSERT                    = 0b0000_0000_0000_0000_0100_0000_0000;
+            const FOCUS_IN_OUT              = 0b0000_0000_0000_0000_1000_0000_0000;
+            const ALT_SCREEN                = 0b0000_0000_0000_0001_0000_0000_0000;
+            const MOUSE_DRAG                = 0b0000_0000_0000_0010_0000_0000_0000;
+            const DISAMBIGUATE_ESC_CODES    = 0b0000_0000_0000_0100_0000_0000_0000;
+            const REPORT_EVENT_TYPES        = 0b0000_0000_0000_1000_0000_0000_0000;
+            const REPORT_ALTERNATE_KEYS     = 0b0000_0000_0001_0000_0000_0000_0000;
+            const REPORT_ALL_KEYS_AS_ESC    = 0b0000_0000_0010_0000_0000_0000_0000;
+            const REPORT_ASSOCIATED_TEXT    = 0b0000_0000_0100_0000_0000_0000_0000;
+            const MODIFY_OTHER_KEYS         = 0b0000_0000_1000_0000_0000_0000_0000;
+            const MODIFY_OTHER_KEYS_ALL     = 0b0000_0001_0000_0000_0000_0000_0000;
+            const REVERSE_VIDEO             = 0b0000_0010_0000_0000_0000_0000_0000;
+            const REVERSE_WRAP              = 0b0000_0100_0000_0000_0000_0000_0000;
+            const UTF8_MOUSE                = 0b0000_1000_0000_0000_0000_0000_0000;
+            const URXVT_MOUSE               = 0b0001_0000_0000_0000_0000_0000_0000;
+            const KITTY_KEYBOARD_PROTOCOL   = Self::DISAMBIGUATE_ESC_CODES.bits
+                                            | Self::REPORT_EVENT_TYPES.bits
+                                            | Self::REPORT_ALTERNATE_KEYS.bits
+                                            | Self::REPORT_ALL_KEYS_AS_ESC.bits
+                                            | Self::REPORT_ASSOCIATED_TEXT.bits;
+            const ANY                       = 0b0001_1111_1111_1111_1111_1111_1111;
+            const NONE                      = 0;
         }
     }
 
@@ -524,6 +882,18 @@ pub mod mode {
             TermMode::SHOW_CURSOR | TermMode::LINE_WRAP
         }
     }
+
+    impl From<KeyboardModes> for TermMode {
+        fn from(modes: KeyboardModes) -> TermMode {
+            let mut mode = TermMode::empty();
+            mode.set(TermMode::DISAMBIGUATE_ESC_CODES, modes.contains(KeyboardModes::DISAMBIGUATE_ESC_CODES));
+            mode.set(TermMode::REPORT_EVENT_TYPES, modes.contains(KeyboardModes::REPORT_EVENT_TYPES));
+            mode.set(TermMode::REPORT_ALTERNATE_KEYS, modes.contains(KeyboardModes::REPORT_ALTERNATE_KEYS));
+            mode.set(TermMode::REPORT_ALL_KEYS_AS_ESC, modes.contains(KeyboardModes::REPORT_ALL_KEYS_AS_ESC));
+            mode.set(TermMode::REPORT_ASSOCIATED_TEXT, modes.contains(KeyboardModes::REPORT_ASSOCIATED_TEXT));
+            mode
+        }
+    }
 }
 
 pub use self::mode::TermMode;
@@ -545,14 +915,14 @@ impl CharsetMapping for StandardCharset {
                 match c {
                     '`' => '◆',
                     'a' => '▒',
-                    'b' => '\t',
-                    'c' => '\u{000c}',
-                    'd' => '\r',
-                    'e' => '\n',
+                    'b' => '\u{2409}',
+                    'c' => '\u{240c}',
+                    'd' => '\u{240d}',
+                    'e' => '\u{240a}',
                     'f' => '°',
                     'g' => '±',
                     'h' => '\u{2424}',
-                    'i' => '\u{000b}',
+                    'i' => '\u{240b}',
                     'j' => '┘',
                     'k' => '┐',
                     'l' => '┌',
@@ -608,6 +978,25 @@ pub struct Cursor {
     charsets: Charsets,
 }
 
+/// Cursor state saved by DECSC
+///
+/// Restoring a cursor which was never saved moves it to the top left corner
+/// with the default attributes.
+#[derive(Default, Copy, Clone)]
+pub struct SavedCursor {
+    /// Position, attributes and character sets
+    cursor: Cursor,
+
+    /// The character set ASCII was mapped to
+    active_charset: CharsetIndex,
+
+    /// Whether origin mode was active
+    origin: bool,
+
+    /// Whether the next character would have wrapped to the next line
+    input_needs_wrap: bool,
+}
+
 pub struct VisualBell {
     /// Visual bell animation
     animation: VisualBellAnimation,
@@ -775,19 +1164,38 @@ pub struct Term {
     /// Size
     size_info: SizeInfo,
 
+    /// Size of the screen the window is on in pixels, if known
+    screen_size: Option<(u32, u32)>,
+
     pub dirty: bool,
 
     pub visual_bell: VisualBell,
     pub next_is_urgent: Option<bool>,
 
+    /// Request attention when the bell rings
+    bell_urgent: bool,
+
+    /// Set when the bell rang, until it's reported
+    pub bell_rung: bool,
+
     /// Saved cursor from main grid
-    cursor_save: Cursor,
+    cursor_save: SavedCursor,
 
     /// Saved cursor from alt grid
-    cursor_save_alt: Cursor,
+    cursor_save_alt: SavedCursor,
+
+    /// Whether `cursor_save` holds the primary screen's cursor, because the
+    /// alternate screen was entered with mode 1049
+    alt_saved_cursor: bool,
 
     semantic_escape_chars: String,
 
+    /// Words for semantic selection, replacing the `semantic_escape_chars`
+    word_pattern: Option<Regex>,
+
+    /// Start line selections at the first non-whitespace character
+    skip_leading_whitespace: bool,
+
     /// Colors used for rendering
     colors: color::List,
 
@@ -800,9 +1208,18 @@ pub struct Term {
     /// Current style of the cursor
     cursor_style: Option<CursorStyle>,
 
+    /// Cursor style of the primary screen while the alternate screen is active
+    cursor_style_save: Option<CursorStyle>,
+
     /// Default style for resetting the cursor
     default_cursor_style: CursorStyle,
 
+    /// Whether the cursor blinks, set together with its style
+    cursor_blinking: bool,
+
+    /// Cursor blinking of the primary screen while the alternate screen is active
+    cursor_blinking_save: bool,
+
     dynamic_title: bool,
 
     /// Number of spaces in one tab
@@ -810,6 +1227,58 @@ pub struct Term {
 
     /// Automatically scroll to bottom when new lines are added
     auto_scroll: bool,
+
+    /// Allow applications to enable the kitty keyboard protocol
+    kitty_keyboard: bool,
+
+    /// Kitty keyboard enhancement flags of the active screen
+    keyboard_mode_stack: Vec<KeyboardModes>,
+
+    /// Kitty keyboard enhancement flags of the inactive screen
+    inactive_keyboard_mode_stack: Vec<KeyboardModes>,
+
+    /// Current window title set by the application
+    title: Option<String>,
+
+    /// Window titles saved with `CSI 22 t`
+    title_stack: Vec<Option<String>>,
+
+    /// Clipboard access through OSC 52
+    osc52: Osc52,
+
+    /// Maximum size of OSC 52 clipboard writes
+    osc52_max_bytes: usize,
+
+    /// Start of the pending synchronized update
+    sync_update_start: Option<Instant>,
+
+    /// Active scrollback search
+    search: Option<SearchState>,
+
+    /// Keyboard selection state, while the vi mode is active
+    vi_mode: Option<ViMode>,
+
+    /// URL which is underlined because the mouse is hovering over it
+    hovered_url: Option<UrlMatch>,
+
+    /// Targets of OSC 8 hyperlinks referenced by cells
+    hyperlinks: Hyperlinks,
+
+    /// Hyperlink applied to newly written cells
+    hyperlink: Option<HyperlinkId>,
+
+    /// Combining characters referenced by cells
+    zerowidth: ZeroWidthChars,
+
+    /// Lines which changed since the last frame
+    damage: DamageTracker,
+
+    /// Number of blink phases since blinking text appeared, shared with the
+    /// blink timer so the render thread can sample it without locking
+    blink_epoch: Arc<AtomicUsize>,
+
+    /// Menu opened with the right mouse button
+    context_menu: Option<ContextMenu>,
 }
 
 /// Terminal size info
@@ -883,1142 +1352,2580 @@ impl Term {
         self.dirty = true;
     }
 
-    #[inline]
-    pub fn get_next_mouse_cursor(&mut self) -> Option<MouseCursor> {
-        self.next_mouse_cursor.take()
-    }
-
-    pub fn new(config: &Config, size: SizeInfo) -> Term {
-        let num_cols = size.cols();
-        let num_lines = size.lines();
-
-        let history_size = config.scrolling().history as usize;
-        let grid = Grid::new(num_lines, num_cols, history_size, Cell::default());
-        let alt = Grid::new(num_lines, num_cols, 0 /* scroll history */, Cell::default());
-
-        let tabspaces = config.tabspaces();
-        let tabs = IndexRange::from(Column(0)..grid.num_cols())
-            .map(|i| (*i as usize) % tabspaces == 0)
-            .collect::<Vec<bool>>();
-
-        let scroll_region = Line(0)..grid.num_lines();
+    /// Jump back to the bottom after output was received from the child
+    ///
+    /// Nothing happens unless `scrolling.auto_scroll` is enabled, or while the
+    /// user is browsing the history in vi mode or with a search.
+    pub fn scroll_to_output(&mut self) {
+        if self.vi_mode.is_some() || self.search.is_some() {
+            return;
+        }
 
-        Term {
-            next_title: None,
-            next_mouse_cursor: None,
-            dirty: false,
-            visual_bell: VisualBell::new(config),
-            next_is_urgent: None,
-            input_needs_wrap: false,
-            grid,
-            alt_grid: alt,
-            alt: false,
-            font_size: config.font().size(),
-            original_font_size: config.font().size(),
-            active_charset: Default::default(),
-            cursor: Default::default(),
-            cursor_save: Default::default(),
-            cursor_save_alt: Default::default(),
-            tabs,
-            mode: Default::default(),
-            scroll_region,
-            size_info: size,
-            colors: color::List::from(config.colors()),
-            color_modified: [false; color::COUNT],
-            original_colors: color::List::from(config.colors()),
-            semantic_escape_chars: config.selection().semantic_escape_chars.clone(),
-            cursor_style: None,
-            default_cursor_style: config.cursor_style(),
-            dynamic_title: config.dynamic_title(),
-            tabspaces,
-            auto_scroll: config.scrolling().auto_scroll,
+        if self.auto_scroll && self.grid.display_offset() != 0 {
+            self.scroll_display(Scroll::Bottom);
         }
     }
 
-    pub fn change_font_size(&mut self, delta: f32) {
-        // Saturating addition with minimum font size FONT_SIZE_STEP
-        let new_size = self.font_size + Size::new(delta);
-        self.font_size = max(new_size, Size::new(FONT_SIZE_STEP));
-        self.dirty = true;
-    }
+    /// Find the URL at a point of the viewport
+    ///
+    /// Rows which are wrapped into each other are joined, so URLs which don't
+    /// fit into a single row are found too.
+    pub fn url_at(&self, point: Point<usize>) -> Option<UrlMatch> {
+        let point = self.grid.visible_to_buffer(Point::new(Line(point.line), point.col));
+        let history_len = self.grid.num_lines().0 + self.grid.scroll_limit();
+        if point.line >= history_len {
+            return None;
+        }
 
-    pub fn reset_font_size(&mut self) {
-        self.font_size = self.original_font_size;
-        self.dirty = true;
-    }
+        // Explicit hyperlinks take precedence over URLs in the text, but any
+        // program can set them, so they're restricted to the same schemes
+        let hyperlink = self.grid[point.line][point.col].hyperlink;
+        let link = hyperlink.and_then(|id| self.hyperlinks.get(id));
+        if let Some(link) = link.filter(|link| is_launchable_url(&link.uri)) {
+            return Some(UrlMatch {
+                text: link.uri.clone(),
+                start: point,
+                end: point,
+                hyperlink,
+            });
+        }
 
-    pub fn update_config(&mut self, config: &Config) {
-        self.semantic_escape_chars = config.selection().semantic_escape_chars.clone();
-        self.original_colors.fill_named(config.colors());
-        self.original_colors.fill_cube(config.colors());
-        self.original_colors.fill_gray_ramp(config.colors());
-        for i in 0..color::COUNT {
-            if !self.color_modified[i] {
-                self.colors[i] = self.original_colors[i];
-            }
+        let last_col = self.grid.num_cols() - Column(1);
+        let wraps = |line: usize| self.grid[line][last_col].flags.contains(cell::Flags::WRAPLINE);
+
+        let mut top = point.line;
+        while top + 1 < history_len && wraps(top + 1) {
+            top += 1;
+        }
+        let mut bottom = point.line;
+        while bottom > 0 && wraps(bottom) {
+            bottom -= 1;
         }
-        self.visual_bell.update_config(config);
-        self.default_cursor_style = config.cursor_style();
-        self.dynamic_title = config.dynamic_title();
-        self.auto_scroll = config.scrolling().auto_scroll;
-        self.grid
-            .update_history(config.scrolling().history as usize, &self.cursor.template);
-    }
 
-    #[inline]
-    pub fn needs_draw(&self) -> bool {
-        self.dirty
-    }
+        // Collect the text of the logical line and the position of the pointer in it
+        let mut text: Vec<(char, Point<usize>)> = Vec::new();
+        let mut index = 0;
+        for line in (bottom..=top).rev() {
+            for col in 0..self.grid.num_cols().0 {
+                let cell = &self.grid[line][Column(col)];
+                if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    continue;
+                }
 
-    pub fn selection_to_string(&self) -> Option<String> {
-        /// Need a generic push() for the Append trait
-        trait PushChar {
-            fn push_char(&mut self, c: char);
-            fn maybe_newline(&mut self, grid: &Grid<Cell>, line: usize, ending: Column) {
-                if ending != Column(0) && !grid[line][ending - 1].flags.contains(cell::Flags::WRAPLINE) {
-                    self.push_char('\n');
+                if line == point.line && col <= point.col.0 {
+                    index = text.len();
                 }
+                text.push((cell.c, Point::new(line, Column(col))));
             }
         }
 
-        impl PushChar for String {
-            #[inline]
-            fn push_char(&mut self, c: char) {
-                self.push(c);
-            }
+        if URL_SEPARATOR_CHARS.contains(&text[index].0) {
+            return None;
         }
 
-        use std::ops::Range;
+        let mut start = index;
+        while start > 0 && !URL_SEPARATOR_CHARS.contains(&text[start - 1].0) {
+            start -= 1;
+        }
+        let mut end = index;
+        while end + 1 < text.len() && !URL_SEPARATOR_CHARS.contains(&text[end + 1].0) {
+            end += 1;
+        }
 
-        trait Append : PushChar {
-            fn append(&mut self, grid: &Grid<Cell>, line: usize, cols: Range<Column>);
+        // Heuristic to remove all leading opening brackets
+        while start < end && URL_BRACKETS.iter().any(|&(open, _)| open == text[start].0) {
+            start += 1;
         }
 
-        impl Append for String {
-            fn append(&mut self, grid: &Grid<Cell>, mut line: usize, cols: Range<Column>) {
-                // Select until last line still within the buffer
-                line = min(line, grid.len() - 1);
+        // Heuristic to remove trailing punctuation and closing brackets without a match
+        while start < end {
+            let c = text[end].0;
+            let chars = &text[start..=end];
+            let unmatched = URL_BRACKETS.iter().any(|&(open, close)| {
+                c == close && count_chars(chars, open) < count_chars(chars, close)
+            });
 
-                let grid_line = &grid[line];
-                let line_length = grid_line.line_length();
-                let line_end = min(line_length, cols.end + 1);
-
-                if line_end.0 == 0 && cols.end >= grid.num_cols() - 1 {
-                    self.push('\n');
-                } else if cols.start < line_end {
-                    for cell in &grid_line[cols.start..line_end] {
-                        if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
-                            self.push(cell.c);
-                        }
-                    }
-
-                    if cols.end >= grid.num_cols() - 1 {
-                        self.maybe_newline(grid, line, line_end);
-                    }
-                }
+            if URL_TRAILING_PUNCTUATION.contains(&c) || unmatched {
+                end -= 1;
+            } else {
+                break;
             }
         }
 
-        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
-        let selection = self.grid.selection.clone()?;
-        let span = selection.to_span(self, alt_screen)?;
+        if index < start || index > end {
+            return None;
+        }
 
-        let mut res = String::new();
+        let url: String = text[start..=end].iter().map(|&(c, _)| c).collect();
+        if !is_launchable_url(&url) {
+            return None;
+        }
 
-        let Locations { mut start, mut end } = span.to_locations();
+        Some(UrlMatch {
+            text: url,
+            start: text[start].1,
+            end: text[end].1,
+            hyperlink: None,
+        })
+    }
 
-        if start > end {
-            ::std::mem::swap(&mut start, &mut end);
+    /// Ids of interned data referenced by the cells of both screens
+    fn used_ids<I, F>(&self, id: F) -> HashSet<I>
+        where I: Eq + Hash,
+              F: Fn(&Cell) -> Option<I>,
+    {
+        let mut used = HashSet::new();
+        for grid in &[&self.grid, &self.alt_grid] {
+            let lines = grid.num_lines().0 + grid.scroll_limit();
+            for line in 0..lines {
+                used.extend(grid[line][..].iter().filter_map(&id));
+            }
         }
+        used
+    }
 
-        let line_count = end.line - start.line;
-        let max_col = Column(usize::max_value() - 1);
+    /// Drop hyperlinks which aren't referenced by any cell anymore
+    fn collect_hyperlinks(&mut self) {
+        let mut used: HashSet<HyperlinkId> = self.used_ids(|cell| cell.hyperlink);
+        used.extend(self.hyperlink);
+        if let Some(id) = self.hovered_url.as_ref().and_then(|url| url.hyperlink) {
+            used.insert(id);
+        }
 
-        match line_count {
-            // Selection within single line
-            0 => {
-                res.append(&self.grid, start.line, start.col..end.col);
-            },
+        trace!("Collecting hyperlinks, {} in use", used.len());
+        self.hyperlinks.retain(&used);
+    }
 
-            // Selection ends on line following start
-            1 => {
-                // Ending line
-                res.append(&self.grid, end.line, end.col..max_col);
+    /// Drop combining characters which aren't referenced by any cell anymore
+    fn collect_zerowidth(&mut self) {
+        let used: HashSet<ZeroWidthId> = self.used_ids(|cell| cell.zerowidth);
 
-                // Starting line
-                res.append(&self.grid, start.line, Column(0)..start.col);
+        trace!("Collecting zero-width characters, {} in use", used.len());
+        self.zerowidth.retain(&used);
+    }
 
-            },
+    /// URL which is underlined because the mouse is hovering over it
+    #[inline]
+    pub fn hovered_url(&self) -> Option<&UrlMatch> {
+        self.hovered_url.as_ref()
+    }
 
-            // Multi line selection
-            _ => {
-                // Ending line
-                res.append(&self.grid, end.line, end.col..max_col);
+    /// Update the URL under the mouse cursor
+    ///
+    /// While a URL is hovered, the mouse cursor is changed to a hand.
+    pub fn set_hovered_url(&mut self, url: Option<UrlMatch>) {
+        if self.hovered_url == url {
+            return;
+        }
 
-                let middle_range = (start.line + 1)..(end.line);
-                for line in middle_range.rev() {
-                    res.append(&self.grid, line, Column(0)..max_col);
-                }
+        let mouse_modes = TermMode::MOUSE_REPORT_CLICK | TermMode::MOUSE_DRAG | TermMode::MOUSE_MOTION;
+        let cursor = if url.is_some() {
+            MouseCursor::Hand
+        } else if self.mode.intersects(mouse_modes) {
+            MouseCursor::Arrow
+        } else {
+            MouseCursor::Text
+        };
 
-                // Starting line
-                res.append(&self.grid, start.line, Column(0)..start.col);
+        self.next_mouse_cursor = Some(cursor);
+        self.hovered_url = url;
+        self.dirty = true;
+    }
 
-            }
-        }
+    /// Open context menu
+    #[inline]
+    pub fn context_menu(&self) -> Option<&ContextMenu> {
+        self.context_menu.as_ref()
+    }
 
-        Some(res)
+    pub fn open_context_menu(&mut self, menu: ContextMenu) {
+        self.context_menu = Some(menu);
+        self.dirty = true;
     }
 
-    pub(crate) fn visible_to_buffer(&self, point: Point) -> Point<usize> {
-        self.grid.visible_to_buffer(point)
+    pub fn close_context_menu(&mut self) -> Option<ContextMenu> {
+        self.dirty = true;
+        self.context_menu.take()
     }
 
-    /// Convert the given pixel values to a grid coordinate
-    ///
-    /// The mouse coordinates are expected to be relative to the top left. The
-    /// line and column returned are also relative to the top left.
-    ///
-    /// Returns None if the coordinates are outside the screen
-    pub fn pixels_to_coords(&self, x: usize, y: usize) -> Option<Point> {
-        if self.size_info.contains_point(x, y) {
-            Some(self.size_info.pixels_to_coords(x, y))
-        } else {
-            None
+    /// Highlight the context menu item at a point of the grid
+    pub fn hover_context_menu(&mut self, point: Point) {
+        if let Some(ref mut menu) = self.context_menu {
+            let hovered = menu.item_at(point);
+            if hovered != menu.hovered {
+                menu.hovered = hovered;
+                self.dirty = true;
+            }
         }
     }
 
-    /// Access to the raw grid data structure
-    ///
-    /// This is a bit of a hack; when the window is closed, the event processor
-    /// serializes the grid state to a file.
-    pub fn grid(&self) -> &Grid<Cell> {
-        &self.grid
+    /// Active scrollback search
+    #[inline]
+    pub fn search(&self) -> Option<&SearchState> {
+        self.search.as_ref()
     }
 
-    /// Iterate over the *renderable* cells in the terminal
+    /// Start searching backward through the scrollback history
     ///
-    /// A renderable cell is any cell which has content other than the default
-    /// background color.  Cells with an alternate background color are
-    /// considered renderable as are cells with any text content.
-    pub fn renderable_cells<'b>(
-        &'b self,
-        config: &'b Config,
-        window_focused: bool,
-    ) -> RenderableCellsIter {
-        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
-        let selection = self.grid.selection.as_ref()
-            .and_then(|s| s.to_span(self, alt_screen))
-            .map(|span| {
-                span.to_locations()
-            });
-
-        let cursor = if window_focused || !config.unfocused_hollow_cursor() {
-            self.cursor_style.unwrap_or(self.default_cursor_style)
-        } else {
-            CursorStyle::HollowBlock
-        };
+    /// There is no history on the alternate screen, so this does nothing
+    /// while it is active.
+    pub fn start_search(&mut self) {
+        if self.mode.contains(TermMode::ALT_SCREEN) {
+            return;
+        }
 
-        RenderableCellsIter::new(
-            &self.grid,
-            &self.cursor.point,
-            &self.colors,
-            self.mode,
-            config,
-            selection,
-            cursor,
-        )
+        self.search = Some(SearchState::new(self.grid.display_offset()));
+        self.dirty = true;
     }
 
-    /// Resize terminal to new dimensions
-    pub fn resize(&mut self, size : &SizeInfo) {
-        debug!("Term::resize");
+    /// Stop searching
+    ///
+    /// If the query wasn't confirmed yet, the viewport jumps back to where the
+    /// search was started.
+    pub fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if search.editing {
+                self.scroll_to_offset(search.origin);
+            }
+            self.dirty = true;
+        }
+    }
 
-        // Bounds check; lots of math assumes width and height are > 0
-        if size.width as usize <= 2 * self.size_info.padding_x as usize ||
-            size.height as usize <= 2 * self.size_info.padding_y as usize
-        {
+    /// Append a character to the search query
+    pub fn search_input(&mut self, c: char) {
+        if let Some(query) = self.search_query_mut() {
+            query.push(c);
+        } else {
             return;
         }
 
-        let old_cols = self.grid.num_cols();
-        let old_lines = self.grid.num_lines();
-        let mut num_cols = size.cols();
-        let mut num_lines = size.lines();
-
-        self.size_info = *size;
+        self.update_search();
+    }
 
-        if old_cols == num_cols && old_lines == num_lines {
-            debug!("Term::resize dimensions unchanged");
+    /// Remove the last character from the search query
+    pub fn search_pop(&mut self) {
+        if let Some(query) = self.search_query_mut() {
+            query.pop();
+        } else {
             return;
         }
 
-        self.grid.selection = None;
-        self.alt_grid.selection = None;
+        self.update_search();
+    }
 
-        // Should not allow less than 1 col, causes all sorts of checks to be required.
-        if num_cols <= Column(1) {
-            num_cols = Column(2);
+    /// Query of the active search, if it's still being edited
+    fn search_query_mut(&mut self) -> Option<&mut String> {
+        match self.search {
+            Some(ref mut search) => if search.editing { Some(&mut search.query) } else { None },
+            None => None,
         }
+    }
 
-        // Should not allow less than 1 line, causes all sorts of checks to be required.
-        if num_lines <= Line(1) {
-            num_lines = Line(2);
+    /// Stop editing the query and start navigating between its matches
+    pub fn search_confirm(&mut self) {
+        if let Some(ref mut search) = self.search {
+            search.editing = false;
         }
+        self.dirty = true;
+    }
 
-        // Scroll up to keep cursor in terminal
-        if self.cursor.point.line >= num_lines {
-            let lines = self.cursor.point.line - num_lines + 1;
-            self.grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor.template);
-        }
+    /// Focus the closest match above the current one
+    pub fn search_previous(&mut self) {
+        self.focus_match(false);
+    }
+
+    /// Focus the closest match below the current one
+    pub fn search_next(&mut self) {
+        self.focus_match(true);
+    }
+
+    /// Focus the match closest to the search origin after the query changed
+    fn update_search(&mut self) {
+        let origin = match self.search {
+            Some(ref mut search) => {
+                search.update_regex();
+                search.focused = None;
+                search.origin
+            },
+            None => return,
+        };
 
-        // Scroll up alt grid as well
-        if self.cursor_save_alt.point.line >= num_lines {
-            let lines = self.cursor_save_alt.point.line - num_lines + 1;
-            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor_save_alt.template);
+        self.focus_match(false);
+
+        // Go back to the start when there's nothing to show
+        if self.search.as_ref().map_or(false, |search| search.focused.is_none()) {
+            self.scroll_to_offset(origin);
         }
+    }
 
-        // Move prompt down when growing if scrollback lines are available
-        if num_lines > old_lines {
-            if self.mode.contains(TermMode::ALT_SCREEN) {
-                let growage = min(num_lines - old_lines, Line(self.alt_grid.scroll_limit()));
-                self.cursor_save.point.line += growage;
-            } else {
-                let growage = min(num_lines - old_lines, Line(self.grid.scroll_limit()));
-                self.cursor.point.line += growage;
-            }
+    /// Move the search focus and bring it into view
+    fn focus_match(&mut self, forward: bool) {
+        let focused = match self.search {
+            Some(ref search) => {
+                // Without a focused match, start below the origin's last line
+                let origin = Point::new(search.origin, self.grid.num_cols());
+                let anchor = search.focused.unwrap_or(Match { start: origin, end: origin });
+                search.regex().and_then(|regex| search::find_next(&self.grid, regex, &anchor, forward))
+            },
+            None => return,
+        };
+
+        if let Some(ref mut search) = self.search {
+            search.focused = focused;
         }
 
-        debug!("num_cols, num_lines = {}, {}", num_cols, num_lines);
+        // The vi cursor follows the search, so the match can be selected
+        if let (Some(vi_mode), Some(focused)) = (self.vi_mode.as_mut(), focused) {
+            vi_mode.cursor = focused.start;
+        }
 
-        // Resize grids to new size
-        self.grid.resize(num_lines, num_cols, &Cell::default());
-        self.alt_grid.resize(num_lines, num_cols, &Cell::default());
+        if let Some(focused) = focused {
+            let in_view = [focused.start.line, focused.end.line].iter().all(|&line| {
+                match self.grid.buffer_line_to_visible(line) {
+                    ViewportPosition::Visible(_) => true,
+                    _ => false,
+                }
+            });
 
-        // Reset scrolling region to new size
-        self.scroll_region = Line(0)..self.grid.num_lines();
+            // Center the match if it's not completely visible
+            if !in_view {
+                let offset = focused.start.line.saturating_sub(self.grid.num_lines().0 / 2);
+                self.scroll_to_offset(offset);
+            }
+        }
 
-        // Ensure cursors are in-bounds.
-        self.cursor.point.col = min(self.cursor.point.col, num_cols - 1);
-        self.cursor.point.line = min(self.cursor.point.line, num_lines - 1);
-        self.cursor_save.point.col = min(self.cursor_save.point.col, num_cols - 1);
-        self.cursor_save.point.line = min(self.cursor_save.point.line, num_lines - 1);
-        self.cursor_save_alt.point.col = min(self.cursor_save_alt.point.col, num_cols - 1);
-        self.cursor_save_alt.point.line = min(self.cursor_save_alt.point.line, num_lines - 1);
+        self.dirty = true;
+    }
 
-        // Recreate tabs list
-        self.tabs = IndexRange::from(Column(0)..self.grid.num_cols())
-            .map(|i| (*i as usize) % self.tabspaces == 0)
-            .collect::<Vec<bool>>();
+    /// Scroll the viewport to a specific display offset
+    fn scroll_to_offset(&mut self, offset: usize) {
+        let delta = offset as isize - self.grid.display_offset() as isize;
+        self.scroll_display(Scroll::Lines(delta));
     }
 
-    #[inline]
-    pub fn size_info(&self) -> &SizeInfo {
-        &self.size_info
+    /// Scroll the viewport just enough to show a buffer line
+    fn scroll_to_line(&mut self, line: usize) {
+        let offset = self.grid.display_offset();
+        let num_lines = self.grid.num_lines().0;
+        if line < offset {
+            self.scroll_to_offset(line);
+        } else if line >= offset + num_lines {
+            self.scroll_to_offset(line + 1 - num_lines);
+        }
     }
 
+    /// State of the vi mode, if it's active
     #[inline]
-    pub fn mode(&self) -> &TermMode {
-        &self.mode
+    pub fn vi_mode(&self) -> Option<&ViMode> {
+        self.vi_mode.as_ref()
     }
 
     #[inline]
-    pub fn cursor(&self) -> &Cursor {
-        &self.cursor
+    pub fn vi_mode_mut(&mut self) -> Option<&mut ViMode> {
+        self.vi_mode.as_mut()
     }
 
-    pub fn swap_alt(&mut self) {
-        if self.alt {
-            let template = &self.cursor.template;
-            self.grid.region_mut(..).each(|c| c.reset(template));
+    /// Enter or leave the vi mode
+    ///
+    /// The vi cursor starts on the terminal cursor's row and column in the
+    /// viewport. Leaving the vi mode clears the selection.
+    pub fn toggle_vi_mode(&mut self) {
+        if self.vi_mode.take().is_some() {
+            self.grid.selection = None;
+        } else {
+            let cursor = self.grid.visible_to_buffer(self.cursor.point);
+            self.vi_mode = Some(ViMode::new(cursor));
         }
 
-        self.alt = !self.alt;
-        ::std::mem::swap(&mut self.grid, &mut self.alt_grid);
+        self.dirty = true;
     }
 
-    /// Scroll screen down
-    ///
-    /// Text moves down; clear at bottom
-    /// Expects origin to be in scroll range.
-    #[inline]
-    fn scroll_down_relative(&mut self, origin: Line, mut lines: Line) {
-        trace!("scroll_down_relative: origin={}, lines={}", origin, lines);
-        lines = min(lines, self.scroll_region.end - self.scroll_region.start);
-        lines = min(lines, self.scroll_region.end - origin);
+    /// Move the vi cursor and extend the selection to it
+    pub fn vi_motion(&mut self, motion: ViMotion) {
+        let (cursor, anchor) = match self.vi_mode {
+            Some(ref mut vi_mode) => {
+                vi_mode.pending = None;
+                vi_mode.cursor = vi_mode::motion(&self.grid, vi_mode.cursor, motion);
+                (vi_mode.cursor, vi_mode.anchor)
+            },
+            None => return,
+        };
 
-        // Scroll between origin and bottom
-        self.grid.scroll_down(&(origin..self.scroll_region.end), lines, &self.cursor.template);
+        match anchor {
+            Some(anchor) => self.select_from_anchor(anchor, cursor),
+            None => if let Some(ref mut selection) = self.grid.selection {
+                selection.update(cursor, Side::Right);
+            },
+        }
+
+        self.scroll_to_line(cursor.line);
+        self.dirty = true;
     }
 
-    /// Scroll screen up
+    /// Start or stop selecting at the vi cursor
     ///
-    /// Text moves up; clear at top
-    /// Expects origin to be in scroll range.
-    #[inline]
-    fn scroll_up_relative(&mut self, origin: Line, lines: Line) {
-        trace!("scroll_up_relative: origin={}, lines={}", origin, lines);
-        let lines = min(lines, self.scroll_region.end - self.scroll_region.start);
-
-        // Scroll from origin to bottom less number of lines
-        self.grid.scroll_up(&(origin..self.scroll_region.end), lines, &self.cursor.template);
-    }
+    /// Selecting the same kind again stops the selection, while the other kind
+    /// replaces it.
+    pub fn vi_select(&mut self, lines: bool) {
+        let (cursor, anchor) = match self.vi_mode {
+            Some(ref vi_mode) => (vi_mode.cursor, vi_mode.anchor),
+            None => return,
+        };
 
-    fn deccolm(&mut self) {
-        // Setting 132 column font makes no sense, but run the other side effects
-        // Clear scrolling region
-        let scroll_region = Line(0)..self.grid.num_lines();
-        self.set_scrolling_region(scroll_region);
+        let same_kind = self.grid.selection.is_some() && anchor.is_some() != lines;
+        self.vi_clear_selection();
+        if same_kind {
+            return;
+        }
 
-        // Clear grid
-        let template = self.cursor.template;
-        self.grid.region_mut(..).each(|c| c.reset(&template));
+        if lines {
+            self.grid.selection = Some(Selection::lines(cursor));
+        } else {
+            if let Some(ref mut vi_mode) = self.vi_mode {
+                vi_mode.anchor = Some(cursor);
+            }
+            self.select_from_anchor(cursor, cursor);
+        }
     }
 
-    #[inline]
-    pub fn background_color(&self) -> Rgb {
-        self.colors[NamedColor::Background]
+    /// Clear the selection of the vi mode
+    pub fn vi_clear_selection(&mut self) {
+        if let Some(ref mut vi_mode) = self.vi_mode {
+            vi_mode.anchor = None;
+        }
+        self.grid.selection = None;
+        self.dirty = true;
     }
-}
 
-impl ansi::TermInfo for Term {
-    #[inline]
-    fn lines(&self) -> Line {
-        self.grid.num_lines()
-    }
+    /// Select all cells from the anchor to the cursor, including both of them
+    fn select_from_anchor(&mut self, anchor: Point<usize>, cursor: Point<usize>) {
+        let anchor_first = anchor.line > cursor.line
+            || (anchor.line == cursor.line && anchor.col <= cursor.col);
+        let (start, end) = if anchor_first { (anchor, cursor) } else { (cursor, anchor) };
 
-    #[inline]
-    fn cols(&self) -> Column {
-        self.grid.num_cols()
+        let mut selection = Selection::simple(start, Side::Left);
+        selection.update(end, Side::Right);
+        self.grid.selection = Some(selection);
     }
-}
 
-impl ansi::Handler for Term {
-    /// Set the window title
-    #[inline]
-    fn set_title(&mut self, title: &str) {
-        if self.dynamic_title {
-            self.next_title = Some(title.to_owned());
+    /// Keep the vi mode on the same content while the grid is scrolled
+    ///
+    /// The viewport doesn't follow new output while the vi mode is active.
+    fn rotate_vi_mode(&mut self, offset: isize, display_offset: usize) {
+        let top = self.grid.num_lines().0 + self.grid.scroll_limit() - 1;
+        let rotate = |point: &mut Point<usize>| {
+            point.line = min(max(point.line as isize + offset, 0) as usize, top);
+        };
+
+        match self.vi_mode {
+            Some(ref mut vi_mode) => {
+                rotate(&mut vi_mode.cursor);
+                if let Some(ref mut anchor) = vi_mode.anchor {
+                    rotate(anchor);
+                }
+            },
+            None => return,
+        }
+
+        if offset > 0 && display_offset == 0 {
+            self.grid.scroll_display(Scroll::Lines(offset));
         }
     }
 
-    /// Set the mouse cursor
     #[inline]
-    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
-        self.next_mouse_cursor = Some(cursor);
+    pub fn get_next_mouse_cursor(&mut self) -> Option<MouseCursor> {
+        self.next_mouse_cursor.take()
     }
 
-    /// A character to be displayed
-    #[inline]
-    fn input(&mut self, c: char) {
-        // If enabled, scroll to bottom when character is received
-        if self.auto_scroll {
-            self.scroll_display(Scroll::Bottom);
-        }
+    pub fn new(config: &Config, size: SizeInfo) -> Term {
+        let num_cols = size.cols();
+        let num_lines = size.lines();
 
-        if self.input_needs_wrap {
-            if !self.mode.contains(mode::TermMode::LINE_WRAP) {
-                return;
-            }
+        let history_size = config.scrolling().history as usize;
+        let grid = Grid::new(num_lines, num_cols, history_size, Cell::default());
+        let alt = Grid::new(num_lines, num_cols, 0 /* scroll history */, Cell::default());
 
-            trace!("wrapping");
+        let tabspaces = config.tabspaces();
+        let tabs = IndexRange::from(Column(0)..grid.num_cols())
+            .map(|i| (*i as usize) % tabspaces == 0)
+            .collect::<Vec<bool>>();
 
-            {
-                let location = Point {
-                    line: self.cursor.point.line,
-                    col: self.cursor.point.col
-                };
+        let scroll_region = Line(0)..grid.num_lines();
 
-                let cell = &mut self.grid[&location];
-                cell.flags.insert(cell::Flags::WRAPLINE);
-            }
+        Term {
+            next_title: None,
+            next_mouse_cursor: None,
+            dirty: false,
+            visual_bell: VisualBell::new(config),
+            next_is_urgent: None,
+            bell_urgent: config.bell().urgent,
+            bell_rung: false,
+            input_needs_wrap: false,
+            grid,
+            alt_grid: alt,
+            alt: false,
+            font_size: config.font().size(),
+            original_font_size: config.font().size(),
+            active_charset: Default::default(),
+            cursor: Default::default(),
+            cursor_save: Default::default(),
+            cursor_save_alt: Default::default(),
+            alt_saved_cursor: false,
+            tabs,
+            mode: Default::default(),
+            scroll_region,
+            size_info: size,
+            screen_size: None,
+            colors: color::List::from(config.colors()),
+            color_modified: [false; color::COUNT],
+            original_colors: color::List::from(config.colors()),
+            semantic_escape_chars: config.selection().semantic_escape_chars.clone(),
+            word_pattern: config.selection().word_pattern.clone(),
+            skip_leading_whitespace: config.selection().skip_leading_whitespace,
+            cursor_style: None,
+            cursor_style_save: None,
+            default_cursor_style: config.cursor_style(),
+            cursor_blinking: false,
+            cursor_blinking_save: false,
+            dynamic_title: config.dynamic_title(),
+            tabspaces,
+            auto_scroll: config.scrolling().auto_scroll,
+            kitty_keyboard: config.enable_kitty_keyboard(),
+            keyboard_mode_stack: Vec::new(),
+            inactive_keyboard_mode_stack: Vec::new(),
+            title: None,
+            title_stack: Vec::new(),
+            osc52: config.terminal().osc52,
+            osc52_max_bytes: config.terminal().osc52_max_bytes,
+            sync_update_start: None,
+            search: None,
+            vi_mode: None,
+            hovered_url: None,
+            hyperlinks: Hyperlinks::default(),
+            hyperlink: None,
+            zerowidth: ZeroWidthChars::default(),
+            damage: DamageTracker::default(),
+            blink_epoch: Arc::new(AtomicUsize::new(0)),
+            context_menu: None,
+        }
+    }
 
-            if (self.cursor.point.line + 1) >= self.scroll_region.end {
-                self.linefeed();
-            } else {
-                self.cursor.point.line += 1;
-            }
+    pub fn change_font_size(&mut self, delta: f32) {
+        let new_size = self.font_size.as_f32_pts() + delta;
+        self.set_font_size(new_size);
+    }
 
-            self.cursor.point.col = Column(0);
-            self.input_needs_wrap = false;
-        }
+    /// Set the font size, clamped to `FONT_SIZE_STEP..=MAX_FONT_SIZE`
+    pub fn set_font_size(&mut self, size: f32) {
+        let size = if size > MAX_FONT_SIZE { MAX_FONT_SIZE } else { size };
+        self.font_size = max(Size::new(size), Size::new(FONT_SIZE_STEP));
+        self.dirty = true;
+    }
 
-        {
-            // Number of cells the char will occupy
-            if let Some(width) = c.width() {
-                // Sigh, borrowck making us check the width twice. Hopefully the
-                // optimizer can fix it.
-                let num_cols = self.grid.num_cols();
-                {
-                    // If in insert mode, first shift cells to the right.
-                    if self.mode.contains(mode::TermMode::INSERT) && self.cursor.point.col + width < num_cols {
-                        let line = self.cursor.point.line; // borrowck
-                        let col = self.cursor.point.col;
-                        let line = &mut self.grid[line];
+    pub fn reset_font_size(&mut self) {
+        self.font_size = self.original_font_size;
+        self.dirty = true;
+    }
 
-                        let src = line[col..].as_ptr();
-                        let dst = line[(col + width)..].as_mut_ptr();
-                        unsafe {
-                            // memmove
-                            ptr::copy(src, dst, (num_cols - col - width).0);
-                        }
-                    }
+    pub fn update_config(&mut self, config: &Config) {
+        self.semantic_escape_chars = config.selection().semantic_escape_chars.clone();
+        self.word_pattern = config.selection().word_pattern.clone();
+        self.skip_leading_whitespace = config.selection().skip_leading_whitespace;
+        self.original_colors.fill_named(config.colors());
+        self.original_colors.fill_cube(config.colors());
+        self.original_colors.fill_gray_ramp(config.colors());
+        for i in 0..color::COUNT {
+            if !self.color_modified[i] {
+                self.colors[i] = self.original_colors[i];
+            }
+        }
+        self.visual_bell.update_config(config);
+        self.bell_urgent = config.bell().urgent;
+        self.default_cursor_style = config.cursor_style();
+        self.damage.damage_all();
+        self.dynamic_title = config.dynamic_title();
+        self.auto_scroll = config.scrolling().auto_scroll;
+        self.kitty_keyboard = config.enable_kitty_keyboard();
+        self.osc52 = config.terminal().osc52;
+        self.osc52_max_bytes = config.terminal().osc52_max_bytes;
+        if !self.kitty_keyboard {
+            self.keyboard_mode_stack.clear();
+            self.inactive_keyboard_mode_stack.clear();
+            self.update_keyboard_mode();
+        }
+        self.grid
+            .update_history(config.scrolling().history as usize, &self.cursor.template);
 
-                    let cell = &mut self.grid[&self.cursor.point];
-                    *cell = self.cursor.template;
-                    cell.c = self.cursor.charsets[self.active_charset].map(c);
+        // A size changed at runtime is kept, resetting it uses the new size
+        let font_size = config.font().size();
+        if self.font_size == self.original_font_size {
+            self.font_size = font_size;
+            self.dirty = true;
+        }
+        self.original_font_size = font_size;
+    }
 
-                    // Handle wide chars
-                    if width == 2 {
-                        cell.flags.insert(cell::Flags::WIDE_CHAR);
-                    }
+    /// Find the match of the word pattern which contains `point`
+    ///
+    /// Wrapped lines are joined, so words can continue on the next line.
+    fn word_at(&self, point: Point<usize>) -> Option<(Point<usize>, Point<usize>)> {
+        let regex = self.word_pattern.as_ref()?;
+
+        let point = Point::new(min(point.line, self.grid.len() - 1), point.col);
+        let top = self.line_search_left(point).line;
+        let bottom = self.line_search_right(point).line;
+
+        // Text of the line with the byte offset of every cell
+        let mut text = String::new();
+        let mut offsets = Vec::new();
+        let mut target = None;
+        for line in (bottom..=top).rev() {
+            for col in 0..self.grid.num_cols().0 {
+                let cell = &self.grid[line][Column(col)];
+                if cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                    continue;
                 }
 
-                // Set spacer cell for wide chars.
-                if width == 2 && self.cursor.point.col + 1 < num_cols {
-                    self.cursor.point.col += 1;
-                    let spacer = &mut self.grid[&self.cursor.point];
-                    *spacer = self.cursor.template;
-                    spacer.flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+                // Spacers are part of the wide char before them
+                if line == point.line && col <= point.col.0 {
+                    target = Some(text.len());
                 }
+
+                offsets.push((text.len(), Point::new(line, Column(col))));
+                text.push(cell.c);
             }
         }
 
-        if (self.cursor.point.col + 1) < self.grid.num_cols() {
-            self.cursor.point.col += 1;
-        } else {
-            self.input_needs_wrap = true;
-        }
+        let target = target?;
+        let found = regex.find_iter(&text).find(|found| {
+            found.start() <= target && target < found.end()
+        })?;
 
+        let cell = |offset: usize| match offsets.binary_search_by_key(&offset, |&(offset, _)| offset) {
+            Ok(index) => offsets[index].1,
+            Err(index) => offsets[index - 1].1,
+        };
+        Some((cell(found.start()), cell(found.end() - 1)))
     }
 
     #[inline]
-    fn dectest(&mut self) {
-        trace!("dectest");
-        let mut template = self.cursor.template;
-        template.c = 'E';
-
-        self.grid.region_mut(..)
-            .each(|c| c.reset(&template));
+    pub fn needs_draw(&self) -> bool {
+        self.dirty && self.sync_update_deadline().is_none()
     }
 
-    #[inline]
-    fn goto(&mut self, line: Line, col: Column) {
-        trace!("goto: line={}, col={}", line, col);
-        let (y_offset, max_y) = if self.mode.contains(mode::TermMode::ORIGIN) {
-            (self.scroll_region.start, self.scroll_region.end - 1)
-        } else {
-            (Line(0), self.grid.num_lines() - 1)
-        };
-
-        self.cursor.point.line = min(line + y_offset, max_y);
-        self.cursor.point.col = min(col, self.grid.num_cols() - 1);
-        self.input_needs_wrap = false;
+    /// Deadline of the pending synchronized update
+    ///
+    /// Drawing is held back until the update ends or this deadline has passed.
+    pub fn sync_update_deadline(&self) -> Option<Instant> {
+        self.sync_update_start
+            .map(|start| start + SYNC_UPDATE_TIMEOUT)
+            .filter(|deadline| *deadline > Instant::now())
     }
 
-    #[inline]
-    fn goto_line(&mut self, line: Line) {
-        trace!("goto_line: {}", line);
-        let col = self.cursor.point.col; // borrowck
-        self.goto(line, col)
-    }
+    pub fn selection_to_string(&self) -> Option<String> {
+        /// Need a generic push() for the Append trait
+        trait PushChar {
+            fn push_char(&mut self, c: char);
+            fn maybe_newline(&mut self, grid: &Grid<Cell>, line: usize, ending: Column) {
+                if ending != Column(0) && !grid[line][ending - 1].flags.contains(cell::Flags::WRAPLINE) {
+                    self.push_char('\n');
+                }
+            }
+        }
 
-    #[inline]
-    fn goto_col(&mut self, col: Column) {
-        trace!("goto_col: {}", col);
-        let line = self.cursor.point.line; // borrowck
-        self.goto(line, col)
-    }
+        impl PushChar for String {
+            #[inline]
+            fn push_char(&mut self, c: char) {
+                self.push(c);
+            }
+        }
 
-    #[inline]
-    fn insert_blank(&mut self, count: Column) {
-        // Ensure inserting within terminal bounds
+        use std::ops::Range;
 
-        let count = min(count, self.size_info.cols() - self.cursor.point.col);
+        trait Append : PushChar {
+            fn append(
+                &mut self,
+                grid: &Grid<Cell>,
+                zerowidth: &ZeroWidthChars,
+                line: usize,
+                cols: Range<Column>,
+            );
+        }
 
-        let source = self.cursor.point.col;
-        let destination = self.cursor.point.col + count;
-        let num_cells = (self.size_info.cols() - destination).0;
+        impl Append for String {
+            fn append(
+                &mut self,
+                grid: &Grid<Cell>,
+                zerowidth: &ZeroWidthChars,
+                mut line: usize,
+                cols: Range<Column>,
+            ) {
+                // Select until last line still within the buffer
+                line = min(line, grid.len() - 1);
 
-        let line = self.cursor.point.line; // borrowck
-        let line = &mut self.grid[line];
+                let grid_line = &grid[line];
+                let line_length = grid_line.line_length();
+                let line_end = min(line_length, cols.end + 1);
 
-        unsafe {
-            let src = line[source..].as_ptr();
-            let dst = line[destination..].as_mut_ptr();
+                if line_end.0 == 0 && cols.end >= grid.num_cols() - 1 {
+                    self.push('\n');
+                } else if cols.start < line_end {
+                    for cell in &grid_line[cols.start..line_end] {
+                        if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                            self.push(cell.c);
+                            if let Some(id) = cell.zerowidth {
+                                self.extend(zerowidth.get(id));
+                            }
+                        }
+                    }
 
-            ptr::copy(src, dst, num_cells);
+                    if cols.end >= grid.num_cols() - 1 {
+                        self.maybe_newline(grid, line, line_end);
+                    }
+                }
+            }
         }
 
-        // Cells were just moved out towards the end of the line; fill in
-        // between source and dest with blanks.
-        let template = self.cursor.template;
-        for c in &mut line[source..destination] {
-            c.reset(&template);
-        }
-    }
+        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+        let selection = self.grid.selection.clone()?;
+        let span = selection.to_span(self, alt_screen)?;
 
-    #[inline]
-    fn move_up(&mut self, lines: Line) {
-        trace!("move_up: {}", lines);
-        let move_to = Line(self.cursor.point.line.0.saturating_sub(lines.0));
-        let col = self.cursor.point.col; // borrowck
-        self.goto(move_to, col)
-    }
+        let mut res = String::new();
 
-    #[inline]
-    fn move_down(&mut self, lines: Line) {
-        trace!("move_down: {}", lines);
-        let move_to = self.cursor.point.line + lines;
-        let col = self.cursor.point.col; // borrowck
-        self.goto(move_to, col)
-    }
+        let Locations { mut start, mut end, block } = span.to_locations();
+
+        if block {
+            // Every line of the block is copied on its own, from top to bottom,
+            // padded with spaces so all of them have the width of the block
+            for line in (start.line..=end.line).rev() {
+                for (i, cell) in self.grid[line][end.col..start.col + 1].iter().enumerate() {
+                    if !cell.flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                        res.push(cell.c);
+                        if let Some(id) = cell.zerowidth {
+                            res.extend(self.zerowidth.get(id));
+                        }
+                    } else if i == 0 {
+                        // The wide char starts left of the block
+                        res.push(' ');
+                    }
+                }
 
-    #[inline]
-    fn move_forward(&mut self, cols: Column) {
-        trace!("move_forward: {}", cols);
-        self.cursor.point.col = min(self.cursor.point.col + cols, self.grid.num_cols() - 1);
-        self.input_needs_wrap = false;
-    }
+                res.push('\n');
+            }
 
-    #[inline]
-    fn move_backward(&mut self, cols: Column) {
-        trace!("move_backward: {}", cols);
-        self.cursor.point.col -= min(self.cursor.point.col, cols);
-        self.input_needs_wrap = false;
-    }
+            return Some(res);
+        }
 
-    #[inline]
-    fn identify_terminal<W: io::Write>(&mut self, writer: &mut W) {
-        let _ = writer.write_all(b"\x1b[?6c");
-    }
+        if start > end {
+            ::std::mem::swap(&mut start, &mut end);
+        }
 
-    #[inline]
-    fn device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
-        trace!("device status: {}", arg);
-        match arg {
-            5 => {
-                let _ = writer.write_all(b"\x1b[0n");
-            },
-            6 => {
-                let pos = self.cursor.point;
-                let _ = write!(writer, "\x1b[{};{}R", pos.line + 1, pos.col + 1);
+        let line_count = end.line - start.line;
+        let max_col = Column(usize::max_value() - 1);
+
+        match line_count {
+            // Selection within single line
+            0 => {
+                res.append(&self.grid, &self.zerowidth, start.line, start.col..end.col);
             },
-            _ => debug!("unknown device status query: {}", arg),
-        };
-    }
 
-    #[inline]
-    fn move_down_and_cr(&mut self, lines: Line) {
-        trace!("move_down_and_cr: {}", lines);
-        let move_to = self.cursor.point.line + lines;
-        self.goto(move_to, Column(0))
-    }
+            // Selection ends on line following start
+            1 => {
+                // Ending line
+                res.append(&self.grid, &self.zerowidth, end.line, end.col..max_col);
 
-    #[inline]
-    fn move_up_and_cr(&mut self, lines: Line) {
-        trace!("move_up_and_cr: {}", lines);
-        let move_to = Line(self.cursor.point.line.0.saturating_sub(lines.0));
-        self.goto(move_to, Column(0))
-    }
+                // Starting line
+                res.append(&self.grid, &self.zerowidth, start.line, Column(0)..start.col);
 
-    #[inline]
-    fn put_tab(&mut self, mut count: i64) {
-        trace!("put_tab: {}", count);
+            },
 
-        let mut col = self.cursor.point.col;
-        while col < self.grid.num_cols() && count != 0 {
-            count -= 1;
-            loop {
-                if (col + 1) == self.grid.num_cols() {
-                    break;
+            // Multi line selection
+            _ => {
+                // Ending line
+                res.append(&self.grid, &self.zerowidth, end.line, end.col..max_col);
+
+                let middle_range = (start.line + 1)..(end.line);
+                for line in middle_range.rev() {
+                    res.append(&self.grid, &self.zerowidth, line, Column(0)..max_col);
                 }
 
-                col += 1;
+                // Starting line
+                res.append(&self.grid, &self.zerowidth, start.line, Column(0)..start.col);
 
-                if self.tabs[*col as usize] {
-                    break;
-                }
             }
         }
 
-        self.cursor.point.col = col;
-        self.input_needs_wrap = false;
+        Some(res)
     }
 
-    /// Backspace `count` characters
-    #[inline]
-    fn backspace(&mut self) {
-        trace!("backspace");
-        if self.cursor.point.col > Column(0) {
-            self.cursor.point.col -= 1;
-            self.input_needs_wrap = false;
-        }
+    pub(crate) fn visible_to_buffer(&self, point: Point) -> Point<usize> {
+        self.grid.visible_to_buffer(point)
     }
 
-    /// Carriage return
-    #[inline]
-    fn carriage_return(&mut self) {
-        trace!("carriage_return");
-        self.cursor.point.col = Column(0);
-        self.input_needs_wrap = false;
+    /// Convert the given pixel values to a grid coordinate
+    ///
+    /// The mouse coordinates are expected to be relative to the top left. The
+    /// line and column returned are also relative to the top left.
+    ///
+    /// Returns None if the coordinates are outside the screen
+    pub fn pixels_to_coords(&self, x: usize, y: usize) -> Option<Point> {
+        if self.size_info.contains_point(x, y) {
+            Some(self.size_info.pixels_to_coords(x, y))
+        } else {
+            None
+        }
     }
 
-    /// Linefeed
-    #[inline]
-    fn linefeed(&mut self) {
-        trace!("linefeed");
-        let next = self.cursor.point.line + 1;
-        if next == self.scroll_region.end {
-            self.scroll_up(Line(1));
-        } else if next < self.grid.num_lines() {
-            self.cursor.point.line += 1;
-        }
+    /// Access to the raw grid data structure
+    ///
+    /// This is a bit of a hack; when the window is closed, the event processor
+    /// serializes the grid state to a file.
+    pub fn grid(&self) -> &Grid<Cell> {
+        &self.grid
     }
 
-    /// Set current position as a tabstop
-    #[inline]
-    fn bell(&mut self) {
-        trace!("bell");
-        self.visual_bell.ring();
-        self.next_is_urgent = Some(true);
+    /// Iterate over the *renderable* cells in the terminal
+    ///
+    /// A renderable cell is any cell which has content other than the default
+    /// background color.  Cells with an alternate background color are
+    /// considered renderable as are cells with any text content.
+    ///
+    /// Blinking text is hidden unless `blink_visible` is set.
+    pub fn renderable_cells<'b>(
+        &'b self,
+        config: &'b Config,
+        window_focused: bool,
+        blink_visible: bool,
+    ) -> RenderableCellsIter {
+        let alt_screen = self.mode.contains(TermMode::ALT_SCREEN);
+        let selection = self.grid.selection.as_ref()
+            .and_then(|s| s.to_span(self, alt_screen))
+            .map(|span| {
+                span.to_locations()
+            });
+
+        let cursor = self.render_cursor_style(config, window_focused);
+
+        RenderableCellsIter::new(
+            &self.grid,
+            &self.cursor.point,
+            &self.colors,
+            self.render_mode(blink_visible),
+            config,
+            selection,
+            cursor,
+            blink_visible,
+            self.search.as_ref(),
+            self.vi_mode.as_ref(),
+            self.hovered_url.as_ref(),
+            &self.zerowidth,
+        )
     }
 
-    #[inline]
-    fn substitute(&mut self) {
-        trace!("[unimplemented] substitute");
+    /// Iterator over the cells of the lines which have to be redrawn
+    pub fn damaged_cells<'b>(
+        &'b self,
+        config: &'b Config,
+        window_focused: bool,
+        blink_visible: bool,
+        damage: &Damage,
+    ) -> RenderableCellsIter {
+        self.renderable_cells(config, window_focused, blink_visible)
+            .only_damaged(damage)
     }
 
-    /// Run LF/NL
-    ///
-    /// LF/NL mode has some interesting history. According to ECMA-48 4th
-    /// edition, in LINE FEED mode,
-    ///
-    /// > The execution of the formatter functions LINE FEED (LF), FORM FEED
-    /// (FF), LINE TABULATION (VT) cause only movement of the active position in
-    /// the direction of the line progression.
-    ///
-    /// In NEW LINE mode,
-    ///
-    /// > The execution of the formatter functions LINE FEED (LF), FORM FEED
-    /// (FF), LINE TABULATION (VT) cause movement to the line home position on
-    /// the following line, the following form, etc. In the case of LF this is
-    /// referred to as the New Line (NL) option.
-    ///
-    /// Additionally, ECMA-48 4th edition says that this option is deprecated.
-    /// ECMA-48 5th edition only mentions this option (without explanation)
-    /// saying that it's been removed.
+    /// Lines which changed since the previous call
     ///
-    /// As an emulator, we need to support it since applications may still rely
-    /// on it.
-    #[inline]
-    fn newline(&mut self) {
-        self.linefeed();
+    /// Everything is damaged after scrolling, resizing and changes to the
+    /// palette, the first call always returns `Damage::Full`.
+    pub fn damage(&mut self, config: &Config, window_focused: bool, blink_visible: bool) -> Damage {
+        let mode = self.render_mode(blink_visible);
+
+        let view = View {
+            display_offset: self.grid.display_offset(),
+            mode: mode - TermMode::SHOW_CURSOR,
+            selection: self.grid.selection.clone(),
+            search: self.search.as_ref().map(|search| (search.query.clone(), search.focused)),
+            vi_cursor: self.vi_mode.map(|vi_mode| vi_mode.cursor),
+            url: self.hovered_url.clone(),
+        };
 
-        if self.mode.contains(mode::TermMode::LINE_FEED_NEW_LINE) {
-            self.carriage_return();
-        }
+        // Position of the terminal cursor inside the viewport
+        let line = self.cursor.point.line + self.grid.display_offset();
+        let cursor = if mode.contains(TermMode::SHOW_CURSOR) && line < self.grid.num_lines() {
+            let point = Point::new(line, self.cursor.point.col);
+            Some((point, self.render_cursor_style(config, window_focused)))
+        } else {
+            None
+        };
+
+        self.damage.update(&self.grid, view, cursor, blink_visible)
     }
 
-    #[inline]
-    fn set_horizontal_tabstop(&mut self) {
-        trace!("set_horizontal_tabstop");
-        let column = self.cursor.point.col;
-        self.tabs[column.0] = true;
+    /// Share the blink epoch advanced by the blink timer
+    pub fn set_blink_epoch(&mut self, epoch: Arc<AtomicUsize>) {
+        self.blink_epoch = epoch;
     }
 
+    /// Whether blinking text is shown in the current blink phase
     #[inline]
-    fn scroll_up(&mut self, lines: Line) {
-        let origin = self.scroll_region.start;
-        self.scroll_up_relative(origin, lines);
+    pub fn blink_visible(&self) -> bool {
+        self.blink_epoch.load(Ordering::Relaxed) % 2 == 0
     }
 
-    #[inline]
-    fn scroll_down(&mut self, lines: Line) {
-        let origin = self.scroll_region.start;
-        self.scroll_down_relative(origin, lines);
+    /// Whether there was blinking text in the last damaged frame
+    pub fn has_blinking_text(&self) -> bool {
+        self.damage.blinking()
     }
 
-    #[inline]
-    fn insert_blank_lines(&mut self, lines: Line) {
-        trace!("insert_blank_lines: {}", lines);
-        if self.scroll_region.contains_(self.cursor.point.line) {
-            let origin = self.cursor.point.line;
-            self.scroll_down_relative(origin, lines);
-        }
+    /// Whether the cursor is shown and blinks
+    pub fn has_blinking_cursor(&self) -> bool {
+        self.cursor_blinking && self.mode.contains(TermMode::SHOW_CURSOR)
     }
 
-    #[inline]
-    fn delete_lines(&mut self, lines: Line) {
-        trace!("delete_lines: {}", lines);
-        if self.scroll_region.contains_(self.cursor.point.line) {
-            let origin = self.cursor.point.line;
-            self.scroll_up_relative(origin, lines);
+    /// Cursor style used for rendering
+    fn render_cursor_style(&self, config: &Config, window_focused: bool) -> CursorStyle {
+        if window_focused || !config.unfocused_hollow_cursor() {
+            self.cursor_style.unwrap_or(self.default_cursor_style)
+        } else {
+            CursorStyle::HollowBlock
         }
     }
 
-    #[inline]
-    fn erase_chars(&mut self, count: Column) {
-        trace!("erase_chars: {}, {}", count, self.cursor.point.col);
-        let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols() - 1);
+    /// Terminal mode used for rendering
+    fn render_mode(&self, blink_visible: bool) -> TermMode {
+        // The vi cursor is drawn instead of the terminal cursor when they overlap
+        let mut mode = self.mode;
+        let cursor_point = self.grid.visible_to_buffer(self.cursor.point);
+        if self.vi_mode.map_or(false, |vi_mode| vi_mode.cursor == cursor_point) {
+            mode.remove(TermMode::SHOW_CURSOR);
+        }
 
-        let row = &mut self.grid[self.cursor.point.line];
-        let template = self.cursor.template; // Cleared cells have current background color set
-        for c in &mut row[start..end] {
-            c.reset(&template);
+        // A blinking cursor is hidden with blinking text
+        if self.cursor_blinking && !blink_visible {
+            mode.remove(TermMode::SHOW_CURSOR);
         }
+
+        mode
     }
 
-    #[inline]
-    fn delete_chars(&mut self, count: Column) {
-        // Ensure deleting within terminal bounds
-        let count = min(count, self.size_info.cols());
+    /// Resize terminal to new dimensions
+    pub fn resize(&mut self, size : &SizeInfo) {
+        debug!("Term::resize");
 
-        let start = self.cursor.point.col;
-        let end = min(start + count, self.grid.num_cols() - 1);
-        let n = (self.size_info.cols() - end).0;
+        // Bounds check; lots of math assumes width and height are > 0
+        if size.width as usize <= 2 * self.size_info.padding_x as usize ||
+            size.height as usize <= 2 * self.size_info.padding_y as usize
+        {
+            return;
+        }
 
-        let line = self.cursor.point.line; // borrowck
-        let line = &mut self.grid[line];
+        let old_cols = self.grid.num_cols();
+        let old_lines = self.grid.num_lines();
+        let mut num_cols = size.cols();
+        let mut num_lines = size.lines();
 
-        unsafe {
-            let src = line[end..].as_ptr();
-            let dst = line[start..].as_mut_ptr();
+        self.size_info = *size;
+        self.damage.damage_all();
 
-            ptr::copy(src, dst, n);
+        if old_cols == num_cols && old_lines == num_lines {
+            debug!("Term::resize dimensions unchanged");
+            return;
         }
 
-        // Clear last `count` cells in line. If deleting 1 char, need to delete
-        // 1 cell.
-        let template = self.cursor.template;
-        let end = self.size_info.cols() - count;
-        for c in &mut line[end..] {
-            c.reset(&template);
+        self.grid.selection = None;
+        self.alt_grid.selection = None;
+        self.context_menu = None;
+
+        // Should not allow less than 1 col, causes all sorts of checks to be required.
+        if num_cols <= Column(1) {
+            num_cols = Column(2);
         }
-    }
 
-    #[inline]
-    fn move_backward_tabs(&mut self, count: i64) {
-        trace!("move_backward_tabs: {}", count);
+        // Should not allow less than 1 line, causes all sorts of checks to be required.
+        if num_lines <= Line(1) {
+            num_lines = Line(2);
+        }
 
-        for _ in 0..count {
-            let mut col = self.cursor.point.col;
-            for i in (0..(col.0)).rev() {
-                if self.tabs[i as usize] {
-                    col = index::Column(i);
-                    break;
-                }
+        // Re-wrap the primary screen; the alternate screen is simply truncated
+        // or padded, since fullscreen applications redraw it anyway
+        if num_cols != old_cols {
+            if self.alt && self.alt_saved_cursor {
+                let saved = &mut self.cursor_save;
+                reflow_grid(&mut self.alt_grid, num_cols, &mut saved.cursor.point, &mut saved.input_needs_wrap);
+            } else if self.alt {
+                // The cursor is shared by both screens, so it marks where the
+                // primary screen continues, but it stays on the alt screen
+                let mut point = self.cursor.point;
+                let mut input_needs_wrap = self.input_needs_wrap;
+                reflow_grid(&mut self.alt_grid, num_cols, &mut point, &mut input_needs_wrap);
+            } else {
+                reflow_grid(&mut self.grid, num_cols, &mut self.cursor.point, &mut self.input_needs_wrap);
             }
-            self.cursor.point.col = col;
         }
+
+        // Scroll up to keep cursor in terminal
+        if self.cursor.point.line >= num_lines {
+            let lines = self.cursor.point.line - num_lines + 1;
+            self.grid.scroll_up(&(Line(0)..old_lines), lines, &self.cursor.template);
+        }
+
+        // Scroll up the inactive grid as well, using the cursor saved for it
+        let inactive_cursor = if self.alt {
+            self.cursor_save.cursor
+        } else {
+            self.cursor_save_alt.cursor
+        };
+        if inactive_cursor.point.line >= num_lines {
+            let lines = inactive_cursor.point.line - num_lines + 1;
+            self.alt_grid.scroll_up(&(Line(0)..old_lines), lines, &inactive_cursor.template);
+        }
+
+        // Move prompt down when growing if scrollback lines are available
+        if num_lines > old_lines {
+            if self.mode.contains(TermMode::ALT_SCREEN) {
+                let growage = min(num_lines - old_lines, Line(self.alt_grid.scroll_limit()));
+                self.cursor_save.cursor.point.line += growage;
+            } else {
+                let growage = min(num_lines - old_lines, Line(self.grid.scroll_limit()));
+                self.cursor.point.line += growage;
+            }
+        }
+
+        debug!("num_cols, num_lines = {}, {}", num_cols, num_lines);
+
+        // Resize grids to new size
+        self.grid.resize(num_lines, num_cols, &Cell::default());
+        self.alt_grid.resize(num_lines, num_cols, &Cell::default());
+
+        // Reset scrolling region to new size
+        self.scroll_region = Line(0)..self.grid.num_lines();
+
+        // Ensure cursors are in-bounds.
+        self.cursor.point.col = min(self.cursor.point.col, num_cols - 1);
+        self.cursor.point.line = min(self.cursor.point.line, num_lines - 1);
+        for saved in &mut [&mut self.cursor_save, &mut self.cursor_save_alt] {
+            saved.cursor.point.col = min(saved.cursor.point.col, num_cols - 1);
+            saved.cursor.point.line = min(saved.cursor.point.line, num_lines - 1);
+        }
+        let max_line = self.grid.num_lines().0 + self.grid.scroll_limit() - 1;
+        if let Some(ref mut vi_mode) = self.vi_mode {
+            vi_mode.cursor.col = min(vi_mode.cursor.col, num_cols - 1);
+            vi_mode.cursor.line = min(vi_mode.cursor.line, max_line);
+            vi_mode.anchor = None;
+        }
+
+        // Keep existing tab stops, new columns get the default ones
+        let tabspaces = self.tabspaces;
+        self.tabs.truncate(num_cols.0);
+        let old_len = self.tabs.len();
+        self.tabs.extend((old_len..num_cols.0).map(|i| i % tabspaces == 0));
     }
 
-    #[inline]
-    fn move_forward_tabs(&mut self, count: i64) {
-        trace!("[unimplemented] move_forward_tabs: {}", count);
+    /// Set tab stops every `tabspaces` columns
+    fn reset_tabs(&mut self) {
+        self.tabs = IndexRange::from(Column(0)..self.grid.num_cols())
+            .map(|i| (*i as usize) % self.tabspaces == 0)
+            .collect::<Vec<bool>>();
     }
 
-    #[inline]
-    fn save_cursor_position(&mut self) {
-        trace!("CursorSave");
-        let cursor = if self.alt {
-            &mut self.cursor_save_alt
+    /// Mark the cursor line as wrapped and move to the start of the next line
+    fn wrapline(&mut self) {
+        trace!("wrapping");
+
+        self.grid[&self.cursor.point].flags.insert(cell::Flags::WRAPLINE);
+
+        if (self.cursor.point.line + 1) >= self.scroll_region.end {
+            self.linefeed();
         } else {
-            &mut self.cursor_save
-        };
+            self.cursor.point.line += 1;
+        }
 
-        *cursor = self.cursor;
+        self.cursor.point.col = Column(0);
     }
 
-    #[inline]
-    fn restore_cursor_position(&mut self) {
-        trace!("CursorRestore");
-        let source = if self.alt {
-            &self.cursor_save_alt
+    /// One-based cursor position for the cursor position reports
+    ///
+    /// Origin mode reports the line relative to the scrolling region.
+    fn reported_cursor_position(&self) -> (usize, usize) {
+        let pos = self.cursor.point;
+        // The cursor may be above the scrolling region after DECRC or DECSTBM
+        let line = if self.mode.contains(mode::TermMode::ORIGIN) {
+            Line(pos.line.saturating_sub(self.scroll_region.start.0))
         } else {
-            &self.cursor_save
+            pos.line
         };
 
-        self.cursor = *source;
-        self.cursor.point.line = min(self.cursor.point.line, self.grid.num_lines() - 1);
-        self.cursor.point.col = min(self.cursor.point.col, self.grid.num_cols() - 1);
+        (line.0 + 1, pos.col.0 + 1)
     }
 
-    #[inline]
-    fn clear_line(&mut self, mode: ansi::LineClearMode) {
-        trace!("clear_line: {:?}", mode);
-        let mut template = self.cursor.template;
-        template.flags ^= template.flags;
+    /// Clear the other half of a wide char when one of its cells is overwritten
+    fn clear_wide_char(&mut self, point: Point) {
+        if point.col >= self.grid.num_cols() {
+            return;
+        }
 
-        let col =  self.cursor.point.col;
+        let flags = self.grid[&point].flags;
+        let other = if flags.contains(cell::Flags::WIDE_CHAR) {
+            Point { line: point.line, col: point.col + 1 }
+        } else if flags.contains(cell::Flags::WIDE_CHAR_SPACER) && point.col > Column(0) {
+            Point { line: point.line, col: point.col - 1 }
+        } else {
+            return;
+        };
 
-        match mode {
-            ansi::LineClearMode::Right => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[col..] {
-                    cell.reset(&template);
-                }
-            },
-            ansi::LineClearMode::Left => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..=col] {
-                    cell.reset(&template);
-                }
-            },
-            ansi::LineClearMode::All => {
-                let row = &mut self.grid[self.cursor.point.line];
-                for cell in &mut row[..] {
-                    cell.reset(&template);
-                }
-            },
+        if other.col < self.grid.num_cols() {
+            let template = self.blank_line_template();
+            self.grid[&other].reset(&template);
         }
     }
 
-    /// Set the indexed color value
     #[inline]
-    fn set_color(&mut self, index: usize, color: Rgb) {
-        trace!("set_color[{}] = {:?}", index, color);
-        self.colors[index] = color;
-        self.color_modified[index] = true;
+    pub fn size_info(&self) -> &SizeInfo {
+        &self.size_info
+    }
+
+    /// Set the size in pixels of the screen the window is on
+    pub fn set_screen_size(&mut self, width: u32, height: u32) {
+        self.screen_size = Some((width, height));
     }
 
-    /// Reset the indexed color to original value
     #[inline]
-    fn reset_color(&mut self, index: usize) {
-        trace!("reset_color[{}]", index);
-        self.colors[index] = self.original_colors[index];
-        self.color_modified[index] = false;
+    pub fn mode(&self) -> &TermMode {
+        &self.mode
     }
 
-    /// Set the clipboard
     #[inline]
-    fn set_clipboard(&mut self, string: &str)
-    {
-        Clipboard::new()
-            .and_then(|mut clipboard| clipboard.store_primary(string))
-            .unwrap_or_else(|err| {
-                warn!("Error storing selection to clipboard. {}", err);
-            });
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
     }
 
+    /// Replace the template's underline style
     #[inline]
-    fn clear_screen(&mut self, mode: ansi::ClearMode) {
-        trace!("clear_screen: {:?}", mode);
-        let mut template = self.cursor.template;
-        template.flags ^= template.flags;
+    fn set_underline(&mut self, style: cell::Flags) {
+        self.cursor.template.flags.remove(cell::Flags::ALL_UNDERLINES);
+        self.cursor.template.flags.insert(style);
+    }
 
-        match mode {
-            ansi::ClearMode::Below => {
-                for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
-                    cell.reset(&template);
-                }
-                if self.cursor.point.line < self.grid.num_lines() - 1 {
-                    self.grid.region_mut((self.cursor.point.line + 1)..)
-                        .each(|cell| cell.reset(&template));
-                }
-            },
-            ansi::ClearMode::All => {
-                self.grid.region_mut(..).each(|c| c.reset(&template));
-            },
-            ansi::ClearMode::Above => {
-                // If clearing more than one line
-                if self.cursor.point.line > Line(1) {
-                    // Fully clear all lines before the current line
-                    self.grid.region_mut(..self.cursor.point.line)
-                        .each(|cell| cell.reset(&template));
-                }
-                // Clear up to the current column in the current line
-                let end = min(self.cursor.point.col + 1, self.grid.num_cols());
-                for cell in &mut self.grid[self.cursor.point.line][..end] {
-                    cell.reset(&template);
-                }
-            },
-            // If scrollback is implemented, this should clear it
-            ansi::ClearMode::Saved => {
-                self.grid.clear_history();
-            }
+    pub fn swap_alt(&mut self) {
+        if self.alt {
+            let template = &self.cursor.template;
+            self.grid.region_mut(..).each(|c| c.reset(template));
         }
-    }
 
-    #[inline]
-    fn clear_tabs(&mut self, mode: ansi::TabulationClearMode) {
-        trace!("clear_tabs: {:?}", mode);
-        match mode {
-            ansi::TabulationClearMode::Current => {
-                let column = self.cursor.point.col;
-                self.tabs[column.0] = false;
-            },
-            ansi::TabulationClearMode::All => {
-                let len = self.tabs.len();
-                // Safe since false boolean is null, each item occupies only 1
-                // byte, and called on the length of the vec.
-                unsafe {
-                    ::std::ptr::write_bytes(self.tabs.as_mut_ptr(), 0, len);
-                }
-            }
+        // Restore the primary screen's cursor style when leaving the alt screen
+        if self.alt {
+            self.cursor_style = self.cursor_style_save;
+            self.cursor_blinking = self.cursor_blinking_save;
+        } else {
+            self.cursor_style_save = self.cursor_style;
+            self.cursor_blinking_save = self.cursor_blinking;
         }
-    }
 
-    // Reset all important fields in the term struct
-    #[inline]
-    fn reset_state(&mut self) {
-        self.input_needs_wrap = false;
-        self.next_title = None;
-        self.next_mouse_cursor = None;
-        self.alt = false;
-        self.cursor = Default::default();
-        self.active_charset = Default::default();
-        self.mode = Default::default();
-        self.font_size = self.original_font_size;
-        self.next_is_urgent = None;
-        self.cursor_save = Default::default();
-        self.cursor_save_alt = Default::default();
-        self.colors = self.original_colors;
-        self.color_modified = [false; color::COUNT];
-        self.cursor_style = None;
-        self.grid.clear_history();
-        self.grid.region_mut(..).each(|c| c.reset(&Cell::default()));
+        // The alternate screen has no scrollback to search through
+        self.search = None;
+        self.vi_mode = None;
+        self.hovered_url = None;
+
+        self.alt = !self.alt;
+        ::std::mem::swap(&mut self.grid, &mut self.alt_grid);
+
+        // Both screens keep their own kitty keyboard stack
+        ::std::mem::swap(&mut self.keyboard_mode_stack, &mut self.inactive_keyboard_mode_stack);
+        self.update_keyboard_mode();
     }
 
-    #[inline]
-    fn reverse_index(&mut self) {
-        trace!("reverse_index");
-        // if cursor is at the top
-        if self.cursor.point.line == self.scroll_region.start {
-            self.scroll_down(Line(1));
+    /// Screen area covered by a rectangle of the DEC rectangle operations
+    ///
+    /// In origin mode, the lines are relative to the scrolling region and the
+    /// area is limited to it. Returns `None` when the area is empty.
+    fn rectangle_area(&self, area: ansi::Rectangle) -> Option<(Range<Line>, Range<Column>)> {
+        let (y_offset, end) = if self.mode.contains(mode::TermMode::ORIGIN) {
+            (self.scroll_region.start, self.scroll_region.end)
         } else {
-            self.cursor.point.line -= min(self.cursor.point.line, Line(1));
+            (Line(0), self.grid.num_lines())
+        };
+
+        let lines = min(area.top + y_offset, end)..min(area.bottom + y_offset + 1, end);
+        let cols = area.left..min(area.right + 1, self.grid.num_cols());
+
+        if lines.start < lines.end && cols.start < cols.end {
+            Some((lines, cols))
+        } else {
+            None
         }
     }
 
-    /// set a terminal attribute
-    #[inline]
-    fn terminal_attribute(&mut self, attr: Attr) {
-        trace!("Set Attribute: {:?}", attr);
-        match attr {
-            Attr::Foreground(color) => self.cursor.template.fg = color,
-            Attr::Background(color) => self.cursor.template.bg = color,
-            Attr::Reset => {
-                self.cursor.template.fg = Color::Named(NamedColor::Foreground);
-                self.cursor.template.bg = Color::Named(NamedColor::Background);
-                self.cursor.template.flags = cell::Flags::empty();
-            },
-            Attr::Reverse => self.cursor.template.flags.insert(cell::Flags::INVERSE),
-            Attr::CancelReverse => self.cursor.template.flags.remove(cell::Flags::INVERSE),
-            Attr::Bold => self.cursor.template.flags.insert(cell::Flags::BOLD),
-            Attr::CancelBold => self.cursor.template.flags.remove(cell::Flags::BOLD),
-            Attr::Dim => self.cursor.template.flags.insert(cell::Flags::DIM),
-            Attr::CancelBoldDim => self.cursor.template.flags.remove(cell::Flags::BOLD | cell::Flags::DIM),
-            Attr::Italic => self.cursor.template.flags.insert(cell::Flags::ITALIC),
-            Attr::CancelItalic => self.cursor.template.flags.remove(cell::Flags::ITALIC),
-            Attr::Underscore => self.cursor.template.flags.insert(cell::Flags::UNDERLINE),
-            Attr::CancelUnderline => self.cursor.template.flags.remove(cell::Flags::UNDERLINE),
-            Attr::Hidden => self.cursor.template.flags.insert(cell::Flags::HIDDEN),
-            Attr::CancelHidden => self.cursor.template.flags.remove(cell::Flags::HIDDEN),
-            _ => {
-                debug!("Term got unhandled attr: {:?}", attr);
-            }
-        }
+    /// Topmost and bottommost line reachable by relative cursor movement
+    ///
+    /// The cursor stops at the scrolling region margins, unless it's already
+    /// beyond them.
+    fn vertical_bounds(&self) -> (Line, Line) {
+        let line = self.cursor.point.line;
+        let top = if line >= self.scroll_region.start {
+            self.scroll_region.start
+        } else {
+            Line(0)
+        };
+        let bottom = if line < self.scroll_region.end {
+            self.scroll_region.end - 1
+        } else {
+            self.grid.num_lines() - 1
+        };
+
+        (top, bottom)
+    }
+
+    /// Mirror the top of the kitty keyboard stack into the terminal mode
+    fn update_keyboard_mode(&mut self) {
+        let modes = self.keyboard_mode_stack.last().cloned().unwrap_or(KeyboardModes::NO_MODE);
+        self.mode.remove(TermMode::KITTY_KEYBOARD_PROTOCOL);
+        self.mode.insert(TermMode::from(modes));
     }
 
+    /// Scroll screen down
+    ///
+    /// Text moves down; clear at bottom
+    /// Expects origin to be in scroll range.
     #[inline]
-    fn set_mode(&mut self, mode: ansi::Mode) {
-        trace!("set_mode: {:?}", mode);
-        match mode {
-            ansi::Mode::SwapScreenAndSetRestoreCursor => {
-                self.mode.insert(mode::TermMode::ALT_SCREEN);
-                self.save_cursor_position();
-                if !self.alt {
-                    self.swap_alt();
-                }
-                self.save_cursor_position();
-            },
-            ansi::Mode::ShowCursor => self.mode.insert(mode::TermMode::SHOW_CURSOR),
-            ansi::Mode::CursorKeys => self.mode.insert(mode::TermMode::APP_CURSOR),
-            ansi::Mode::ReportMouseClicks => {
-                self.mode.insert(mode::TermMode::MOUSE_REPORT_CLICK);
-                self.set_mouse_cursor(MouseCursor::Arrow);
-            },
-            ansi::Mode::ReportCellMouseMotion => {
-                self.mode.insert(mode::TermMode::MOUSE_DRAG);
-                self.set_mouse_cursor(MouseCursor::Arrow);
-            },
-            ansi::Mode::ReportAllMouseMotion => {
-                self.mode.insert(mode::TermMode::MOUSE_MOTION);
-                self.set_mouse_cursor(MouseCursor::Arrow);
-            },
-            ansi::Mode::ReportFocusInOut => self.mode.insert(mode::TermMode::FOCUS_IN_OUT),
-            ansi::Mode::BracketedPaste => self.mode.insert(mode::TermMode::BRACKETED_PASTE),
-            ansi::Mode::SgrMouse => self.mode.insert(mode::TermMode::SGR_MOUSE),
-            ansi::Mode::LineWrap => self.mode.insert(mode::TermMode::LINE_WRAP),
-            ansi::Mode::LineFeedNewLine => self.mode.insert(mode::TermMode::LINE_FEED_NEW_LINE),
-            ansi::Mode::Origin => self.mode.insert(mode::TermMode::ORIGIN),
-            ansi::Mode::DECCOLM => self.deccolm(),
-            ansi::Mode::Insert => self.mode.insert(mode::TermMode::INSERT), // heh
-            _ => {
-                trace!(".. ignoring set_mode");
-            }
+    fn scroll_down_relative(&mut self, origin: Line, mut lines: Line) {
+        trace!("scroll_down_relative: origin={}, lines={}", origin, lines);
+        lines = min(lines, self.scroll_region.end - self.scroll_region.start);
+        lines = min(lines, self.scroll_region.end - origin);
+
+        // Without scrollback the selected content is lost when scrolling
+        if self.alt {
+            self.grid.selection = None;
+        }
+
+        // Scroll between origin and bottom
+        self.damage.damage_all();
+        let template = self.blank_line_template();
+        self.grid.scroll_down(&(origin..self.scroll_region.end), lines, &template);
+
+        if origin == Line(0) {
+            let display_offset = self.grid.display_offset();
+            self.rotate_vi_mode(-(lines.0 as isize), display_offset);
         }
     }
 
+    /// Scroll screen up
+    ///
+    /// Text moves up; clear at top
+    /// Expects origin to be in scroll range.
     #[inline]
-    fn unset_mode(&mut self,mode: ansi::Mode) {
-        trace!("unset_mode: {:?}", mode);
-        match mode {
-            ansi::Mode::SwapScreenAndSetRestoreCursor => {
-                self.mode.remove(mode::TermMode::ALT_SCREEN);
-                self.restore_cursor_position();
-                if self.alt {
-                    self.swap_alt();
+    fn scroll_up_relative(&mut self, origin: Line, lines: Line) {
+        trace!("scroll_up_relative: origin={}, lines={}", origin, lines);
+        let lines = min(lines, self.scroll_region.end - self.scroll_region.start);
+        let lines = min(lines, self.scroll_region.end - origin);
+
+        // Without scrollback the selected content is lost when scrolling
+        if self.alt {
+            self.grid.selection = None;
+        }
+
+        // Scroll from origin to bottom less number of lines
+        self.damage.damage_all();
+        let display_offset = self.grid.display_offset();
+        let template = self.blank_line_template();
+        self.grid.scroll_up(&(origin..self.scroll_region.end), lines, &template);
+
+        if origin == Line(0) {
+            self.rotate_vi_mode(lines.0 as isize, display_offset);
+        }
+    }
+
+    /// Cell for lines which are scrolled in
+    ///
+    /// New lines have the current background color set, but no text attributes.
+    #[inline]
+    fn blank_line_template(&self) -> Cell {
+        let mut template = self.cursor.template;
+        template.flags = cell::Flags::empty();
+        template
+    }
+
+    fn deccolm(&mut self) {
+        // Setting 132 column font makes no sense, but run the other side effects
+        // Clear scrolling region
+        let scroll_region = Line(0)..self.grid.num_lines();
+        self.set_scrolling_region(scroll_region);
+
+        // Clear grid
+        let template = self.cursor.template;
+        self.grid.region_mut(..).each(|c| c.reset(&template));
+    }
+
+    #[inline]
+    pub fn background_color(&self) -> Rgb {
+        if self.mode.contains(mode::TermMode::REVERSE_VIDEO) {
+            self.colors[NamedColor::Foreground]
+        } else {
+            self.colors[NamedColor::Background]
+        }
+    }
+}
+
+impl ansi::TermInfo for Term {
+    #[inline]
+    fn lines(&self) -> Line {
+        self.grid.num_lines()
+    }
+
+    #[inline]
+    fn cols(&self) -> Column {
+        self.grid.num_cols()
+    }
+}
+
+impl ansi::Handler for Term {
+    /// Set the window title
+    #[inline]
+    fn set_title(&mut self, title: &str) {
+        self.title = Some(title.to_owned());
+
+        if self.dynamic_title {
+            self.next_title = Some(title.to_owned());
+        }
+    }
+
+    #[inline]
+    fn push_title(&mut self) {
+        trace!("push_title: {:?}", self.title);
+        if self.title_stack.len() >= TITLE_STACK_MAX_DEPTH {
+            self.title_stack.remove(0);
+        }
+
+        let title = self.title.clone();
+        self.title_stack.push(title);
+    }
+
+    #[inline]
+    fn pop_title(&mut self) {
+        trace!("pop_title");
+        if let Some(Some(title)) = self.title_stack.pop() {
+            self.set_title(&title);
+        }
+    }
+
+    /// Set the mouse cursor
+    #[inline]
+    fn set_mouse_cursor(&mut self, cursor: MouseCursor) {
+        self.next_mouse_cursor = Some(cursor);
+    }
+
+    /// A character to be displayed
+    #[inline]
+    fn input(&mut self, c: char) {
+        // Zero-width characters are attached to the previously written cell
+        if c.width() == Some(0) {
+            let line = self.cursor.point.line;
+            let mut col = self.cursor.point.col;
+            if !self.input_needs_wrap {
+                // Nothing to combine with at the start of a line
+                if col == Column(0) {
+                    return;
+                }
+                col = col - 1;
+            }
+
+            if self.grid[line][col].flags.contains(cell::Flags::WIDE_CHAR_SPACER) && col > Column(0) {
+                col = col - 1;
+            }
+
+            let id = self.grid[line][col].zerowidth;
+            self.grid[line][col].zerowidth = Some(self.zerowidth.push(id, c));
+
+            if self.zerowidth.needs_collect() {
+                self.collect_zerowidth();
+            }
+            return;
+        }
+
+        // Without auto-wrap, the last cell is overwritten instead
+        if self.input_needs_wrap && self.mode.contains(mode::TermMode::LINE_WRAP) {
+            self.wrapline();
+            self.input_needs_wrap = false;
+        }
+
+        {
+            // Number of cells the char will occupy
+            if let Some(width) = c.width() {
+                // Sigh, borrowck making us check the width twice. Hopefully the
+                // optimizer can fix it.
+                let num_cols = self.grid.num_cols();
+
+                // A wide char never straddles the last column; it moves to the
+                // next line instead, leaving a blank cell behind. Without
+                // auto-wrap it takes up the last two cells of the line.
+                if width == 2 && self.cursor.point.col + 1 >= num_cols {
+                    if !self.mode.contains(mode::TermMode::LINE_WRAP) {
+                        if num_cols < Column(2) {
+                            return;
+                        }
+
+                        self.cursor.point.col = num_cols - 2;
+                    } else {
+                        let point = self.cursor.point;
+                        self.clear_wide_char(point);
+                        let template = self.cursor.template;
+                        self.grid[&point].reset(&template);
+                        self.wrapline();
+                    }
+                }
+
+                // Overwriting either half of a wide char clears the other half
+                if !self.mode.contains(mode::TermMode::INSERT) {
+                    let point = self.cursor.point;
+                    self.clear_wide_char(point);
+                    if width == 2 {
+                        self.clear_wide_char(Point { line: point.line, col: point.col + 1 });
+                    }
+                }
+
+                {
+                    // If in insert mode, first shift cells to the right.
+                    if self.mode.contains(mode::TermMode::INSERT) && self.cursor.point.col + width < num_cols {
+                        let line = self.cursor.point.line; // borrowck
+                        let col = self.cursor.point.col;
+                        let line = &mut self.grid[line];
+
+                        let src = line[col..].as_ptr();
+                        let dst = line[(col + width)..].as_mut_ptr();
+                        unsafe {
+                            // memmove
+                            ptr::copy(src, dst, (num_cols - col - width).0);
+                        }
+                    }
+
+                    let cell = &mut self.grid[&self.cursor.point];
+                    *cell = self.cursor.template;
+                    cell.c = self.cursor.charsets[self.active_charset].map(c);
+                    cell.hyperlink = self.hyperlink;
+
+                    // Handle wide chars
+                    if width == 2 {
+                        cell.flags.insert(cell::Flags::WIDE_CHAR);
+                    }
+                }
+
+                // Set spacer cell for wide chars.
+                if width == 2 && self.cursor.point.col + 1 < num_cols {
+                    self.cursor.point.col += 1;
+                    let spacer = &mut self.grid[&self.cursor.point];
+                    *spacer = self.cursor.template;
+                    spacer.flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+                    spacer.hyperlink = self.hyperlink;
                 }
-                self.restore_cursor_position();
-            },
-            ansi::Mode::ShowCursor => self.mode.remove(mode::TermMode::SHOW_CURSOR),
-            ansi::Mode::CursorKeys => self.mode.remove(mode::TermMode::APP_CURSOR),
-            ansi::Mode::ReportMouseClicks => {
-                self.mode.remove(mode::TermMode::MOUSE_REPORT_CLICK);
-                self.set_mouse_cursor(MouseCursor::Text);
-            },
-            ansi::Mode::ReportCellMouseMotion => {
-                self.mode.remove(mode::TermMode::MOUSE_DRAG);
-                self.set_mouse_cursor(MouseCursor::Text);
-            },
-            ansi::Mode::ReportAllMouseMotion => {
-                self.mode.remove(mode::TermMode::MOUSE_MOTION);
-                self.set_mouse_cursor(MouseCursor::Text);
-            },
-            ansi::Mode::ReportFocusInOut => self.mode.remove(mode::TermMode::FOCUS_IN_OUT),
-            ansi::Mode::BracketedPaste => self.mode.remove(mode::TermMode::BRACKETED_PASTE),
-            ansi::Mode::SgrMouse => self.mode.remove(mode::TermMode::SGR_MOUSE),
-            ansi::Mode::LineWrap => self.mode.remove(mode::TermMode::LINE_WRAP),
-            ansi::Mode::LineFeedNewLine => self.mode.remove(mode::TermMode::LINE_FEED_NEW_LINE),
-            ansi::Mode::Origin => self.mode.remove(mode::TermMode::ORIGIN),
-            ansi::Mode::DECCOLM => self.deccolm(),
-            ansi::Mode::Insert => self.mode.remove(mode::TermMode::INSERT),
-            _ => {
-                trace!(".. ignoring unset_mode");
             }
         }
+
+        if (self.cursor.point.col + 1) < self.grid.num_cols() {
+            self.cursor.point.col += 1;
+        } else {
+            self.input_needs_wrap = true;
+        }
+
     }
 
+    /// Repeat a character, stopping at the end of a custom scrolling region
     #[inline]
-    fn set_scrolling_region(&mut self, region: Range<Line>) {
-        trace!("set scroll region: {:?}", region);
-        self.scroll_region.start = min(region.start, self.grid.num_lines());
-        self.scroll_region.end = min(region.end, self.grid.num_lines());
-        self.goto(Line(0), Column(0));
+    fn repeat(&mut self, c: char, mut count: usize) {
+        trace!("repeat: {:?} x {}", c, count);
+        let num_lines = self.grid.num_lines();
+        let num_cols = self.grid.num_cols().0;
+        let point = self.cursor.point;
+        let region = self.scroll_region.clone();
+
+        if region != (Line(0)..num_lines) && region.contains_(point.line) {
+            // Cells left until the bottom right corner of the region
+            let mut available = (region.end.0 - point.line.0 - 1) * num_cols + num_cols - point.col.0;
+            if self.input_needs_wrap {
+                available -= 1;
+            }
+
+            let width = c.width().unwrap_or(1).max(1);
+            count = min(count, available / width);
+        }
+
+        for _ in 0..count {
+            self.input(c);
+        }
     }
 
     #[inline]
-    fn set_keypad_application_mode(&mut self) {
-        trace!("set mode::TermMode::APP_KEYPAD");
-        self.mode.insert(mode::TermMode::APP_KEYPAD);
+    fn dectest(&mut self) {
+        trace!("dectest");
+        let mut template = self.cursor.template;
+        template.c = 'E';
+
+        self.grid.region_mut(..)
+            .each(|c| c.reset(&template));
     }
 
     #[inline]
-    fn unset_keypad_application_mode(&mut self) {
-        trace!("unset mode::TermMode::APP_KEYPAD");
-        self.mode.remove(mode::TermMode::APP_KEYPAD);
+    fn goto(&mut self, line: Line, col: Column) {
+        trace!("goto: line={}, col={}", line, col);
+        let (y_offset, max_y) = if self.mode.contains(mode::TermMode::ORIGIN) {
+            (self.scroll_region.start, self.scroll_region.end - 1)
+        } else {
+            (Line(0), self.grid.num_lines() - 1)
+        };
+
+        self.cursor.point.line = min(line + y_offset, max_y);
+        self.cursor.point.col = min(col, self.grid.num_cols() - 1);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn goto_line(&mut self, line: Line) {
+        trace!("goto_line: {}", line);
+        let col = self.cursor.point.col; // borrowck
+        self.goto(line, col)
+    }
+
+    #[inline]
+    fn goto_col(&mut self, col: Column) {
+        trace!("goto_col: {}", col);
+        self.cursor.point.col = min(col, self.grid.num_cols() - 1);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn insert_blank(&mut self, count: Column) {
+        // Ensure inserting within terminal bounds
+
+        let count = min(count, self.size_info.cols() - self.cursor.point.col);
+
+        let source = self.cursor.point.col;
+        let destination = self.cursor.point.col + count;
+        let num_cells = (self.size_info.cols() - destination).0;
+
+        // Inserting in the middle of a wide char splits it
+        let point = self.cursor.point;
+        if self.grid[&point].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+            self.clear_wide_char(point);
+            let template = self.cursor.template;
+            self.grid[&point].reset(&template);
+        }
+
+        // Blanks only keep the current background color
+        let mut template = self.cursor.template;
+        template.flags = cell::Flags::empty();
+
+        let line = self.cursor.point.line; // borrowck
+        let line = &mut self.grid[line];
+
+        unsafe {
+            let src = line[source..].as_ptr();
+            let dst = line[destination..].as_mut_ptr();
+
+            ptr::copy(src, dst, num_cells);
+        }
+
+        // Cells were just moved out towards the end of the line; fill in
+        // between source and dest with blanks.
+        for c in &mut line[source..destination] {
+            c.reset(&template);
+        }
+
+        // A wide char pushed against the edge lost its spacer
+        let last = self.size_info.cols() - 1;
+        if line[last].flags.contains(cell::Flags::WIDE_CHAR) {
+            line[last].reset(&template);
+        }
+    }
+
+    #[inline]
+    fn move_up(&mut self, lines: Line) {
+        trace!("move_up: {}", lines);
+        let (top, _) = self.vertical_bounds();
+        self.cursor.point.line = max(Line(self.cursor.point.line.0.saturating_sub(lines.0)), top);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn move_down(&mut self, lines: Line) {
+        trace!("move_down: {}", lines);
+        let (_, bottom) = self.vertical_bounds();
+        self.cursor.point.line = min(self.cursor.point.line + lines, bottom);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn report_mode<W: io::Write>(&mut self, writer: &mut W, mode: ansi::Mode, number: i64) {
+        trace!("report_mode: {:?} ({})", mode, number);
+        let term_mode = self.mode;
+        let flag = |flag| Some(term_mode.contains(flag));
+        let is_set = match mode {
+            ansi::Mode::CursorKeys => flag(mode::TermMode::APP_CURSOR),
+            ansi::Mode::Insert => flag(mode::TermMode::INSERT),
+            ansi::Mode::ReverseVideo => flag(mode::TermMode::REVERSE_VIDEO),
+            ansi::Mode::Origin => flag(mode::TermMode::ORIGIN),
+            ansi::Mode::LineWrap => flag(mode::TermMode::LINE_WRAP),
+            ansi::Mode::ReverseWrap => flag(mode::TermMode::REVERSE_WRAP),
+            ansi::Mode::LineFeedNewLine => flag(mode::TermMode::LINE_FEED_NEW_LINE),
+            ansi::Mode::ShowCursor => flag(mode::TermMode::SHOW_CURSOR),
+            ansi::Mode::ReportMouseClicks => flag(mode::TermMode::MOUSE_REPORT_CLICK),
+            ansi::Mode::ReportCellMouseMotion => flag(mode::TermMode::MOUSE_DRAG),
+            ansi::Mode::ReportAllMouseMotion => flag(mode::TermMode::MOUSE_MOTION),
+            ansi::Mode::ReportFocusInOut => flag(mode::TermMode::FOCUS_IN_OUT),
+            ansi::Mode::Utf8Mouse => flag(mode::TermMode::UTF8_MOUSE),
+            ansi::Mode::SgrMouse => flag(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => flag(mode::TermMode::URXVT_MOUSE),
+            ansi::Mode::SwapScreen | ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                flag(mode::TermMode::ALT_SCREEN)
+            },
+            ansi::Mode::BracketedPaste => flag(mode::TermMode::BRACKETED_PASTE),
+            ansi::Mode::SyncUpdate => Some(self.sync_update_start.is_some()),
+            ansi::Mode::DECCOLM | ansi::Mode::BlinkingCursor => None,
+        };
+
+        // 0 = not recognized, 1 = set, 2 = reset
+        let value = match is_set {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0,
+        };
+
+        let prefix = match mode {
+            ansi::Mode::Insert | ansi::Mode::LineFeedNewLine => "",
+            _ => "?",
+        };
+        let _ = write!(writer, "\x1b[{}{};{}$y", prefix, number, value);
+    }
+
+    #[inline]
+    fn text_area_size_pixels<W: io::Write>(&mut self, writer: &mut W) {
+        let width = self.size_info.cell_width * self.grid.num_cols().0 as f32;
+        let height = self.size_info.cell_height * self.grid.num_lines().0 as f32;
+        let _ = write!(writer, "\x1b[4;{};{}t", height as usize, width as usize);
+    }
+
+    #[inline]
+    fn cell_size_pixels<W: io::Write>(&mut self, writer: &mut W) {
+        let width = self.size_info.cell_width as usize;
+        let height = self.size_info.cell_height as usize;
+        let _ = write!(writer, "\x1b[6;{};{}t", height, width);
+    }
+
+    /// Falls back to the size of the text area while the screen is unknown
+    #[inline]
+    fn screen_size_chars<W: io::Write>(&mut self, writer: &mut W) {
+        let (lines, cols) = match self.screen_size {
+            Some((width, height)) => (
+                (height as f32 / self.size_info.cell_height) as usize,
+                (width as f32 / self.size_info.cell_width) as usize,
+            ),
+            None => (self.grid.num_lines().0, self.grid.num_cols().0),
+        };
+        let _ = write!(writer, "\x1b[9;{};{}t", lines, cols);
+    }
+
+    #[inline]
+    fn move_forward(&mut self, cols: Column) {
+        trace!("move_forward: {}", cols);
+        self.cursor.point.col = min(self.cursor.point.col + cols, self.grid.num_cols() - 1);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn move_backward(&mut self, cols: Column) {
+        trace!("move_backward: {}", cols);
+        self.cursor.point.col -= min(self.cursor.point.col, cols);
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn identify_terminal<W: io::Write>(&mut self, writer: &mut W, intermediate: Option<char>) {
+        match intermediate {
+            // DA1 - VT220 with ANSI colors
+            None => {
+                let _ = writer.write_all(b"\x1b[?62;22c");
+            },
+            // DA2 - VT220 followed by the version, without ROM cartridge
+            Some('>') => {
+                let _ = write!(writer, "\x1b[>1;{};0c", version_number());
+            },
+            Some(c) => debug!("unknown device attributes request: {}", c),
+        }
+    }
+
+    #[inline]
+    fn device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
+        trace!("device status: {}", arg);
+        match arg {
+            5 => {
+                let _ = writer.write_all(b"\x1b[0n");
+            },
+            6 => {
+                let (line, col) = self.reported_cursor_position();
+                let _ = write!(writer, "\x1b[{};{}R", line, col);
+            },
+            _ => debug!("unknown device status query: {}", arg),
+        };
+    }
+
+    #[inline]
+    fn private_device_status<W: io::Write>(&mut self, writer: &mut W, arg: usize) {
+        trace!("private device status: {}", arg);
+        match arg {
+            // DECXCPR - Like CPR, but including the page which is always the first
+            6 => {
+                let (line, col) = self.reported_cursor_position();
+                let _ = write!(writer, "\x1b[?{};{};1R", line, col);
+            },
+            _ => debug!("unknown private device status query: {}", arg),
+        };
+    }
+
+    #[inline]
+    fn move_down_and_cr(&mut self, lines: Line) {
+        trace!("move_down_and_cr: {}", lines);
+        self.move_down(lines);
+        self.cursor.point.col = Column(0);
+    }
+
+    #[inline]
+    fn move_up_and_cr(&mut self, lines: Line) {
+        trace!("move_up_and_cr: {}", lines);
+        self.move_up(lines);
+        self.cursor.point.col = Column(0);
+    }
+
+    #[inline]
+    fn put_tab(&mut self, mut count: i64) {
+        trace!("put_tab: {}", count);
+
+        let mut col = self.cursor.point.col;
+        while col < self.grid.num_cols() && count != 0 {
+            count -= 1;
+            loop {
+                if (col + 1) == self.grid.num_cols() {
+                    break;
+                }
+
+                col += 1;
+
+                if self.tabs[*col as usize] {
+                    break;
+                }
+            }
+        }
+
+        self.cursor.point.col = col;
+        self.input_needs_wrap = false;
+    }
+
+    /// Backspace `count` characters
+    #[inline]
+    fn backspace(&mut self) {
+        trace!("backspace");
+        if self.cursor.point.col > Column(0) {
+            self.cursor.point.col -= 1;
+            self.input_needs_wrap = false;
+            return;
+        }
+
+        // Like in xterm, reverse-wraparound only applies while auto-wrap is enabled
+        let reverse_wrap = mode::TermMode::REVERSE_WRAP | mode::TermMode::LINE_WRAP;
+        let (top, _) = self.vertical_bounds();
+        if self.mode.contains(reverse_wrap) && self.cursor.point.line > top {
+            self.cursor.point.line -= 1;
+            self.cursor.point.col = self.grid.num_cols() - 1;
+            self.input_needs_wrap = false;
+        }
+    }
+
+    /// Carriage return
+    #[inline]
+    fn carriage_return(&mut self) {
+        trace!("carriage_return");
+        self.cursor.point.col = Column(0);
+        self.input_needs_wrap = false;
+    }
+
+    /// Linefeed
+    #[inline]
+    fn linefeed(&mut self) {
+        trace!("linefeed");
+        let next = self.cursor.point.line + 1;
+        if next == self.scroll_region.end {
+            let origin = self.scroll_region.start;
+            self.scroll_up_relative(origin, Line(1));
+        } else if next < self.grid.num_lines() {
+            self.cursor.point.line += 1;
+        }
+    }
+
+    /// Set current position as a tabstop
+    #[inline]
+    fn bell(&mut self) {
+        trace!("bell");
+        self.visual_bell.ring();
+        if self.bell_urgent {
+            self.next_is_urgent = Some(true);
+        }
+        self.bell_rung = true;
+    }
+
+    #[inline]
+    fn substitute(&mut self) {
+        trace!("[unimplemented] substitute");
+    }
+
+    /// Run LF/NL
+    ///
+    /// LF/NL mode has some interesting history. According to ECMA-48 4th
+    /// edition, in LINE FEED mode,
+    ///
+    /// > The execution of the formatter functions LINE FEED (LF), FORM FEED
+    /// (FF), LINE TABULATION (VT) cause only movement of the active position in
+    /// the direction of the line progression.
+    ///
+    /// In NEW LINE mode,
+    ///
+    /// > The execution of the formatter functions LINE FEED (LF), FORM FEED
+    /// (FF), LINE TABULATION (VT) cause movement to the line home position on
+    /// the following line, the following form, etc. In the case of LF this is
+    /// referred to as the New Line (NL) option.
+    ///
+    /// Additionally, ECMA-48 4th edition says that this option is deprecated.
+    /// ECMA-48 5th edition only mentions this option (without explanation)
+    /// saying that it's been removed.
+    ///
+    /// As an emulator, we need to support it since applications may still rely
+    /// on it.
+    #[inline]
+    fn newline(&mut self) {
+        self.linefeed();
+
+        if self.mode.contains(mode::TermMode::LINE_FEED_NEW_LINE) {
+            self.carriage_return();
+        }
+    }
+
+    #[inline]
+    fn set_horizontal_tabstop(&mut self) {
+        trace!("set_horizontal_tabstop");
+        let column = self.cursor.point.col;
+        self.tabs[column.0] = true;
+    }
+
+    /// Scroll the content of the scroll region up
+    ///
+    /// Unlike a linefeed at the bottom of the region, this doesn't add the
+    /// lines scrolled out of the region to the scrollback history.
+    #[inline]
+    fn scroll_up(&mut self, lines: Line) {
+        trace!("scroll_up: {}", lines);
+        let lines = min(lines, self.scroll_region.end - self.scroll_region.start);
+
+        // Without scrollback the selected content is lost when scrolling
+        if self.alt {
+            self.grid.selection = None;
+        }
+
+        self.damage.damage_all();
+        let template = self.blank_line_template();
+        self.grid.scroll_region_up(&self.scroll_region, lines, &template);
+    }
+
+    #[inline]
+    fn scroll_down(&mut self, lines: Line) {
+        let origin = self.scroll_region.start;
+        self.scroll_down_relative(origin, lines);
+    }
+
+    /// Insert blank lines at the cursor, moving the cursor to the first column
+    #[inline]
+    fn insert_blank_lines(&mut self, lines: Line) {
+        trace!("insert_blank_lines: {}", lines);
+        if self.scroll_region.contains_(self.cursor.point.line) {
+            let origin = self.cursor.point.line;
+            self.scroll_down_relative(origin, lines);
+            self.carriage_return();
+        }
+    }
+
+    /// Delete lines at the cursor, moving the cursor to the first column
+    #[inline]
+    fn delete_lines(&mut self, lines: Line) {
+        trace!("delete_lines: {}", lines);
+        if self.scroll_region.contains_(self.cursor.point.line) {
+            let origin = self.cursor.point.line;
+            let lines = min(lines, self.scroll_region.end - origin);
+
+            // Without scrollback the selected content is lost when scrolling
+            if self.alt {
+                self.grid.selection = None;
+            }
+
+            // Deleted lines are discarded instead of being added to the history
+            let template = self.blank_line_template();
+            self.grid.scroll_region_up(&(origin..self.scroll_region.end), lines, &template);
+            self.carriage_return();
+        }
+    }
+
+    #[inline]
+    fn erase_chars(&mut self, count: Column) {
+        trace!("erase_chars: {}, {}", count, self.cursor.point.col);
+        let start = self.cursor.point.col;
+        let end = min(start + count, self.grid.num_cols());
+
+        // Wide chars cut by the erased range are cleared entirely
+        let line = self.cursor.point.line;
+        if start < end {
+            self.clear_wide_char(Point { line, col: start });
+            self.clear_wide_char(Point { line, col: end - 1 });
+        }
+
+        // Cleared cells have current background color set, but no text attributes
+        let mut template = self.cursor.template;
+        template.flags = cell::Flags::empty();
+
+        let row = &mut self.grid[self.cursor.point.line];
+        for c in &mut row[start..end] {
+            c.reset(&template);
+        }
+    }
+
+    #[inline]
+    fn delete_chars(&mut self, count: Column) {
+        // Ensure deleting within terminal bounds
+        let count = min(count, self.size_info.cols() - self.cursor.point.col);
+
+        let start = self.cursor.point.col;
+        let end = start + count;
+        let n = (self.size_info.cols() - end).0;
+
+        // Deleting half of a wide char clears the remaining half
+        let point = self.cursor.point;
+        if self.grid[&point].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+            self.clear_wide_char(point);
+        }
+        if end < self.size_info.cols() {
+            let point = Point { line: point.line, col: end };
+            if self.grid[&point].flags.contains(cell::Flags::WIDE_CHAR_SPACER) {
+                let template = self.cursor.template;
+                self.grid[&point].reset(&template);
+            }
+        }
+
+        // Blanks only keep the current background color
+        let mut template = self.cursor.template;
+        template.flags = cell::Flags::empty();
+
+        let line = self.cursor.point.line; // borrowck
+        let line = &mut self.grid[line];
+
+        unsafe {
+            let src = line[end..].as_ptr();
+            let dst = line[start..].as_mut_ptr();
+
+            ptr::copy(src, dst, n);
+        }
+
+        // Clear last `count` cells in line. If deleting 1 char, need to delete
+        // 1 cell.
+        let end = self.size_info.cols() - count;
+        for c in &mut line[end..] {
+            c.reset(&template);
+        }
+    }
+
+    #[inline]
+    fn move_backward_tabs(&mut self, count: i64) {
+        trace!("move_backward_tabs: {}", count);
+
+        for _ in 0..count {
+            // Without a tab stop before the cursor, stop at the first column
+            let mut col = index::Column(0);
+            for i in (0..(self.cursor.point.col.0)).rev() {
+                if self.tabs[i as usize] {
+                    col = index::Column(i);
+                    break;
+                }
+            }
+            self.cursor.point.col = col;
+        }
+        self.input_needs_wrap = false;
+    }
+
+    #[inline]
+    fn move_forward_tabs(&mut self, count: i64) {
+        trace!("move_forward_tabs: {}", count);
+        self.put_tab(count);
+    }
+
+    #[inline]
+    fn save_cursor_position(&mut self) {
+        trace!("CursorSave");
+        let saved = SavedCursor {
+            cursor: self.cursor,
+            active_charset: self.active_charset,
+            origin: self.mode.contains(mode::TermMode::ORIGIN),
+            input_needs_wrap: self.input_needs_wrap,
+        };
+
+        if self.alt {
+            self.cursor_save_alt = saved;
+        } else {
+            self.cursor_save = saved;
+        }
+    }
+
+    #[inline]
+    fn restore_cursor_position(&mut self) {
+        trace!("CursorRestore");
+        let saved = if self.alt {
+            self.cursor_save_alt
+        } else {
+            self.cursor_save
+        };
+
+        self.cursor = saved.cursor;
+        self.active_charset = saved.active_charset;
+        self.mode.set(mode::TermMode::ORIGIN, saved.origin);
+        self.cursor.point.line = min(self.cursor.point.line, self.grid.num_lines() - 1);
+        self.cursor.point.col = min(self.cursor.point.col, self.grid.num_cols() - 1);
+
+        // The pending wrap is only kept while the cursor is still in the last column
+        self.input_needs_wrap = saved.input_needs_wrap
+            && saved.cursor.point.col == self.cursor.point.col
+            && self.cursor.point.col == self.grid.num_cols() - 1;
+    }
+
+    #[inline]
+    fn clear_line(&mut self, mode: ansi::LineClearMode) {
+        trace!("clear_line: {:?}", mode);
+        let template = self.blank_line_template();
+        self.input_needs_wrap = false;
+
+        let col =  self.cursor.point.col;
+
+        match mode {
+            ansi::LineClearMode::Right => {
+                let point = self.cursor.point;
+                self.clear_wide_char(point);
+                let row = &mut self.grid[self.cursor.point.line];
+                for cell in &mut row[col..] {
+                    cell.reset(&template);
+                }
+            },
+            ansi::LineClearMode::Left => {
+                let point = self.cursor.point;
+                self.clear_wide_char(point);
+                let row = &mut self.grid[self.cursor.point.line];
+                for cell in &mut row[..=col] {
+                    cell.reset(&template);
+                }
+            },
+            ansi::LineClearMode::All => {
+                let row = &mut self.grid[self.cursor.point.line];
+                for cell in &mut row[..] {
+                    cell.reset(&template);
+                }
+            },
+        }
+    }
+
+    /// Set the indexed color value
+    #[inline]
+    fn set_color(&mut self, index: usize, color: Rgb) {
+        trace!("set_color[{}] = {:?}", index, color);
+        self.colors[index] = color;
+        self.color_modified[index] = true;
+        self.damage.damage_all();
+    }
+
+    /// Reset the indexed color to original value
+    #[inline]
+    fn reset_color(&mut self, index: usize) {
+        trace!("reset_color[{}]", index);
+        self.colors[index] = self.original_colors[index];
+        self.color_modified[index] = false;
+        self.damage.damage_all();
+    }
+
+    /// Start a hyperlink, or end the current one
+    #[inline]
+    fn set_hyperlink(&mut self, link: Option<ansi::Hyperlink>) {
+        trace!("Setting hyperlink: {:?}", link);
+        let id = match link {
+            Some(link) => Some(self.hyperlinks.intern(link)),
+            None => None,
+        };
+        self.hyperlink = id;
+
+        if self.hyperlinks.needs_collect() {
+            self.collect_hyperlinks();
+        }
+    }
+
+    /// Set the clipboard
+    #[inline]
+    fn set_clipboard(&mut self, clipboard: u8, string: &str) {
+        trace!("set_clipboard: {}", clipboard as char);
+        if self.osc52 == Osc52::Disabled {
+            return;
+        }
+
+        if string.len() > self.osc52_max_bytes {
+            warn!("Ignoring clipboard request of {} bytes", string.len());
+            return;
+        }
+
+        let result = Clipboard::new().and_then(|mut clipboard_ctx| match clipboard {
+            b'c' => clipboard_ctx.store_primary(string),
+            b'p' | b's' => clipboard_ctx.store_selection(string),
+            _ => Ok(()),
+        });
+
+        result.unwrap_or_else(|err| {
+            warn!("Error storing selection to clipboard. {}", err);
+        });
+    }
+
+    #[inline]
+    fn write_clipboard<W: io::Write>(&mut self, clipboard: u8, writer: &mut W) {
+        trace!("write_clipboard: {}", clipboard as char);
+        if self.osc52 == Osc52::Disabled {
+            return;
+        }
+
+        // Without permission applications only receive an empty reply
+        let mut contents = String::new();
+        if self.osc52 == Osc52::CopyPaste {
+            let loaded = Clipboard::new().and_then(|clipboard_ctx| match clipboard {
+                b'c' => clipboard_ctx.load_primary(),
+                b'p' | b's' => clipboard_ctx.load_selection(),
+                _ => Ok(String::new()),
+            });
+
+            match loaded {
+                Ok(loaded) => contents = loaded,
+                Err(err) => warn!("Error loading data from clipboard. {}", err),
+            }
+        }
+
+        let _ = write!(writer, "\x1b]52;{};{}\x07", clipboard as char, base64::encode(&contents));
+    }
+
+    #[inline]
+    fn clear_screen(&mut self, mode: ansi::ClearMode) {
+        trace!("clear_screen: {:?}", mode);
+        let template = self.blank_line_template();
+        self.input_needs_wrap = false;
+
+        match mode {
+            ansi::ClearMode::Below => {
+                let point = self.cursor.point;
+                self.clear_wide_char(point);
+                for cell in &mut self.grid[self.cursor.point.line][self.cursor.point.col..] {
+                    cell.reset(&template);
+                }
+                if self.cursor.point.line < self.grid.num_lines() - 1 {
+                    self.grid.region_mut((self.cursor.point.line + 1)..)
+                        .each(|cell| cell.reset(&template));
+                }
+            },
+            ansi::ClearMode::All => {
+                self.grid.region_mut(..).each(|c| c.reset(&template));
+            },
+            ansi::ClearMode::Above => {
+                let point = self.cursor.point;
+                self.clear_wide_char(point);
+
+                if self.cursor.point.line > Line(0) {
+                    // Fully clear all lines before the current line
+                    self.grid.region_mut(..self.cursor.point.line)
+                        .each(|cell| cell.reset(&template));
+                }
+                // Clear up to the current column in the current line
+                let end = min(self.cursor.point.col + 1, self.grid.num_cols());
+                for cell in &mut self.grid[self.cursor.point.line][..end] {
+                    cell.reset(&template);
+                }
+            },
+            ansi::ClearMode::Saved => {
+                self.grid.clear_history();
+            }
+        }
+    }
+
+    #[inline]
+    fn clear_tabs(&mut self, mode: ansi::TabulationClearMode) {
+        trace!("clear_tabs: {:?}", mode);
+        match mode {
+            ansi::TabulationClearMode::Current => {
+                let column = self.cursor.point.col;
+                self.tabs[column.0] = false;
+            },
+            ansi::TabulationClearMode::All => {
+                for tab in &mut self.tabs {
+                    *tab = false;
+                }
+            }
+        }
+    }
+
+    /// RIS - Reset all important fields in the term struct
+    ///
+    /// Both screens and the scrollback history are cleared and everything else
+    /// returns to the state of a freshly created terminal.
+    #[inline]
+    fn reset_state(&mut self) {
+        trace!("reset_state");
+        if self.alt {
+            self.swap_alt();
+        }
+
+        self.input_needs_wrap = false;
+        self.next_title = None;
+        self.title_stack.clear();
+        self.sync_update_start = None;
+        self.search = None;
+        self.vi_mode = None;
+        self.hovered_url = None;
+        self.hyperlinks.clear();
+        self.hyperlink = None;
+        self.zerowidth.clear();
+        self.next_mouse_cursor = None;
+        self.cursor = Default::default();
+        self.active_charset = Default::default();
+        self.mode = Default::default();
+        self.scroll_region = Line(0)..self.grid.num_lines();
+        self.font_size = self.original_font_size;
+        self.next_is_urgent = None;
+        self.cursor_save = Default::default();
+        self.cursor_save_alt = Default::default();
+        self.alt_saved_cursor = false;
+        self.colors = self.original_colors;
+        self.color_modified = [false; color::COUNT];
+        self.cursor_style = None;
+        self.cursor_style_save = None;
+        self.cursor_blinking = false;
+        self.cursor_blinking_save = false;
+        self.keyboard_mode_stack.clear();
+        self.inactive_keyboard_mode_stack.clear();
+        self.reset_tabs();
+        self.grid.selection = None;
+        self.alt_grid.selection = None;
+        self.grid.clear_history();
+        self.grid.region_mut(..).each(|c| c.reset(&Cell::default()));
+        self.alt_grid.region_mut(..).each(|c| c.reset(&Cell::default()));
+        self.dirty = true;
+    }
+
+    /// DECSTR - Reset modes and cursor state without touching the grid content
+    ///
+    /// The cursor keeps its position, but loses its attributes and charsets.
+    /// The auto-wrap and reverse-wraparound modes are left untouched.
+    #[inline]
+    fn soft_reset(&mut self) {
+        trace!("soft_reset");
+        self.input_needs_wrap = false;
+        self.cursor = Cursor { point: self.cursor.point, ..Default::default() };
+        self.active_charset = Default::default();
+        self.cursor_save = Default::default();
+        self.cursor_save_alt = Default::default();
+        self.cursor_style = None;
+        self.cursor_blinking = false;
+        self.mode.remove(
+            mode::TermMode::ORIGIN
+                | mode::TermMode::INSERT
+                | mode::TermMode::APP_CURSOR
+                | mode::TermMode::APP_KEYPAD
+        );
+        self.mode.insert(mode::TermMode::SHOW_CURSOR);
+        self.scroll_region = Line(0)..self.grid.num_lines();
+        self.reset_tabs();
+    }
+
+    #[inline]
+    fn reverse_index(&mut self) {
+        trace!("reverse_index");
+        // if cursor is at the top
+        if self.cursor.point.line == self.scroll_region.start {
+            self.scroll_down(Line(1));
+        } else {
+            self.cursor.point.line -= min(self.cursor.point.line, Line(1));
+        }
+    }
+
+    /// set a terminal attribute
+    #[inline]
+    fn terminal_attribute(&mut self, attr: Attr) {
+        trace!("Set Attribute: {:?}", attr);
+        match attr {
+            Attr::Foreground(color) => self.cursor.template.fg = color,
+            Attr::Background(color) => self.cursor.template.bg = color,
+            Attr::UnderlineColor(color) => self.cursor.template.underline_color = color,
+            Attr::Reset => {
+                self.cursor.template.fg = Color::Named(NamedColor::Foreground);
+                self.cursor.template.bg = Color::Named(NamedColor::Background);
+                self.cursor.template.underline_color = None;
+                self.cursor.template.flags = cell::Flags::empty();
+            },
+            Attr::Reverse => self.cursor.template.flags.insert(cell::Flags::INVERSE),
+            Attr::CancelReverse => self.cursor.template.flags.remove(cell::Flags::INVERSE),
+            Attr::Bold => self.cursor.template.flags.insert(cell::Flags::BOLD),
+            Attr::CancelBold => self.cursor.template.flags.remove(cell::Flags::BOLD),
+            Attr::Dim => self.cursor.template.flags.insert(cell::Flags::DIM),
+            Attr::CancelBoldDim => self.cursor.template.flags.remove(cell::Flags::BOLD | cell::Flags::DIM),
+            Attr::Italic => self.cursor.template.flags.insert(cell::Flags::ITALIC),
+            Attr::CancelItalic => self.cursor.template.flags.remove(cell::Flags::ITALIC),
+            Attr::Underscore => self.set_underline(cell::Flags::UNDERLINE),
+            Attr::DoubleUnderline => self.set_underline(cell::Flags::DOUBLE_UNDERLINE),
+            Attr::CurlyUnderline => self.set_underline(cell::Flags::CURLY_UNDERLINE),
+            Attr::DottedUnderline => self.set_underline(cell::Flags::DOTTED_UNDERLINE),
+            Attr::DashedUnderline => self.set_underline(cell::Flags::DASHED_UNDERLINE),
+            Attr::CancelUnderline => self.cursor.template.flags.remove(cell::Flags::ALL_UNDERLINES),
+            Attr::BlinkSlow | Attr::BlinkFast => self.cursor.template.flags.insert(cell::Flags::BLINK),
+            Attr::CancelBlink => self.cursor.template.flags.remove(cell::Flags::BLINK),
+            Attr::Strike => self.cursor.template.flags.insert(cell::Flags::STRIKEOUT),
+            Attr::CancelStrike => self.cursor.template.flags.remove(cell::Flags::STRIKEOUT),
+            Attr::Overline => self.cursor.template.flags.insert(cell::Flags::OVERLINE),
+            Attr::CancelOverline => self.cursor.template.flags.remove(cell::Flags::OVERLINE),
+            Attr::Hidden => self.cursor.template.flags.insert(cell::Flags::HIDDEN),
+            Attr::CancelHidden => self.cursor.template.flags.remove(cell::Flags::HIDDEN),
+            _ => {
+                debug!("Term got unhandled attr: {:?}", attr);
+            }
+        }
+    }
+
+    #[inline]
+    fn set_mode(&mut self, mode: ansi::Mode) {
+        trace!("set_mode: {:?}", mode);
+        match mode {
+            ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                self.mode.insert(mode::TermMode::ALT_SCREEN);
+                self.save_cursor_position();
+                if !self.alt {
+                    self.swap_alt();
+                    self.alt_saved_cursor = true;
+                }
+                self.save_cursor_position();
+            },
+            ansi::Mode::SwapScreen => {
+                self.mode.insert(mode::TermMode::ALT_SCREEN);
+                if !self.alt {
+                    self.swap_alt();
+                    self.alt_saved_cursor = false;
+                }
+            },
+            ansi::Mode::ShowCursor => self.mode.insert(mode::TermMode::SHOW_CURSOR),
+            ansi::Mode::CursorKeys => self.mode.insert(mode::TermMode::APP_CURSOR),
+            // Only one mouse tracking mode is active at a time
+            ansi::Mode::ReportMouseClicks => {
+                self.mode.remove(mode::TermMode::MOUSE_DRAG | mode::TermMode::MOUSE_MOTION);
+                self.mode.insert(mode::TermMode::MOUSE_REPORT_CLICK);
+                self.set_mouse_cursor(MouseCursor::Arrow);
+            },
+            ansi::Mode::ReportCellMouseMotion => {
+                self.mode.remove(mode::TermMode::MOUSE_REPORT_CLICK | mode::TermMode::MOUSE_MOTION);
+                self.mode.insert(mode::TermMode::MOUSE_DRAG);
+                self.set_mouse_cursor(MouseCursor::Arrow);
+            },
+            ansi::Mode::ReportAllMouseMotion => {
+                self.mode.remove(mode::TermMode::MOUSE_REPORT_CLICK | mode::TermMode::MOUSE_DRAG);
+                self.mode.insert(mode::TermMode::MOUSE_MOTION);
+                self.set_mouse_cursor(MouseCursor::Arrow);
+            },
+            ansi::Mode::ReportFocusInOut => self.mode.insert(mode::TermMode::FOCUS_IN_OUT),
+            ansi::Mode::BracketedPaste => self.mode.insert(mode::TermMode::BRACKETED_PASTE),
+            // Only one mouse encoding is active at a time
+            ansi::Mode::Utf8Mouse => {
+                self.mode.remove(mode::TermMode::SGR_MOUSE | mode::TermMode::URXVT_MOUSE);
+                self.mode.insert(mode::TermMode::UTF8_MOUSE);
+            },
+            ansi::Mode::SgrMouse => {
+                self.mode.remove(mode::TermMode::UTF8_MOUSE | mode::TermMode::URXVT_MOUSE);
+                self.mode.insert(mode::TermMode::SGR_MOUSE);
+            },
+            ansi::Mode::UrxvtMouse => {
+                self.mode.remove(mode::TermMode::UTF8_MOUSE | mode::TermMode::SGR_MOUSE);
+                self.mode.insert(mode::TermMode::URXVT_MOUSE);
+            },
+            ansi::Mode::LineWrap => self.mode.insert(mode::TermMode::LINE_WRAP),
+            ansi::Mode::ReverseWrap => self.mode.insert(mode::TermMode::REVERSE_WRAP),
+            ansi::Mode::LineFeedNewLine => self.mode.insert(mode::TermMode::LINE_FEED_NEW_LINE),
+            ansi::Mode::Origin => {
+                // The cursor moves to the top of the scrolling region
+                self.mode.insert(mode::TermMode::ORIGIN);
+                self.goto(Line(0), Column(0));
+            },
+            ansi::Mode::ReverseVideo => {
+                self.mode.insert(mode::TermMode::REVERSE_VIDEO);
+                self.dirty = true;
+            },
+            ansi::Mode::DECCOLM => self.deccolm(),
+            ansi::Mode::Insert => self.mode.insert(mode::TermMode::INSERT), // heh
+            ansi::Mode::SyncUpdate => self.sync_update_start = Some(Instant::now()),
+            _ => {
+                trace!(".. ignoring set_mode");
+            }
+        }
+    }
+
+    #[inline]
+    fn unset_mode(&mut self,mode: ansi::Mode) {
+        trace!("unset_mode: {:?}", mode);
+        match mode {
+            ansi::Mode::SwapScreenAndSetRestoreCursor => {
+                // Applications leaving the alt screen are done with pasted input
+                self.mode.remove(mode::TermMode::ALT_SCREEN | mode::TermMode::BRACKETED_PASTE);
+                self.restore_cursor_position();
+                if self.alt {
+                    self.swap_alt();
+                }
+                self.restore_cursor_position();
+            },
+            ansi::Mode::SwapScreen => {
+                self.mode.remove(mode::TermMode::ALT_SCREEN);
+                if self.alt {
+                    self.swap_alt();
+                }
+            },
+            ansi::Mode::ShowCursor => self.mode.remove(mode::TermMode::SHOW_CURSOR),
+            ansi::Mode::CursorKeys => self.mode.remove(mode::TermMode::APP_CURSOR),
+            ansi::Mode::ReportMouseClicks => {
+                self.mode.remove(mode::TermMode::MOUSE_REPORT_CLICK);
+                self.set_mouse_cursor(MouseCursor::Text);
+            },
+            ansi::Mode::ReportCellMouseMotion => {
+                self.mode.remove(mode::TermMode::MOUSE_DRAG);
+                self.set_mouse_cursor(MouseCursor::Text);
+            },
+            ansi::Mode::ReportAllMouseMotion => {
+                self.mode.remove(mode::TermMode::MOUSE_MOTION);
+                self.set_mouse_cursor(MouseCursor::Text);
+            },
+            ansi::Mode::ReportFocusInOut => self.mode.remove(mode::TermMode::FOCUS_IN_OUT),
+            ansi::Mode::BracketedPaste => self.mode.remove(mode::TermMode::BRACKETED_PASTE),
+            ansi::Mode::Utf8Mouse => self.mode.remove(mode::TermMode::UTF8_MOUSE),
+            ansi::Mode::SgrMouse => self.mode.remove(mode::TermMode::SGR_MOUSE),
+            ansi::Mode::UrxvtMouse => self.mode.remove(mode::TermMode::URXVT_MOUSE),
+            ansi::Mode::LineWrap => self.mode.remove(mode::TermMode::LINE_WRAP),
+            ansi::Mode::ReverseWrap => self.mode.remove(mode::TermMode::REVERSE_WRAP),
+            ansi::Mode::LineFeedNewLine => self.mode.remove(mode::TermMode::LINE_FEED_NEW_LINE),
+            ansi::Mode::Origin => {
+                self.mode.remove(mode::TermMode::ORIGIN);
+                self.goto(Line(0), Column(0));
+            },
+            ansi::Mode::ReverseVideo => {
+                self.mode.remove(mode::TermMode::REVERSE_VIDEO);
+                self.dirty = true;
+            },
+            ansi::Mode::DECCOLM => self.deccolm(),
+            ansi::Mode::Insert => self.mode.remove(mode::TermMode::INSERT),
+            ansi::Mode::SyncUpdate => {
+                self.sync_update_start = None;
+                self.dirty = true;
+            },
+            _ => {
+                trace!(".. ignoring unset_mode");
+            }
+        }
+    }
+
+    #[inline]
+    fn set_scrolling_region(&mut self, region: Range<Line>) {
+        trace!("set scroll region: {:?}", region);
+        let start = min(region.start, self.grid.num_lines());
+        let end = min(region.end, self.grid.num_lines());
+
+        // A region needs at least two lines, otherwise it's ignored
+        if start + 1 >= end {
+            debug!("Ignoring invalid scroll region: {:?}", region);
+            return;
+        }
+
+        self.scroll_region = start..end;
+        self.goto(Line(0), Column(0));
+    }
+
+    #[inline]
+    fn set_rectangle_attributes(&mut self, area: ansi::Rectangle, attrs: &[Attr]) {
+        trace!("set_rectangle_attributes: {:?}, {:?}", area, attrs);
+        let (lines, cols) = match self.rectangle_area(area) {
+            Some(area) => area,
+            None => return,
+        };
+
+        for line in IndexRange(lines) {
+            for cell in &mut self.grid[line][cols.clone()] {
+                for attr in attrs {
+                    match *attr {
+                        Attr::Reset => cell.flags.remove(
+                            cell::Flags::BOLD
+                                | cell::Flags::ALL_UNDERLINES
+                                | cell::Flags::BLINK
+                                | cell::Flags::INVERSE
+                        ),
+                        Attr::Bold => cell.flags.insert(cell::Flags::BOLD),
+                        Attr::CancelBoldDim => cell.flags.remove(cell::Flags::BOLD | cell::Flags::DIM),
+                        Attr::Underscore => {
+                            cell.flags.remove(cell::Flags::ALL_UNDERLINES);
+                            cell.flags.insert(cell::Flags::UNDERLINE);
+                        },
+                        Attr::CancelUnderline => cell.flags.remove(cell::Flags::ALL_UNDERLINES),
+                        Attr::BlinkSlow | Attr::BlinkFast => cell.flags.insert(cell::Flags::BLINK),
+                        Attr::CancelBlink => cell.flags.remove(cell::Flags::BLINK),
+                        Attr::Reverse => cell.flags.insert(cell::Flags::INVERSE),
+                        Attr::CancelReverse => cell.flags.remove(cell::Flags::INVERSE),
+                        _ => debug!("Ignoring rectangle attribute: {:?}", attr),
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn copy_rectangle(&mut self, area: ansi::Rectangle, line: Line, col: Column) {
+        trace!("copy_rectangle: {:?} to line={}, col={}", area, line, col);
+        let (lines, cols) = match self.rectangle_area(area) {
+            Some(area) => area,
+            None => return,
+        };
+
+        // The destination is clipped to the screen like the source
+        let destination = ansi::Rectangle {
+            top: line,
+            left: col,
+            bottom: line + (lines.end - lines.start) - 1,
+            right: col + (cols.end - cols.start) - 1,
+        };
+        let (dest_lines, dest_cols) = match self.rectangle_area(destination) {
+            Some(area) => area,
+            None => return,
+        };
+
+        // Read the whole source first, since it might overlap with the destination
+        let source: Vec<Vec<Cell>> = IndexRange(lines)
+            .map(|line| self.grid[line][cols.clone()].to_vec())
+            .collect();
+
+        for (row, line) in source.iter().zip(IndexRange(dest_lines)) {
+            let cells = &mut self.grid[line][dest_cols.clone()];
+            let len = cells.len();
+            cells.copy_from_slice(&row[..len]);
+        }
+    }
+
+    #[inline]
+    fn set_keypad_application_mode(&mut self) {
+        trace!("set mode::TermMode::APP_KEYPAD");
+        self.mode.insert(mode::TermMode::APP_KEYPAD);
+    }
+
+    #[inline]
+    fn unset_keypad_application_mode(&mut self) {
+        trace!("unset mode::TermMode::APP_KEYPAD");
+        self.mode.remove(mode::TermMode::APP_KEYPAD);
     }
 
     #[inline]
@@ -2027,37 +3934,2649 @@ impl ansi::Handler for Term {
         self.cursor.charsets[index] = charset;
     }
 
-    #[inline]
-    fn set_active_charset(&mut self, index: CharsetIndex) {
-        trace!("Activate {:?} character set", index);
-        self.active_charset = index;
-    }
+    #[inline]
+    fn set_active_charset(&mut self, index: CharsetIndex) {
+        trace!("Activate {:?} character set", index);
+        self.active_charset = index;
+    }
+
+    #[inline]
+    fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
+        trace!("set_cursor_style {:?}", style);
+        self.cursor_style = style;
+    }
+
+    #[inline]
+    fn set_cursor_blinking(&mut self, blinking: bool) {
+        trace!("set_cursor_blinking {}", blinking);
+        self.cursor_blinking = blinking;
+    }
+
+    #[inline]
+    fn push_keyboard_mode(&mut self, mode: KeyboardModes) {
+        trace!("push_keyboard_mode: {:?}", mode);
+        if !self.kitty_keyboard {
+            return;
+        }
+
+        if self.keyboard_mode_stack.len() >= KEYBOARD_MODE_STACK_MAX_DEPTH {
+            self.keyboard_mode_stack.remove(0);
+        }
+        self.keyboard_mode_stack.push(mode);
+        self.update_keyboard_mode();
+    }
+
+    #[inline]
+    fn pop_keyboard_modes(&mut self, count: u16) {
+        trace!("pop_keyboard_modes: {}", count);
+        let new_len = self.keyboard_mode_stack.len().saturating_sub(count as usize);
+        self.keyboard_mode_stack.truncate(new_len);
+        self.update_keyboard_mode();
+    }
+
+    #[inline]
+    fn set_keyboard_mode(&mut self, mode: KeyboardModes, behavior: KeyboardModesApplyBehavior) {
+        trace!("set_keyboard_mode: {:?}, {:?}", mode, behavior);
+        // Without the kitty protocol only the basic CSI u encoding can be requested
+        let mode = if self.kitty_keyboard {
+            mode
+        } else {
+            mode & KeyboardModes::DISAMBIGUATE_ESC_CODES
+        };
+
+        if self.keyboard_mode_stack.is_empty() {
+            self.keyboard_mode_stack.push(KeyboardModes::NO_MODE);
+        }
+
+        if let Some(active) = self.keyboard_mode_stack.last_mut() {
+            *active = match behavior {
+                KeyboardModesApplyBehavior::Replace => mode,
+                KeyboardModesApplyBehavior::Union => *active | mode,
+                KeyboardModesApplyBehavior::Difference => *active & !mode,
+            };
+        }
+        self.update_keyboard_mode();
+    }
+
+    #[inline]
+    fn report_keyboard_mode<W: io::Write>(&mut self, writer: &mut W) {
+        trace!("report_keyboard_mode");
+        // Always reply, so applications probing for support don't hang. The
+        // stack stays empty while the protocol is disabled, reporting no modes.
+        let modes = self.keyboard_mode_stack.last().cloned().unwrap_or(KeyboardModes::NO_MODE);
+        let _ = write!(writer, "\x1b[?{}u", modes.bits());
+    }
+
+    #[inline]
+    fn set_modify_other_keys(&mut self, modify: ModifyOtherKeys) {
+        trace!("set_modify_other_keys: {:?}", modify);
+        self.mode.remove(TermMode::MODIFY_OTHER_KEYS | TermMode::MODIFY_OTHER_KEYS_ALL);
+        match modify {
+            ModifyOtherKeys::Reset => (),
+            ModifyOtherKeys::EnableExceptWellDefined => self.mode.insert(TermMode::MODIFY_OTHER_KEYS),
+            ModifyOtherKeys::EnableAll => self.mode.insert(TermMode::MODIFY_OTHER_KEYS_ALL),
+        }
+    }
+
+    #[inline]
+    fn report_modify_other_keys<W: io::Write>(&mut self, writer: &mut W) {
+        trace!("report_modify_other_keys");
+        let level = if self.mode.contains(TermMode::MODIFY_OTHER_KEYS_ALL) {
+            2
+        } else if self.mode.contains(TermMode::MODIFY_OTHER_KEYS) {
+            1
+        } else {
+            0
+        };
+        let _ = write!(writer, "\x1b[>4;{}m", level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::{Cell, RenderableCell, Term, SizeInfo, TermMode, MAX_FONT_SIZE, SYNC_UPDATE_TIMEOUT};
+    use term::damage::Damage;
+    use term::{cell, color, Search};
+    use term::vi_mode::ViMotion;
+
+    use grid::{Grid, Scroll};
+    use index::{Point, Line, Column, Side};
+    use ansi::{self, Handler, CharsetIndex, StandardCharset, KeyboardModes, KeyboardModesApplyBehavior};
+    use ansi::{Attr, Color, CursorStyle, NamedColor};
+    use Rgb;
+    use selection::Selection;
+    use std::mem;
+    use std::ops::Range;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use input::FONT_SIZE_STEP;
+    use font::Size;
+    use config::Config;
+    use regex::Regex;
+    use std::time::Instant;
+
+    /// Size of a terminal with 17 lines and 7 columns of 3x3 pixel cells
+    fn test_size() -> SizeInfo {
+        SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        }
+    }
+
+    #[test]
+    fn semantic_selection_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for i in 0..5 {
+            for j in 0..2 {
+                grid[Line(j)][Column(i)].c = 'a';
+            }
+        }
+        grid[Line(0)][Column(0)].c = '"';
+        grid[Line(0)][Column(3)].c = '"';
+        grid[Line(1)][Column(2)].c = '"';
+        grid[Line(0)][Column(4)].flags.insert(cell::Flags::WRAPLINE);
+
+        let mut escape_chars = String::from("\"");
+
+        mem::swap(&mut term.grid, &mut grid);
+        mem::swap(&mut term.semantic_escape_chars, &mut escape_chars);
+
+        {
+            *term.selection_mut() = Some(Selection::semantic(Point { line: 2, col: Column(1) }));
+            assert_eq!(term.selection_to_string(), Some(String::from("aa")));
+        }
+
+        {
+            *term.selection_mut() = Some(Selection::semantic(Point { line: 2, col: Column(4) }));
+            assert_eq!(term.selection_to_string(), Some(String::from("aaa")));
+        }
+
+        {
+            *term.selection_mut() = Some(Selection::semantic(Point { line: 1, col: Column(1) }));
+            assert_eq!(term.selection_to_string(), Some(String::from("aaa")));
+        }
+    }
+
+    #[test]
+    fn semantic_selection_word_pattern() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(2), Column(6), 0, Cell::default());
+        for (i, c) in "a-b.cdxe.fg".chars().enumerate() {
+            grid[Line(i / 6)][Column(i % 6)].c = c;
+        }
+        grid[Line(0)][Column(5)].flags.insert(cell::Flags::WRAPLINE);
+
+        mem::swap(&mut term.grid, &mut grid);
+        term.word_pattern = Some(Regex::new(r"[a-z]+(\.[a-z]+)*").unwrap());
+
+        // Words continue on wrapped lines
+        *term.selection_mut() = Some(Selection::semantic(Point { line: 1, col: Column(3) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("b.cdxe.fg")));
+
+        *term.selection_mut() = Some(Selection::semantic(Point { line: 1, col: Column(0) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("a")));
+
+        // Outside of words only the clicked cell is selected
+        *term.selection_mut() = Some(Selection::semantic(Point { line: 1, col: Column(1) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("-")));
+    }
+
+    #[test]
+    fn line_selection_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(5), 0, Cell::default());
+        for i in 0..5 {
+            grid[Line(0)][Column(i)].c = 'a';
+        }
+        grid[Line(0)][Column(0)].c = '"';
+        grid[Line(0)][Column(3)].c = '"';
+
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(3) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("\"aa\"a\n")));
+    }
+
+    #[test]
+    fn line_selection_skips_leading_whitespace() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for (i, c) in "      ab cd".chars().enumerate() {
+            grid[Line(i / 5)][Column(i % 5)].c = c;
+        }
+        grid[Line(0)][Column(4)].flags.insert(cell::Flags::WRAPLINE);
+        grid[Line(1)][Column(4)].flags.insert(cell::Flags::WRAPLINE);
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        // The whole logical line is selected from any of its rows
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(2) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("      ab cd\n")));
+
+        term.skip_leading_whitespace = true;
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(2) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("ab cd\n")));
+    }
+
+    #[test]
+    fn semantic_selection_extends_by_words() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(20), 0, Cell::default());
+        for (i, c) in "cp \"~/a.txt\" /tmp/b".chars().enumerate() {
+            grid[Line(0)][Column(i)].c = c;
+        }
+
+        let mut escape_chars = String::from("\" ");
+
+        mem::swap(&mut term.grid, &mut grid);
+        mem::swap(&mut term.semantic_escape_chars, &mut escape_chars);
+
+        let mut selection = Selection::semantic(Point { line: 0, col: Column(5) });
+        *term.selection_mut() = Some(selection.clone());
+        assert_eq!(term.selection_to_string(), Some(String::from("~/a.txt")));
+
+        // Dragging extends the selection to the end of the word under the mouse
+        selection.update(Point { line: 0, col: Column(15) }, Side::Left);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some(String::from("~/a.txt\" /tmp/b")));
+    }
+
+    #[test]
+    fn line_selection_includes_wrapped_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
+        for (i, c) in "abcdefg".chars().enumerate() {
+            grid[Line(i / 3)][Column(i % 3)].c = c;
+        }
+        grid[Line(0)][Column(2)].flags.insert(cell::Flags::WRAPLINE);
+        grid[Line(1)][Column(2)].flags.insert(cell::Flags::WRAPLINE);
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 1, col: Column(0) }));
+        assert_eq!(term.selection_to_string(), Some(String::from("abcdefg\n")));
+    }
+
+    #[test]
+    fn block_selection_copies_rows() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for (line, text) in ["ab cd", "e    ", "fghij"].iter().enumerate() {
+            for (col, c) in text.chars().enumerate() {
+                grid[Line(line)][Column(col)].c = c;
+            }
+        }
+        grid[Line(1)][Column(2)].c = '中';
+        grid[Line(1)][Column(2)].flags.insert(cell::Flags::WIDE_CHAR);
+        grid[Line(1)][Column(3)].flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Rows are padded to the width of the block and wide chars on the right edge are included
+        let mut selection = Selection::block(Point { line: 2, col: Column(1) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some(String::from("b \n 中\ngh\n")));
+
+        // Wide chars starting left of the block are replaced by a space
+        let mut selection = Selection::block(Point { line: 1, col: Column(3) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(4) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some(String::from("  \nij\n")));
+    }
+
+    #[test]
+    fn block_selection_highlights_columns() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let fg = term.colors[NamedColor::Foreground];
+
+        let start = term.visible_to_buffer(Point::new(Line(1), Column(1)));
+        let end = term.visible_to_buffer(Point::new(Line(2), Column(2)));
+        let mut selection = Selection::block(start, Side::Left);
+        selection.update(end, Side::Right);
+        *term.selection_mut() = Some(selection);
+
+        // The cursor on the first line isn't part of the selection
+        let selected: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line != Line(0) && rc.bg == fg)
+            .map(|rc| (rc.line.0, rc.column.0))
+            .collect();
+        assert_eq!(selected, vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn selecting_empty_line() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
+        for l in 0..3 {
+            if l != 1 {
+                for c in 0..3 {
+                    grid[Line(l)][Column(c)].c = 'a';
+                }
+            }
+        }
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        let mut selection = Selection::simple(Point { line: 2, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("aaa\n\naaa\n".into()));
+    }
+
+    #[test]
+    fn selecting_multiple_lines_trims_trailing_whitespace() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for (line, text) in ["ab", "cdefg", "hi"].iter().enumerate() {
+            for (col, c) in text.chars().enumerate() {
+                grid[Line(line)][Column(col)].c = c;
+            }
+        }
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        let mut selection = Selection::simple(Point { line: 2, col: Column(1) }, Side::Left);
+        selection.update(Point { line: 0, col: Column(1) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("b\ncdefg\nhi".into()));
+    }
+
+    #[test]
+    fn selecting_reversed_multiple_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
+        for (line, text) in ["ab", "cdefg", "hi"].iter().enumerate() {
+            for (col, c) in text.chars().enumerate() {
+                grid[Line(line)][Column(col)].c = c;
+            }
+        }
+
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Start below and drag upwards past the anchor
+        let mut selection = Selection::simple(Point { line: 0, col: Column(1) }, Side::Right);
+        selection.update(Point { line: 2, col: Column(1) }, Side::Left);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("b\ncdefg\nhi".into()));
+    }
+
+    #[test]
+    fn alt_screen_restores_primary_screen() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.input('a');
+        term.input('b');
+
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(term.mode().contains(TermMode::ALT_SCREEN));
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, ' ');
+        term.input('x');
+
+        // Everything written to the alternate screen disappears on exit
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(!term.mode().contains(TermMode::ALT_SCREEN));
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, 'a');
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'b');
+        assert_eq!(term.grid()[Line(0)][Column(2)].c, ' ');
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(2) });
+
+        // The alternate screen is blank when entered again
+        term.set_mode(ansi::Mode::SwapScreen);
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, ' ');
+        term.unset_mode(ansi::Mode::SwapScreen);
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, 'a');
+    }
+
+    #[test]
+    fn alt_screen_has_no_scrollback() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.set_mode(ansi::Mode::SwapScreen);
+        for _ in 0..40 {
+            term.input('x');
+            term.newline();
+        }
+        assert_eq!(term.grid().scroll_limit(), 0);
+
+        term.unset_mode(ansi::Mode::SwapScreen);
+        assert_eq!(term.grid().scroll_limit(), 0);
+    }
+
+    #[test]
+    fn alt_screen_restores_cursor_style() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.set_cursor_style(Some(CursorStyle::Underline));
+
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.set_cursor_style(Some(CursorStyle::Beam));
+        assert_eq!(term.cursor_style, Some(CursorStyle::Beam));
+
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert_eq!(term.cursor_style, Some(CursorStyle::Underline));
+
+        // Resetting the style falls back to the configured one
+        term.set_cursor_style(None);
+        assert_eq!(term.cursor_style.unwrap_or(term.default_cursor_style), CursorStyle::Block);
+    }
+
+    #[test]
+    fn search_scrolls_to_previous_match() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for c in "abc".chars() {
+            term.input(c);
+        }
+        term.carriage_return();
+        for _ in 0..60 {
+            term.linefeed();
+        }
+
+        // The match is centered in the viewport while typing
+        term.start_search();
+        term.search_input('a');
+        term.search_input('B');
+        assert!(term.search().unwrap().focused.is_none());
+        term.search_pop();
+        term.search_input('b');
+        let focused = term.search().unwrap().focused.unwrap();
+        assert_eq!(focused.start, Point::new(60, Column(0)));
+        assert_eq!(term.grid.display_offset(), 52);
+
+        // Cancelling before confirming the query restores the viewport
+        term.cancel_search();
+        assert!(term.search().is_none());
+        assert_eq!(term.grid.display_offset(), 0);
+
+        // Searching is stopped and unavailable on the alternate screen
+        term.start_search();
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(term.search().is_none());
+        term.start_search();
+        assert!(term.search().is_none());
+    }
+
+    #[test]
+    fn vi_mode_selection() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for c in "foo bar".chars() {
+            term.input(c);
+        }
+        term.carriage_return();
+        term.linefeed();
+
+        // The vi cursor starts at the terminal cursor
+        term.toggle_vi_mode();
+        assert_eq!(term.vi_mode().unwrap().cursor, Point::new(15, Column(0)));
+
+        term.vi_motion(ViMotion::Up);
+        term.vi_motion(ViMotion::WordRight);
+        term.vi_select(false);
+        assert_eq!(term.selection_to_string(), Some(String::from("b")));
+
+        // The anchor stays selected in both directions
+        term.vi_motion(ViMotion::WordLeft);
+        assert_eq!(term.selection_to_string(), Some(String::from("foo b")));
+        term.vi_motion(ViMotion::Last);
+        assert_eq!(term.selection_to_string(), Some(String::from("bar\n")));
+
+        // Selecting again stops the selection
+        term.vi_select(false);
+        assert!(term.selection().is_none());
+
+        // New output doesn't move the viewport or the vi cursor's content
+        for _ in 0..20 {
+            term.linefeed();
+        }
+        assert_eq!(term.vi_mode().unwrap().cursor, Point::new(21, Column(6)));
+        assert_eq!(term.grid.display_offset(), 5);
+
+        term.vi_select(true);
+        term.toggle_vi_mode();
+        assert!(term.vi_mode().is_none());
+        assert!(term.selection().is_none());
+    }
+
+    #[test]
+    fn alt_screen_resizes_both_grids() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+
+        let size = SizeInfo { width: 30.0, height: 30.0, ..size };
+        term.resize(&size);
+        assert_eq!(term.grid().num_cols(), Column(10));
+        assert_eq!(term.grid().num_lines(), Line(10));
+
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert_eq!(term.grid().num_cols(), Column(10));
+        assert_eq!(term.grid().num_lines(), Line(10));
+    }
+
+    #[test]
+    fn alt_screen_scroll_clears_selection() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+
+        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(0) }));
+        term.scroll_up(Line(1));
+        assert!(term.selection().is_none());
+    }
+
+    /// Check that the grid can be serialized back and forth losslessly
+    ///
+    /// This test is in the term module as opposed to the grid since we want to
+    /// test this property with a T=Cell.
+    #[test]
+    fn grid_serde() {
+        let template = Cell::default();
+
+        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
+        let serialized = serde_json::to_string(&grid).expect("ser");
+        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
+                                      .expect("de");
+
+        assert_eq!(deserialized, grid);
+    }
+
+    #[test]
+    fn input_line_drawing_character() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let cursor = Point::new(Line(0), Column(0));
+        term.configure_charset(CharsetIndex::G0,
+                               StandardCharset::SpecialCharacterAndLineDrawing);
+        term.input('a');
+
+        assert_eq!(term.grid()[&cursor].c, '▒');
+    }
+
+    #[test]
+    fn input_line_drawing_control_pictures() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.configure_charset(CharsetIndex::G1,
+                               StandardCharset::SpecialCharacterAndLineDrawing);
+        term.set_active_charset(CharsetIndex::G1);
+        for c in "bcdeiq".chars() {
+            term.input(c);
+        }
+
+        let line: String = (0..6).map(|col| term.grid()[Line(0)][Column(col)].c).collect();
+        assert_eq!(line, "␉␌␍␊␋─");
+
+        // Switching back to G0 restores ASCII
+        term.set_active_charset(CharsetIndex::G0);
+        term.input('q');
+        assert_eq!(term.grid()[Line(0)][Column(6)].c, 'q');
+
+        // A full reset restores ASCII in both slots
+        term.configure_charset(CharsetIndex::G0,
+                               StandardCharset::SpecialCharacterAndLineDrawing);
+        term.set_active_charset(CharsetIndex::G1);
+        term.reset_state();
+        term.input('q');
+        term.set_active_charset(CharsetIndex::G1);
+        term.input('q');
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, 'q');
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'q');
+    }
+
+    #[test]
+    fn scrolling_region_keeps_outer_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for line in 0..7 {
+            term.goto(Line(line), Column(0));
+            term.input((b'a' + line as u8) as char);
+        }
+        let first_column = |term: &Term| -> String {
+            (0..7).map(|line| term.grid()[Line(line)][Column(0)].c).collect()
+        };
+
+        // Three line region covering "cde"
+        term.set_scrolling_region(Line(2)..Line(5));
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(0) });
+
+        // Linefeed at the bottom margin scrolls only the region
+        term.goto(Line(4), Column(0));
+        term.linefeed();
+        assert_eq!(first_column(&term), "abde fg");
+        assert_eq!(term.cursor().point.line, Line(4));
+
+        // Reverse index at the top margin scrolls the region down
+        term.goto(Line(2), Column(0));
+        term.reverse_index();
+        assert_eq!(first_column(&term), "ab defg");
+
+        term.scroll_up(Line(1));
+        term.scroll_down(Line(2));
+        assert_eq!(first_column(&term), "ab  dfg");
+    }
+
+    #[test]
+    fn repeat_preceding_char() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let line_text = |term: &Term, line| -> String {
+            (0..7).map(|col| term.grid()[Line(line)][Column(col)].c).collect()
+        };
+
+        // Repeated chars wrap and keep the current attributes
+        term.terminal_attribute(Attr::Bold);
+        term.input('a');
+        term.repeat('a', 9);
+        assert_eq!(line_text(&term, 0), "aaaaaaa");
+        assert_eq!(line_text(&term, 1), "aaa    ");
+        assert!(term.grid()[Line(1)][Column(2)].flags.contains(cell::Flags::BOLD));
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(3) });
+
+        // Repeating stops at the end of the scrolling region
+        term.set_scrolling_region(Line(2)..Line(4));
+        term.goto(Line(2), Column(4));
+        term.input('x');
+        term.repeat('x', 20);
+        assert_eq!(line_text(&term, 2), "    xxx");
+        assert_eq!(line_text(&term, 3), "xxxxxxx");
+        assert_eq!(line_text(&term, 4), "       ");
+
+        term.repeat('x', 1);
+        assert_eq!(line_text(&term, 2), "    xxx");
+    }
+
+    #[test]
+    fn scroll_to_output() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for _ in 0..30 {
+            term.linefeed();
+        }
+
+        term.scroll_display(Scroll::Lines(5));
+        assert_eq!(term.grid().display_offset(), 5);
+        term.scroll_to_output();
+        assert_eq!(term.grid().display_offset(), 0);
+
+        // The viewport stays in the history in vi mode and while searching
+        term.scroll_display(Scroll::Lines(5));
+        term.toggle_vi_mode();
+        term.scroll_to_output();
+        assert_eq!(term.grid().display_offset(), 5);
+        term.toggle_vi_mode();
+
+        term.start_search();
+        term.scroll_to_output();
+        assert_eq!(term.grid().display_offset(), 5);
+        term.cancel_search();
+
+        term.scroll_to_output();
+        assert_eq!(term.grid().display_offset(), 0);
+
+        // The viewport stays in the history without `auto_scroll`
+        term.auto_scroll = false;
+        term.scroll_display(Scroll::Lines(5));
+        term.scroll_to_output();
+        assert_eq!(term.grid().display_offset(), 5);
+    }
+
+    #[test]
+    fn repeat_wide_char() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        for byte in "中\x1b[3b".as_bytes() {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+
+        // The last repetition doesn't fit and wraps to the next line
+        for col in &[0, 2, 4] {
+            assert_eq!(term.grid()[Line(0)][Column(*col)].c, '中');
+            assert!(term.grid()[Line(0)][Column(*col + 1)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+        }
+        assert_eq!(term.grid()[Line(0)][Column(6)].c, ' ');
+        assert_eq!(term.grid()[Line(1)][Column(0)].c, '中');
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(2) });
+
+        // A zero parameter repeats once
+        for byte in b"\x1b[0b" {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        assert_eq!(term.grid()[Line(1)][Column(2)].c, '中');
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(4) });
+    }
+
+    #[test]
+    fn erase_chars_across_wide_char() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        let line_text = |term: &Term| -> String {
+            (0..7).map(|col| term.grid()[Line(0)][Column(col)].c).collect()
+        };
+
+        // Starting on the spacer of one wide char and ending on the next one
+        for byte in "a中中b\x1b[1;3H\x1b[2X".as_bytes() {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        assert_eq!(line_text(&term), "a    b ");
+        for col in 1..5 {
+            let flags = term.grid()[Line(0)][Column(col)].flags;
+            assert!(!flags.intersects(cell::Flags::WIDE_CHAR | cell::Flags::WIDE_CHAR_SPACER));
+        }
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(2) });
+
+        // A zero parameter erases one char
+        for byte in b"\x1b[1;1H\x1b[0X" {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        assert_eq!(line_text(&term), "     b ");
+    }
+
+    #[test]
+    fn scrolling_region_origin_mode_and_invalid_ranges() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.set_scrolling_region(Line(2)..Line(5));
+
+        // Cursor addressing is relative to and clamped by the region
+        term.set_mode(ansi::Mode::Origin);
+        term.goto(Line(1), Column(3));
+        assert_eq!(term.cursor().point, Point { line: Line(3), col: Column(3) });
+        term.goto(Line(10), Column(0));
+        assert_eq!(term.cursor().point.line, Line(4));
+        term.unset_mode(ansi::Mode::Origin);
+
+        // Inverted and single line regions are ignored
+        term.goto(Line(6), Column(0));
+        term.set_scrolling_region(Line(5)..Line(3));
+        term.set_scrolling_region(Line(4)..Line(5));
+        assert_eq!(term.scroll_region, Line(2)..Line(5));
+        assert_eq!(term.cursor().point.line, Line(6));
+
+        // Bounds are clamped to the screen
+        term.set_scrolling_region(Line(10)..Line(100));
+        assert_eq!(term.scroll_region, Line(10)..Line(17));
+    }
+
+    #[test]
+    fn cursor_movement_is_clamped() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        // HPA, HPR, VPA and VPR stop at the screen edges
+        term.goto_col(Column(100));
+        assert_eq!(term.cursor().point.col, Column(6));
+        term.goto_col(Column(2));
+        term.move_forward(Column(3));
+        assert_eq!(term.cursor().point.col, Column(5));
+        term.move_forward(Column(100));
+        assert_eq!(term.cursor().point.col, Column(6));
+        term.goto_line(Line(100));
+        assert_eq!(term.cursor().point, Point { line: Line(16), col: Column(6) });
+        term.goto_line(Line(3));
+        term.move_down(Line(100));
+        assert_eq!(term.cursor().point.line, Line(16));
+
+        // Relative movement stops at the scrolling region margins
+        term.set_scrolling_region(Line(2)..Line(5));
+        term.goto(Line(3), Column(0));
+        term.move_down(Line(10));
+        assert_eq!(term.cursor().point.line, Line(4));
+        term.move_up(Line(10));
+        assert_eq!(term.cursor().point.line, Line(2));
+
+        // Horizontal movement doesn't change the line in origin mode
+        term.set_mode(ansi::Mode::Origin);
+        term.goto_line(Line(1));
+        term.goto_col(Column(4));
+        assert_eq!(term.cursor().point, Point { line: Line(3), col: Column(4) });
+    }
+
+    #[test]
+    fn erase_chars() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let line_text = |term: &Term| -> String {
+            (0..7).map(|col| term.grid()[Line(0)][Column(col)].c).collect()
+        };
+        for c in "abcdefg".chars() {
+            term.input(c);
+        }
+
+        // Erasing clips to the last column without moving the cursor
+        term.goto(Line(0), Column(5));
+        term.terminal_attribute(Attr::Background(Color::Named(NamedColor::Red)));
+        term.terminal_attribute(Attr::Underscore);
+        term.erase_chars(Column(10));
+        assert_eq!(line_text(&term), "abcde  ");
+        assert_eq!(term.cursor().point, Point { line: Line(0), col: Column(5) });
+
+        let erased = term.grid()[Line(0)][Column(6)];
+        assert_eq!(erased.bg, Color::Named(NamedColor::Red));
+        assert!(erased.flags.is_empty());
+
+        term.goto(Line(0), Column(0));
+        term.erase_chars(Column(1));
+        assert_eq!(line_text(&term), " bcde  ");
+    }
+
+    #[test]
+    fn insert_and_delete_chars() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let line = |term: &Term| -> String {
+            (0..7).map(|col| term.grid()[Line(0)][Column(col)].c).collect()
+        };
+        for c in "abcdefg".chars() {
+            term.input(c);
+        }
+
+        term.goto(Line(0), Column(2));
+        term.insert_blank(Column(2));
+        assert_eq!(line(&term), "ab  cde");
+        term.delete_chars(Column(1));
+        assert_eq!(line(&term), "ab cde ");
+
+        // Counts past the end of the line only affect cells after the cursor
+        term.goto(Line(0), Column(5));
+        term.delete_chars(Column(100));
+        assert_eq!(line(&term), "ab cd  ");
+        term.goto(Line(0), Column(1));
+        term.insert_blank(Column(100));
+        assert_eq!(line(&term), "a      ");
+
+        // Blanks use the current background color without other attributes
+        let bg = Color::Named(NamedColor::Red);
+        term.terminal_attribute(Attr::Background(bg));
+        term.terminal_attribute(Attr::Underscore);
+        term.goto(Line(0), Column(0));
+        term.insert_blank(Column(1));
+        assert_eq!(term.grid()[Line(0)][Column(0)].bg, bg);
+        assert_eq!(term.grid()[Line(0)][Column(0)].flags, cell::Flags::empty());
+    }
+
+    #[test]
+    fn insert_and_delete_chars_split_wide_chars() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for c in "a漢字".chars() {
+            term.input(c);
+        }
+
+        // Deleting the lead leaves no orphaned spacer behind
+        term.goto(Line(0), Column(1));
+        term.delete_chars(Column(1));
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, ' ');
+        assert!(!term.grid()[Line(0)][Column(1)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+        assert_eq!(term.grid()[Line(0)][Column(2)].c, '字');
+
+        // Inserting at a spacer clears its lead
+        term.goto(Line(0), Column(3));
+        term.insert_blank(Column(1));
+        assert_eq!(term.grid()[Line(0)][Column(2)].c, ' ');
+        assert!(!term.grid()[Line(0)][Column(2)].flags.contains(cell::Flags::WIDE_CHAR));
+
+        // Wide chars pushed off the edge are removed
+        term.goto(Line(0), Column(0));
+        for c in "abcde漢".chars() {
+            term.input(c);
+        }
+        term.goto(Line(0), Column(0));
+        term.insert_blank(Column(1));
+        assert_eq!(term.grid()[Line(0)][Column(6)].c, ' ');
+        assert!(!term.grid()[Line(0)][Column(6)].flags.contains(cell::Flags::WIDE_CHAR));
+    }
+
+    #[test]
+    fn insert_and_delete_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for line in 0..7 {
+            term.goto(Line(line), Column(0));
+            term.input((b'a' + line as u8) as char);
+        }
+        let first_column = |term: &Term| -> String {
+            (0..7).map(|line| term.grid()[Line(line)][Column(0)].c).collect()
+        };
+        term.set_scrolling_region(Line(1)..Line(5));
+
+        term.goto(Line(2), Column(0));
+        term.insert_blank_lines(Line(1));
+        assert_eq!(first_column(&term), "ab cdfg");
+        term.delete_lines(Line(1));
+        assert_eq!(first_column(&term), "abcd fg");
+
+        // Counts larger than the remaining region clear up to its bottom
+        term.delete_lines(Line(100));
+        assert_eq!(first_column(&term), "ab   fg");
+
+        // No-op outside of the scrolling region
+        term.goto(Line(6), Column(0));
+        term.insert_blank_lines(Line(1));
+        term.delete_lines(Line(1));
+        assert_eq!(first_column(&term), "ab   fg");
+    }
+
+    #[test]
+    fn deleted_lines_use_background_color() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.input('a');
+        term.goto(Line(16), Column(0));
+        term.input('z');
+
+        term.terminal_attribute(Attr::Background(Color::Named(NamedColor::Red)));
+        term.terminal_attribute(Attr::Bold);
+        term.goto(Line(0), Column(3));
+        term.delete_lines(Line(1));
+
+        // Deleted lines are not added to the scrollback history
+        assert_eq!(term.grid().scroll_limit(), 0);
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(0)));
+        assert_eq!(term.grid()[Line(15)][Column(0)].c, 'z');
+
+        // The new blank line at the bottom is filled with the background color only
+        for col in 0..7 {
+            let cell = term.grid()[Line(16)][Column(col)];
+            assert_eq!(cell.bg, Color::Named(NamedColor::Red));
+            assert!(!cell.flags.contains(cell::Flags::BOLD));
+        }
+
+        term.insert_blank_lines(Line(1));
+        assert_eq!(term.grid()[Line(0)][Column(6)].bg, Color::Named(NamedColor::Red));
+        assert_eq!(term.grid()[Line(16)][Column(0)].c, 'z');
+    }
+
+    #[test]
+    fn rectangle_operations() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for line in 0..3 {
+            term.goto(Line(line), Column(0));
+            for c in "abcde".chars() {
+                term.input(c);
+            }
+        }
+
+        let area = ansi::Rectangle { top: Line(0), left: Column(1), bottom: Line(1), right: Column(2) };
+        term.set_rectangle_attributes(area, &[Attr::Bold, Attr::Reverse]);
+        assert!(term.grid()[Line(1)][Column(2)].flags.contains(cell::Flags::BOLD | cell::Flags::INVERSE));
+        assert!(term.grid()[Line(0)][Column(0)].flags.is_empty());
+        assert!(term.grid()[Line(2)][Column(1)].flags.is_empty());
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'b');
+
+        term.set_rectangle_attributes(area, &[Attr::Reset]);
+        assert!(term.grid()[Line(1)][Column(2)].flags.is_empty());
+
+        // Overlapping copies read the source before writing, bounds are clipped to the screen
+        let area = ansi::Rectangle { top: Line(0), left: Column(0), bottom: Line(2), right: Column(100) };
+        term.copy_rectangle(area, Line(1), Column(2));
+        let text = |term: &Term, line: usize| -> String {
+            (0..7).map(|col| term.grid()[Line(line)][Column(col)].c).collect()
+        };
+        assert_eq!(text(&term, 0), "abcde  ");
+        assert_eq!(text(&term, 1), "ababcde");
+        assert_eq!(text(&term, 3), "  abcde");
+
+        // In origin mode, the area is relative to the scrolling region
+        term.set_scrolling_region(Line(3)..Line(5));
+        term.set_mode(ansi::Mode::Origin);
+        let area = ansi::Rectangle { top: Line(0), left: Column(0), bottom: Line(10), right: Column(0) };
+        term.set_rectangle_attributes(area, &[Attr::Underscore]);
+        assert!(!term.grid()[Line(2)][Column(0)].flags.contains(cell::Flags::UNDERLINE));
+        assert!(term.grid()[Line(4)][Column(0)].flags.contains(cell::Flags::UNDERLINE));
+        assert!(!term.grid()[Line(5)][Column(0)].flags.contains(cell::Flags::UNDERLINE));
+    }
+
+    #[test]
+    fn hidden_text_is_copied() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        term.input('a');
+        term.terminal_attribute(Attr::Hidden);
+        term.input('b');
+        term.terminal_attribute(Attr::CancelHidden);
+        term.input('c');
+
+        assert!(term.grid()[Line(0)][Column(1)].flags.contains(cell::Flags::HIDDEN));
+        assert!(!term.grid()[Line(0)][Column(2)].flags.contains(cell::Flags::HIDDEN));
+
+        let mut selection = Selection::simple(Point { line: 16, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 16, col: Column(2) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("abc".into()));
+    }
+
+    #[test]
+    fn bracketed_paste_mode() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        let mut reply = Vec::new();
+        term.report_mode(&mut reply, ansi::Mode::BracketedPaste, 2004);
+        assert_eq!(reply, b"\x1b[?2004;2$y");
+
+        reply.clear();
+        term.set_mode(ansi::Mode::BracketedPaste);
+        term.report_mode(&mut reply, ansi::Mode::BracketedPaste, 2004);
+        assert_eq!(reply, b"\x1b[?2004;1$y");
+
+        // Leaving the alt screen and resetting disable it
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(term.mode().contains(TermMode::BRACKETED_PASTE));
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(!term.mode().contains(TermMode::BRACKETED_PASTE));
+
+        term.set_mode(ansi::Mode::BracketedPaste);
+        term.reset_state();
+        assert!(!term.mode().contains(TermMode::BRACKETED_PASTE));
+
+        reply.clear();
+        term.report_mode(&mut reply, ansi::Mode::Insert, 4);
+        assert_eq!(reply, b"\x1b[4;2$y");
+    }
+
+    #[test]
+    fn mode_report_echoes_requested_number() {
+        let mut term = Term::new(&Default::default(), test_size());
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+
+        // ?47 is an alias of ?1047, but clients expect the number they asked for
+        for byte in b"\x1b[?47h\x1b[?47$p\x1b[?1047$p" {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+        assert_eq!(reply, b"\x1b[?47;1$y\x1b[?1047;1$y");
+    }
+
+    #[test]
+    fn pixel_size_reports() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        let mut reply = Vec::new();
+        term.text_area_size_pixels(&mut reply);
+        assert_eq!(reply, b"\x1b[4;51;21t");
+
+        reply.clear();
+        term.cell_size_pixels(&mut reply);
+        assert_eq!(reply, b"\x1b[6;3;3t");
+    }
+
+    #[test]
+    fn screen_size_report() {
+        let size = test_size();
+        let mut term = Term::new(&Default::default(), size);
+
+        // The text area is reported until the screen size is known
+        let mut reply = Vec::new();
+        term.screen_size_chars(&mut reply);
+        assert_eq!(reply, b"\x1b[9;17;7t");
+
+        reply.clear();
+        term.set_screen_size(1920, 1080);
+        term.screen_size_chars(&mut reply);
+        assert_eq!(reply, b"\x1b[9;360;640t");
+    }
+
+    #[test]
+    fn device_status_reports() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        let mut reply = Vec::new();
+        term.device_status(&mut reply, 5);
+        assert_eq!(reply, b"\x1b[0n");
+
+        reply.clear();
+        term.goto(Line(3), Column(4));
+        term.device_status(&mut reply, 6);
+        assert_eq!(reply, b"\x1b[4;5R");
+
+        // Origin mode reports relative to the scrolling region
+        reply.clear();
+        term.set_scrolling_region(Line(2)..Line(10));
+        term.set_mode(ansi::Mode::Origin);
+        term.goto(Line(1), Column(0));
+        term.device_status(&mut reply, 6);
+        assert_eq!(reply, b"\x1b[2;1R");
+
+        // The extended report includes the page
+        reply.clear();
+        term.private_device_status(&mut reply, 6);
+        assert_eq!(reply, b"\x1b[?2;1;1R");
+
+        // Entering origin mode homes the cursor to the top margin
+        reply.clear();
+        term.unset_mode(ansi::Mode::Origin);
+        term.goto(Line(0), Column(2));
+        term.set_mode(ansi::Mode::Origin);
+        assert_eq!(term.cursor.point, Point::new(Line(2), Column(0)));
+        term.device_status(&mut reply, 6);
+        assert_eq!(reply, b"\x1b[1;1R");
+
+        // A cursor restored above the top margin doesn't underflow the report
+        reply.clear();
+        term.cursor.point.line = Line(0);
+        term.device_status(&mut reply, 6);
+        term.private_device_status(&mut reply, 6);
+        assert_eq!(reply, b"\x1b[1;1R\x1b[?1;1;1R");
+
+        // Responses are written through the parser's writer, like typed input
+        reply.clear();
+        let mut term = Term::new(&Default::default(), test_size());
+        let mut parser = ansi::Processor::new();
+        for byte in b"\x1b[5n\x1b[5;10r\x1b[?6h\x1b[6n\x1b[?6n" {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+        assert_eq!(reply, b"\x1b[0n\x1b[1;1R\x1b[?1;1;1R");
+        assert_eq!(term.cursor.point, Point::new(Line(4), Column(0)));
+    }
+
+    #[test]
+    fn device_attributes() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+
+        // DA1, with and without the default parameter, and DECID
+        for byte in b"\x1b[c\x1b[0c\x1bZ" {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+        assert_eq!(reply, &b"\x1b[?62;22c\x1b[?62;22c\x1b[?62;22c"[..]);
+
+        // DA2 reports the crate version, `0.2.1` is `201`
+        reply.clear();
+        for byte in b"\x1b[>c\x1b[>0c" {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+        let version: Vec<usize> = env!("CARGO_PKG_VERSION")
+            .split('-')
+            .next()
+            .unwrap()
+            .split('.')
+            .map(|part| part.parse().unwrap())
+            .collect();
+        let da2 = format!("\x1b[>1;{};0c", version[0] * 10_000 + version[1] * 100 + version[2]);
+        assert_eq!(String::from_utf8(reply.clone()).unwrap(), da2.repeat(2));
+
+        // Other intermediates are ignored
+        reply.clear();
+        for byte in b"\x1b[=c\x1b[?c" {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn tab_stops() {
+        let size = SizeInfo {
+            width: 60.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        // Default stops every 8 columns, then the last column
+        term.put_tab(1);
+        assert_eq!(term.cursor().point.col, Column(8));
+        term.put_tab(2);
+        assert_eq!(term.cursor().point.col, Column(19));
+        term.move_backward_tabs(1);
+        assert_eq!(term.cursor().point.col, Column(16));
+
+        // HTS and TBC 0
+        term.goto_col(Column(3));
+        term.set_horizontal_tabstop();
+        term.goto_col(Column(0));
+        term.move_forward_tabs(1);
+        assert_eq!(term.cursor().point.col, Column(3));
+        term.clear_tabs(ansi::TabulationClearMode::Current);
+        term.goto_col(Column(0));
+        term.move_forward_tabs(2);
+        assert_eq!(term.cursor().point.col, Column(16));
+
+        // TBC 3 leaves no stops
+        term.clear_tabs(ansi::TabulationClearMode::All);
+        term.goto_col(Column(1));
+        term.put_tab(1);
+        assert_eq!(term.cursor().point.col, Column(19));
+        term.move_backward_tabs(1);
+        assert_eq!(term.cursor().point.col, Column(0));
+
+        // RIS restores the default stops
+        term.reset_state();
+        term.put_tab(1);
+        assert_eq!(term.cursor().point.col, Column(8));
+    }
+
+    #[test]
+    fn resize_keeps_tab_stops() {
+        let size = SizeInfo {
+            width: 60.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.clear_tabs(ansi::TabulationClearMode::All);
+        term.goto_col(Column(5));
+        term.set_horizontal_tabstop();
+
+        term.resize(&SizeInfo { width: 90.0, ..size });
+        term.goto_col(Column(0));
+        term.put_tab(1);
+        assert_eq!(term.cursor().point.col, Column(5));
+        term.put_tab(1);
+        assert_eq!(term.cursor().point.col, Column(24));
+    }
+
+    /// Text of all rows including the history, starting with the oldest one
+    fn buffer_text(term: &Term) -> Vec<String> {
+        let grid = term.grid();
+        (0..grid.num_lines().0 + grid.scroll_limit())
+            .rev()
+            .map(|line| {
+                let text: String = grid[line][..].iter().map(|cell| cell.c).collect();
+                text.trim_right().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reflow_wrapped_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 12.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        for byte in &b"aaaaaaaaaa\r\nbb\r\ncccccccc\r\ndd"[..] {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        assert_eq!(buffer_text(&term), vec!["aaaaaaa", "aaa", "bb", "ccccccc", "c", "dd"]);
+
+        // Shrinking pushes the additional rows into the history
+        term.resize(&SizeInfo { width: 9.0, ..size });
+        assert_eq!(
+            buffer_text(&term),
+            vec!["aaa", "aaa", "aaa", "a", "bb", "ccc", "ccc", "cc", "dd"]
+        );
+        assert_eq!(term.cursor.point, Point::new(Line(3), Column(2)));
+
+        // Growing unwraps the lines again
+        term.resize(&size);
+        assert_eq!(buffer_text(&term), vec!["aaaaaaa", "aaa", "bb", "ccccccc", "c", "dd"]);
+        assert_eq!(term.cursor.point, Point::new(Line(3), Column(2)));
+
+        // Unwrapped lines are joined when growing
+        term.resize(&SizeInfo { width: 30.0, ..size });
+        assert_eq!(buffer_text(&term), vec!["aaaaaaaaaa", "bb", "cccccccc", "dd"]);
+        assert_eq!(term.grid().scroll_limit(), 0);
+    }
+
+    #[test]
+    fn reflow_round_trips() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 15.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let input = "one two three four\r\n\r\nfive\r\nsix seven eight nine ten\r\nab";
+
+        for cols in 2..12 {
+            let mut term = Term::new(&Default::default(), size);
+            let mut parser = ansi::Processor::new();
+            for byte in input.as_bytes() {
+                parser.advance(&mut term, *byte, &mut Vec::new());
+            }
+            let text = buffer_text(&term);
+            let cursor = term.cursor.point;
+
+            term.resize(&SizeInfo { width: cols as f32 * 3.0, ..size });
+            assert!(term.cursor.point.col < Column(cols));
+            term.resize(&size);
+
+            assert_eq!(buffer_text(&term), text, "round trip through {} columns", cols);
+            assert_eq!(term.cursor.point, cursor, "round trip through {} columns", cols);
+        }
+    }
+
+    #[test]
+    fn reflow_wide_chars_round_trip() {
+        let size = test_size();
+        let wide_size = SizeInfo { width: 24.0, ..size };
+        let input = "ab\u{4e2d}cd\u{4e2d}";
+
+        let mut term = Term::new(&Default::default(), size);
+        let mut wide_term = Term::new(&Default::default(), wide_size);
+        for c in input.chars() {
+            term.input(c);
+            wide_term.input(c);
+        }
+        let text = buffer_text(&term);
+        let cursor = term.cursor.point;
+
+        // The blank cell in front of the wrapped wide char is not content
+        term.resize(&wide_size);
+        assert_eq!(buffer_text(&term), buffer_text(&wide_term));
+
+        term.resize(&size);
+        assert_eq!(buffer_text(&term), text);
+        assert_eq!(term.cursor.point, cursor);
+    }
+
+    #[test]
+    fn reflow_keeps_cursor_on_character() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for c in "abcdefghijklm".chars() {
+            term.input(c);
+        }
+        term.goto(Line(1), Column(3));
+        assert_eq!(term.grid()[&term.cursor.point].c, 'k');
+
+        for &width in &[12.0, 9.0, 30.0, 21.0] {
+            term.resize(&SizeInfo { width, ..size });
+            assert_eq!(term.grid()[&term.cursor.point].c, 'k');
+        }
+
+        // A pending wrap moves behind the character when it's not in the last column anymore
+        term.goto(Line(0), Column(6));
+        term.input('x');
+        assert!(term.input_needs_wrap);
+        term.resize(&SizeInfo { width: 30.0, ..size });
+        assert!(!term.input_needs_wrap);
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(7)));
+    }
+
+    #[test]
+    fn reflow_skips_alt_screen() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for c in "abcdefghij".chars() {
+            term.input(c);
+        }
+
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.goto(Line(0), Column(0));
+        for c in "klmnopqrst".chars() {
+            term.input(c);
+        }
+
+        term.resize(&SizeInfo { width: 12.0, ..size });
+        term.resize(&size);
+        assert_eq!(&buffer_text(&term)[..2], &["klmn", "rst"]);
+
+        // The primary screen was reflowed in the background
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert_eq!(&buffer_text(&term)[..2], &["abcdefg", "hij"]);
+        assert_eq!(term.cursor.point, Point::new(Line(1), Column(3)));
+    }
+
+    #[test]
+    fn reflow_alt_screen_moves_saved_cursor() {
+        let size = test_size();
+        let mut term = Term::new(&Default::default(), size);
+        for c in "abcdefghij".chars() {
+            term.input(c);
+        }
+
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.goto(Line(5), Column(0));
+        term.input('x');
+
+        // The cursor returns behind the 'j' after a single resize
+        term.resize(&SizeInfo { width: 12.0, ..size });
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert_eq!(&buffer_text(&term)[..3], &["abcd", "efgh", "ij"]);
+        assert_eq!(term.cursor.point, Point::new(Line(2), Column(2)));
+    }
+
+    #[test]
+    fn reflow_alt_screen_without_saved_cursor() {
+        let size = test_size();
+        let mut term = Term::new(&Default::default(), size);
+        for c in "abcdefghij".chars() {
+            term.input(c);
+        }
+
+        // Nothing is saved when switching screens without 1049
+        term.set_mode(ansi::Mode::SwapScreen);
+        term.goto(Line(3), Column(0));
+        term.input('x');
+
+        term.resize(&SizeInfo { width: 12.0, ..size });
+        assert_eq!(term.cursor.point, Point::new(Line(3), Column(1)));
+
+        term.unset_mode(ansi::Mode::SwapScreen);
+        assert_eq!(&buffer_text(&term)[..4], &["abcd", "efgh", "ij", ""]);
+        assert_eq!(term.cursor.point, Point::new(Line(3), Column(1)));
+    }
+
+    #[test]
+    fn resize_moves_lines_through_history() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut parser = ansi::Processor::new();
+        for byte in &b"1\r\n2\r\n3\r\n4\r\n5\r\n6\r\n7\r\n8\r\n9\r\n10"[..] {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        let lines: Vec<_> = (1..11).map(|i| i.to_string()).collect();
+
+        // The lines above the cursor are pushed into the history
+        term.resize(&SizeInfo { height: 15.0, ..size });
+        assert_eq!(term.cursor.point, Point::new(Line(4), Column(2)));
+        assert_eq!(term.grid().scroll_limit(), 5);
+        assert_eq!(buffer_text(&term), lines);
+
+        // And pulled back out when growing
+        term.resize(&size);
+        assert_eq!(term.cursor.point, Point::new(Line(9), Column(2)));
+        assert_eq!(term.grid().scroll_limit(), 0);
+        assert_eq!(&buffer_text(&term)[..10], &lines[..]);
+    }
+
+    #[test]
+    fn resize_primary_screen_behind_alt_screen() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for _ in 0..9 {
+            term.input('x');
+            term.carriage_return();
+            term.linefeed();
+        }
+        term.input('y');
+
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.resize(&SizeInfo { height: 15.0, ..size });
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+
+        // The primary screen is scrolled using its own cursor
+        assert_eq!(term.cursor.point, Point::new(Line(4), Column(1)));
+        assert_eq!(term.grid()[Line(4)][Column(0)].c, 'y');
+    }
+
+    #[test]
+    fn random_resizes() {
+        let size = SizeInfo {
+            width: 240.0,
+            height: 72.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+
+        // Xorshift, so failures can be reproduced
+        let mut state = 0x2545_f491u32;
+        let mut random = |max: usize| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize % max
+        };
+
+        for _ in 0..500 {
+            match random(6) {
+                0 => {
+                    for _ in 0..random(200) {
+                        term.input('a');
+                    }
+                },
+                1 => {
+                    let (line, col) = (Line(random(40)), Column(random(100)));
+                    term.goto(line, col);
+                },
+                2 => term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor),
+                3 => term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor),
+                4 => term.save_cursor_position(),
+                _ => term.scroll_display(Scroll::Lines(random(20) as isize)),
+            }
+
+            let width = (random(100) + 1) as f32 * 3.0;
+            let height = (random(40) + 1) as f32 * 3.0;
+            term.resize(&SizeInfo { width, height, ..size });
+
+            let (lines, cols) = (term.grid().num_lines(), term.grid().num_cols());
+            assert!(term.cursor.point.line < lines && term.cursor.point.col < cols);
+            for saved in &[term.cursor_save, term.cursor_save_alt] {
+                assert!(saved.cursor.point.line < lines && saved.cursor.point.col < cols);
+            }
+            assert_eq!(term.alt_grid.num_lines(), lines);
+            assert_eq!(term.alt_grid.num_cols(), cols);
+            assert_eq!(term.scroll_region, Line(0)..lines);
+            assert_eq!(term.tabs.len(), cols.0);
+            assert!(term.grid().display_offset() <= term.grid().scroll_limit());
+
+            let _ = term.renderable_cells(&config, false, true).count();
+        }
+    }
+
+    #[test]
+    fn strikeout_attribute() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        term.terminal_attribute(Attr::Strike);
+        term.input('a');
+        term.terminal_attribute(Attr::CancelStrike);
+        term.input('b');
+
+        assert!(term.grid()[Line(0)][Column(0)].flags.contains(cell::Flags::STRIKEOUT));
+        assert!(!term.grid()[Line(0)][Column(1)].flags.contains(cell::Flags::STRIKEOUT));
+    }
+
+    #[test]
+    fn sgr_text_attributes() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
+
+        let attributes = cell::Flags::ITALIC
+            | cell::Flags::DIM
+            | cell::Flags::STRIKEOUT
+            | cell::Flags::HIDDEN;
+        for byte in &b"\x1b[3;2;9;8ma\x1b[23;22;29;28mb\x1b[3;2;9;8m\x1b[0mc"[..] {
+            parser.advance(&mut term, *byte, &mut reply);
+        }
+
+        assert_eq!(term.grid()[Line(0)][Column(0)].flags & attributes, attributes);
+        assert_eq!(term.grid()[Line(0)][Column(1)].flags & attributes, cell::Flags::empty());
+        assert_eq!(term.grid()[Line(0)][Column(2)].flags & attributes, cell::Flags::empty());
+    }
+
+    #[test]
+    fn dim_darkens_foreground() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let rgb = Rgb { r: 150, g: 90, b: 30 };
+
+        term.terminal_attribute(Attr::Dim);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.input('a');
+        term.terminal_attribute(Attr::Foreground(Color::Spec(rgb)));
+        term.input('b');
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(100)));
+        term.input('c');
+        term.terminal_attribute(Attr::CancelBoldDim);
+        term.input('d');
+
+        let colors = color::List::from(config.colors());
+        let fg: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line == Line(0) && rc.column < Column(4))
+            .map(|rc| rc.fg)
+            .collect();
+        assert_eq!(fg, vec![
+            colors[NamedColor::DimRed],
+            Rgb { r: 99, g: 59, b: 19 },
+            colors[100] * color::DIM_FACTOR,
+            colors[100],
+        ]);
+    }
+
+    #[test]
+    fn dim_uses_dim_palette_for_indexed_colors() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        assert!(config.draw_bold_text_with_bright_colors());
+        let mut term = Term::new(&config, size);
+        let rgb = Rgb { r: 150, g: 90, b: 30 };
+
+        term.terminal_attribute(Attr::Dim);
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(1)));
+        term.input('a');
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(9)));
+        term.input('b');
+        term.terminal_attribute(Attr::Foreground(Color::Spec(rgb)));
+        term.input('c');
+
+        // Bold and dim cancel each other out
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Indexed(1)));
+        term.input('d');
+        term.terminal_attribute(Attr::Foreground(Color::Spec(rgb)));
+        term.input('e');
+
+        let colors = color::List::from(config.colors());
+        let fg: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line == Line(0) && rc.column < Column(5))
+            .map(|rc| rc.fg)
+            .collect();
+        assert_eq!(fg, vec![
+            colors[NamedColor::DimRed],
+            colors[NamedColor::Red],
+            rgb * color::DIM_FACTOR,
+            colors[NamedColor::Red],
+            rgb,
+        ]);
+    }
+
+    #[test]
+    fn unfocused_cursor_is_hollow() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let colors = color::List::from(config.colors());
+
+        term.terminal_attribute(Attr::Background(Color::Named(NamedColor::Red)));
+        term.input('a');
+        term.terminal_attribute(Attr::Reverse);
+        term.input('b');
+
+        // The outline is drawn on top of the cell without hiding its background
+        term.goto_col(Column(0));
+        let cells: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line == Line(0) && rc.column == Column(0))
+            .map(|rc| (rc.chars[0], rc.fg, rc.bg_alpha))
+            .collect();
+        assert_eq!(cells, vec![
+            ('a', colors[NamedColor::Foreground], 1.0),
+            (::font::BOX_CURSOR_CHAR, colors[NamedColor::Foreground], 0.0),
+        ]);
+
+        // Inverse text keeps the color of its text for the outline
+        term.goto_col(Column(1));
+        let cells: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line == Line(0) && rc.column == Column(1))
+            .map(|rc| (rc.chars[0], rc.fg, rc.bg_alpha))
+            .collect();
+        assert_eq!(cells, vec![
+            ('b', colors[NamedColor::Red], 1.0),
+            (::font::BOX_CURSOR_CHAR, colors[NamedColor::Red], 0.0),
+        ]);
+
+        // The focused window shows the block cursor
+        let cursor = term.renderable_cells(&config, true, true)
+            .filter(|rc| rc.line == Line(0) && rc.column == Column(1))
+            .last()
+            .map(|rc| (rc.chars[0], rc.bg_alpha));
+        assert_eq!(cursor, Some(('b', 1.0)));
+    }
+
+    #[test]
+    fn blinking_text_is_hidden_while_off() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+
+        term.terminal_attribute(Attr::BlinkSlow);
+        term.input('a');
+        term.terminal_attribute(Attr::CancelBlink);
+        term.input('b');
+
+        assert!(term.grid()[Line(0)][Column(0)].flags.contains(cell::Flags::BLINK));
+        assert!(!term.grid()[Line(0)][Column(1)].flags.contains(cell::Flags::BLINK));
+
+        let hidden = |visible| {
+            let cells: Vec<_> = term.renderable_cells(&config, false, visible)
+                .filter(|rc| rc.line == Line(0) && rc.column < Column(2))
+                .map(|rc| rc.flags.contains(cell::Flags::HIDDEN))
+                .collect();
+            cells
+        };
+        assert_eq!(hidden(true), vec![false, false]);
+        assert_eq!(hidden(false), vec![true, false]);
+    }
+
+    #[test]
+    fn blink_phase_follows_shared_epoch() {
+        let size = test_size();
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+
+        let epoch = Arc::new(AtomicUsize::new(0));
+        term.set_blink_epoch(Arc::clone(&epoch));
+        assert!(term.blink_visible());
+
+        epoch.fetch_add(1, Ordering::Relaxed);
+        assert!(!term.blink_visible());
+
+        epoch.fetch_add(1, Ordering::Relaxed);
+        assert!(term.blink_visible());
+    }
+
+    #[test]
+    fn blinking_cursor_is_hidden_while_off() {
+        let size = test_size();
+        let mut term = Term::new(&Default::default(), size);
+
+        term.set_cursor_style(Some(CursorStyle::Beam));
+        term.set_cursor_blinking(false);
+        assert!(term.render_mode(false).contains(TermMode::SHOW_CURSOR));
+
+        term.set_cursor_blinking(true);
+        assert!(term.has_blinking_cursor());
+        assert!(term.render_mode(true).contains(TermMode::SHOW_CURSOR));
+        assert!(!term.render_mode(false).contains(TermMode::SHOW_CURSOR));
+
+        // The alternate screen keeps its own blinking state
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.set_cursor_blinking(false);
+        term.unset_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        assert!(term.cursor_blinking);
+    }
+
+    #[test]
+    fn reverse_video_swaps_default_colors() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let fg = term.colors[NamedColor::Foreground];
+        let bg = term.colors[NamedColor::Background];
+
+        term.input('a');
+        term.terminal_attribute(Attr::Reverse);
+        term.input('b');
+
+        term.dirty = false;
+        term.set_mode(ansi::Mode::ReverseVideo);
+        assert!(term.dirty);
+        assert_eq!(term.background_color(), fg);
+
+        let cells: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line == Line(0) && rc.column < Column(2))
+            .map(|rc| (rc.fg, rc.bg, rc.bg_alpha))
+            .collect();
+        assert_eq!(cells, vec![(bg, fg, 0.0), (fg, bg, 1.0)]);
+
+        term.reset_state();
+        assert!(!term.mode.contains(TermMode::REVERSE_VIDEO));
+        assert_eq!(term.background_color(), bg);
+    }
+
+    #[test]
+    fn underline_styles_replace_each_other() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        term.terminal_attribute(Attr::Underscore);
+        term.terminal_attribute(Attr::DoubleUnderline);
+        term.input('a');
+        term.terminal_attribute(Attr::Underscore);
+        term.input('b');
+        term.terminal_attribute(Attr::DoubleUnderline);
+        term.terminal_attribute(Attr::CancelUnderline);
+        term.input('c');
+        term.terminal_attribute(Attr::CurlyUnderline);
+        term.input('d');
+        term.terminal_attribute(Attr::DottedUnderline);
+        term.input('e');
+        term.terminal_attribute(Attr::DashedUnderline);
+        term.input('f');
+
+        let flags = |col| term.grid()[Line(0)][Column(col)].flags & cell::Flags::ALL_UNDERLINES;
+        assert_eq!(flags(0), cell::Flags::DOUBLE_UNDERLINE);
+        assert_eq!(flags(1), cell::Flags::UNDERLINE);
+        assert_eq!(flags(2), cell::Flags::empty());
+        assert_eq!(flags(3), cell::Flags::CURLY_UNDERLINE);
+        assert_eq!(flags(4), cell::Flags::DOTTED_UNDERLINE);
+        assert_eq!(flags(5), cell::Flags::DASHED_UNDERLINE);
+    }
+
+    #[test]
+    fn underline_color_is_stored_on_cells() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let color = Color::Spec(Rgb { r: 255, g: 0, b: 0 });
+
+        term.terminal_attribute(Attr::Underscore);
+        term.terminal_attribute(Attr::UnderlineColor(Some(color)));
+        term.input('a');
+        term.terminal_attribute(Attr::UnderlineColor(None));
+        term.input('b');
+        term.terminal_attribute(Attr::UnderlineColor(Some(color)));
+        term.terminal_attribute(Attr::Reset);
+        term.input('c');
+
+        assert_eq!(term.grid()[Line(0)][Column(0)].underline_color, Some(color));
+        assert_eq!(term.grid()[Line(0)][Column(1)].underline_color, None);
+        assert_eq!(term.grid()[Line(0)][Column(2)].underline_color, None);
+    }
+
+    #[test]
+    fn underline_color_is_resolved_like_foreground() {
+        let size = test_size();
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let colors = color::List::from(config.colors());
+
+        term.terminal_attribute(Attr::Underscore);
+        term.terminal_attribute(Attr::Dim);
+        term.terminal_attribute(Attr::UnderlineColor(Some(Color::Named(NamedColor::Red))));
+        term.input('a');
+
+        let underline = term.renderable_cells(&config, false, true)
+            .find(|rc| rc.line == Line(0) && rc.column == Column(0))
+            .and_then(|rc| rc.underline_color);
+        assert_eq!(underline, Some(colors[NamedColor::DimRed]));
+    }
+
+    #[test]
+    fn input_combining_chars() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        // Dropped without a preceding cell
+        term.input('\u{301}');
+        for c in "e\u{301}漢\u{302}x".chars() {
+            term.input(c);
+        }
+
+        let zerowidth = |col| {
+            let cell = term.grid()[Line(0)][Column(col)];
+            cell.zerowidth.map(|id| term.zerowidth.get(id))
+        };
+        assert_eq!(zerowidth(0), Some(&['\u{301}'][..]));
+        assert_eq!(zerowidth(1), Some(&['\u{302}'][..]));
+        assert_eq!(zerowidth(2), None);
+        assert_eq!(term.grid()[Line(0)][Column(3)].c, 'x');
+        assert_eq!(term.cursor().point.col, Column(4));
+
+        let mut selection = Selection::simple(Point { line: 16, col: Column(0) }, Side::Left);
+        selection.update(Point { line: 16, col: Column(3) }, Side::Right);
+        *term.selection_mut() = Some(selection);
+        assert_eq!(term.selection_to_string(), Some("e\u{301}漢\u{302}x".into()));
+    }
+
+    #[test]
+    fn wide_char_at_last_column_wraps() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        for _ in 0..6 {
+            term.input('a');
+        }
+        term.input('漢');
+
+        assert_eq!(term.grid()[Line(0)][Column(6)].c, ' ');
+        assert!(term.grid()[Line(0)][Column(6)].flags.contains(cell::Flags::WRAPLINE));
+        assert!(!term.grid()[Line(0)][Column(6)].flags.contains(cell::Flags::WIDE_CHAR));
+
+        assert_eq!(term.grid()[Line(1)][Column(0)].c, '漢');
+        assert!(term.grid()[Line(1)][Column(0)].flags.contains(cell::Flags::WIDE_CHAR));
+        assert!(term.grid()[Line(1)][Column(1)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+        assert_eq!(term.cursor().point, Point { line: Line(1), col: Column(2) });
+    }
+
+    #[test]
+    fn overwriting_half_of_wide_char_clears_both() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.input('漢');
+        term.input('字');
+
+        // Overwrite the spacer of the first wide char
+        term.goto_col(Column(1));
+        term.input('a');
+        assert_eq!(term.grid()[Line(0)][Column(0)].c, ' ');
+        assert!(!term.grid()[Line(0)][Column(0)].flags.contains(cell::Flags::WIDE_CHAR));
+        assert_eq!(term.grid()[Line(0)][Column(1)].c, 'a');
+
+        // Overwrite the lead cell of the second wide char
+        term.input('b');
+        assert_eq!(term.grid()[Line(0)][Column(2)].c, 'b');
+        assert!(!term.grid()[Line(0)][Column(3)].flags.contains(cell::Flags::WIDE_CHAR_SPACER));
+    }
+
+    fn change_font_size_works(font_size: f32) {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+        term.change_font_size(font_size);
+
+        let expected_font_size: Size = config.font().size() + Size::new(font_size);
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    #[test]
+    fn increase_font_size_works() {
+        change_font_size_works(10.0);
+    }
+
+    #[test]
+    fn decrease_font_size_works() {
+        change_font_size_works(-10.0);
+    }
+
+    #[test]
+    fn prevent_font_below_threshold_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.change_font_size(-100.0);
+
+        let expected_font_size: Size = Size::new(FONT_SIZE_STEP);
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    #[test]
+    fn set_font_size_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.set_font_size(14.0);
+        assert_eq!(term.font_size, Size::new(14.0));
+
+        term.set_font_size(0.0);
+        assert_eq!(term.font_size, Size::new(FONT_SIZE_STEP));
+
+        term.set_font_size(1e9);
+        assert_eq!(term.font_size, Size::new(MAX_FONT_SIZE));
+
+        term.change_font_size(1e9);
+        assert_eq!(term.font_size, Size::new(MAX_FONT_SIZE));
+    }
+
+    #[test]
+    fn reset_font_size_works() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        term.change_font_size(10.0);
+        term.reset_font_size();
+
+        let expected_font_size: Size = config.font().size();
+        assert_eq!(term.font_size, expected_font_size);
+    }
+
+    fn font_config(size: &str) -> Config {
+        let yaml = format!("font:\n  normal:\n    family: monospace\n  size: {}", size);
+        ::serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn reset_font_size_after_config_reload() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        // An unchanged size follows the config
+        let config: Config = font_config("20.5");
+        term.update_config(&config);
+        assert_eq!(term.font_size, Size::new(20.5));
+
+        // A size changed with the bindings is kept until it's reset
+        term.change_font_size(FONT_SIZE_STEP);
+        term.change_font_size(FONT_SIZE_STEP);
+        let config: Config = font_config("9.0");
+        term.update_config(&config);
+        assert_eq!(term.font_size, Size::new(21.5));
+
+        term.reset_font_size();
+        assert_eq!(term.font_size, Size::new(9.0));
+    }
+
+    #[test]
+    fn bell_requests_attention() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term: Term = Term::new(&Default::default(), size);
+        term.bell();
+        assert_eq!(term.next_is_urgent, Some(true));
+
+        let config: Config = ::serde_yaml::from_str("bell:\n  urgent: false").unwrap();
+        term.update_config(&config);
+        term.next_is_urgent = None;
+        term.bell();
+        assert_eq!(term.next_is_urgent, None);
+        assert!(term.bell_rung);
+    }
+
+    #[test]
+    fn erase_uses_background_color() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        term.terminal_attribute(Attr::Background(Color::Named(NamedColor::Blue)));
+        term.terminal_attribute(Attr::Bold);
+
+        let mut blank = Cell::default();
+        blank.bg = Color::Named(NamedColor::Blue);
+
+        // Fill the first three lines and erase with the cursor on the middle one
+        let erase = |term: &mut Term, clear: &Fn(&mut Term)| -> Vec<(usize, usize)> {
+            for line in 0..3 {
+                for col in 0..7 {
+                    term.grid[Line(line)][Column(col)] = Cell::default();
+                    term.grid[Line(line)][Column(col)].c = 'x';
+                }
+            }
+            term.grid[Line(1)][Column(2)].flags.insert(cell::Flags::WIDE_CHAR);
+            term.grid[Line(1)][Column(3)].flags.insert(cell::Flags::WIDE_CHAR_SPACER);
+
+            clear(term);
+
+            let mut erased = Vec::new();
+            for line in 0..3 {
+                for col in 0..7 {
+                    if term.grid[Line(line)][Column(col)] == blank {
+                        erased.push((line, col));
+                    }
+                }
+            }
+            erased
+        };
+        let span = |line: usize, cols: Range<usize>| -> Vec<(usize, usize)> {
+            cols.map(|col| (line, col)).collect()
+        };
+
+        // Erasing half of a wide char erases both of its cells
+        term.goto(Line(1), Column(3));
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_line(ansi::LineClearMode::Right));
+        assert_eq!(erased, span(1, 2..7));
+
+        term.goto(Line(1), Column(2));
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_line(ansi::LineClearMode::Left));
+        assert_eq!(erased, span(1, 0..4));
+
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_line(ansi::LineClearMode::All));
+        assert_eq!(erased, span(1, 0..7));
+
+        term.goto(Line(1), Column(4));
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_screen(ansi::ClearMode::Below));
+        assert_eq!(erased, [span(1, 4..7), span(2, 0..7)].concat());
+
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_screen(ansi::ClearMode::Above));
+        assert_eq!(erased, [span(0, 0..7), span(1, 0..5)].concat());
+
+        let erased = erase(&mut term, &|term: &mut Term| term.clear_screen(ansi::ClearMode::All));
+        assert_eq!(erased, [span(0, 0..7), span(1, 0..7), span(2, 0..7)].concat());
+    }
+
+    #[test]
+    fn clear_saved_lines() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config: Config = Default::default();
+        let mut term: Term = Term::new(&config, size);
+
+        // Add one line of scrollback
+        term.grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
+
+        term.scroll_display(Scroll::Lines(1));
+        assert_eq!(term.grid.display_offset(), 1);
+
+        // Clear the history
+        term.clear_screen(ansi::ClearMode::Saved);
+        assert_eq!(term.grid.display_offset(), 0);
+
+        // Make sure that scrolling does not change the grid
+        let mut scrolled_grid = term.grid.clone();
+        scrolled_grid.scroll_display(Scroll::Top);
+        assert_eq!(term.grid, scrolled_grid);
+    }
+
+    // `((ftp://a.de))` -> `Some("ftp://a.de")`
+    #[test]
+    fn url_trim_unmatched_parens() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
+        grid[Line(0)][Column(0)].c = '(';
+        grid[Line(0)][Column(1)].c = '(';
+        grid[Line(0)][Column(2)].c = 'f';
+        grid[Line(0)][Column(3)].c = 't';
+        grid[Line(0)][Column(4)].c = 'p';
+        grid[Line(0)][Column(5)].c = ':';
+        grid[Line(0)][Column(6)].c = '/';
+        grid[Line(0)][Column(7)].c = '/';
+        grid[Line(0)][Column(8)].c = 'a';
+        grid[Line(0)][Column(9)].c = '.';
+        grid[Line(0)][Column(10)].c = 'd';
+        grid[Line(0)][Column(11)].c = 'e';
+        grid[Line(0)][Column(12)].c = ')';
+        grid[Line(0)][Column(13)].c = ')';
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Search for URL in grid
+        let url = term.url_search(Point::new(0, Column(4)));
+
+        assert_eq!(url, Some("ftp://a.de".into()));
+    }
+
+    // `ftp://a.de/()` -> `Some("ftp://a.de/()")`
+    #[test]
+    fn url_allow_matching_parens() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
+        grid[Line(0)][Column(0)].c = 'f';
+        grid[Line(0)][Column(1)].c = 't';
+        grid[Line(0)][Column(2)].c = 'p';
+        grid[Line(0)][Column(3)].c = ':';
+        grid[Line(0)][Column(4)].c = '/';
+        grid[Line(0)][Column(5)].c = '/';
+        grid[Line(0)][Column(6)].c = 'a';
+        grid[Line(0)][Column(7)].c = '.';
+        grid[Line(0)][Column(8)].c = 'd';
+        grid[Line(0)][Column(9)].c = 'e';
+        grid[Line(0)][Column(10)].c = '/';
+        grid[Line(0)][Column(11)].c = '(';
+        grid[Line(0)][Column(12)].c = ')';
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Search for URL in grid
+        let url = term.url_search(Point::new(0, Column(4)));
+
+        assert_eq!(url, Some("ftp://a.de/()".into()));
+    }
+
+    // `aze` -> `None`
+    #[test]
+    fn url_skip_invalid() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
+        grid[Line(0)][Column(0)].c = 'a';
+        grid[Line(0)][Column(1)].c = 'z';
+        grid[Line(0)][Column(2)].c = 'e';
+        mem::swap(&mut term.grid, &mut grid);
+
+        // Search for URL in grid
+        let url = term.url_search(Point::new(0, Column(1)));
 
-    #[inline]
-    fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
-        trace!("set_cursor_style {:?}", style);
-        self.cursor_style = style;
+        assert_eq!(url, None);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    extern crate serde_json;
-
-    use super::{Cell, Term, SizeInfo};
-    use term::{cell, Search};
-
-    use grid::{Grid, Scroll};
-    use index::{Point, Line, Column, Side};
-    use ansi::{self, Handler, CharsetIndex, StandardCharset};
-    use selection::Selection;
-    use std::mem;
-    use input::FONT_SIZE_STEP;
-    use font::Size;
-    use config::Config;
 
     #[test]
-    fn semantic_selection_works() {
+    fn url_across_wrapped_lines() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2067,40 +6586,86 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(5), 0, Cell::default());
-        for i in 0..5 {
-            for j in 0..2 {
-                grid[Line(j)][Column(i)].c = 'a';
-            }
+        for c in "see https://a.de/xyz.".chars() {
+            term.input(c);
         }
-        grid[Line(0)][Column(0)].c = '"';
-        grid[Line(0)][Column(3)].c = '"';
-        grid[Line(1)][Column(2)].c = '"';
-        grid[Line(0)][Column(4)].flags.insert(cell::Flags::WRAPLINE);
 
-        let mut escape_chars = String::from("\"");
+        let url = term.url_at(Point::new(1, Column(2))).unwrap();
+        assert_eq!(url.text, "https://a.de/xyz");
+        assert_eq!(url.start, Point::new(16, Column(4)));
+        assert_eq!(url.end, Point::new(14, Column(5)));
 
-        mem::swap(&mut term.grid, &mut grid);
-        mem::swap(&mut term.semantic_escape_chars, &mut escape_chars);
+        // Trailing punctuation isn't part of the URL
+        assert_eq!(term.url_at(Point::new(2, Column(6))), None);
+        assert_eq!(term.url_at(Point::new(0, Column(1))), None);
+    }
 
-        {
-            *term.selection_mut() = Some(Selection::semantic(Point { line: 2, col: Column(1) }));
-            assert_eq!(term.selection_to_string(), Some(String::from("aa")));
-        }
+    #[test]
+    fn hyperlinks() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+        let mut reply = Vec::new();
 
-        {
-            *term.selection_mut() = Some(Selection::semantic(Point { line: 2, col: Column(4) }));
-            assert_eq!(term.selection_to_string(), Some(String::from("aaa")));
+        let bytes = b"\x1b]8;id=a;https://a.de\x07abcdefghi\x1b]8;;\x07x\x1b]8;id=a;https://a.de\x07y";
+        for byte in &bytes[..] {
+            parser.advance(&mut term, *byte, &mut reply);
         }
 
-        {
-            *term.selection_mut() = Some(Selection::semantic(Point { line: 1, col: Column(1) }));
-            assert_eq!(term.selection_to_string(), Some(String::from("aaa")));
+        let url = term.url_at(Point::new(1, Column(3))).unwrap();
+        assert_eq!(url.text, "https://a.de");
+        assert!(url.hyperlink.is_some());
+        assert_eq!(term.url_at(Point::new(1, Column(2))), None);
+
+        // Links to other schemes or without any aren't launched
+        let mut unsafe_links = Term::new(&config, size);
+        let bytes = b"\x1b]8;;javascript:alert(1)\x07a\x1b]8;;-rf\x07b\x1b]8;;\x07";
+        for byte in &bytes[..] {
+            parser.advance(&mut unsafe_links, *byte, &mut reply);
         }
+        assert!(unsafe_links.grid[Line(0)][Column(0)].hyperlink.is_some());
+        assert_eq!(unsafe_links.url_at(Point::new(0, Column(0))), None);
+        assert_eq!(unsafe_links.url_at(Point::new(0, Column(1))), None);
+
+        // All cells of the link are underlined on hover, even across wraps
+        term.unset_mode(ansi::Mode::ShowCursor);
+        term.set_hovered_url(Some(url));
+        let underlined: Vec<_> = term.renderable_cells(&config, false, true)
+            .filter(|rc| rc.line < Line(2))
+            .map(|rc| rc.flags.contains(cell::Flags::UNDERLINE))
+            .collect();
+        let mut expected = vec![true; 7];
+        expected.extend_from_slice(&[true, true, false, true, false, false, false]);
+        assert_eq!(underlined, expected);
+        term.set_hovered_url(None);
+
+        // Links in the history survive collection, unused ones are dropped
+        let id = term.grid[Line(0)][Column(0)].hyperlink.unwrap();
+        for _ in 0..20 {
+            term.linefeed();
+        }
+        term.set_hyperlink(Some(ansi::Hyperlink { id: None, uri: "https://b.de".into() }));
+        let unused = term.hyperlink.unwrap();
+        for _ in 0..300 {
+            term.set_hyperlink(Some(ansi::Hyperlink { id: None, uri: "https://b.de".into() }));
+        }
+        assert!(term.hyperlinks.get(id).is_some());
+        assert!(term.hyperlinks.get(unused).is_none());
+
+        term.reset_state();
+        assert!(term.hyperlinks.get(id).is_none());
     }
 
     #[test]
-    fn line_selection_works() {
+    fn url_schemes_and_brackets() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2110,22 +6675,22 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(5), 0, Cell::default());
-        for i in 0..5 {
-            grid[Line(0)][Column(i)].c = 'a';
+        let mut grid: Grid<Cell> = Grid::new(Line(2), Column(20), 0, Cell::default());
+        for (col, c) in "[mailto:a@b.de]".chars().enumerate() {
+            grid[Line(0)][Column(col)].c = c;
+        }
+        for (col, c) in "ssh://a.de".chars().enumerate() {
+            grid[Line(1)][Column(col)].c = c;
         }
-        grid[Line(0)][Column(0)].c = '"';
-        grid[Line(0)][Column(3)].c = '"';
-
-
         mem::swap(&mut term.grid, &mut grid);
 
-        *term.selection_mut() = Some(Selection::lines(Point { line: 0, col: Column(3) }));
-        assert_eq!(term.selection_to_string(), Some(String::from("\"aa\"a\n")));
+        let url = term.url_at(Point::new(0, Column(3)));
+        assert_eq!(url.map(|url| url.text), Some("mailto:a@b.de".into()));
+        assert_eq!(term.url_at(Point::new(1, Column(3))), None);
     }
 
     #[test]
-    fn selecting_empty_line() {
+    fn kitty_keyboard_mode_stack() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2135,41 +6700,38 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(3), Column(3), 0, Cell::default());
-        for l in 0..3 {
-            if l != 1 {
-                for c in 0..3 {
-                    grid[Line(l)][Column(c)].c = 'a';
-                }
-            }
-        }
 
-        mem::swap(&mut term.grid, &mut grid);
+        // Disabled by default, so the query reports no modes
+        let mut reply = Vec::new();
+        term.push_keyboard_mode(KeyboardModes::DISAMBIGUATE_ESC_CODES);
+        term.report_keyboard_mode(&mut reply);
+        assert_eq!(reply, b"\x1b[?0u");
+        assert!(!term.mode().intersects(TermMode::KITTY_KEYBOARD_PROTOCOL));
 
-        let mut selection = Selection::simple(Point { line: 2, col: Column(0) }, Side::Left);
-        selection.update(Point { line: 0, col: Column(2) }, Side::Right);
-        *term.selection_mut() = Some(selection);
-        assert_eq!(term.selection_to_string(), Some("aaa\n\naaa\n".into()));
-    }
+        reply.clear();
 
-    /// Check that the grid can be serialized back and forth losslessly
-    ///
-    /// This test is in the term module as opposed to the grid since we want to
-    /// test this property with a T=Cell.
-    #[test]
-    fn grid_serde() {
-        let template = Cell::default();
+        term.kitty_keyboard = true;
+        term.push_keyboard_mode(KeyboardModes::DISAMBIGUATE_ESC_CODES);
+        term.push_keyboard_mode(KeyboardModes::REPORT_EVENT_TYPES);
+        term.set_keyboard_mode(KeyboardModes::REPORT_ALL_KEYS_AS_ESC, KeyboardModesApplyBehavior::Union);
+        term.report_keyboard_mode(&mut reply);
+        assert_eq!(reply, b"\x1b[?10u");
 
-        let grid: Grid<Cell> = Grid::new(Line(24), Column(80), 0, template);
-        let serialized = serde_json::to_string(&grid).expect("ser");
-        let deserialized = serde_json::from_str::<Grid<Cell>>(&serialized)
-                                      .expect("de");
+        term.pop_keyboard_modes(1);
+        assert_eq!(*term.mode() & TermMode::KITTY_KEYBOARD_PROTOCOL, TermMode::DISAMBIGUATE_ESC_CODES);
 
-        assert_eq!(deserialized, grid);
+        // The alternate screen has its own stack
+        term.swap_alt();
+        assert!(!term.mode().intersects(TermMode::KITTY_KEYBOARD_PROTOCOL));
+        term.swap_alt();
+        assert!(term.mode().contains(TermMode::DISAMBIGUATE_ESC_CODES));
+
+        term.pop_keyboard_modes(5);
+        assert!(!term.mode().intersects(TermMode::KITTY_KEYBOARD_PROTOCOL));
     }
 
     #[test]
-    fn input_line_drawing_character() {
+    fn title_stack() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2179,15 +6741,30 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let cursor = Point::new(Line(0), Column(0));
-        term.configure_charset(CharsetIndex::G0,
-                               StandardCharset::SpecialCharacterAndLineDrawing);
-        term.input('a');
 
-        assert_eq!(term.grid()[&cursor].c, '▒');
+        // Popping an empty stack is a no-op
+        term.pop_title();
+        assert_eq!(term.get_next_title(), None);
+
+        term.set_title("shell");
+        term.push_title();
+        term.set_title("vim");
+        assert_eq!(term.get_next_title(), Some("vim".into()));
+
+        term.pop_title();
+        assert_eq!(term.get_next_title(), Some("shell".into()));
+
+        // The stack is capped and drops the oldest entries
+        for i in 0..40 {
+            term.set_title(&i.to_string());
+            term.push_title();
+        }
+        assert_eq!(term.title_stack.len(), 32);
+        assert_eq!(term.title_stack[0], Some("8".into()));
     }
 
-    fn change_font_size_works(font_size: f32) {
+    #[test]
+    fn auto_wrap_modes() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2196,26 +6773,91 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
-        term.change_font_size(font_size);
+        let mut term = Term::new(&Default::default(), size);
 
-        let expected_font_size: Size = config.font().size() + Size::new(font_size);
-        assert_eq!(term.font_size, expected_font_size);
-    }
+        // Without auto-wrap the last cell is overwritten
+        term.unset_mode(ansi::Mode::LineWrap);
+        for c in "abcdefgh".chars() {
+            term.input(c);
+        }
+        assert_eq!(term.grid[Line(0)][Column(6)].c, 'h');
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(6)));
 
-    #[test]
-    fn increase_font_size_works() {
-        change_font_size_works(10.0);
+        // Wide chars take up the last two cells instead
+        term.input('Ｗ');
+        assert!(term.grid[Line(0)][Column(5)].flags.contains(cell::Flags::WIDE_CHAR));
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(6)));
+
+        // Backspace only wraps to the previous line with reverse-wraparound
+        term.set_mode(ansi::Mode::LineWrap);
+        term.carriage_return();
+        term.linefeed();
+        term.backspace();
+        assert_eq!(term.cursor.point, Point::new(Line(1), Column(0)));
+
+        term.set_mode(ansi::Mode::ReverseWrap);
+        term.backspace();
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(6)));
+
+        // There's no previous line to wrap to at the top
+        term.carriage_return();
+        term.backspace();
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(0)));
+
+        // Both modes survive a soft reset
+        term.unset_mode(ansi::Mode::LineWrap);
+        term.soft_reset();
+        assert!(term.mode().contains(TermMode::REVERSE_WRAP));
+        assert!(!term.mode().contains(TermMode::LINE_WRAP));
     }
 
     #[test]
-    fn decrease_font_size_works() {
-        change_font_size_works(-10.0);
+    fn scroll_sequences_keep_history() {
+        let size = SizeInfo {
+            width: 21.0,
+            height: 51.0,
+            cell_width: 3.0,
+            cell_height: 3.0,
+            padding_x: 0.0,
+            padding_y: 0.0,
+        };
+        let mut term = Term::new(&Default::default(), size);
+
+        // Push a single line into the scrollback history
+        term.input('h');
+        for _ in 0..17 {
+            term.linefeed();
+        }
+        assert_eq!(term.grid().scroll_limit(), 1);
+        term.grid[Line(0)][Column(0)].c = 'a';
+        term.grid[Line(1)][Column(0)].c = 'b';
+
+        // Lines scrolled out of the screen are discarded
+        term.scroll_up(Line(1));
+        assert_eq!(term.grid().scroll_limit(), 1);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, 'b');
+        assert_eq!(term.grid[Line(16)][Column(0)].c, ' ');
+
+        // Scrolling down doesn't consume the history either
+        term.scroll_down(Line(1));
+        assert_eq!(term.grid().scroll_limit(), 1);
+        assert_eq!(term.grid[Line(0)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(1)][Column(0)].c, 'b');
+        assert_eq!(term.grid[17][Column(0)].c, 'h');
+
+        // Only the scroll region is affected
+        term.set_scrolling_region(Line(1)..Line(3));
+        term.scroll_up(Line(5));
+        assert_eq!(term.grid[Line(1)][Column(0)].c, ' ');
+        assert_eq!(term.grid[Line(3)][Column(0)].c, ' ');
+        term.grid[Line(3)][Column(0)].c = 'd';
+        term.scroll_down(Line(1));
+        assert_eq!(term.grid[Line(3)][Column(0)].c, 'd');
+        assert_eq!(term.grid().scroll_limit(), 1);
     }
 
     #[test]
-    fn prevent_font_below_threshold_works() {
+    fn soft_reset_keeps_content() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2224,17 +6866,42 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
-
-        term.change_font_size(-100.0);
+        let mut term = Term::new(&Default::default(), size);
+        term.input('a');
 
-        let expected_font_size: Size = Size::new(FONT_SIZE_STEP);
-        assert_eq!(term.font_size, expected_font_size);
+        term.set_scrolling_region(Line(2)..Line(5));
+        term.set_mode(ansi::Mode::Origin);
+        term.set_mode(ansi::Mode::Insert);
+        term.unset_mode(ansi::Mode::LineWrap);
+        term.terminal_attribute(ansi::Attr::Bold);
+        term.configure_charset(CharsetIndex::G0, StandardCharset::SpecialCharacterAndLineDrawing);
+        term.clear_tabs(ansi::TabulationClearMode::All);
+        term.set_keypad_application_mode();
+        term.goto(Line(1), Column(2));
+        term.save_cursor_position();
+
+        term.soft_reset();
+
+        // The cursor keeps its position, but the saved cursor is moved home
+        assert_eq!(term.grid[Line(0)][Column(0)].c, 'a');
+        assert_eq!(term.cursor.point, Point::new(Line(3), Column(2)));
+        assert_eq!(term.cursor.template.flags, cell::Flags::empty());
+        assert_eq!(term.cursor_save.cursor.point, Point::new(Line(0), Column(0)));
+        assert_eq!(term.scroll_region, Line(0)..Line(17));
+        assert!(!term.mode().intersects(TermMode::ORIGIN | TermMode::INSERT | TermMode::APP_KEYPAD));
+        // Auto-wrap is preserved
+        assert!(!term.mode().contains(TermMode::LINE_WRAP));
+        assert!(term.tabs[8]);
+
+        // Replace mode is active again and the charset is ASCII
+        term.goto(Line(0), Column(0));
+        term.input('r');
+        assert_eq!(term.grid[Line(0)][Column(0)].c, 'r');
+        assert_eq!(term.grid[Line(0)][Column(1)].c, ' ');
     }
 
     #[test]
-    fn reset_font_size_works() {
+    fn saved_cursor_restores_attributes() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2243,18 +6910,48 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
-
-        term.change_font_size(10.0);
-        term.reset_font_size();
+        let mut term = Term::new(&Default::default(), size);
 
-        let expected_font_size: Size = config.font().size();
-        assert_eq!(term.font_size, expected_font_size);
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.configure_charset(CharsetIndex::G1, StandardCharset::SpecialCharacterAndLineDrawing);
+        term.set_active_charset(CharsetIndex::G1);
+        term.set_mode(ansi::Mode::Origin);
+        term.goto(Line(2), Column(6));
+        term.input('x');
+        term.save_cursor_position();
+
+        term.terminal_attribute(Attr::Reset);
+        term.set_active_charset(CharsetIndex::G0);
+        term.unset_mode(ansi::Mode::Origin);
+        term.goto(Line(5), Column(0));
+
+        term.restore_cursor_position();
+        assert_eq!(term.cursor.point, Point::new(Line(2), Column(6)));
+        assert!(term.mode().contains(TermMode::ORIGIN));
+
+        // The pending wrap and the saved attributes apply to the next character
+        term.input('q');
+        let cell = term.grid[Line(3)][Column(0)];
+        assert_eq!(cell.c, '─');
+        assert!(cell.flags.contains(cell::Flags::BOLD));
+        assert_eq!(cell.fg, Color::Named(NamedColor::Red));
+
+        // Each screen has its own saved cursor, restoring without one moves home
+        term.set_mode(ansi::Mode::SwapScreen);
+        term.goto(Line(4), Column(4));
+        term.restore_cursor_position();
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(0)));
+        assert_eq!(term.cursor.template, Cell::default());
+        assert!(!term.mode().contains(TermMode::ORIGIN));
+
+        term.unset_mode(ansi::Mode::SwapScreen);
+        term.restore_cursor_position();
+        assert_eq!(term.cursor.point, Point::new(Line(2), Column(6)));
     }
 
     #[test]
-    fn clear_saved_lines() {
+    fn full_reset_restores_defaults() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2263,24 +6960,82 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let config: Config = Default::default();
-        let mut term: Term = Term::new(&config, size);
+        let mut term = Term::new(&Default::default(), size);
 
-        // Add one line of scrollback
-        term.grid.scroll_up(&(Line(0)..Line(1)), Line(1), &Cell::default());
+        // Fill the scrollback history and scroll into it
+        term.input('a');
+        for _ in 0..20 {
+            term.linefeed();
+        }
+        term.scroll_display(Scroll::Lines(2));
+        term.grid[Line(0)][Column(0)].c = 'p';
+        *term.selection_mut() = Some(Selection::lines(Point::new(0, Column(0))));
+
+        // Change the state of the alternate screen
+        term.set_mode(ansi::Mode::SwapScreenAndSetRestoreCursor);
+        term.input('b');
+        term.set_scrolling_region(Line(2)..Line(5));
+        term.set_mode(ansi::Mode::Origin);
+        term.set_mode(ansi::Mode::Insert);
+        term.set_mode(ansi::Mode::CursorKeys);
+        term.set_mode(ansi::Mode::BracketedPaste);
+        term.set_mode(ansi::Mode::ReportMouseClicks);
+        term.unset_mode(ansi::Mode::LineWrap);
+        term.set_keypad_application_mode();
+        term.terminal_attribute(Attr::Bold);
+        term.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        term.configure_charset(CharsetIndex::G1, StandardCharset::SpecialCharacterAndLineDrawing);
+        term.set_active_charset(CharsetIndex::G1);
+        term.clear_tabs(ansi::TabulationClearMode::All);
+        term.set_cursor_style(Some(CursorStyle::Beam));
+        term.set_color(1, Rgb { r: 1, g: 2, b: 3 });
+        term.push_title();
+        term.goto(Line(2), Column(3));
+        term.save_cursor_position();
+
+        term.reset_state();
+
+        // Back on a cleared primary screen without any history
+        assert!(!term.mode().contains(TermMode::ALT_SCREEN));
+        assert!(!term.alt);
+        assert_eq!(term.grid().display_offset(), 0);
+        assert_eq!(term.grid().scroll_limit(), 0);
+        assert!(term.selection().is_none());
+        for line in 0..17 {
+            for col in 0..7 {
+                assert_eq!(term.grid[Line(line)][Column(col)], Cell::default());
+                assert_eq!(term.alt_grid[Line(line)][Column(col)], Cell::default());
+            }
+        }
 
-        // Clear the history
-        term.clear_screen(ansi::ClearMode::Saved);
+        // Modes, margins and tab stops
+        assert_eq!(term.mode(), &TermMode::default());
+        assert_eq!(term.scroll_region, Line(0)..Line(17));
+        assert_eq!(term.tabs, vec![true, false, false, false, false, false, false]);
 
-        // Make sure that scrolling does not change the grid
-        let mut scrolled_grid = term.grid.clone();
-        scrolled_grid.scroll_display(Scroll::Top);
-        assert_eq!(term.grid, scrolled_grid);
+        // Cursor position, attributes, charsets and style
+        assert_eq!(term.cursor.point, Point::new(Line(0), Column(0)));
+        assert_eq!(term.cursor.template, Cell::default());
+        assert_eq!(term.active_charset, CharsetIndex::G0);
+        assert_eq!(term.cursor_style, None);
+        assert_eq!(term.cursor_save.cursor.point, Point::new(Line(0), Column(0)));
+        assert_eq!(term.cursor_save_alt.cursor.point, Point::new(Line(0), Column(0)));
+
+        // Color palette and title stack
+        assert_eq!(term.colors[1], term.original_colors[1]);
+        assert!(term.color_modified.iter().all(|modified| !modified));
+        assert!(term.title_stack.is_empty());
+
+        // Both charset slots map to ASCII again
+        term.input('q');
+        term.set_active_charset(CharsetIndex::G1);
+        term.input('q');
+        assert_eq!(term.grid[Line(0)][Column(0)].c, 'q');
+        assert_eq!(term.grid[Line(0)][Column(1)].c, 'q');
     }
 
-    // `((ftp://a.de))` -> `Some("ftp://a.de")`
     #[test]
-    fn url_trim_unmatched_parens() {
+    fn sync_update_holds_back_drawing() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2290,32 +7045,23 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
-        grid[Line(0)][Column(0)].c = '(';
-        grid[Line(0)][Column(1)].c = '(';
-        grid[Line(0)][Column(2)].c = 'f';
-        grid[Line(0)][Column(3)].c = 't';
-        grid[Line(0)][Column(4)].c = 'p';
-        grid[Line(0)][Column(5)].c = ':';
-        grid[Line(0)][Column(6)].c = '/';
-        grid[Line(0)][Column(7)].c = '/';
-        grid[Line(0)][Column(8)].c = 'a';
-        grid[Line(0)][Column(9)].c = '.';
-        grid[Line(0)][Column(10)].c = 'd';
-        grid[Line(0)][Column(11)].c = 'e';
-        grid[Line(0)][Column(12)].c = ')';
-        grid[Line(0)][Column(13)].c = ')';
-        mem::swap(&mut term.grid, &mut grid);
 
-        // Search for URL in grid
-        let url = term.url_search(Point::new(0, Column(4)));
+        term.set_mode(ansi::Mode::SyncUpdate);
+        term.input('a');
+        term.dirty = true;
+        assert!(!term.needs_draw());
+        assert!(term.sync_update_deadline().is_some());
 
-        assert_eq!(url, Some("ftp://a.de".into()));
+        term.unset_mode(ansi::Mode::SyncUpdate);
+        assert!(term.needs_draw());
+
+        // Stale updates are drawn once the timeout expires
+        term.sync_update_start = Some(Instant::now() - SYNC_UPDATE_TIMEOUT);
+        assert!(term.needs_draw());
     }
 
-    // `ftp://a.de/()` -> `Some("ftp://a.de/()")`
     #[test]
-    fn url_allow_matching_parens() {
+    fn csi_u_mode_without_kitty_keyboard() {
         let size = SizeInfo {
             width: 21.0,
             height: 51.0,
@@ -2325,32 +7071,51 @@ mod tests {
             padding_y: 0.0,
         };
         let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
-        grid[Line(0)][Column(0)].c = 'f';
-        grid[Line(0)][Column(1)].c = 't';
-        grid[Line(0)][Column(2)].c = 'p';
-        grid[Line(0)][Column(3)].c = ':';
-        grid[Line(0)][Column(4)].c = '/';
-        grid[Line(0)][Column(5)].c = '/';
-        grid[Line(0)][Column(6)].c = 'a';
-        grid[Line(0)][Column(7)].c = '.';
-        grid[Line(0)][Column(8)].c = 'd';
-        grid[Line(0)][Column(9)].c = 'e';
-        grid[Line(0)][Column(10)].c = '/';
-        grid[Line(0)][Column(11)].c = '(';
-        grid[Line(0)][Column(12)].c = ')';
-        mem::swap(&mut term.grid, &mut grid);
 
-        // Search for URL in grid
-        let url = term.url_search(Point::new(0, Column(4)));
+        let all = KeyboardModes::all();
+        term.set_keyboard_mode(all, KeyboardModesApplyBehavior::Replace);
+        assert_eq!(*term.mode() & TermMode::KITTY_KEYBOARD_PROTOCOL, TermMode::DISAMBIGUATE_ESC_CODES);
+
+        term.set_keyboard_mode(KeyboardModes::NO_MODE, KeyboardModesApplyBehavior::Replace);
+        assert!(!term.mode().intersects(TermMode::KITTY_KEYBOARD_PROTOCOL));
+    }
+
+    /// Draw the damaged lines into `frame`, like the renderer does
+    fn draw_damage(
+        term: &mut Term,
+        frame: &mut Vec<Vec<RenderableCell>>,
+        config: &Config,
+        focused: bool,
+        blink_visible: bool,
+    ) -> Damage {
+        let damage = term.damage(config, focused, blink_visible);
+        match damage {
+            Damage::Full => *frame = vec![Vec::new(); term.grid().num_lines().0],
+            Damage::Lines(ref lines) => {
+                for line in lines {
+                    frame[line.0].clear();
+                }
+            },
+        }
 
-        assert_eq!(url, Some("ftp://a.de/()".into()));
+        for cell in term.damaged_cells(config, focused, blink_visible, &damage) {
+            frame[cell.line.0].push(cell);
+        }
+
+        // The result has to be the same as redrawing everything
+        let full: Vec<RenderableCell> = term.renderable_cells(config, focused, blink_visible)
+            .collect();
+        let damaged: Vec<RenderableCell> = frame.iter()
+            .flat_map(|line| line.iter().cloned())
+            .collect();
+        assert_eq!(damaged, full);
+
+        damage
     }
 
-    // `aze` -> `None`
     #[test]
-    fn url_skip_invalid() {
-        let size = SizeInfo {
+    fn damaged_lines_match_full_redraw() {
+        let mut size = SizeInfo {
             width: 21.0,
             height: 51.0,
             cell_width: 3.0,
@@ -2358,17 +7123,89 @@ mod tests {
             padding_x: 0.0,
             padding_y: 0.0,
         };
-        let mut term = Term::new(&Default::default(), size);
-        let mut grid: Grid<Cell> = Grid::new(Line(1), Column(15), 0, Cell::default());
-        grid[Line(0)][Column(0)].c = 'a';
-        grid[Line(0)][Column(1)].c = 'z';
-        grid[Line(0)][Column(2)].c = 'e';
-        mem::swap(&mut term.grid, &mut grid);
+        let config = Config::default();
+        let mut term = Term::new(&config, size);
+        let mut parser = ansi::Processor::new();
+        let mut frame = Vec::new();
+
+        let streams: &[&[u8]] = &[
+            b"hello\r\nworld",
+            // Overwriting a single line
+            b"\x1b[1;3HXY",
+            // Erasing parts of the screen
+            b"\x1b[2;2H\x1b[K\x1b[3;4H\x1b[1J",
+            // Colors and attributes
+            b"\x1b[31;1mred\x1b[7minv\x1b[0m",
+            // Wide chars, inserting and deleting characters
+            b"\x1b[5;1H\xe4\xb8\xadab\x1b[3D\x1b[2@\x1b[1P",
+            // Scrolling the screen and scroll regions
+            b"\x1b[17;1H\n\nscrolled",
+            b"\x1b[3;6r\x1b[6;1H\nregion\x1b[r",
+            // Palette changes
+            b"\x1b]4;1;rgb:12/34/56\x07",
+            b"\x1b]104;1\x07",
+            // Hidden cursor and blinking text
+            b"\x1b[?25l\x1b[10;1H\x1b[5mblink\x1b[0m",
+            b"\x1b[?25h",
+            b"\x1b[12;2Hx",
+            // Reverse video and the alternate screen
+            b"\x1b[?5h",
+            b"\x1b[?5l\x1b[?1049halt",
+            b"\x1b[?1049l",
+        ];
+
+        for (i, stream) in streams.iter().enumerate() {
+            for byte in stream.iter() {
+                parser.advance(&mut term, *byte, &mut Vec::new());
+            }
 
-        // Search for URL in grid
-        let url = term.url_search(Point::new(0, Column(1)));
+            let damage = draw_damage(&mut term, &mut frame, &config, i % 3 != 0, i % 2 == 0);
 
-        assert_eq!(url, None);
+            // Only the overwritten line and the old cursor line are redrawn
+            if i == 1 {
+                assert_eq!(damage, Damage::Lines(vec![Line(0), Line(1)]));
+            }
+        }
+
+        // Nothing changed
+        let damage = draw_damage(&mut term, &mut frame, &config, true, true);
+        assert_eq!(damage, Damage::Lines(Vec::new()));
+
+        // Moving the cursor left on an otherwise unchanged line
+        for byte in b"\x1b[5;4H".iter() {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        draw_damage(&mut term, &mut frame, &config, true, true);
+        for _ in 0..3 {
+            for byte in b"\x1b[D".iter() {
+                parser.advance(&mut term, *byte, &mut Vec::new());
+            }
+            let damage = draw_damage(&mut term, &mut frame, &config, true, true);
+            assert_eq!(damage, Damage::Lines(vec![Line(4)]));
+        }
+
+        // Scrolling into the history
+        term.scroll_display(Scroll::Lines(2));
+        assert_eq!(draw_damage(&mut term, &mut frame, &config, true, true), Damage::Full);
+        term.scroll_display(Scroll::Bottom);
+        draw_damage(&mut term, &mut frame, &config, true, true);
+
+        // Selections
+        let mut selection = Selection::simple(Point::new(5, Column(1)), Side::Left);
+        selection.update(Point::new(3, Column(4)), Side::Right);
+        *term.selection_mut() = Some(selection);
+        draw_damage(&mut term, &mut frame, &config, true, true);
+        *term.selection_mut() = None;
+        draw_damage(&mut term, &mut frame, &config, true, true);
+
+        // Resizing
+        size.height = 39.0;
+        term.resize(&size);
+        assert_eq!(draw_damage(&mut term, &mut frame, &config, true, true), Damage::Full);
+        for byte in b"\x1b[HAB".iter() {
+            parser.advance(&mut term, *byte, &mut Vec::new());
+        }
+        draw_damage(&mut term, &mut frame, &config, true, true);
     }
 }
 
@@ -2426,7 +7263,7 @@ mod benches {
         mem::swap(&mut terminal.grid, &mut grid);
 
         b.iter(|| {
-            let iter = terminal.renderable_cells(&config, false);
+            let iter = terminal.renderable_cells(&config, false, true);
             for cell in iter {
                 test::black_box(cell);
             }
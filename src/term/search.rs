@@ -0,0 +1,365 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Scrollback search
+//!
+//! Queries are regular expressions, which are matched against the text of the
+//! cells. Rows which are wrapped into each other are joined before matching,
+//! so a match can span the boundary between two rows of the same logical line.
+use std::cmp::min;
+
+use regex::{Regex, RegexBuilder};
+
+use grid::Grid;
+use index::{Column, Point};
+use term::cell::{Cell, Flags};
+
+/// Occurrence of the search query in the grid
+///
+/// Both points are in buffer coordinates and inclusive, `start` being the
+/// cell closest to the top of the history.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Match {
+    pub start: Point<usize>,
+    pub end: Point<usize>,
+}
+
+impl Match {
+    /// Check if the match starts above `other`
+    #[inline]
+    fn is_before(&self, other: &Match) -> bool {
+        self.start.line > other.start.line
+            || (self.start.line == other.start.line && self.start.col < other.start.col)
+    }
+}
+
+/// State of an active search
+#[derive(Debug, Default, Clone)]
+pub struct SearchState {
+    /// Text which is searched for
+    pub query: String,
+
+    /// Whether the query is still being typed
+    pub editing: bool,
+
+    /// Match the viewport is currently centered on
+    pub focused: Option<Match>,
+
+    /// Display offset when the search was started
+    pub origin: usize,
+
+    /// Regex compiled from the query, along with the query it belongs to
+    regex: Option<(String, Option<Regex>)>,
+}
+
+impl SearchState {
+    pub fn new(origin: usize) -> SearchState {
+        SearchState {
+            editing: true,
+            origin,
+            ..SearchState::default()
+        }
+    }
+
+    /// Compile the query, unless it's unchanged since the last call
+    pub fn update_regex(&mut self) {
+        if self.regex.as_ref().map_or(true, |&(ref query, _)| *query != self.query) {
+            self.regex = Some((self.query.clone(), build_regex(&self.query)));
+        }
+    }
+
+    /// Compiled query, `None` if it isn't a valid regular expression
+    pub fn regex(&self) -> Option<&Regex> {
+        match self.regex {
+            Some((ref query, Some(ref regex))) if *query == self.query => Some(regex),
+            _ => None,
+        }
+    }
+}
+
+/// Compile a query into a regex
+///
+/// Matching is case-insensitive unless the query contains an uppercase
+/// character. Empty queries and invalid regular expressions, like incomplete
+/// ones while typing, don't match at all.
+pub fn build_regex(query: &str) -> Option<Regex> {
+    if query.is_empty() {
+        return None;
+    }
+
+    RegexBuilder::new(query).case_insensitive(!has_uppercase(query)).build().ok()
+}
+
+/// Find all matches of `regex` from buffer line `top` down to `bottom`
+///
+/// The range is extended to the wrapped lines it is part of.
+pub fn find_matches(grid: &Grid<Cell>, regex: &Regex, top: usize, bottom: usize) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    let history_len = grid.num_lines().0 + grid.scroll_limit();
+    if bottom >= history_len {
+        return matches;
+    }
+
+    let last_col = grid.num_cols() - Column(1);
+    let wraps = |line: usize| grid[line][last_col].flags.contains(Flags::WRAPLINE);
+
+    let mut top = min(top, history_len - 1);
+    while top + 1 < history_len && wraps(top + 1) {
+        top += 1;
+    }
+    let mut bottom = min(bottom, top);
+    while bottom > 0 && wraps(bottom) {
+        bottom -= 1;
+    }
+
+    let mut text: Vec<(char, Point<usize>)> = Vec::new();
+    for line in (bottom..=top).rev() {
+        for col in 0..grid.num_cols().0 {
+            let cell = &grid[line][Column(col)];
+            if !cell.flags.contains(Flags::WIDE_CHAR_SPACER) {
+                text.push((cell.c, Point::new(line, Column(col))));
+            }
+        }
+
+        if line == bottom || !wraps(line) {
+            push_matches(&mut matches, &text, regex);
+            text.clear();
+        }
+    }
+
+    matches
+}
+
+/// Find the closest match below `origin`, or above it if `forward` is false
+///
+/// The search wraps around at the end of the buffer. Lines are matched a
+/// screen at a time, starting at the origin, so the rest of the history is
+/// only searched when there's nothing closer.
+pub fn find_next(grid: &Grid<Cell>, regex: &Regex, origin: &Match, forward: bool) -> Option<Match> {
+    find_next_in_chunks(grid, regex, origin, forward, grid.num_lines().0)
+}
+
+fn find_next_in_chunks(
+    grid: &Grid<Cell>,
+    regex: &Regex,
+    origin: &Match,
+    forward: bool,
+    chunk: usize,
+) -> Option<Match> {
+    let history_len = grid.num_lines().0 + grid.scroll_limit();
+    let origin_line = min(origin.start.line, history_len - 1);
+    let matches = |&(top, bottom): &(usize, usize)| find_matches(grid, regex, top, bottom);
+
+    if forward {
+        chunks_down(origin_line, chunk).iter()
+            .filter_map(|range| matches(range).into_iter().find(|m| origin.is_before(m)))
+            .next()
+            .or_else(|| {
+                chunks_down(history_len - 1, chunk).iter()
+                    .filter_map(|range| matches(range).into_iter().next())
+                    .next()
+            })
+    } else {
+        chunks_up(origin_line, history_len, chunk).iter()
+            .filter_map(|range| matches(range).into_iter().rev().find(|m| m.is_before(origin)))
+            .next()
+            .or_else(|| {
+                chunks_up(0, history_len, chunk).iter()
+                    .filter_map(|range| matches(range).pop())
+                    .next()
+            })
+    }
+}
+
+/// Split the lines from `top` down to the bottom of the buffer into chunks
+fn chunks_down(top: usize, size: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut top = Some(top);
+    while let Some(chunk_top) = top {
+        let bottom = chunk_top.saturating_sub(size - 1);
+        chunks.push((chunk_top, bottom));
+        top = bottom.checked_sub(1);
+    }
+    chunks
+}
+
+/// Split the lines from `bottom` up to the top of the history into chunks
+fn chunks_up(mut bottom: usize, history_len: usize, size: usize) -> Vec<(usize, usize)> {
+    let mut chunks = Vec::new();
+    while bottom < history_len {
+        let top = min(bottom + size - 1, history_len - 1);
+        chunks.push((top, bottom));
+        bottom = top + 1;
+    }
+    chunks
+}
+
+/// Check for uppercase characters which aren't part of an escape like `\S`
+fn has_uppercase(query: &str) -> bool {
+    let mut escaped = false;
+    query.chars().any(|c| {
+        let uppercase = !escaped && c.is_uppercase();
+        escaped = !escaped && c == '\\';
+        uppercase
+    })
+}
+
+/// Search a single logical line for non-overlapping matches of `regex`
+fn push_matches(matches: &mut Vec<Match>, text: &[(char, Point<usize>)], regex: &Regex) {
+    let line: String = text.iter().map(|&(c, _)| c).collect();
+
+    // Byte offset of every char, to map matches back to their cells
+    let offsets: Vec<usize> = line.char_indices().map(|(offset, _)| offset).collect();
+    let cell = |offset: usize| match offsets.binary_search(&offset) {
+        Ok(index) => index,
+        Err(index) => index - 1,
+    };
+
+    for found in regex.find_iter(&line).filter(|found| found.start() != found.end()) {
+        matches.push(Match {
+            start: text[cell(found.start())].1,
+            end: text[cell(found.end() - 1)].1,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grid::Grid;
+    use index::{Column, Line, Point};
+    use term::cell::{Cell, Flags};
+
+    use super::{build_regex, find_next_in_chunks, Match, SearchState};
+
+    fn grid_from(lines: &[&str], cols: usize) -> Grid<Cell> {
+        let mut grid = Grid::new(Line(lines.len()), Column(cols), 0, Cell::default());
+        for (line, text) in lines.iter().enumerate() {
+            for (col, c) in text.chars().enumerate() {
+                grid[Line(line)][Column(col)].c = c;
+            }
+        }
+        grid
+    }
+
+    fn find_matches(grid: &Grid<Cell>, query: &str, top: usize, bottom: usize) -> Vec<Match> {
+        match build_regex(query) {
+            Some(regex) => super::find_matches(grid, &regex, top, bottom),
+            None => Vec::new(),
+        }
+    }
+
+    fn matched(start: (usize, usize), end: (usize, usize)) -> Match {
+        Match {
+            start: Point::new(start.0, Column(start.1)),
+            end: Point::new(end.0, Column(end.1)),
+        }
+    }
+
+    #[test]
+    fn match_across_wrapped_lines() {
+        let mut grid = grid_from(&["   ala", "critty", "alacri"], 6);
+        grid[Line(0)][Column(5)].flags.insert(Flags::WRAPLINE);
+
+        // Only the bottom line is searched, but its wrapped line is included
+        let matches = find_matches(&grid, "alacritty", 1, 1);
+        assert_eq!(matches, vec![matched((2, 3), (1, 5))]);
+
+        // The last line isn't wrapped, so it can't be completed
+        assert!(find_matches(&grid, "alacritty", 0, 0).is_empty());
+    }
+
+    #[test]
+    fn smart_case() {
+        let grid = grid_from(&["Foo foo", "FOO   "], 7);
+
+        assert_eq!(find_matches(&grid, "foo", 1, 0).len(), 3);
+        assert_eq!(find_matches(&grid, "Foo", 1, 0), vec![matched((1, 0), (1, 2))]);
+    }
+
+    #[test]
+    fn regex() {
+        let grid = grid_from(&["error: 12", "warning 3"], 9);
+
+        assert_eq!(find_matches(&grid, "[0-9]+", 1, 0), vec![
+            matched((1, 7), (1, 8)),
+            matched((0, 8), (0, 8)),
+        ]);
+        assert_eq!(find_matches(&grid, "^w\\w+", 1, 0), vec![matched((0, 0), (0, 6))]);
+
+        // Escapes don't disable the smart case
+        assert_eq!(find_matches(&grid, "ERROR\\S", 1, 0), vec![]);
+        assert_eq!(find_matches(&grid, "error\\S", 1, 0), vec![matched((1, 0), (1, 5))]);
+
+        // Incomplete expressions and empty matches are ignored
+        assert!(find_matches(&grid, "(err", 1, 0).is_empty());
+        assert!(find_matches(&grid, "x*", 1, 0).is_empty());
+    }
+
+    #[test]
+    fn wide_chars() {
+        let mut grid = grid_from(&["a文 b"], 5);
+        grid[Line(0)][Column(1)].flags.insert(Flags::WIDE_CHAR);
+        grid[Line(0)][Column(2)].flags.insert(Flags::WIDE_CHAR_SPACER);
+
+        // The spacer isn't part of the text
+        assert_eq!(find_matches(&grid, "文b", 0, 0), vec![matched((0, 1), (0, 3))]);
+    }
+
+    #[test]
+    fn regex_follows_query() {
+        let mut search = SearchState::new(0);
+        search.query.push_str("ab");
+        assert!(search.regex().is_none());
+
+        search.update_regex();
+        assert_eq!(search.regex().map(|regex| regex.as_str()), Some("ab"));
+
+        // A stale regex is never used
+        search.query.push('(');
+        assert!(search.regex().is_none());
+        search.update_regex();
+        assert!(search.regex().is_none());
+    }
+
+    #[test]
+    fn navigation_wraps_around() {
+        let grid = grid_from(&["ab  ab", "      ", "      ", "ab    ", "      "], 6);
+        let regex = build_regex("ab").unwrap();
+        let matches = [matched((4, 0), (4, 1)), matched((4, 4), (4, 5)), matched((1, 0), (1, 1))];
+
+        // Searching a chunk at a time finds the same matches as searching everything
+        for chunk in 1..6 {
+            let find_next = |origin: &Match, forward| {
+                find_next_in_chunks(&grid, &regex, origin, forward, chunk)
+            };
+
+            assert_eq!(find_next(&matches[2], false), Some(matches[1]));
+            assert_eq!(find_next(&matches[1], false), Some(matches[0]));
+            assert_eq!(find_next(&matches[0], false), Some(matches[2]));
+            assert_eq!(find_next(&matches[0], true), Some(matches[1]));
+            assert_eq!(find_next(&matches[1], true), Some(matches[2]));
+            assert_eq!(find_next(&matches[2], true), Some(matches[0]));
+
+            // Starting below the last line, like at the search origin
+            let origin = Point::new(0, Column(6));
+            let origin = Match { start: origin, end: origin };
+            assert_eq!(find_next(&origin, false), Some(matches[2]));
+            assert_eq!(find_next(&origin, true), Some(matches[0]));
+        }
+
+        let nothing = build_regex("xyz").unwrap();
+        assert_eq!(find_next_in_chunks(&grid, &nothing, &matches[0], true, 2), None);
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Keyboard driven cursor for selecting text
+//!
+//! While the vi mode is active, the cursor moves freely through the visible
+//! lines and the scrollback history. All positions are in buffer coordinates,
+//! so the cursor stays on the same content while the viewport is scrolled.
+use std::cmp::min;
+
+use grid::Grid;
+use index::{Column, Point};
+use term::cell::{Cell, Flags, LineLength};
+
+/// Cursor motions of the vi mode
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ViMotion {
+    /// Move up one line (`k`)
+    Up,
+    /// Move down one line (`j`)
+    Down,
+    /// Move left one cell (`h`)
+    Left,
+    /// Move right one cell (`l`)
+    Right,
+    /// Move to the first column (`0`)
+    First,
+    /// Move to the last occupied column (`$`)
+    Last,
+    /// Move to the start of the previous word (`b`)
+    WordLeft,
+    /// Move to the start of the next word (`w`)
+    WordRight,
+    /// Move to the top of the scrollback history (`gg`)
+    Top,
+    /// Move to the bottom of the screen (`G`)
+    Bottom,
+}
+
+/// State of the vi mode
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ViMode {
+    /// Position of the vi cursor
+    pub cursor: Point<usize>,
+
+    /// Start of the character-wise selection
+    pub anchor: Option<Point<usize>>,
+
+    /// First key of a command which is still waiting for its second key
+    pub pending: Option<char>,
+}
+
+impl ViMode {
+    pub fn new(cursor: Point<usize>) -> ViMode {
+        ViMode {
+            cursor,
+            anchor: None,
+            pending: None,
+        }
+    }
+}
+
+/// Apply a motion to a point
+///
+/// Words are separated by whitespace, so punctuation is part of a word.
+pub fn motion(grid: &Grid<Cell>, point: Point<usize>, motion: ViMotion) -> Point<usize> {
+    let top = grid.num_lines().0 + grid.scroll_limit() - 1;
+    let last_col = grid.num_cols() - 1;
+    let point = Point::new(min(point.line, top), min(point.col, last_col));
+
+    match motion {
+        ViMotion::Up => Point::new(min(point.line + 1, top), point.col),
+        ViMotion::Down => Point::new(point.line.saturating_sub(1), point.col),
+        ViMotion::Left => Point::new(point.line, Column(point.col.saturating_sub(1))),
+        ViMotion::Right => Point::new(point.line, min(point.col + 1, last_col)),
+        ViMotion::First => Point::new(point.line, Column(0)),
+        ViMotion::Last => {
+            let length = grid[point.line].line_length();
+            Point::new(point.line, Column(length.saturating_sub(1)))
+        },
+        ViMotion::WordLeft => word_left(grid, point, top),
+        ViMotion::WordRight => word_right(grid, point),
+        ViMotion::Top => Point::new(top, Column(0)),
+        ViMotion::Bottom => Point::new(0, Column(0)),
+    }
+}
+
+/// Find the start of the next word, or the last cell if there's none
+fn word_right(grid: &Grid<Cell>, mut point: Point<usize>) -> Point<usize> {
+    // Skip the rest of the current word, then the whitespace after it
+    while !is_space(grid, point) {
+        match next_cell(grid, point) {
+            Some(next) => point = next,
+            None => return point,
+        }
+    }
+
+    while is_space(grid, point) {
+        match next_cell(grid, point) {
+            Some(next) => point = next,
+            None => return point,
+        }
+    }
+
+    point
+}
+
+/// Find the start of the previous word, or the first cell if there's none
+fn word_left(grid: &Grid<Cell>, point: Point<usize>, top: usize) -> Point<usize> {
+    let mut point = match previous_cell(grid, point, top) {
+        Some(previous) => previous,
+        None => return point,
+    };
+
+    while is_space(grid, point) {
+        match previous_cell(grid, point, top) {
+            Some(previous) => point = previous,
+            None => return point,
+        }
+    }
+
+    while let Some(previous) = previous_cell(grid, point, top) {
+        if is_space(grid, previous) {
+            break;
+        }
+        point = previous;
+    }
+
+    point
+}
+
+fn next_cell(grid: &Grid<Cell>, point: Point<usize>) -> Option<Point<usize>> {
+    if point.col + 1 < grid.num_cols() {
+        Some(Point::new(point.line, point.col + 1))
+    } else if point.line > 0 {
+        Some(Point::new(point.line - 1, Column(0)))
+    } else {
+        None
+    }
+}
+
+fn previous_cell(grid: &Grid<Cell>, point: Point<usize>, top: usize) -> Option<Point<usize>> {
+    if point.col > Column(0) {
+        Some(Point::new(point.line, point.col - 1))
+    } else if point.line < top {
+        Some(Point::new(point.line + 1, grid.num_cols() - 1))
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn is_space(grid: &Grid<Cell>, point: Point<usize>) -> bool {
+    let cell = &grid[point.line][point.col];
+    cell.c == ' ' && !cell.flags.contains(Flags::WIDE_CHAR_SPACER)
+}
+
+#[cfg(test)]
+mod tests {
+    use grid::Grid;
+    use index::{Column, Line, Point};
+    use term::cell::Cell;
+
+    use super::{motion, ViMotion};
+
+    /// Create a grid with the first line in the scrollback history
+    fn grid_from(lines: &[&str], cols: usize) -> Grid<Cell> {
+        let num_lines = Line(lines.len() - 1);
+        let mut grid = Grid::new(num_lines, Column(cols), 10, Cell::default());
+
+        let write = |grid: &mut Grid<Cell>, line: Line, text: &str| {
+            for (col, c) in text.chars().enumerate() {
+                grid[line][Column(col)].c = c;
+            }
+        };
+
+        for (line, text) in lines[..lines.len() - 1].iter().enumerate() {
+            write(&mut grid, Line(line), text);
+        }
+        grid.scroll_up(&(Line(0)..num_lines), Line(1), &Cell::default());
+        write(&mut grid, num_lines - 1, lines[lines.len() - 1]);
+
+        grid
+    }
+
+    #[test]
+    fn simple_motions() {
+        let grid = grid_from(&["foo   ", "barbaz", "qux   "], 6);
+        let point = Point::new(1, Column(0));
+
+        assert_eq!(motion(&grid, point, ViMotion::Right), Point::new(1, Column(1)));
+        assert_eq!(motion(&grid, point, ViMotion::Left), point);
+        assert_eq!(motion(&grid, point, ViMotion::Down), Point::new(0, Column(0)));
+        assert_eq!(motion(&grid, point, ViMotion::Up), Point::new(2, Column(0)));
+        assert_eq!(motion(&grid, point, ViMotion::Last), Point::new(1, Column(5)));
+
+        // The scrollback history is reachable, but not beyond its top
+        let top = Point::new(2, Column(5));
+        assert_eq!(motion(&grid, top, ViMotion::Up), top);
+        assert_eq!(motion(&grid, top, ViMotion::First), Point::new(2, Column(0)));
+        assert_eq!(motion(&grid, top, ViMotion::Last), Point::new(2, Column(2)));
+        assert_eq!(motion(&grid, point, ViMotion::Top), Point::new(2, Column(0)));
+        assert_eq!(motion(&grid, top, ViMotion::Bottom), Point::new(0, Column(0)));
+    }
+
+    #[test]
+    fn word_motions() {
+        let grid = grid_from(&["ab cd ", "   ef ", "      "], 6);
+
+        let point = motion(&grid, Point::new(2, Column(1)), ViMotion::WordRight);
+        assert_eq!(point, Point::new(2, Column(3)));
+
+        // Words continue on the next line
+        let point = motion(&grid, point, ViMotion::WordRight);
+        assert_eq!(point, Point::new(1, Column(3)));
+        assert_eq!(motion(&grid, point, ViMotion::WordRight), Point::new(0, Column(5)));
+
+        let point = motion(&grid, point, ViMotion::WordLeft);
+        assert_eq!(point, Point::new(2, Column(3)));
+        let point = motion(&grid, point, ViMotion::WordLeft);
+        assert_eq!(point, Point::new(2, Column(0)));
+        assert_eq!(motion(&grid, point, ViMotion::WordLeft), point);
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Storage for zero-width characters
+//!
+//! Combining characters are rare, so instead of reserving room for them in
+//! every cell, a cell only carries a `ZeroWidthId`. Sequences are interned,
+//! cells with the same combining characters share an id.
+use std::collections::HashSet;
+
+use term::cell::MAX_ZEROWIDTH_CHARS;
+use term::interned::{Interned, InternedId};
+
+/// Handle for zero-width characters stored in `ZeroWidthChars`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ZeroWidthId(u32);
+
+impl InternedId for ZeroWidthId {
+    fn from_index(index: u32) -> ZeroWidthId {
+        ZeroWidthId(index)
+    }
+}
+
+#[derive(Default)]
+pub struct ZeroWidthChars {
+    chars: Interned<ZeroWidthId, Vec<char>>,
+}
+
+impl ZeroWidthChars {
+    /// Get the id for the characters of `id` followed by `c`
+    ///
+    /// Characters beyond `MAX_ZEROWIDTH_CHARS` are dropped.
+    pub fn push(&mut self, id: Option<ZeroWidthId>, c: char) -> ZeroWidthId {
+        let mut chars = id.map_or_else(Vec::new, |id| self.get(id).to_vec());
+        match id {
+            Some(id) if chars.len() >= MAX_ZEROWIDTH_CHARS => return id,
+            _ => chars.push(c),
+        }
+
+        self.chars.insert(chars, true)
+    }
+
+    #[inline]
+    pub fn get(&self, id: ZeroWidthId) -> &[char] {
+        self.chars.get(id).map_or(&[][..], |chars| &chars[..])
+    }
+
+    /// Check if enough sequences were added since the last collection
+    #[inline]
+    pub fn needs_collect(&self) -> bool {
+        self.chars.needs_collect()
+    }
+
+    /// Drop all sequences which aren't in `used`
+    pub fn retain(&mut self, used: &HashSet<ZeroWidthId>) {
+        self.chars.retain(used);
+    }
+
+    /// Drop all sequences
+    pub fn clear(&mut self) {
+        self.chars.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use term::interned::MIN_COLLECT_LEN;
+    use super::ZeroWidthChars;
+
+    #[test]
+    fn push_keeps_order_and_drops_overflow() {
+        let mut zerowidth = ZeroWidthChars::default();
+
+        let mut id = None;
+        for c in "\u{301}\u{302}\u{303}\u{304}\u{305}\u{306}".chars() {
+            id = Some(zerowidth.push(id, c));
+        }
+
+        let chars: String = zerowidth.get(id.unwrap()).iter().collect();
+        assert_eq!(chars, "\u{301}\u{302}\u{303}\u{304}\u{305}");
+    }
+
+    #[test]
+    fn equal_sequences_are_shared() {
+        let mut zerowidth = ZeroWidthChars::default();
+
+        let acute = zerowidth.push(None, '\u{301}');
+        assert_eq!(zerowidth.push(None, '\u{301}'), acute);
+        assert_ne!(zerowidth.push(None, '\u{302}'), acute);
+
+        let both = zerowidth.push(Some(acute), '\u{302}');
+        assert_eq!(zerowidth.get(both), &['\u{301}', '\u{302}']);
+        assert_eq!(zerowidth.get(acute), &['\u{301}']);
+    }
+
+    #[test]
+    fn retain_drops_unused_sequences() {
+        let mut zerowidth = ZeroWidthChars::default();
+
+        let ids: Vec<_> = (0..MIN_COLLECT_LEN as u32)
+            .map(|i| zerowidth.push(None, ::std::char::from_u32(0x300 + i).unwrap()))
+            .collect();
+        assert!(zerowidth.needs_collect());
+
+        let used: HashSet<_> = ids[..2].iter().cloned().collect();
+        zerowidth.retain(&used);
+        assert!(!zerowidth.needs_collect());
+
+        assert_eq!(zerowidth.get(ids[1]), &['\u{301}']);
+        assert!(zerowidth.get(ids[2]).is_empty());
+
+        // Dropped sequences get a new id
+        assert_ne!(zerowidth.push(None, '\u{302}'), ids[2]);
+        assert_eq!(zerowidth.push(None, '\u{301}'), ids[1]);
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2016 Joe Wilm, The Alacritty Project Contributors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//! Timer for periodic work in the render loop
+//!
+//! While the ticker is running, a background thread wakes up the render loop
+//! at a fixed interval and counts the ticks. The thread is parked while the
+//! ticker is stopped.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use display::Notifier;
+use util;
+
+pub struct Ticker {
+    running: Arc<AtomicBool>,
+    /// Time between wakeups in microseconds
+    interval: Arc<AtomicUsize>,
+    /// Number of wakeups since the ticker was created
+    ticks: Arc<AtomicUsize>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Ticker {
+    /// Create a stopped ticker
+    pub fn new(name: &str, interval: Duration, notifier: Notifier) -> Ticker {
+        let running = Arc::new(AtomicBool::new(false));
+        let interval = Arc::new(AtomicUsize::new(micros(interval)));
+        let ticks = Arc::new(AtomicUsize::new(0));
+
+        let thread_running = Arc::clone(&running);
+        let thread_interval = Arc::clone(&interval);
+        let thread_ticks = Arc::clone(&ticks);
+        let thread = util::thread::spawn_named(name, move || loop {
+            if thread_running.load(Ordering::Relaxed) {
+                let interval = thread_interval.load(Ordering::Relaxed) as u64;
+                thread::sleep(Duration::from_micros(interval));
+
+                // Don't count ticks which ended after the ticker was stopped
+                if thread_running.load(Ordering::Relaxed) {
+                    thread_ticks.fetch_add(1, Ordering::Relaxed);
+                    notifier.notify();
+                }
+            } else {
+                thread::park();
+            }
+        });
+
+        Ticker {
+            running,
+            interval,
+            ticks,
+            thread,
+        }
+    }
+
+    /// Change the time between wakeups, starting with the next one
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval.store(micros(interval), Ordering::Relaxed);
+    }
+
+    /// Start waking up the render loop
+    pub fn start(&self) {
+        if !self.running.swap(true, Ordering::Relaxed) {
+            self.thread.thread().unpark();
+        }
+    }
+
+    /// Tick counter, shared with the ticker thread
+    pub fn ticks(&self) -> &Arc<AtomicUsize> {
+        &self.ticks
+    }
+
+    /// Stop waking up the render loop
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+fn micros(duration: Duration) -> usize {
+    duration.as_secs() as usize * 1_000_000 + duration.subsec_micros() as usize
+}
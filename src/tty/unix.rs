@@ -247,15 +247,17 @@ pub fn new<T: ToWinsize>(
     builder.env("SHELL", shell.program());
     builder.env("HOME", pw.dir);
 
-    // TERM; default to 'alacritty' if it is available, otherwise
+    // TERM; default to `term_env` if its terminfo is available, otherwise
     // default to 'xterm-256color'. May be overridden by user's config
-    // below.
-    let term = if Database::from_name("alacritty").is_ok() {
-        "alacritty"
-    } else {
-        "xterm-256color"
-    };
-    builder.env("TERM", term);
+    // below, in which case the terminfo lookup is skipped.
+    if !config.env().contains_key("TERM") {
+        let term = if Database::from_name(config.term_env()).is_ok() {
+            config.term_env()
+        } else {
+            "xterm-256color"
+        };
+        builder.env("TERM", term);
+    }
 
     builder.env("COLORTERM", "truecolor"); // advertise 24-bit support
     if let Some(window_id) = window_id {
@@ -19,6 +19,7 @@ use std::os::windows::io::{FromRawHandle, IntoRawHandle};
 use std::os::windows::fs::OpenOptionsExt;
 use std::env;
 use std::cell::UnsafeCell;
+use std::collections::BTreeMap;
 
 use dunce::canonicalize;
 use mio;
@@ -100,13 +101,25 @@ pub fn new<'a>(
     let cwd = options.working_dir.as_ref().map(|dir| canonicalize(dir).unwrap());
     let cwd = cwd.as_ref().map(|dir| dir.to_str().unwrap());
 
+    // Give the child its own environment block, so the variables don't leak
+    // into other processes we spawn; user config takes precedence.
+    // Advertise 24-bit color support
+    let mut child_env = vec![(String::from("COLORTERM"), String::from("truecolor"))];
+    child_env.extend(config.env().iter().map(|(key, value)| (key.clone(), value.clone())));
+    let env_block = env_block(env::vars_os().filter_map(|(key, value)| {
+        match (key.into_string(), value.into_string()) {
+            (Ok(key), Ok(value)) => Some((key, value)),
+            _ => None,
+        }
+    }).chain(child_env));
+
     // Spawn process
     let spawnconfig = SpawnConfig::new(
         SpawnFlags::AUTO_SHUTDOWN | SpawnFlags::EXIT_AFTER_SHUTDOWN,
         None, // appname
         Some(&cmdline.join(" ")),
         cwd,
-        None, // Env
+        Some(&env_block),
     ).unwrap();
 
     let default_opts = &mut OpenOptions::new();
@@ -164,6 +177,23 @@ pub fn new<'a>(
     }
 }
 
+/// Build an environment block from `NAME=VALUE` pairs
+///
+/// Names are case insensitive on Windows, later variables replace earlier ones
+/// with the same name. Winpty adds the final terminator.
+fn env_block<I: IntoIterator<Item = (String, String)>>(vars: I) -> String {
+    let mut env = BTreeMap::new();
+    for (key, value) in vars {
+        env.insert(key.to_uppercase(), (key, value));
+    }
+
+    let mut block = String::new();
+    for &(ref key, ref value) in env.values() {
+        block.push_str(&format!("{}={}\0", key, value));
+    }
+    block
+}
+
 impl<'a> EventedReadWrite for Pty<'a, NamedPipe, NamedPipe> {
     type Reader = NamedPipe;
     type Writer = NamedPipe;
@@ -12,6 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::cmp;
+use std::ffi::OsStr;
+use std::io;
+use std::process::Command;
+use std::time::{Duration, Instant};
+#[cfg(not(windows))]
+use std::os::unix::process::CommandExt;
 
 #[cfg(not(feature = "nightly"))]
 #[inline(always)]
@@ -44,6 +50,54 @@ pub fn limit<T: Ord>(value: T, min: T, max: T) -> T {
     cmp::min(cmp::max(value, min), max)
 }
 
+/// Run a program in the background, without waiting for it to exit
+pub fn start_daemon<I, S>(program: &str, args: I) -> io::Result<()>
+    where I: IntoIterator<Item = S>,
+          S: AsRef<OsStr>
+{
+    let mut command = Command::new(program);
+    command.args(args);
+
+    #[cfg(not(windows))]
+    command.before_exec(|| {
+        // Detach forked process from Alacritty. This will cause
+        // init or whatever to clean up child processes for us.
+        unsafe { ::libc::daemon(1, 0); }
+        Ok(())
+    });
+
+    let child = command.spawn()?;
+    debug!("spawned new proc with pid: {}", child.id());
+
+    Ok(())
+}
+
+/// Limit how often an action runs
+pub struct Throttle {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new(interval: Duration) -> Throttle {
+        Throttle {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Whether the action may run at `now`, which counts as running it
+    pub fn ready(&mut self, now: Instant) -> bool {
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            },
+        }
+    }
+}
+
 /// Utilities for writing to the
 pub mod fmt {
     use std::fmt;
@@ -83,7 +137,9 @@ pub mod fmt {
 
 #[cfg(test)]
 mod tests {
-    use super::limit;
+    use std::time::{Duration, Instant};
+
+    use super::{limit, Throttle};
 
     #[test]
     fn limit_works() {
@@ -91,4 +147,16 @@ mod tests {
         assert_eq!(10, limit(5, 10, 100));
         assert_eq!(100, limit(1000, 10, 100));
     }
+
+    #[test]
+    fn throttle_limits_rate() {
+        let mut throttle = Throttle::new(Duration::from_secs(1));
+        let start = Instant::now();
+
+        assert!(throttle.ready(start));
+        assert!(!throttle.ready(start));
+        assert!(!throttle.ready(start + Duration::from_millis(999)));
+        assert!(throttle.ready(start + Duration::from_secs(1)));
+        assert!(!throttle.ready(start + Duration::from_millis(1500)));
+    }
 }
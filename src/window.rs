@@ -72,6 +72,17 @@ pub struct Window {
     window: glutin::GlWindow,
     mouse_visible: bool,
 
+    /// Scale factor of the monitors the window is on
+    hidpi_factor: f32,
+
+    /// Monitors known when the scale factor was looked up, with their factors
+    #[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+    monitors: Vec<(Area, f32)>,
+
+    /// Indices of the monitors in `monitors` the window is on
+    #[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+    overlapped: Vec<usize>,
+
     /// Whether or not the window is the focused window.
     pub is_focused: bool,
 }
@@ -209,24 +220,29 @@ fn create_gl_window(
     window: WindowBuilder,
     event_loop: &EventsLoop,
     srgb: bool,
+    vsync: bool,
 ) -> ::std::result::Result<glutin::GlWindow, glutin::CreationError> {
-    let context = ContextBuilder::new().with_srgb(srgb).with_vsync(true);
+    let context = ContextBuilder::new().with_srgb(srgb).with_vsync(vsync);
     ::glutin::GlWindow::new(window, context, event_loop)
 }
 
 impl Window {
     /// Create a new window
     ///
-    /// This creates a window and fully initializes a window.
-    pub fn new(options: &Options, window_config: &WindowConfig) -> Result<Window> {
+    /// This creates a window and fully initializes a window. With `vsync`, buffer
+    /// swaps wait for the next refresh of the display where that's supported.
+    pub fn new(options: &Options, window_config: &WindowConfig, vsync: bool) -> Result<Window> {
         let event_loop = EventsLoop::new();
 
-        let title = options.title.as_ref().map_or(DEFAULT_TITLE, |t| t);
+        let title = options.title.as_ref()
+            .map(String::as_str)
+            .or_else(|| window_config.title())
+            .unwrap_or(DEFAULT_TITLE);
         let class = options.class.as_ref().map_or(DEFAULT_TITLE, |c| c);
         let window_builder = Window::get_platform_window(title, window_config);
         let window_builder = Window::platform_builder_ext(window_builder, &class);
-        let window = create_gl_window(window_builder.clone(), &event_loop, false)
-            .or_else(|_| create_gl_window(window_builder, &event_loop, true))?;
+        let window = create_gl_window(window_builder.clone(), &event_loop, false, vsync)
+            .or_else(|_| create_gl_window(window_builder, &event_loop, true, vsync))?;
         window.show();
 
         // Text cursor
@@ -240,12 +256,16 @@ impl Window {
         // Set OpenGL symbol loader. This call MUST be after window.make_current on windows.
         gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
 
-        let window = Window {
+        let mut window = Window {
             event_loop,
             window,
             mouse_visible: true,
+            hidpi_factor: 1.0,
+            monitors: Vec::new(),
+            overlapped: Vec::new(),
             is_focused: false,
         };
+        window.update_hidpi_factor();
 
         window.run_os_extensions();
 
@@ -269,11 +289,74 @@ impl Window {
         })
     }
 
+    /// Scale factor used for rasterizing glyphs
+    ///
+    /// When the window spans monitors with different DPI, this is the highest
+    /// of their factors so text stays sharp on all of them. Conversions
+    /// between points and pixels still use the window's own factor.
     #[inline]
     pub fn hidpi_factor(&self) -> f32 {
+        self.hidpi_factor
+    }
+
+    /// Look up the scale factor again after the window moved between monitors
+    ///
+    /// Returns whether the factor changed.
+    pub fn update_hidpi_factor(&mut self) -> bool {
+        let hidpi_factor = self.monitor_hidpi_factor();
+        let changed = hidpi_factor != self.hidpi_factor;
+        self.hidpi_factor = hidpi_factor;
+        changed
+    }
+
+    #[cfg(
+        any(target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd")
+    )]
+    fn monitor_hidpi_factor(&mut self) -> f32 {
+        let hidpi_factor = self.window.hidpi_factor();
+
+        // Wayland already reports the highest scale of all outputs the surface
+        // is on, and doesn't expose window positions to compare with
+        if self.is_wayland() {
+            return hidpi_factor;
+        }
+
+        let window = match (self.window.get_position(), self.window.get_outer_size()) {
+            (Some(position), Some(size)) => (position, size),
+            _ => return hidpi_factor,
+        };
+
+        // The factor can only change when the window crosses a monitor edge,
+        // so the monitors are only listed again when that happens
+        let overlapped = overlapped_monitors(window, &self.monitors);
+        if self.monitors.is_empty() || overlapped != self.overlapped {
+            self.monitors = self.event_loop
+                .get_available_monitors()
+                .map(|monitor| {
+                    ((monitor.get_position(), monitor.get_dimensions()), monitor.get_hidpi_factor())
+                })
+                .collect();
+            self.overlapped = overlapped_monitors(window, &self.monitors);
+        }
+
+        self.overlapped
+            .iter()
+            .map(|&index| self.monitors[index].1)
+            .fold(hidpi_factor, f32::max)
+    }
+
+    /// The backing scale factor on macOS and the window's DPI on Windows are
+    /// picked by the system for the whole window
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn monitor_hidpi_factor(&mut self) -> f32 {
         self.window.hidpi_factor()
     }
 
+    /// Size in pixels of the monitor the window is on
+    pub fn screen_size(&self) -> (u32, u32) {
+        self.window.get_current_monitor().get_dimensions()
+    }
+
     #[inline]
     pub fn create_window_proxy(&self) -> Proxy {
         Proxy {
@@ -436,13 +519,65 @@ impl Window {
         self.window.set_urgent(is_urgent);
     }
 
+    /// Flash the taskbar button until the window is focused
+    #[cfg(windows)]
+    pub fn set_urgent(&self, is_urgent: bool) {
+        use std::mem;
+
+        use glutin::os::windows::WindowExt;
+        use winapi::um::winuser::{
+            FlashWindowEx, FLASHWINFO, FLASHW_STOP, FLASHW_TIMERNOFG, FLASHW_TRAY,
+        };
+
+        let flags = if is_urgent {
+            FLASHW_TRAY | FLASHW_TIMERNOFG
+        } else {
+            FLASHW_STOP
+        };
+
+        let mut info = FLASHWINFO {
+            cbSize: mem::size_of::<FLASHWINFO>() as u32,
+            hwnd: self.window.get_hwnd() as _,
+            dwFlags: flags,
+            uCount: 0,
+            dwTimeout: 0,
+        };
+
+        unsafe {
+            FlashWindowEx(&mut info);
+        }
+    }
+
+    /// Bounce the dock icon once
+    ///
+    /// The request is cancelled by macOS when the application is activated.
+    #[cfg(target_os = "macos")]
+    pub fn set_urgent(&self, is_urgent: bool) {
+        use objc::runtime::{Class, Object};
+
+        // NSInformationalRequest
+        const INFORMATIONAL_REQUEST: isize = 10;
+
+        if !is_urgent {
+            return;
+        }
+
+        unsafe {
+            let app_class = Class::get("NSApplication").unwrap();
+            let app: *mut Object = msg_send![app_class, sharedApplication];
+            let _: isize = msg_send![app, requestUserAttention: INFORMATIONAL_REQUEST];
+        }
+    }
+
     #[cfg(
         not(
             any(
                 target_os = "linux",
                 target_os = "freebsd",
                 target_os = "dragonfly",
-                target_os = "openbsd"
+                target_os = "openbsd",
+                target_os = "macos",
+                windows
             )
         )
     )]
@@ -475,6 +610,31 @@ impl Window {
     }
 }
 
+/// Rectangle on the desktop, given as position and size
+type Area = ((i32, i32), (u32, u32));
+
+/// Whether two rectangles share any pixels
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+fn overlaps(a: Area, b: Area) -> bool {
+    let (((ax, ay), (aw, ah)), ((bx, by), (bw, bh))) = (a, b);
+
+    i64::from(ax) < i64::from(bx) + i64::from(bw)
+        && i64::from(bx) < i64::from(ax) + i64::from(aw)
+        && i64::from(ay) < i64::from(by) + i64::from(bh)
+        && i64::from(by) < i64::from(ay) + i64::from(ah)
+}
+
+/// Indices of the monitors which share pixels with the window
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+fn overlapped_monitors(window: Area, monitors: &[(Area, f32)]) -> Vec<usize> {
+    monitors
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(monitor, _))| overlaps(window, monitor))
+        .map(|(index, _)| index)
+        .collect()
+}
+
 pub trait OsExtensions {
     fn run_os_extensions(&self) {}
 }
@@ -561,8 +721,40 @@ pub trait SetInnerSize<T> {
 
 impl SetInnerSize<Pixels<u32>> for Window {
     fn set_inner_size<T: ToPoints>(&mut self, size: &T) {
-        let size = size.to_points(self.hidpi_factor());
+        let size = size.to_points(self.window.hidpi_factor());
         self.window
             .set_inner_size(*size.width as _, *size.height as _);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{overlapped_monitors, overlaps};
+
+    #[test]
+    fn window_spanning_monitors() {
+        let left = ((0, 0), (1920, 1080));
+        let right = ((1920, 0), (3840, 2160));
+
+        assert!(overlaps(((1800, 100), (400, 300)), left));
+        assert!(overlaps(((1800, 100), (400, 300)), right));
+
+        // Touching edges don't share any pixels
+        assert!(!overlaps(((1520, 100), (400, 300)), right));
+        assert!(!overlaps(((-400, 0), (400, 300)), left));
+    }
+
+    #[test]
+    fn monitors_change_at_edges() {
+        let monitors = [(((0, 0), (1920, 1080)), 1.0), (((1920, 0), (3840, 2160)), 2.0)];
+
+        // Moving inside a monitor doesn't change anything
+        assert_eq!(overlapped_monitors(((100, 100), (400, 300)), &monitors), vec![0]);
+        assert_eq!(overlapped_monitors(((1500, 700), (400, 300)), &monitors), vec![0]);
+
+        // Until the window crosses an edge
+        assert_eq!(overlapped_monitors(((1600, 700), (400, 300)), &monitors), vec![0, 1]);
+        assert_eq!(overlapped_monitors(((2000, 700), (400, 300)), &monitors), vec![1]);
+        assert!(overlapped_monitors(((-500, 0), (400, 300)), &monitors).is_empty());
+    }
+}